@@ -0,0 +1,640 @@
+//! `--archive`: packs the whole tree into a `.tar`, `.tar.gz`/`.tgz` or `.zip` before it
+//! gets deleted.
+//!
+//! The archive is streamed to a `.partial` file next to it, one entry at a time, and only
+//! renamed into place once complete, so an archive at the path is always a whole one.
+//! Entries are named below the target's own name, the way `tar -C parent -cf ... name`
+//! would. Symlinks are archived as links, never followed. Zip files are limited to what
+//! fits without the zip64 extensions: below 4 GiB, with fewer than 65536 entries.
+
+use std::{
+    fs::{File, Metadata},
+    io::{BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    deflate::{Crc32, Deflater, GzEncoder},
+    rate_limit, scan,
+};
+
+/// The kinds of archive `--archive` writes, told apart by the extension.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Format {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl Format {
+    pub(crate) fn of(path: &Path) -> Option<Format> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Format::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(Format::Tar)
+        } else if name.ends_with(".zip") {
+            Some(Format::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses an `--archive` path, which has to end in a known extension.
+pub(crate) fn parse_archive_path(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    match Format::of(&path) {
+        Some(_) => Ok(path),
+        None => Err(format!(
+            "cannot tell the archive format of '{value}', use .tar, .tar.gz, .tgz or .zip"
+        )),
+    }
+}
+
+/// Archives everything in `root`, itself included, into `archive_path`, returning the
+/// size of the finished archive.
+pub(crate) fn archive_tree(root: &Path, archive_path: &Path) -> Result<u64, std::io::Error> {
+    let format = Format::of(archive_path).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "unknown archive extension",
+        )
+    })?;
+    if std::fs::symlink_metadata(archive_path).is_ok() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "it already exists",
+        ));
+    }
+
+    let mut tmp_name = archive_path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".partial");
+    let tmp_path = archive_path.with_file_name(tmp_name);
+
+    let written = write_archive(root, &tmp_path, format)
+        .and_then(|()| std::fs::rename(&tmp_path, archive_path));
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e);
+    }
+
+    Ok(std::fs::metadata(archive_path)?.len())
+}
+
+fn write_archive(root: &Path, tmp_path: &Path, format: Format) -> Result<(), std::io::Error> {
+    let root_metadata = std::fs::symlink_metadata(root)?;
+    let entries = scan::scan_dir(root)?;
+    let base = root.file_name().map_or_else(
+        || "archive".to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+
+    let mut members = vec![(base.clone(), root.to_path_buf(), root_metadata)];
+    for entry in entries {
+        let relative = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+        let name = format!("{base}/{}", relative.to_string_lossy().replace('\\', "/"));
+        members.push((name, entry.path, entry.metadata));
+    }
+
+    let file = BufWriter::new(File::create(tmp_path)?);
+    let file = match format {
+        Format::Tar => write_tar(file, &members)?,
+        Format::TarGz => write_tar(GzEncoder::new(file)?, &members)?.finish()?,
+        Format::Zip => write_zip(Counting::new(file), &members)?.inner,
+    };
+    file.into_inner().map_err(|e| e.into_error())?.sync_all()
+}
+
+/// One archive entry: its name in the archive, where it is on disk and its metadata.
+type Member = (String, PathBuf, Metadata);
+
+fn unsupported(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!(
+            "cannot archive {}, it is not a file, folder or symlink",
+            path.to_string_lossy()
+        ),
+    )
+}
+
+/// Copies exactly `size` bytes of the file at `path` to `write`, failing when it shrank
+/// or grew since the scan, as the bytes past `size` would be deleted without a copy.
+fn copy_file(
+    path: &Path,
+    size: u64,
+    mut write: impl FnMut(&[u8]) -> Result<(), std::io::Error>,
+) -> Result<(), std::io::Error> {
+    let mut file = File::open(path)?.take(size);
+    let mut buffer = vec![0; 64 * 1024];
+    let mut copied = 0;
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        write(&buffer[..read])?;
+        rate_limit::consume(read as u64);
+        copied += read as u64;
+    }
+
+    if copied < size {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            format!("{} shrank while being archived", path.to_string_lossy()),
+        ));
+    }
+    if file.into_inner().read(&mut buffer[..1])? > 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{} grew while being archived", path.to_string_lossy()),
+        ));
+    }
+    Ok(())
+}
+
+fn mtime(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
+
+/// The permission bits, with a usual default where there are none.
+fn mode(metadata: &Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o7777
+    }
+    #[cfg(not(unix))]
+    {
+        if metadata.is_dir() {
+            0o755
+        } else {
+            0o644
+        }
+    }
+}
+
+fn owner(metadata: &Metadata) -> (u64, u64) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        (u64::from(metadata.uid()), u64::from(metadata.gid()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        (0, 0)
+    }
+}
+
+const TAR_BLOCK: usize = 512;
+
+/// Writes `members` as a POSIX tar, with PAX headers for what ustar cannot hold.
+fn write_tar<W: Write>(mut out: W, members: &[Member]) -> Result<W, std::io::Error> {
+    for (name, path, metadata) in members {
+        let file_type = metadata.file_type();
+        let (kind, name, link, size) = if file_type.is_symlink() {
+            let target = std::fs::read_link(path)?;
+            (b'2', name.clone(), target.to_string_lossy().to_string(), 0)
+        } else if file_type.is_dir() {
+            (b'5', format!("{name}/"), String::new(), 0)
+        } else if file_type.is_file() {
+            (b'0', name.clone(), String::new(), metadata.len())
+        } else {
+            return Err(unsupported(path));
+        };
+
+        let mut pax = String::new();
+        if name.len() > 100 {
+            pax.push_str(&pax_record("path", &name));
+        }
+        if link.len() > 100 {
+            pax.push_str(&pax_record("linkpath", &link));
+        }
+        if size >= 0o77777777777 {
+            pax.push_str(&pax_record("size", &size.to_string()));
+        }
+        if !pax.is_empty() {
+            let header = tar_header("././@PaxHeader", b'x', "", pax.len() as u64, metadata);
+            out.write_all(&header)?;
+            out.write_all(pax.as_bytes())?;
+            out.write_all(&vec![0; padding(pax.len() as u64)])?;
+        }
+
+        out.write_all(&tar_header(&name, kind, &link, size, metadata))?;
+        if kind == b'0' {
+            copy_file(path, size, |bytes| out.write_all(bytes))?;
+            out.write_all(&vec![0; padding(size)])?;
+        }
+    }
+
+    out.write_all(&[0; 2 * TAR_BLOCK])?;
+    Ok(out)
+}
+
+fn padding(size: u64) -> usize {
+    (TAR_BLOCK - (size % TAR_BLOCK as u64) as usize) % TAR_BLOCK
+}
+
+/// A `key=value` PAX record, which starts with its own length in bytes.
+fn pax_record(key: &str, value: &str) -> String {
+    let body = key.len() + value.len() + 3;
+    let mut length = body + 1;
+    while length != body + length.to_string().len() {
+        length = body + length.to_string().len();
+    }
+    format!("{length} {key}={value}\n")
+}
+
+/// A ustar header, with names cut to the fields when a PAX header holds them in full.
+fn tar_header(name: &str, kind: u8, link: &str, size: u64, metadata: &Metadata) -> Vec<u8> {
+    let mut header = vec![0; TAR_BLOCK];
+    let field = |header: &mut [u8], start: usize, length: usize, value: &[u8]| {
+        let used = value.len().min(length);
+        header[start..start + used].copy_from_slice(&value[..used]);
+    };
+    let octal = |header: &mut [u8], start: usize, length: usize, value: u64| {
+        let digits = format!("{:0width$o}", value, width = length - 1);
+        let digits = &digits.as_bytes()[digits.len().saturating_sub(length - 1)..];
+        header[start..start + length - 1].copy_from_slice(digits);
+    };
+
+    let (uid, gid) = owner(metadata);
+    field(&mut header, 0, 100, name.as_bytes());
+    octal(&mut header, 100, 8, u64::from(mode(metadata)));
+    octal(&mut header, 108, 8, uid.min(0o7777777));
+    octal(&mut header, 116, 8, gid.min(0o7777777));
+    octal(&mut header, 124, 12, size.min(0o77777777777));
+    octal(&mut header, 136, 12, mtime(metadata).min(0o77777777777));
+    header[156] = kind;
+    field(&mut header, 157, 100, link.as_bytes());
+    field(&mut header, 257, 8, b"ustar\x0000");
+
+    // The checksum is summed with its own field as spaces.
+    header[148..156].fill(b' ');
+    let checksum: u64 = header.iter().map(|byte| u64::from(*byte)).sum();
+    octal(&mut header, 148, 7, checksum);
+    header
+}
+
+/// A writer keeping count of the bytes written to it, for the offsets zip records.
+struct Counting<W: Write> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Counting<W> {
+    fn new(inner: W) -> Self {
+        Counting { inner, written: 0 }
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Zip flags: sizes and CRC follow the data, names are UTF-8.
+const DATA_DESCRIPTOR: u16 = 1 << 3;
+const UTF8_NAMES: u16 = 1 << 11;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+fn too_large_for_zip() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "the zip would need zip64 for 4 GiB or 65536 entries and more, use .tar.gz instead",
+    )
+}
+
+fn to_u32(value: u64) -> Result<u32, std::io::Error> {
+    u32::try_from(value).map_err(|_| too_large_for_zip())
+}
+
+/// Writes `members` as a zip, files deflated and folders and symlinks stored.
+fn write_zip<W: Write>(
+    mut out: Counting<W>,
+    members: &[Member],
+) -> Result<Counting<W>, std::io::Error> {
+    let mut central = Vec::new();
+    for (name, path, metadata) in members {
+        let file_type = metadata.file_type();
+        let offset = to_u32(out.written)?;
+        let (time, date) = dos_time(mtime(metadata));
+
+        // Stored entries are known in full up front, files are streamed.
+        let (name, kind, stored) = if file_type.is_symlink() {
+            let target = std::fs::read_link(path)?;
+            let target = target.to_string_lossy().into_owned().into_bytes();
+            (name.clone(), 0o120000, Some(target))
+        } else if file_type.is_dir() {
+            (format!("{name}/"), 0o040000, Some(Vec::new()))
+        } else if file_type.is_file() {
+            (name.clone(), 0o100000, None)
+        } else {
+            return Err(unsupported(path));
+        };
+
+        let (flags, method, crc, compressed, size) = match &stored {
+            Some(content) => {
+                let mut crc = Crc32::default();
+                crc.update(content);
+                let size = to_u32(content.len() as u64)?;
+                write_local_header(
+                    &mut out,
+                    &name,
+                    UTF8_NAMES,
+                    STORED,
+                    time,
+                    date,
+                    crc.value(),
+                    size,
+                    size,
+                )?;
+                out.write_all(content)?;
+                (UTF8_NAMES, STORED, crc.value(), size, size)
+            }
+            None => {
+                let flags = UTF8_NAMES | DATA_DESCRIPTOR;
+                write_local_header(&mut out, &name, flags, DEFLATED, time, date, 0, 0, 0)?;
+                let start = out.written;
+                let mut crc = Crc32::default();
+                let mut deflater = Deflater::new(&mut out);
+                copy_file(path, metadata.len(), |bytes| {
+                    crc.update(bytes);
+                    deflater.write_all(bytes)
+                })?;
+                deflater.finish()?;
+                let compressed = to_u32(out.written - start)?;
+                let size = to_u32(metadata.len())?;
+
+                out.write_all(&0x0807_4B50u32.to_le_bytes())?;
+                out.write_all(&crc.value().to_le_bytes())?;
+                out.write_all(&compressed.to_le_bytes())?;
+                out.write_all(&size.to_le_bytes())?;
+                (flags, DEFLATED, crc.value(), compressed, size)
+            }
+        };
+
+        // Made by Unix, so the external attributes hold the mode in their upper half.
+        central.extend_from_slice(&0x0201_4B50u32.to_le_bytes());
+        central.extend_from_slice(&(3u16 << 8 | 20).to_le_bytes());
+        central.extend_from_slice(&20u16.to_le_bytes());
+        central.extend_from_slice(&flags.to_le_bytes());
+        central.extend_from_slice(&method.to_le_bytes());
+        central.extend_from_slice(&time.to_le_bytes());
+        central.extend_from_slice(&date.to_le_bytes());
+        central.extend_from_slice(&crc.to_le_bytes());
+        central.extend_from_slice(&compressed.to_le_bytes());
+        central.extend_from_slice(&size.to_le_bytes());
+        central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        // Extra field, comment, disk number and internal attributes.
+        central.extend_from_slice(&[0; 8]);
+        let dos_directory = if kind == 0o040000 { 0x10 } else { 0 };
+        let external = (kind | mode(metadata)) << 16 | dos_directory;
+        central.extend_from_slice(&external.to_le_bytes());
+        central.extend_from_slice(&offset.to_le_bytes());
+        central.extend_from_slice(name.as_bytes());
+    }
+
+    let count = u16::try_from(members.len()).map_err(|_| too_large_for_zip())?;
+    let central_offset = to_u32(out.written)?;
+    out.write_all(&central)?;
+    let central_size = to_u32(central.len() as u64)?;
+    to_u32(out.written)?;
+
+    out.write_all(&0x0605_4B50u32.to_le_bytes())?;
+    out.write_all(&[0; 4])?;
+    out.write_all(&count.to_le_bytes())?;
+    out.write_all(&count.to_le_bytes())?;
+    out.write_all(&central_size.to_le_bytes())?;
+    out.write_all(&central_offset.to_le_bytes())?;
+    out.write_all(&[0; 2])?;
+    Ok(out)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_local_header(
+    out: &mut impl Write,
+    name: &str,
+    flags: u16,
+    method: u16,
+    time: u16,
+    date: u16,
+    crc: u32,
+    compressed: u32,
+    size: u32,
+) -> Result<(), std::io::Error> {
+    let name_length = u16::try_from(name.len()).map_err(|_| too_large_for_zip())?;
+    out.write_all(&0x0403_4B50u32.to_le_bytes())?;
+    out.write_all(&20u16.to_le_bytes())?;
+    out.write_all(&flags.to_le_bytes())?;
+    out.write_all(&method.to_le_bytes())?;
+    out.write_all(&time.to_le_bytes())?;
+    out.write_all(&date.to_le_bytes())?;
+    out.write_all(&crc.to_le_bytes())?;
+    out.write_all(&compressed.to_le_bytes())?;
+    out.write_all(&size.to_le_bytes())?;
+    out.write_all(&name_length.to_le_bytes())?;
+    out.write_all(&0u16.to_le_bytes())?;
+    out.write_all(name.as_bytes())
+}
+
+/// The MS-DOS time and date zip stores, in UTC and clamped to the 1980 to 2107 it covers.
+fn dos_time(seconds: u64) -> (u16, u16) {
+    let (year, month, day) = crate::units::civil_from_days((seconds / 86400) as i64);
+    if year < 1980 {
+        return (0, 1 << 5 | 1);
+    }
+    let year = year.min(2107);
+    let second_of_day = seconds % 86400;
+
+    let time =
+        (second_of_day / 3600) << 11 | (second_of_day / 60 % 60) << 5 | (second_of_day % 60 / 2);
+    let date = (year - 1980) << 9 | month << 5 | day;
+    (time as u16, date as u16)
+}
+
+#[cfg(test)]
+mod copy_file_should {
+    use super::*;
+
+    #[test]
+    fn fail_when_the_file_changed_size_since_the_scan() {
+        let path = PathBuf::from("./data/archive-copy-file.txt");
+        std::fs::write(&path, "twelve bytes").unwrap();
+
+        let copy = |size: u64| {
+            let mut copied = Vec::new();
+            let result = copy_file(&path, size, |bytes| {
+                copied.extend_from_slice(bytes);
+                Ok(())
+            });
+            (result.map_err(|e| e.kind()), copied)
+        };
+        let same = copy(12);
+        let grown = copy(6);
+        let shrunk = copy(20);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(same, (Ok(()), b"twelve bytes".to_vec()));
+        assert_eq!(grown.0, Err(std::io::ErrorKind::InvalidData));
+        assert_eq!(shrunk.0, Err(std::io::ErrorKind::UnexpectedEof));
+    }
+}
+
+#[cfg(test)]
+mod archive_tree_should {
+    use std::{process::Command, sync::atomic::AtomicU8};
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Creates a target with a nested file, an empty folder and, on Unix, a symlink.
+    fn create_target() -> PathBuf {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/archive-dir-{unique}"));
+        std::fs::create_dir_all(dir.join("nested/empty")).unwrap();
+        std::fs::write(dir.join("top.txt"), "top").unwrap();
+        std::fs::write(dir.join("nested/log.txt"), "a line of log\n".repeat(500)).unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("../top.txt", dir.join("nested/link")).unwrap();
+        dir
+    }
+
+    /// Reads the names and contents of the files in a tar back.
+    fn read_tar(mut tar: &[u8]) -> Vec<(String, u8, Vec<u8>)> {
+        let mut members = Vec::new();
+        while tar.len() >= TAR_BLOCK && tar[..TAR_BLOCK].iter().any(|byte| *byte != 0) {
+            let header = &tar[..TAR_BLOCK];
+            let text = |range: std::ops::Range<usize>| {
+                String::from_utf8_lossy(&header[range])
+                    .trim_end_matches(['\0', ' '])
+                    .to_string()
+            };
+            let size = u64::from_str_radix(&text(124..135), 8).unwrap() as usize;
+            let content = tar[TAR_BLOCK..TAR_BLOCK + size].to_vec();
+            members.push((text(0..100), header[156], content));
+            tar = &tar[TAR_BLOCK + size + padding(size as u64)..];
+        }
+        members
+    }
+
+    #[test]
+    fn pack_every_entry_below_the_target_name() {
+        let dir = create_target();
+        let archive = dir.with_file_name(format!(
+            "{}.tar",
+            dir.file_name().unwrap().to_string_lossy()
+        ));
+
+        let size = archive_tree(&dir, &archive).unwrap();
+        let tar = std::fs::read(&archive).unwrap();
+        std::fs::remove_file(&archive).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let base = dir.file_name().unwrap().to_string_lossy().to_string();
+        let members = read_tar(&tar);
+        let find = |name: &str| {
+            members
+                .iter()
+                .find(|(member, _, _)| *member == format!("{base}/{name}"))
+                .unwrap_or_else(|| panic!("{name} is missing from {members:?}"))
+        };
+        assert_eq!(size, tar.len() as u64);
+        assert_eq!(find("top.txt").2, b"top");
+        assert_eq!(
+            find("nested/log.txt").2,
+            "a line of log\n".repeat(500).as_bytes()
+        );
+        assert_eq!(find("nested/empty/").1, b'5');
+        #[cfg(unix)]
+        assert_eq!(find("nested/link").1, b'2');
+    }
+
+    #[test]
+    fn leave_nothing_behind_when_it_fails() {
+        let dir = create_target();
+        let archive = dir.join("inside.tar");
+        std::fs::write(&archive, "taken").unwrap();
+
+        let result = archive_tree(&dir, &archive);
+        let partial_left = dir.join("inside.tar.partial").exists();
+        let kept = std::fs::read_to_string(&archive).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            result.unwrap_err().kind(),
+            std::io::ErrorKind::AlreadyExists
+        );
+        assert!(!partial_left);
+        assert_eq!(kept, "taken");
+    }
+
+    /// Extracts an archive into `into` with the system `tar` or `unzip`, false without them.
+    fn extract(tool: &str, args: &[&str], archive: &Path, into: &Path) -> bool {
+        std::fs::create_dir_all(into).unwrap();
+        let Ok(output) = Command::new(tool)
+            .args(args)
+            .arg(archive)
+            .current_dir(into)
+            .output()
+        else {
+            return false;
+        };
+        assert!(
+            output.status.success(),
+            "{tool} rejected the archive: {output:?}"
+        );
+        true
+    }
+
+    #[test]
+    fn write_archives_tar_and_unzip_read() {
+        let dir = create_target();
+        let base = dir.file_name().unwrap().to_string_lossy().to_string();
+        let archives = [("tar", ["-xzf"], "tgz"), ("unzip", ["-q"], "zip")];
+
+        for (tool, args, extension) in archives {
+            let archive = std::fs::canonicalize(&dir)
+                .unwrap()
+                .with_file_name(format!("{base}.{extension}"));
+            let into = dir.with_file_name(format!("{base}-{extension}"));
+            archive_tree(&dir, &archive).unwrap();
+
+            let extracted = extract(tool, &args, &archive, &into);
+            let read = |name: &str| std::fs::read_to_string(into.join(&base).join(name)).ok();
+            let (top, log) = (read("top.txt"), read("nested/log.txt"));
+            let empty = into.join(&base).join("nested/empty").is_dir();
+            let link = std::fs::read_link(into.join(&base).join("nested/link")).ok();
+            std::fs::remove_file(&archive).unwrap();
+            std::fs::remove_dir_all(&into).unwrap();
+            if !extracted {
+                continue;
+            }
+
+            assert_eq!(top.as_deref(), Some("top"), "{tool}");
+            assert_eq!(log, Some("a line of log\n".repeat(500)), "{tool}");
+            assert!(empty, "{tool}");
+            #[cfg(unix)]
+            assert_eq!(link, Some(PathBuf::from("../top.txt")), "{tool}");
+            #[cfg(not(unix))]
+            let _ = link;
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
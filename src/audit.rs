@@ -0,0 +1,235 @@
+//! Permission audit that flags setuid, setgid, sticky and world-writable entries
+//! before they get deleted. Only Unix exposes these bits.
+//!
+//! Also the symlink report, listing every link in the tree with where it points.
+
+use std::path::{Path, PathBuf};
+
+use crate::scan::Entry;
+
+const SETUID: u32 = 0o4000;
+const SETGID: u32 = 0o2000;
+const STICKY: u32 = 0o1000;
+const WORLD_WRITABLE: u32 = 0o0002;
+
+/// An entry whose permissions warrant a second look before deleting it.
+pub(crate) struct Finding {
+    pub(crate) path: PathBuf,
+    pub(crate) mode: u32,
+}
+
+impl Finding {
+    pub(crate) fn reasons(&self) -> Vec<&'static str> {
+        [
+            (SETUID, "setuid"),
+            (SETGID, "setgid"),
+            (STICKY, "sticky"),
+            (WORLD_WRITABLE, "world-writable"),
+        ]
+        .into_iter()
+        .filter(|(bit, _)| self.mode & bit != 0)
+        .map(|(_, reason)| reason)
+        .collect()
+    }
+}
+
+/// Returns the scanned entries with flagged permission bits. Symlinks are skipped
+/// since their own mode is meaningless.
+#[cfg(unix)]
+pub(crate) fn audit_permissions(entries: &[Entry]) -> Vec<Finding> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let flagged = SETUID | SETGID | STICKY | WORLD_WRITABLE;
+
+    entries
+        .iter()
+        .filter(|e| !e.metadata.file_type().is_symlink())
+        .map(|e| Finding {
+            path: e.path.clone(),
+            mode: e.metadata.permissions().mode() & 0o7777,
+        })
+        .filter(|f| f.mode & flagged != 0)
+        .collect()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn audit_permissions(_entries: &[Entry]) -> Vec<Finding> {
+    println!("Permission audit is only supported on Unix, skipping.");
+    Vec::new()
+}
+
+/// Where a symlink found in the tree leads.
+#[derive(Debug, PartialEq)]
+pub(crate) enum LinkKind {
+    /// Resolves to something inside the tree, which goes along with it.
+    Internal,
+    /// Resolves to something outside the tree, which is kept.
+    External,
+    /// Resolves to nothing.
+    Dangling,
+}
+
+impl std::fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinkKind::Internal => write!(f, "inside the tree"),
+            LinkKind::External => write!(f, "outside the tree"),
+            LinkKind::Dangling => write!(f, "dangling"),
+        }
+    }
+}
+
+/// A symlink in the tree, with its target as written in the link.
+pub(crate) struct LinkFinding {
+    pub(crate) path: PathBuf,
+    pub(crate) target: PathBuf,
+    pub(crate) kind: LinkKind,
+}
+
+/// Returns every scanned symlink, telling apart links into `root`, links out of it and
+/// dangling ones by resolving them. `root` has to be canonical.
+pub(crate) fn audit_symlinks(root: &Path, entries: &[Entry]) -> Vec<LinkFinding> {
+    entries
+        .iter()
+        .filter(|e| e.metadata.file_type().is_symlink())
+        .map(|e| {
+            let target = std::fs::read_link(&e.path).unwrap_or_default();
+            let kind = match std::fs::canonicalize(&e.path) {
+                Ok(resolved) if resolved.starts_with(root) => LinkKind::Internal,
+                Ok(_) => LinkKind::External,
+                Err(_) => LinkKind::Dangling,
+            };
+            LinkFinding {
+                path: e.path.clone(),
+                target,
+                kind,
+            }
+        })
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod audit_symlinks_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+    use crate::scan::scan_dir;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn tell_internal_external_and_dangling_links_apart() {
+        use std::os::unix::fs::symlink;
+
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/audit-links-dir-{unique}");
+        let outside = format!("{dir}-outside");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        let root = std::fs::canonicalize(&dir).unwrap();
+        let outside = std::fs::canonicalize(&outside).unwrap();
+
+        std::fs::File::create(root.join("nested/file.txt")).unwrap();
+        symlink("nested/file.txt", root.join("internal")).unwrap();
+        symlink(&outside, root.join("nested/external")).unwrap();
+        symlink("missing.txt", root.join("dangling")).unwrap();
+
+        let entries = scan_dir(&root).unwrap();
+        let mut findings: Vec<(String, PathBuf, LinkKind)> = audit_symlinks(&root, &entries)
+            .into_iter()
+            .map(|f| {
+                let name = f.path.file_name().unwrap().to_string_lossy().to_string();
+                (name, f.target, f.kind)
+            })
+            .collect();
+        findings.sort_by(|a, b| a.0.cmp(&b.0));
+
+        std::fs::remove_dir_all(&root).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+
+        assert_eq!(
+            findings,
+            vec![
+                (
+                    "dangling".to_string(),
+                    PathBuf::from("missing.txt"),
+                    LinkKind::Dangling
+                ),
+                ("external".to_string(), outside, LinkKind::External),
+                (
+                    "internal".to_string(),
+                    PathBuf::from("nested/file.txt"),
+                    LinkKind::Internal
+                ),
+            ]
+        );
+    }
+}
+
+#[cfg(all(test, unix))]
+mod audit_permissions_should {
+    use std::{os::unix::fs::PermissionsExt, path::Path, sync::atomic::AtomicU8};
+
+    use super::*;
+    use crate::scan::scan_dir;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn create_with_mode(path: &str, mode: u32) {
+        std::fs::File::create(path).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn flag_setuid_setgid_and_world_writable_files() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/audit-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        create_with_mode(&format!("{dir}/plain.txt"), 0o644);
+        create_with_mode(&format!("{dir}/setuid"), 0o4755);
+        create_with_mode(&format!("{dir}/setgid"), 0o2755);
+        create_with_mode(&format!("{dir}/shared.txt"), 0o666);
+
+        let entries = scan_dir(Path::new(&dir)).unwrap();
+        let mut findings: Vec<(String, Vec<&str>)> = audit_permissions(&entries)
+            .iter()
+            .map(|f| {
+                let name = f.path.file_name().unwrap().to_string_lossy().to_string();
+                (name, f.reasons())
+            })
+            .collect();
+        findings.sort();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            findings,
+            vec![
+                ("setgid".to_string(), vec!["setgid"]),
+                ("setuid".to_string(), vec!["setuid"]),
+                ("shared.txt".to_string(), vec!["world-writable"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignore_ordinary_permissions() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/audit-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::set_permissions(
+            format!("{dir}/nested"),
+            std::fs::Permissions::from_mode(0o755),
+        )
+        .unwrap();
+
+        create_with_mode(&format!("{dir}/nested/plain.txt"), 0o600);
+
+        let entries = scan_dir(Path::new(&dir)).unwrap();
+        let findings = audit_permissions(&entries);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(findings.is_empty(), "Ordinary permissions were flagged");
+    }
+}
@@ -0,0 +1,264 @@
+//! `--benchmark`: deletes a tree, recreates it and deletes it again a number of times,
+//! timing only the deletes.
+//!
+//! Recreating the tree is a separate, untimed step, either a user supplied command or a
+//! copy of a snapshot folder, so the figures are plain `remove_dir_all` throughput.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{exec::ExecTemplate, scan};
+
+/// How the tree is recreated between two timed deletes.
+pub(crate) enum Regenerate {
+    /// Run this command, with `{}` standing for the target, which has to create it.
+    Command(ExecTemplate),
+    /// Copy this folder to the target.
+    Snapshot(PathBuf),
+}
+
+/// One timed delete.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Timing {
+    pub(crate) files: u64,
+    pub(crate) elapsed: Duration,
+}
+
+impl Timing {
+    fn files_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.files as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Deletes `dir` `iterations` times, recreating it as `regenerate` says before every
+/// delete but the first, and writes a line per delete to `output`. The target is gone
+/// afterwards, as after any other run.
+pub(crate) fn run(
+    dir: &Path,
+    iterations: u32,
+    regenerate: &Regenerate,
+    output: &mut impl Write,
+) -> Result<Vec<Timing>, std::io::Error> {
+    if let Regenerate::Snapshot(snapshot) = regenerate {
+        if std::fs::canonicalize(snapshot)?.starts_with(dir) {
+            return Err(std::io::Error::other(format!(
+                "the snapshot {} is inside the target and would be deleted with it",
+                snapshot.to_string_lossy()
+            )));
+        }
+    }
+
+    let mut timings = Vec::new();
+    for iteration in 1..=iterations {
+        if iteration > 1 {
+            recreate(dir, regenerate)?;
+        }
+
+        let files = count_files(dir)?;
+        let started = Instant::now();
+        std::fs::remove_dir_all(dir)?;
+        let timing = Timing {
+            files,
+            elapsed: started.elapsed(),
+        };
+
+        writeln!(
+            output,
+            "Iteration {iteration}/{iterations}: {} files in {:.3}s ({:.0} files/s)",
+            timing.files,
+            timing.elapsed.as_secs_f64(),
+            timing.files_per_second()
+        )?;
+        timings.push(timing);
+    }
+
+    Ok(timings)
+}
+
+/// The min, median and max delete times and the median throughput, e.g.
+/// `min 0.102s, median 0.118s, max 0.164s, median 84746 files/s over 5 iterations`.
+pub(crate) fn summarize(timings: &[Timing]) -> String {
+    let mut elapsed: Vec<Duration> = timings.iter().map(|timing| timing.elapsed).collect();
+    elapsed.sort();
+    let mut rates: Vec<f64> = timings.iter().map(Timing::files_per_second).collect();
+    rates.sort_by(f64::total_cmp);
+
+    format!(
+        "min {:.3}s, median {:.3}s, max {:.3}s, median {:.0} files/s over {} iterations",
+        elapsed.first().copied().unwrap_or_default().as_secs_f64(),
+        median(
+            &elapsed
+                .iter()
+                .map(Duration::as_secs_f64)
+                .collect::<Vec<_>>()
+        ),
+        elapsed.last().copied().unwrap_or_default().as_secs_f64(),
+        median(&rates),
+        timings.len()
+    )
+}
+
+/// The middle of `sorted`, or the mean of the two middle values for an even count.
+fn median(sorted: &[f64]) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        len if len % 2 == 0 => (sorted[len / 2 - 1] + sorted[len / 2]) / 2.0,
+        len => sorted[len / 2],
+    }
+}
+
+fn recreate(dir: &Path, regenerate: &Regenerate) -> Result<(), std::io::Error> {
+    match regenerate {
+        Regenerate::Command(command) => command.run(dir)?,
+        Regenerate::Snapshot(snapshot) => copy_tree(snapshot, dir)?,
+    }
+
+    if !dir.is_dir() {
+        return Err(std::io::Error::other(format!(
+            "regenerating did not recreate {}",
+            dir.to_string_lossy()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Copies the folder `from` to `to`, which must not exist yet. Symlinks are copied as
+/// links on Unix and skipped elsewhere.
+fn copy_tree(from: &Path, to: &Path) -> Result<(), std::io::Error> {
+    std::fs::create_dir(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            #[cfg(unix)]
+            std::os::unix::fs::symlink(std::fs::read_link(entry.path())?, &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn count_files(dir: &Path) -> Result<u64, std::io::Error> {
+    let entries = scan::scan_dir(dir)?;
+    Ok(entries
+        .iter()
+        .filter(|entry| !entry.metadata.is_dir())
+        .count() as u64)
+}
+
+#[cfg(test)]
+mod run_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Creates a snapshot with two files and a nested one, and a copy of it as the target.
+    fn create_snapshot_and_target() -> (PathBuf, PathBuf) {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let snapshot = PathBuf::from(format!("./data/benchmark-dir-{unique}-snapshot"));
+        std::fs::create_dir_all(snapshot.join("nested")).unwrap();
+        for file in ["a.txt", "b.txt", "nested/c.txt"] {
+            std::fs::write(snapshot.join(file), "x").unwrap();
+        }
+
+        let target = PathBuf::from(format!("./data/benchmark-dir-{unique}"));
+        copy_tree(&snapshot, &target).unwrap();
+        (snapshot, std::fs::canonicalize(target).unwrap())
+    }
+
+    #[test]
+    fn delete_the_tree_again_after_copying_the_snapshot() {
+        let (snapshot, target) = create_snapshot_and_target();
+        let mut output = Vec::new();
+
+        let timings = run(
+            &target,
+            2,
+            &Regenerate::Snapshot(snapshot.clone()),
+            &mut output,
+        )
+        .unwrap();
+        let snapshot_kept = snapshot.join("nested/c.txt").exists();
+        std::fs::remove_dir_all(&snapshot).unwrap();
+
+        assert_eq!(timings.len(), 2);
+        assert!(timings.iter().all(|timing| timing.files == 3));
+        assert!(!target.exists());
+        assert!(snapshot_kept);
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2, "{output}");
+        assert!(
+            lines[1].starts_with("Iteration 2/2: 3 files in "),
+            "{output}"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn delete_the_tree_again_after_running_the_command() {
+        let (snapshot, target) = create_snapshot_and_target();
+        std::fs::remove_dir_all(&snapshot).unwrap();
+        let command = crate::exec::parse_template("mkdir {}").unwrap();
+
+        let timings = run(&target, 2, &Regenerate::Command(command), &mut Vec::new()).unwrap();
+
+        assert_eq!(timings.iter().map(|t| t.files).collect::<Vec<_>>(), [3, 0]);
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn refuse_a_snapshot_inside_the_target() {
+        let (snapshot, target) = create_snapshot_and_target();
+        std::fs::remove_dir_all(&snapshot).unwrap();
+
+        let result = run(
+            &target,
+            2,
+            &Regenerate::Snapshot(target.join("nested")),
+            &mut Vec::new(),
+        );
+        let target_kept = target.exists();
+        std::fs::remove_dir_all(&target).unwrap();
+
+        assert!(result.is_err());
+        assert!(target_kept);
+    }
+}
+
+#[cfg(test)]
+mod summarize_should {
+    use super::*;
+
+    #[test]
+    fn report_min_median_and_max() {
+        let timings: Vec<Timing> = [400, 100, 200, 300]
+            .iter()
+            .map(|millis| Timing {
+                files: 1000,
+                elapsed: Duration::from_millis(*millis),
+            })
+            .collect();
+
+        assert_eq!(
+            summarize(&timings),
+            "min 0.100s, median 0.250s, max 0.400s, median 4167 files/s over 4 iterations"
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Resumable whole-tree deletion. The names of top-level entries that are already
+//! gone get recorded in a checkpoint file so an interrupted run can pick up where
+//! it stopped.
+
+use std::{
+    collections::BTreeSet,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::interrupt;
+
+const HEADER: &str = "rm-dir checkpoint v1";
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The top-level entries of `root` deleted so far.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Checkpoint {
+    root: PathBuf,
+    pub(crate) done: BTreeSet<String>,
+}
+
+impl Checkpoint {
+    pub(crate) fn new(root: &Path) -> Self {
+        Checkpoint {
+            root: root.to_path_buf(),
+            done: BTreeSet::new(),
+        }
+    }
+
+    /// Reads a checkpoint written by `save`, refusing one recorded for another root.
+    pub(crate) fn load(path: &Path, root: &Path) -> Result<Self, std::io::Error> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+
+        let invalid = |reason: String| std::io::Error::new(ErrorKind::InvalidData, reason);
+
+        if lines.next() != Some(HEADER) {
+            return Err(invalid(format!(
+                "{} is not a checkpoint file",
+                path.to_string_lossy()
+            )));
+        }
+
+        let recorded_root = lines
+            .next()
+            .and_then(|line| line.strip_prefix("root: "))
+            .map(unescape)
+            .ok_or_else(|| invalid(format!("{} has no root", path.to_string_lossy())))?;
+
+        if Path::new(&recorded_root) != root {
+            return Err(invalid(format!(
+                "{} was recorded for {}, not {}",
+                path.to_string_lossy(),
+                recorded_root,
+                root.to_string_lossy()
+            )));
+        }
+
+        Ok(Checkpoint {
+            root: root.to_path_buf(),
+            done: lines.map(unescape).collect(),
+        })
+    }
+
+    /// Writes to a temporary file next to `path` and renames it over, so a crash
+    /// never leaves a half written checkpoint behind.
+    pub(crate) fn save(&self, path: &Path) -> Result<(), std::io::Error> {
+        let mut contents = format!("{HEADER}\nroot: {}\n", escape(&self.root.to_string_lossy()));
+        for name in &self.done {
+            contents.push_str(&escape(name));
+            contents.push('\n');
+        }
+
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+
+        std::fs::write(&temp, contents)?;
+        std::fs::rename(&temp, path)
+    }
+}
+
+/// Deletes `root` one top-level entry at a time, skipping those already in the
+/// checkpoint. Progress is saved to `checkpoint_path` every second, when an entry fails
+/// and when Ctrl-C is pressed. Ctrl-C is only checked between top-level entries.
+/// The checkpoint file is removed once the whole tree is gone.
+pub(crate) fn remove_resumable(
+    root: &Path,
+    checkpoint: &mut Checkpoint,
+    checkpoint_path: &Path,
+) -> Result<(), std::io::Error> {
+    interrupt::install_handler();
+
+    let mut children = Vec::new();
+    for dir_entry in std::fs::read_dir(root)? {
+        let dir_entry = dir_entry?;
+        children.push((dir_entry.file_name(), dir_entry.file_type()?));
+    }
+
+    let mut last_save = Instant::now();
+
+    for (name, file_type) in children {
+        let name_key = name.to_string_lossy().to_string();
+        if checkpoint.done.contains(&name_key) {
+            continue;
+        }
+
+        if interrupt::is_interrupted() {
+            checkpoint.save(checkpoint_path)?;
+            return Err(std::io::Error::new(
+                ErrorKind::Interrupted,
+                format!(
+                    "Interrupted, progress saved to {}",
+                    checkpoint_path.to_string_lossy()
+                ),
+            ));
+        }
+
+        let path = root.join(&name);
+        let result = if file_type.is_dir() {
+            std::fs::remove_dir_all(&path)
+        } else {
+            std::fs::remove_file(&path)
+        };
+
+        if let Err(e) = result {
+            checkpoint.save(checkpoint_path)?;
+            return Err(e);
+        }
+
+        checkpoint.done.insert(name_key);
+
+        if last_save.elapsed() >= SAVE_INTERVAL {
+            checkpoint.save(checkpoint_path)?;
+            last_save = Instant::now();
+        }
+    }
+
+    if let Err(e) = std::fs::remove_dir(root) {
+        checkpoint.save(checkpoint_path)?;
+        return Err(e);
+    }
+
+    match std::fs::remove_file(checkpoint_path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+pub(crate) fn unescape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                result.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                result.push('\\');
+                chars.next();
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod checkpoint_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn unique_dir() -> String {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/checkpoint-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn round_trip_through_save_and_load() {
+        let dir = unique_dir();
+        let checkpoint_path = PathBuf::from(format!("{dir}.checkpoint"));
+        let root = std::fs::canonicalize(&dir).unwrap();
+
+        let mut checkpoint = Checkpoint::new(&root);
+        checkpoint.done.insert("plain".to_string());
+        checkpoint
+            .done
+            .insert("with\nnewline\\and slash".to_string());
+        checkpoint.save(&checkpoint_path).unwrap();
+
+        let loaded = Checkpoint::load(&checkpoint_path, &root).unwrap();
+
+        std::fs::remove_file(&checkpoint_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded, checkpoint);
+    }
+
+    #[test]
+    fn refuse_checkpoint_of_another_root() {
+        let dir = unique_dir();
+        let checkpoint_path = PathBuf::from(format!("{dir}.checkpoint"));
+
+        Checkpoint::new(Path::new("/somewhere/else"))
+            .save(&checkpoint_path)
+            .unwrap();
+        let result = Checkpoint::load(&checkpoint_path, Path::new(&dir));
+
+        std::fs::remove_file(&checkpoint_path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn skip_recorded_entries_and_remove_checkpoint_when_done() {
+        let dir = unique_dir();
+        let checkpoint_path = PathBuf::from(format!("{dir}.checkpoint"));
+        std::fs::create_dir_all(format!("{dir}/remaining/nested")).unwrap();
+        std::fs::File::create(format!("{dir}/remaining/nested/file.txt")).unwrap();
+        std::fs::File::create(format!("{dir}/file.txt")).unwrap();
+
+        let mut checkpoint = Checkpoint::new(Path::new(&dir));
+        checkpoint.done.insert("already-gone".to_string());
+        checkpoint.save(&checkpoint_path).unwrap();
+
+        remove_resumable(Path::new(&dir), &mut checkpoint, &checkpoint_path).unwrap();
+
+        assert!(!Path::new(&dir).exists(), "Dir was not removed");
+        assert!(!checkpoint_path.exists(), "Checkpoint was not cleaned up");
+    }
+}
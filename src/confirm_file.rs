@@ -0,0 +1,172 @@
+//! `--confirm-via-file`: takes the answers to prompts from a file a separate approval step
+//! creates, rather than from a terminal, for pipelines gated by a human elsewhere.
+//!
+//! Every answer is awaited by polling for the file. Once it exists and holds something,
+//! its contents are the answer and the file is deleted, so the next prompt, or the next
+//! run, waits for a fresh approval. An empty file counts as still being written. Past
+//! `--prompt-timeout` the `--prompt-timeout-default` goes instead, as on a terminal.
+
+use std::{
+    io::{BufRead, ErrorKind, Read, Write},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// How often the file is looked for.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Answers read from the file at `path`, one per prompt, noting on `notice` what is
+/// awaited.
+pub(crate) struct ConfirmFile<W: Write> {
+    path: PathBuf,
+    timeout: Option<Duration>,
+    default: String,
+    notice: W,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<W: Write> ConfirmFile<W> {
+    pub(crate) fn new(path: PathBuf, timeout: Option<Duration>, default: &str, notice: W) -> Self {
+        ConfirmFile {
+            path,
+            timeout,
+            default: default.to_string(),
+            notice,
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Waits for the file, then takes its contents and deletes it. None once the timeout
+    /// passes first.
+    fn wait_for_answer(&mut self) -> std::io::Result<Option<String>> {
+        let started = Instant::now();
+        loop {
+            match std::fs::read_to_string(&self.path) {
+                Ok(answer) if !answer.trim().is_empty() => {
+                    std::fs::remove_file(&self.path)?;
+                    return Ok(Some(answer));
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+
+            if self
+                .timeout
+                .is_some_and(|timeout| started.elapsed() >= timeout)
+            {
+                return Ok(None);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+impl<W: Write> Read for ConfirmFile<W> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<W: Write> BufRead for ConfirmFile<W> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.position >= self.pending.len() {
+            writeln!(
+                self.notice,
+                "\nWaiting for the answer in {}",
+                self.path.to_string_lossy()
+            )?;
+            let answer = match self.wait_for_answer()? {
+                Some(answer) => answer,
+                None => {
+                    writeln!(
+                        self.notice,
+                        "No answer within {:?}, going with the --prompt-timeout-default '{}'",
+                        self.timeout.unwrap_or_default(),
+                        self.default
+                    )?;
+                    self.default.clone()
+                }
+            };
+            self.pending = format!("{}\n", answer.trim()).into_bytes();
+            self.position = 0;
+        }
+
+        Ok(&self.pending[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.pending.len());
+    }
+}
+
+#[cfg(test)]
+mod confirm_file_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn confirm_path() -> PathBuf {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let path = PathBuf::from(format!("./data/confirm-file-{unique}"));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn read_answer(input: &mut impl BufRead) -> String {
+        let mut answer = String::new();
+        input.read_line(&mut answer).unwrap();
+        answer
+    }
+
+    #[test]
+    fn take_the_answer_once_the_file_appears_and_delete_it() {
+        let path = confirm_path();
+        let writer = {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(250));
+                std::fs::write(path, "y").unwrap();
+            })
+        };
+        let mut notice = Vec::new();
+        let mut input = ConfirmFile::new(path.clone(), None, "n", &mut notice);
+
+        let answer = read_answer(&mut input);
+        writer.join().unwrap();
+
+        assert_eq!(answer, "y\n");
+        assert!(!path.exists());
+        assert_eq!(
+            String::from_utf8(notice).unwrap(),
+            format!("\nWaiting for the answer in {}\n", path.to_string_lossy())
+        );
+    }
+
+    #[test]
+    fn go_with_the_default_when_no_file_appears_in_time() {
+        let path = confirm_path();
+        let mut notice = Vec::new();
+        let mut input = ConfirmFile::new(
+            path.clone(),
+            Some(Duration::from_millis(150)),
+            "n",
+            &mut notice,
+        );
+
+        let answer = read_answer(&mut input);
+
+        assert_eq!(answer, "n\n");
+        assert!(String::from_utf8(notice)
+            .unwrap()
+            .ends_with("No answer within 150ms, going with the --prompt-timeout-default 'n'\n"));
+    }
+}
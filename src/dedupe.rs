@@ -0,0 +1,151 @@
+//! `--case-preserving-dedupe`: collapses targets naming the same directory, such as
+//! `Build/` and `build` on a case-insensitive filesystem, so a batch deletes it once.
+//!
+//! Case sensitivity is a property of each filesystem, not of the platform, so it is
+//! probed in the folder holding every target: a probe file is created there and looked
+//! up again with its name upper-cased.
+
+use std::{
+    collections::HashMap,
+    path::{Component, Path, PathBuf},
+};
+
+/// A target dropped because an earlier one names the same directory.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Duplicate {
+    pub(crate) target: String,
+    pub(crate) same_as: String,
+}
+
+/// Returns `targets` without the ones naming a directory already named earlier, in the
+/// original order, along with what was dropped.
+pub(crate) fn dedupe_targets(targets: &[String]) -> (Vec<String>, Vec<Duplicate>) {
+    let mut insensitive_parents: HashMap<PathBuf, bool> = HashMap::new();
+    let mut seen: HashMap<String, &String> = HashMap::new();
+    let mut kept = Vec::new();
+    let mut duplicates = Vec::new();
+
+    for target in targets {
+        let path = normalize(Path::new(target));
+        let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let insensitive = *insensitive_parents
+            .entry(parent.clone())
+            .or_insert_with(|| is_case_insensitive(&parent).unwrap_or(false));
+
+        let key = path.to_string_lossy();
+        let key = if insensitive {
+            key.to_lowercase()
+        } else {
+            key.to_string()
+        };
+
+        match seen.get(&key) {
+            Some(same_as) => duplicates.push(Duplicate {
+                target: target.clone(),
+                same_as: (*same_as).clone(),
+            }),
+            None => {
+                seen.insert(key, target);
+                kept.push(target.clone());
+            }
+        }
+    }
+
+    (kept, duplicates)
+}
+
+/// True when names differing only in case find the same entry in `dir`. Errors when the
+/// probe file cannot be created, e.g. in a read-only or missing folder.
+pub(crate) fn is_case_insensitive(dir: &Path) -> Result<bool, std::io::Error> {
+    let probe = dir.join(format!(".rm-dir-case-probe-{}", std::process::id()));
+    std::fs::File::create(&probe)?;
+
+    let upper = dir.join(format!(".RM-DIR-CASE-PROBE-{}", std::process::id()));
+    let insensitive = std::fs::symlink_metadata(upper).is_ok();
+    std::fs::remove_file(&probe)?;
+
+    Ok(insensitive)
+}
+
+/// An absolute version of `path` without `.` components or trailing separators, so
+/// `./build/` and `build` compare equal. `..` is kept, as it may cross a symlink.
+fn normalize(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    absolute
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect()
+}
+
+#[cfg(test)]
+mod dedupe_targets_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn create_dirs(names: &[&str]) -> String {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/dedupe-dir-{unique}");
+        for name in names {
+            std::fs::create_dir_all(format!("{dir}/{name}")).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn collapse_the_same_path_written_differently() {
+        let dir = create_dirs(&["build"]);
+        let targets = vec![
+            format!("{dir}/build"),
+            format!("{dir}/./build/"),
+            format!("{dir}/other"),
+        ];
+
+        let (kept, duplicates) = dedupe_targets(&targets);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(kept, vec![targets[0].clone(), targets[2].clone()]);
+        assert_eq!(
+            duplicates,
+            vec![Duplicate {
+                target: targets[1].clone(),
+                same_as: targets[0].clone(),
+            }]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn keep_case_variants_on_a_case_sensitive_filesystem() {
+        let dir = create_dirs(&["build", "Build"]);
+        let targets = vec![format!("{dir}/Build"), format!("{dir}/build")];
+
+        let insensitive = is_case_insensitive(Path::new(&dir)).unwrap();
+        let (kept, duplicates) = dedupe_targets(&targets);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!insensitive);
+        assert_eq!(kept, targets);
+        assert!(duplicates.is_empty());
+    }
+
+    #[cfg(any(target_os = "macos", windows))]
+    #[test]
+    fn collapse_case_variants_on_a_case_insensitive_filesystem() {
+        let dir = create_dirs(&["build"]);
+        let targets = vec![format!("{dir}/Build/"), format!("{dir}/build")];
+
+        let insensitive = is_case_insensitive(Path::new(&dir)).unwrap();
+        let (kept, duplicates) = dedupe_targets(&targets);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(
+            insensitive,
+            "Expected the default filesystem to ignore case"
+        );
+        assert_eq!(kept, vec![targets[0].clone()]);
+        assert_eq!(duplicates.len(), 1);
+    }
+}
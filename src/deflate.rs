@@ -0,0 +1,387 @@
+//! Just enough DEFLATE (RFC 1951) and gzip (RFC 1952) writing for `--archive`, without a
+//! compression crate.
+//!
+//! Every block uses the fixed Huffman codes, with LZ77 matches found through hash chains
+//! over the last 32 KiB. That compresses text and build output well enough, if not as
+//! tightly as `gzip -6` with its dynamic codes. Input is compressed a block at a time, so
+//! any amount of it streams through in bounded memory.
+
+use std::io::Write;
+
+const WINDOW: usize = 32 * 1024;
+const BLOCK: usize = 64 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions with the same hash are tried for a longer match.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+const END_OF_BLOCK: u16 = 256;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+/// The CRC-32 gzip and zip check their contents with.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Crc32(u32);
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+impl Crc32 {
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        let mut crc = !self.0;
+        for byte in bytes {
+            crc = CRC_TABLE[((crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (crc >> 8);
+        }
+        self.0 = !crc;
+    }
+
+    pub(crate) fn value(self) -> u32 {
+        self.0
+    }
+}
+
+/// Writes bits least significant first, as DEFLATE packs them.
+struct BitWriter<W: Write> {
+    inner: W,
+    bits: u64,
+    count: u32,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> BitWriter<W> {
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.bits |= u64::from(value) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.buffer.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which goes most significant bit first.
+    fn write_code(&mut self, code: u32, length: u32) {
+        let reversed = code.reverse_bits() >> (32 - length);
+        self.write_bits(reversed, length);
+    }
+
+    fn drain(&mut self) -> std::io::Result<()> {
+        self.inner.write_all(&self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<W> {
+        if self.count > 0 {
+            self.buffer.push(self.bits as u8);
+        }
+        self.drain()?;
+        Ok(self.inner)
+    }
+}
+
+/// A raw DEFLATE stream of everything written to it, see `finish`.
+pub(crate) struct Deflater<W: Write> {
+    out: BitWriter<W>,
+    /// The end of what was compressed so far, for matches to refer back to.
+    window: Vec<u8>,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Deflater<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Deflater {
+            out: BitWriter {
+                inner,
+                bits: 0,
+                count: 0,
+                buffer: Vec::new(),
+            },
+            window: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Compresses what is left as the final block and returns the inner writer.
+    pub(crate) fn finish(mut self) -> std::io::Result<W> {
+        self.compress_block(true)?;
+        self.out.finish()
+    }
+
+    fn compress_block(&mut self, last: bool) -> std::io::Result<()> {
+        let data = [self.window.as_slice(), self.pending.as_slice()].concat();
+        let start = self.window.len();
+
+        self.out.write_bits(u32::from(last), 1);
+        // BTYPE 01, the fixed Huffman codes.
+        self.out.write_bits(1, 2);
+
+        let mut chains = Chains {
+            head: vec![usize::MAX; 1 << HASH_BITS],
+            previous: vec![usize::MAX; data.len()],
+        };
+        for position in 0..start {
+            chains.insert(&data, position);
+        }
+
+        let mut position = start;
+        while position < data.len() {
+            match chains.longest_match(&data, position) {
+                Some((length, distance)) => {
+                    self.write_match(length, distance);
+                    for covered in position..position + length {
+                        chains.insert(&data, covered);
+                    }
+                    position += length;
+                }
+                None => {
+                    self.write_symbol(u16::from(data[position]));
+                    chains.insert(&data, position);
+                    position += 1;
+                }
+            }
+        }
+        self.write_symbol(END_OF_BLOCK);
+        self.out.drain()?;
+
+        self.window = data[data.len().saturating_sub(WINDOW)..].to_vec();
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Writes a literal byte, the end of block or a length with its fixed code.
+    fn write_symbol(&mut self, symbol: u16) {
+        let symbol = u32::from(symbol);
+        let (code, length) = match symbol {
+            0..=143 => (0x30 + symbol, 8),
+            144..=255 => (0x190 + symbol - 144, 9),
+            256..=279 => (symbol - 256, 7),
+            _ => (0xC0 + symbol - 280, 8),
+        };
+        self.out.write_code(code, length);
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let code = LENGTH_BASE
+            .iter()
+            .rposition(|base| usize::from(*base) <= length)
+            .unwrap_or(0);
+        self.write_symbol(257 + code as u16);
+        self.out.write_bits(
+            (length - usize::from(LENGTH_BASE[code])) as u32,
+            u32::from(LENGTH_EXTRA[code]),
+        );
+
+        let code = DISTANCE_BASE
+            .iter()
+            .rposition(|base| usize::from(*base) <= distance)
+            .unwrap_or(0);
+        self.out.write_code(code as u32, 5);
+        self.out.write_bits(
+            (distance - usize::from(DISTANCE_BASE[code])) as u32,
+            u32::from(DISTANCE_EXTRA[code]),
+        );
+    }
+}
+
+impl<W: Write> Write for Deflater<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        if self.pending.len() >= BLOCK {
+            self.compress_block(false)?;
+        }
+        Ok(buf.len())
+    }
+
+    /// Blocks are only written once full, or by `finish`.
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Earlier positions in the data by the hash of the 3 bytes starting there, newest first.
+struct Chains {
+    head: Vec<usize>,
+    previous: Vec<usize>,
+}
+
+impl Chains {
+    fn insert(&mut self, data: &[u8], position: usize) {
+        if position + MIN_MATCH <= data.len() {
+            let hash = hash(&data[position..position + MIN_MATCH]);
+            self.previous[position] = self.head[hash];
+            self.head[hash] = position;
+        }
+    }
+
+    /// The longest earlier match for the data at `position`, as its length and distance.
+    fn longest_match(&self, data: &[u8], position: usize) -> Option<(usize, usize)> {
+        if position + MIN_MATCH > data.len() {
+            return None;
+        }
+        let limit = MAX_MATCH.min(data.len() - position);
+
+        let mut best: Option<(usize, usize)> = None;
+        let mut candidate = self.head[hash(&data[position..position + MIN_MATCH])];
+        for _ in 0..MAX_CHAIN {
+            if candidate == usize::MAX || position - candidate > WINDOW {
+                break;
+            }
+            let length = data[candidate..]
+                .iter()
+                .zip(&data[position..position + limit])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length >= MIN_MATCH && best.is_none_or(|(best, _)| length > best) {
+                best = Some((length, position - candidate));
+                if length == limit {
+                    break;
+                }
+            }
+            candidate = self.previous[candidate];
+        }
+        best
+    }
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let value = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+    (value.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// A gzip file of everything written to it, see `finish`.
+pub(crate) struct GzEncoder<W: Write> {
+    deflater: Deflater<W>,
+    crc: Crc32,
+    size: u64,
+}
+
+impl<W: Write> GzEncoder<W> {
+    pub(crate) fn new(mut inner: W) -> std::io::Result<Self> {
+        // Magic, deflate, no flags, no mtime, no extra flags, unknown OS.
+        inner.write_all(&[0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF])?;
+        Ok(GzEncoder {
+            deflater: Deflater::new(inner),
+            crc: Crc32::default(),
+            size: 0,
+        })
+    }
+
+    /// Ends the stream with its checksum and size, returning the inner writer.
+    pub(crate) fn finish(self) -> std::io::Result<W> {
+        let mut inner = self.deflater.finish()?;
+        inner.write_all(&self.crc.value().to_le_bytes())?;
+        inner.write_all(&(self.size as u32).to_le_bytes())?;
+        Ok(inner)
+    }
+}
+
+impl<W: Write> Write for GzEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.deflater.write(buf)?;
+        self.crc.update(&buf[..written]);
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.deflater.flush()
+    }
+}
+
+#[cfg(test)]
+mod gz_encoder_should {
+    use std::process::{Command, Stdio};
+
+    use super::*;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new()).unwrap();
+        // Odd sized writes, so blocks and matches cross write boundaries.
+        for chunk in data.chunks(7919) {
+            encoder.write_all(chunk).unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    /// Decompresses with the system gzip, or None without one.
+    fn gunzip(compressed: &[u8]) -> Option<Vec<u8>> {
+        let mut child = Command::new("gzip")
+            .arg("-dc")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut stdin = child.stdin.take().unwrap();
+        let compressed = compressed.to_vec();
+        let writer = std::thread::spawn(move || stdin.write_all(&compressed));
+        let output = child.wait_with_output().unwrap();
+        writer.join().unwrap().unwrap();
+        assert!(output.status.success(), "gzip rejected the stream");
+        Some(output.stdout)
+    }
+
+    #[test]
+    fn round_trip_through_gzip() {
+        let text: Vec<u8> = (0..20_000)
+            .flat_map(|i| format!("line {} of a build log\n", i % 997).into_bytes())
+            .collect();
+        let noise: Vec<u8> = (0u32..150_000)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+
+        for data in [Vec::new(), b"a".to_vec(), text.clone(), noise] {
+            let compressed = gzip(&data);
+            if let Some(decompressed) = gunzip(&compressed) {
+                assert_eq!(decompressed.len(), data.len());
+                assert!(
+                    decompressed == data,
+                    "Round trip of {} bytes differs",
+                    data.len()
+                );
+            }
+        }
+        assert!(gzip(&text).len() < text.len() / 4, "Text should compress");
+    }
+
+    #[test]
+    fn check_contents_with_the_standard_crc() {
+        let mut crc = Crc32::default();
+        crc.update(b"123456789");
+
+        assert_eq!(crc.value(), 0xCBF4_3926);
+    }
+}
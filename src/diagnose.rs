@@ -0,0 +1,150 @@
+//! `--diagnose`: explains a folder that is still there once everything in it was deleted,
+//! the "can't delete, files in use" mystery.
+//!
+//! On Linux the entries left below the folder are listed along with the processes holding
+//! them open, found by matching the device and inode of every `/proc/<pid>/fd/<n>` this
+//! user may read. A typical culprit is an `.nfsXXXX` file that NFS leaves in place of a
+//! deleted file some process still has open. Elsewhere there is no `/proc` to ask.
+//!
+//! The same lookup lets `--exclude-open-files` keep whatever is in use.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// An entry still below the folder after the delete.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Remaining {
+    pub(crate) path: PathBuf,
+    /// The processes holding it open.
+    pub(crate) holders: Vec<Holder>,
+}
+
+/// A process with an entry open.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Holder {
+    pub(crate) pid: u32,
+    /// The command name from `/proc/<pid>/comm`.
+    pub(crate) command: String,
+}
+
+/// The entries left below `dir`, each with the processes holding it open.
+#[cfg(target_os = "linux")]
+pub(crate) fn remaining_entries(dir: &Path) -> Result<Vec<Remaining>, std::io::Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let entries = crate::scan::scan_dir(dir)?;
+    let handles = open_handles();
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            let key = (entry.metadata.dev(), entry.metadata.ino());
+            let holders = handles
+                .iter()
+                .filter(|(open, _)| *open == key)
+                .map(|(_, holder)| holder.clone())
+                .collect();
+            Remaining {
+                path: entry.path,
+                holders,
+            }
+        })
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn remaining_entries(_dir: &Path) -> Result<Vec<Remaining>, std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "finding open handles needs /proc, which only Linux has",
+    ))
+}
+
+/// The device and inode of every file open in a process this user can look into. Empty
+/// outside Linux, where nothing tells which files are open.
+#[cfg(target_os = "linux")]
+pub(crate) fn open_files() -> HashSet<(u64, u64)> {
+    open_handles().into_iter().map(|(key, _)| key).collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn open_files() -> HashSet<(u64, u64)> {
+    HashSet::new()
+}
+
+/// The device and inode of every file open in a process this user can look into, once per
+/// process and file.
+#[cfg(target_os = "linux")]
+fn open_handles() -> Vec<((u64, u64), Holder)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(processes) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+
+    let mut handles = Vec::new();
+    for process in processes.flatten() {
+        let Some(pid) = process
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        else {
+            continue;
+        };
+        // Processes of other users, or gone by now, cannot be looked into.
+        let Ok(fds) = std::fs::read_dir(process.path().join("fd")) else {
+            continue;
+        };
+        let command = std::fs::read_to_string(process.path().join("comm"))
+            .map(|comm| comm.trim_end().to_string())
+            .unwrap_or_default();
+
+        let mut seen = std::collections::HashSet::new();
+        for fd in fds.flatten() {
+            // Follows the fd link to the open file itself, even when it was unlinked.
+            let Ok(metadata) = std::fs::metadata(fd.path()) else {
+                continue;
+            };
+            let key = (metadata.dev(), metadata.ino());
+            if seen.insert(key) {
+                let command = command.clone();
+                handles.push((key, Holder { pid, command }));
+            }
+        }
+    }
+    handles
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod remaining_entries_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn name_this_process_as_holding_an_open_file() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/diagnose-dir-{unique}"));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/closed.txt"), "x").unwrap();
+        let open = std::fs::File::create(dir.join("open.txt")).unwrap();
+
+        let remaining = remaining_entries(&dir).unwrap();
+        drop(open);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let holders_of = |name: &str| {
+            remaining
+                .iter()
+                .find(|entry| entry.path.ends_with(name))
+                .map(|entry| entry.holders.iter().map(|h| h.pid).collect::<Vec<_>>())
+        };
+        assert_eq!(remaining.len(), 3, "{remaining:?}");
+        assert_eq!(holders_of("open.txt"), Some(vec![std::process::id()]));
+        assert_eq!(holders_of("closed.txt"), Some(Vec::new()));
+    }
+}
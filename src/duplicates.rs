@@ -0,0 +1,202 @@
+//! `--dedupe`: keeps one copy of every set of byte-identical files in a tree and deletes
+//! the others, the oldest copy staying unless `--dedupe-keep newest` says otherwise.
+//!
+//! Files are grouped by size first, so only files sharing a size are read at all, then by
+//! a hash of their contents. A matching hash does not prove two files identical, so the
+//! files sharing one are compared byte by byte before any counts as a copy. Empty files
+//! are left out, as deleting them reclaims nothing, and so is a file that cannot be read,
+//! which is kept.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    hash::{DefaultHasher, Hasher},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use clap::ValueEnum;
+
+use crate::{filter, rate_limit, scan::Entry};
+
+/// Which copy of a set of duplicates `--dedupe` keeps, by mtime.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub(crate) enum DedupeKeep {
+    Oldest,
+    Newest,
+}
+
+/// The sets of byte-identical files found among those handed to `find_duplicates`.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Duplicates {
+    /// Sets of two or more byte-identical files.
+    pub(crate) sets: u64,
+    /// The one file kept of each set.
+    pub(crate) kept: HashSet<PathBuf>,
+    /// Every other file of each set, to be deleted.
+    pub(crate) copies: HashSet<PathBuf>,
+}
+
+/// Sorts the regular files among `files` into sets of byte-identical ones and picks the
+/// copy of each to keep.
+pub(crate) fn find_duplicates(files: &[Entry], keep: DedupeKeep) -> Duplicates {
+    let mut by_size: HashMap<u64, Vec<&Entry>> = HashMap::new();
+    for file in files
+        .iter()
+        .filter(|file| file.metadata.is_file() && file.metadata.len() > 0)
+    {
+        by_size.entry(file.metadata.len()).or_default().push(file);
+    }
+
+    let mut duplicates = Duplicates::default();
+    for same_size in by_size.into_values().filter(|files| files.len() > 1) {
+        let mut by_hash: HashMap<u64, Vec<&Entry>> = HashMap::new();
+        for file in same_size {
+            if let Ok(hash) = content_hash(&file.path) {
+                by_hash.entry(hash).or_default().push(file);
+            }
+        }
+
+        for same_hash in by_hash.into_values().filter(|files| files.len() > 1) {
+            for mut set in identical_sets(same_hash) {
+                if set.len() < 2 {
+                    continue;
+                }
+                set.sort_by_key(|file| (modified(file), &file.path));
+                let kept = match keep {
+                    DedupeKeep::Oldest => 0,
+                    DedupeKeep::Newest => set.len() - 1,
+                };
+
+                duplicates.sets += 1;
+                for (index, file) in set.into_iter().enumerate() {
+                    match index == kept {
+                        true => duplicates.kept.insert(file.path.clone()),
+                        false => duplicates.copies.insert(file.path.clone()),
+                    };
+                }
+            }
+        }
+    }
+
+    duplicates
+}
+
+/// Splits files of one size and hash into the sets whose contents really are equal, each
+/// file compared against the first file of every set so far.
+fn identical_sets(files: Vec<&Entry>) -> Vec<Vec<&Entry>> {
+    let mut sets: Vec<Vec<&Entry>> = Vec::new();
+    for file in files {
+        let mut unreadable = false;
+        let matching = sets.iter().position(|set| {
+            filter::same_contents(&set[0].path, &file.path).unwrap_or_else(|_| {
+                unreadable = true;
+                false
+            })
+        });
+        match matching {
+            Some(index) => sets[index].push(file),
+            None if !unreadable => sets.push(vec![file]),
+            None => {}
+        }
+    }
+    sets
+}
+
+/// Hashes the whole contents of the file at `path`.
+fn content_hash(path: &Path) -> Result<u64, std::io::Error> {
+    let mut file = File::open(path)?;
+    let mut hasher = DefaultHasher::new();
+    let mut chunk = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = filter::read_full(&mut file, &mut chunk)?;
+        rate_limit::consume(read as u64);
+        if read == 0 {
+            return Ok(hasher.finish());
+        }
+        hasher.write(&chunk[..read]);
+    }
+}
+
+fn modified(file: &Entry) -> SystemTime {
+    file.metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+#[cfg(test)]
+mod find_duplicates_should {
+    use std::{
+        sync::atomic::AtomicU8,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Writes `contents` to `path`, last modified `age_secs` ago.
+    fn write_aged(path: &Path, contents: &str, age_secs: u64) {
+        std::fs::write(path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(age_secs))
+            .unwrap();
+    }
+
+    fn sorted(paths: &HashSet<PathBuf>, dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = paths
+            .iter()
+            .map(|path| {
+                path.strip_prefix(dir)
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn keep_one_copy_of_each_set_of_identical_files() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/duplicates-dir-{unique}"));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        write_aged(&dir.join("photo.jpg"), "photo", 300);
+        write_aged(&dir.join("nested/photo (1).jpg"), "photo", 200);
+        write_aged(&dir.join("nested/photo (2).jpg"), "photo", 100);
+        write_aged(&dir.join("notes.txt"), "notes", 100);
+        write_aged(&dir.join("nested/notes.txt"), "notes", 200);
+        // Same size as the copies, other contents.
+        write_aged(&dir.join("other.jpg"), "other", 400);
+        write_aged(&dir.join("unique.bin"), "unique contents", 500);
+        write_aged(&dir.join("empty-1"), "", 100);
+        write_aged(&dir.join("empty-2"), "", 200);
+
+        let entries = crate::scan::scan_dir(&dir).unwrap();
+        let oldest = find_duplicates(&entries, DedupeKeep::Oldest);
+        let newest = find_duplicates(&entries, DedupeKeep::Newest);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(oldest.sets, 2);
+        assert_eq!(
+            sorted(&oldest.kept, &dir),
+            ["nested/notes.txt", "photo.jpg"]
+        );
+        assert_eq!(
+            sorted(&oldest.copies, &dir),
+            ["nested/photo (1).jpg", "nested/photo (2).jpg", "notes.txt"]
+        );
+        assert_eq!(newest.sets, 2);
+        assert_eq!(
+            sorted(&newest.kept, &dir),
+            ["nested/photo (2).jpg", "notes.txt"]
+        );
+        assert_eq!(
+            sorted(&newest.copies, &dir),
+            ["nested/notes.txt", "nested/photo (1).jpg", "photo.jpg"]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! What went wrong deleting a target, and at which step.
+
+use std::{
+    fmt::{self, Display},
+    path::PathBuf,
+};
+
+/// A failed delete, along with the path it failed on.
+#[derive(Debug)]
+pub(crate) enum RemoveError {
+    /// The target could not be resolved to an absolute path, e.g. as it does not exist.
+    Canonicalize {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Walking the tree to work out what to delete failed.
+    Scan {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// Deleting, quarantining or running `--exec` for an entry failed.
+    Delete {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The delete reported success, yet the path is still there.
+    Verify { path: PathBuf },
+    /// A path given on the command line lies inside the target, so it would be deleted
+    /// along with it.
+    InsideTarget { path: PathBuf, target: PathBuf },
+    /// A directory given on the command line holds the target or lies inside it.
+    Overlaps { path: PathBuf, target: PathBuf },
+    /// A failure not tied to one path, e.g. a passed deadline or an unwritable listing.
+    Io(std::io::Error),
+}
+
+impl Display for RemoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoveError::Canonicalize { path, source } => {
+                write!(
+                    f,
+                    "Failed to resolve {}: {}",
+                    path.to_string_lossy(),
+                    source
+                )
+            }
+            RemoveError::Scan { path, source } => {
+                write!(f, "Failed to scan {}: {}", path.to_string_lossy(), source)
+            }
+            RemoveError::Delete { path, source } => {
+                write!(f, "Failed to delete {}: {}", path.to_string_lossy(), source)
+            }
+            RemoveError::Verify { path } => write!(
+                f,
+                "{} is still there after deleting it",
+                path.to_string_lossy()
+            ),
+            RemoveError::InsideTarget { path, target } => write!(
+                f,
+                "{} must not be inside {}",
+                path.to_string_lossy(),
+                target.to_string_lossy()
+            ),
+            RemoveError::Overlaps { path, target } => write!(
+                f,
+                "{} must not overlap {}",
+                path.to_string_lossy(),
+                target.to_string_lossy()
+            ),
+            RemoveError::Io(source) => write!(f, "{source}"),
+        }
+    }
+}
+
+impl std::error::Error for RemoveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RemoveError::Canonicalize { source, .. }
+            | RemoveError::Scan { source, .. }
+            | RemoveError::Delete { source, .. }
+            | RemoveError::Io(source) => Some(source),
+            RemoveError::Verify { .. }
+            | RemoveError::InsideTarget { .. }
+            | RemoveError::Overlaps { .. } => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RemoveError {
+    fn from(source: std::io::Error) -> Self {
+        RemoveError::Io(source)
+    }
+}
+
+#[cfg(test)]
+mod display_should {
+    use super::*;
+
+    #[test]
+    fn name_the_step_and_the_path() {
+        let delete = RemoveError::Delete {
+            path: PathBuf::from("/target/file"),
+            source: std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        };
+        let verify = RemoveError::Verify {
+            path: PathBuf::from("/target"),
+        };
+
+        assert_eq!(
+            delete.to_string(),
+            "Failed to delete /target/file: permission denied"
+        );
+        assert!(std::error::Error::source(&delete).is_some());
+        assert_eq!(
+            verify.to_string(),
+            "/target is still there after deleting it"
+        );
+        assert!(std::error::Error::source(&verify).is_none());
+    }
+}
@@ -0,0 +1,209 @@
+//! The `--exec` hook: a user supplied command run for every file a walk removes. The same
+//! templates run `--regenerate` and `--notify` commands.
+//!
+//! The template is split into words once, honouring single and double quotes, and
+//! `{}` is substituted per file. Nothing goes through a shell, so file names cannot
+//! inject commands, but pipes and redirections need an explicit `sh -c`.
+
+use std::{
+    ffi::{OsStr, OsString},
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+/// A parsed `--exec` template.
+#[derive(Clone, Debug)]
+pub(crate) struct ExecTemplate {
+    words: Vec<String>,
+}
+
+impl ExecTemplate {
+    /// Runs the command for `path`, failing on a spawn error or a non-zero exit.
+    pub(crate) fn run(&self, path: &Path) -> Result<(), std::io::Error> {
+        self.run_with(path.as_os_str(), None)
+    }
+
+    /// Runs the command with `{}` replaced by `value`, writing `input` to its stdin when
+    /// given, and failing like `run`.
+    pub(crate) fn run_with(
+        &self,
+        value: &OsStr,
+        input: Option<&[u8]>,
+    ) -> Result<(), std::io::Error> {
+        let mut args = self.words.iter().map(|word| substitute(word, value));
+        let program = args.next().unwrap_or_default();
+
+        let mut child = Command::new(&program)
+            .args(args)
+            .stdin(if input.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .spawn()?;
+        if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+            // A command that does not read its input closes the pipe early, which is fine.
+            if let Err(e) = stdin.write_all(input) {
+                if e.kind() != std::io::ErrorKind::BrokenPipe {
+                    return Err(e);
+                }
+            }
+        }
+        let status = child.wait()?;
+
+        if !status.success() {
+            return Err(std::io::Error::other(format!(
+                "{} {}",
+                program.to_string_lossy(),
+                describe_exit(status)
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn describe_exit(status: std::process::ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("exited with code {code}"),
+        None => "was killed by a signal".to_string(),
+    }
+}
+
+/// Replaces `{}` in `word` with `value`. A word that is exactly `{}` gets the value
+/// as is, so non UTF-8 names survive.
+fn substitute(word: &str, value: &OsStr) -> OsString {
+    if word == "{}" {
+        return value.to_owned();
+    }
+
+    word.replace("{}", &value.to_string_lossy()).into()
+}
+
+/// Parses an `--exec` template such as `shred -u {}`.
+pub(crate) fn parse_template(template: &str) -> Result<ExecTemplate, String> {
+    let command = parse_command(template)?;
+
+    if !command.words.iter().skip(1).any(|word| word.contains("{}")) {
+        return Err("the command has to contain {} for the file path".to_string());
+    }
+
+    Ok(command)
+}
+
+/// Parses a command like `parse_template`, where `{}` is optional.
+pub(crate) fn parse_command(template: &str) -> Result<ExecTemplate, String> {
+    let words = split_words(template)?;
+
+    if words.is_empty() {
+        return Err("the command is empty".to_string());
+    }
+
+    Ok(ExecTemplate { words })
+}
+
+/// Splits on whitespace outside quotes. Backslash escapes the next character outside
+/// single quotes.
+fn split_words(template: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated single quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => word.push(c),
+                            None => return Err("unterminated double quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated double quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => return Err("trailing backslash".to_string()),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
+
+#[cfg(test)]
+mod parse_template_should {
+    use super::*;
+
+    #[test]
+    fn split_on_whitespace_and_keep_quoted_words() {
+        let template = parse_template(r#"sh -c 'echo "$0" >> log' {}"#).unwrap();
+
+        assert_eq!(
+            template.words,
+            vec!["sh", "-c", r#"echo "$0" >> log"#, "{}"]
+        );
+    }
+
+    #[test]
+    fn reject_a_template_without_placeholder() {
+        assert!(parse_template("shred -u").is_err());
+        assert!(parse_template("   ").is_err());
+        assert!(parse_template("rm 'oops {}").is_err());
+    }
+
+    #[test]
+    fn substitute_the_path_inside_words() {
+        let path = Path::new("dir/a file.txt").as_os_str();
+
+        assert_eq!(substitute("{}", path), OsString::from("dir/a file.txt"));
+        assert_eq!(
+            substitute("--file={}.bak", path),
+            OsString::from("--file=dir/a file.txt.bak")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn report_a_non_zero_exit() {
+        let ok = parse_template("test -n {}").unwrap();
+        let failing = parse_template("test -z {}").unwrap();
+
+        assert!(ok.run(Path::new("file")).is_ok());
+        let error = failing.run(Path::new("file")).unwrap_err();
+        assert_eq!(error.to_string(), "test exited with code 1");
+    }
+}
@@ -0,0 +1,765 @@
+//! Name, size and age filters that select which files a partial delete touches.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    fs::Metadata,
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use crate::{glob::Glob, rate_limit, regex::Regex, scan::Entry};
+
+/// Criteria a file has to meet to be deleted. An empty filter matches everything.
+#[derive(Default)]
+pub(crate) struct Filter {
+    /// When not empty, only files matching one of these go.
+    pub(crate) include: Vec<Glob>,
+    /// Files matching one of these, or inside a folder that does, are kept.
+    pub(crate) exclude: Vec<Glob>,
+    /// Lowercase extensions, without the dot, that count as matching `include`.
+    pub(crate) include_extensions: Vec<String>,
+    /// Lowercase extensions, without the dot, of files that are kept.
+    pub(crate) exclude_extensions: Vec<String>,
+    /// When not empty, only files whose relative path matches one of these go.
+    pub(crate) regex: Vec<Regex>,
+    pub(crate) min_size: Option<u64>,
+    pub(crate) max_size: Option<u64>,
+    pub(crate) older_than: Option<Duration>,
+    pub(crate) newer_than: Option<Duration>,
+    /// Keep files modified at or after this instant, local midnight for `--exclude-today`.
+    pub(crate) keep_modified_since: Option<SystemTime>,
+    /// Keep whole folders holding anything modified within this window, see `recent_dirs`.
+    pub(crate) keep_recent_dirs: Option<Duration>,
+    /// Only delete files identical to their counterpart in a reference tree.
+    pub(crate) compare_to: Option<Comparison>,
+    /// Only delete files whose full path is longer than the limit.
+    pub(crate) path_limit: Option<PathLimit>,
+    /// Which dangling symlinks go, and whether anything else does.
+    pub(crate) dangling: Option<DanglingLinks>,
+    /// Keep everything but regular files of zero bytes.
+    pub(crate) empty_only: bool,
+}
+
+/// A length in characters that full paths below `root` must not exceed.
+pub(crate) struct PathLimit {
+    pub(crate) root: PathBuf,
+    pub(crate) max: usize,
+}
+
+impl PathLimit {
+    /// The length of the full path of `relative`, in characters.
+    pub(crate) fn length(&self, relative: &Path) -> usize {
+        self.root.join(relative).to_string_lossy().chars().count()
+    }
+}
+
+/// How dangling symlinks below `root` are handled: a link is dangling when what it points
+/// to cannot be found, which includes a target on a filesystem not mounted right now.
+pub(crate) struct DanglingLinks {
+    pub(crate) root: PathBuf,
+    /// Keep everything but dangling symlinks.
+    pub(crate) only: bool,
+    /// Keep dangling symlinks whose own mtime is within this window, as they may be
+    /// mid-setup or point somewhere only missing for now.
+    pub(crate) grace: Option<Duration>,
+}
+
+/// A target tree and the reference its files are compared to by relative path.
+pub(crate) struct Comparison {
+    pub(crate) target: PathBuf,
+    pub(crate) reference: PathBuf,
+}
+
+/// Why a file is kept rather than deleted.
+#[derive(Debug, PartialEq)]
+pub(crate) enum KeepReason {
+    /// The file, or a folder holding it, matches this exclude pattern.
+    Excluded(String),
+    NotIncluded,
+    NoRegexMatch,
+    /// Directories only go once emptied.
+    Folder,
+    /// Nothing at the same relative path below `--compare-to`.
+    NotInReference,
+    /// The counterpart below `--compare-to` has other contents.
+    Different,
+    /// Named by `--preserve`, or inside a folder that was.
+    Preserved,
+    TooSmall,
+    TooLarge,
+    /// Modified more recently than `--older-than` allows.
+    TooNew,
+    /// Modified longer ago than `--newer-than` allows.
+    TooOld,
+    /// Modified since local midnight under `--exclude-today`.
+    ModifiedToday,
+    /// Inside a folder kept by `--exclude-recent-dirs`.
+    RecentFolder,
+    /// The full path is within `--max-path-length`.
+    PathWithinLimit,
+    /// Held open by some process under `--exclude-open-files`.
+    Open,
+    /// Anything but a dangling symlink under `--dangling-only`.
+    NotDangling,
+    /// A dangling symlink changed within `--delete-broken-after`.
+    RecentlyBroken,
+    /// Anything but a regular file of zero bytes under `--delete-empty-files`.
+    NotEmpty,
+    /// The copy `--dedupe` keeps of a set of byte-identical files.
+    KeptCopy,
+    /// No other file has the same contents under `--dedupe`.
+    Unique,
+}
+
+impl std::fmt::Display for KeepReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            KeepReason::Excluded(pattern) => write!(f, "excluded by {pattern}"),
+            KeepReason::NotIncluded => write!(f, "not matching any --include"),
+            KeepReason::NoRegexMatch => write!(f, "not matching any --regex"),
+            KeepReason::Folder => write!(f, "folder"),
+            KeepReason::NotInReference => write!(f, "missing from reference"),
+            KeepReason::Different => write!(f, "different from reference"),
+            KeepReason::Preserved => write!(f, "preserved"),
+            KeepReason::TooSmall => write!(f, "too small"),
+            KeepReason::TooLarge => write!(f, "too large"),
+            KeepReason::TooNew => write!(f, "too new"),
+            KeepReason::TooOld => write!(f, "too old"),
+            KeepReason::ModifiedToday => write!(f, "modified today"),
+            KeepReason::RecentFolder => write!(f, "in a recently used folder"),
+            KeepReason::PathWithinLimit => write!(f, "path within the length limit"),
+            KeepReason::Open => write!(f, "held open by a process"),
+            KeepReason::NotDangling => write!(f, "not a dangling symlink"),
+            KeepReason::RecentlyBroken => write!(f, "dangling symlink too new"),
+            KeepReason::NotEmpty => write!(f, "not an empty file"),
+            KeepReason::KeptCopy => write!(f, "the copy kept of its duplicates"),
+            KeepReason::Unique => write!(f, "no duplicate"),
+        }
+    }
+}
+
+impl Filter {
+    /// True when any criteria is set, meaning only part of the tree may get deleted.
+    pub(crate) fn is_active(&self) -> bool {
+        !self.include.is_empty()
+            || !self.exclude.is_empty()
+            || !self.include_extensions.is_empty()
+            || !self.exclude_extensions.is_empty()
+            || !self.regex.is_empty()
+            || self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.older_than.is_some()
+            || self.newer_than.is_some()
+            || self.keep_modified_since.is_some()
+            || self.keep_recent_dirs.is_some()
+            || self.compare_to.is_some()
+            || self.path_limit.is_some()
+            || self.dangling.is_some()
+            || self.empty_only
+    }
+
+    /// True when `relative`, or one of the folders holding it, matches an exclude pattern.
+    pub(crate) fn is_excluded(&self, relative: &Path) -> bool {
+        self.excluded_by(relative).is_some()
+    }
+
+    /// The exclude pattern matching `relative` or one of the folders holding it.
+    fn excluded_by(&self, relative: &Path) -> Option<&Glob> {
+        relative
+            .ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .find_map(|ancestor| self.exclude.iter().find(|glob| glob.matches_path(ancestor)))
+    }
+
+    /// The outermost folders below `root` that, counting their own mtime and that of
+    /// everything inside them, were modified within `keep_recent_dirs`. As a folder is as
+    /// recent as its most recent descendant, these are in effect the top level folders
+    /// still in use. Entries have to be in scan order, parents before their children.
+    pub(crate) fn recent_dirs(
+        &self,
+        root: &Path,
+        entries: &[Entry],
+        now: SystemTime,
+    ) -> Vec<PathBuf> {
+        let Some(window) = self.keep_recent_dirs else {
+            return Vec::new();
+        };
+
+        // Walking backwards sees every entry before the folder holding it.
+        let mut latest: HashMap<&Path, SystemTime> = HashMap::new();
+        for entry in entries.iter().rev() {
+            // A missing mtime counts as brand new, like with the age filters.
+            let mut modified = entry.metadata.modified().unwrap_or(now);
+            if let Some(inside) = latest.get(entry.path.as_path()) {
+                modified = modified.max(*inside);
+            }
+            if entry.is_dir_like() {
+                latest.insert(&entry.path, modified);
+            }
+            if let Some(parent) = entry.path.parent() {
+                let newest = latest.entry(parent).or_insert(modified);
+                *newest = (*newest).max(modified);
+            }
+        }
+
+        let mut recent: Vec<PathBuf> = Vec::new();
+        for entry in entries.iter().filter(|entry| entry.is_dir_like()) {
+            let is_recent = latest.get(entry.path.as_path()).is_some_and(|modified| {
+                now.duration_since(*modified).unwrap_or(Duration::ZERO) <= window
+            });
+            if is_recent
+                && entry.path != root
+                && !recent.iter().any(|kept| entry.path.starts_with(kept))
+            {
+                recent.push(entry.path.clone());
+            }
+        }
+        recent
+    }
+
+    /// Exclusions win over inclusions. A file passes the include step when it matches one
+    /// of the include globs or has one of the include extensions.
+    fn pattern_keep_reason(&self, relative: &Path) -> Option<KeepReason> {
+        if let Some(glob) = self.excluded_by(relative) {
+            return Some(KeepReason::Excluded(glob.as_str().to_string()));
+        }
+
+        let extension = relative
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let has_extension =
+            |extensions: &[String]| extension.as_ref().is_some_and(|e| extensions.contains(e));
+
+        if has_extension(&self.exclude_extensions) {
+            return Some(KeepReason::Excluded(format!(
+                "extension {}",
+                extension.unwrap_or_default()
+            )));
+        }
+
+        let includes = !self.include.is_empty() || !self.include_extensions.is_empty();
+        if includes
+            && !self.include.iter().any(|glob| glob.matches_path(relative))
+            && !has_extension(&self.include_extensions)
+        {
+            return Some(KeepReason::NotIncluded);
+        }
+
+        if let Some(limit) = &self.path_limit {
+            if limit.length(relative) <= limit.max {
+                return Some(KeepReason::PathWithinLimit);
+            }
+        }
+
+        if !self.regex.is_empty() {
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            if !self.regex.iter().any(|regex| regex.is_match(&relative)) {
+                return Some(KeepReason::NoRegexMatch);
+            }
+        }
+
+        None
+    }
+
+    /// Returns the first criteria the file fails, or None when it is to be deleted.
+    /// Directories are never matched directly, they only go once emptied. Comparing
+    /// against the reference reads both files, so it is checked last.
+    pub(crate) fn keep_reason(
+        &self,
+        relative: &Path,
+        metadata: &Metadata,
+        now: SystemTime,
+    ) -> Option<KeepReason> {
+        if metadata.is_dir() {
+            return Some(KeepReason::Folder);
+        }
+
+        self.pattern_keep_reason(relative)
+            .or_else(|| self.metadata_keep_reason(metadata, now))
+            .or_else(|| self.dangling_keep_reason(relative, metadata, now))
+            .or_else(|| self.reference_keep_reason(relative, metadata))
+    }
+
+    fn dangling_keep_reason(
+        &self,
+        relative: &Path,
+        metadata: &Metadata,
+        now: SystemTime,
+    ) -> Option<KeepReason> {
+        let dangling = self.dangling.as_ref()?;
+        let is_dangling = metadata.file_type().is_symlink()
+            && std::fs::metadata(dangling.root.join(relative)).is_err();
+        if !is_dangling {
+            return dangling.only.then_some(KeepReason::NotDangling);
+        }
+
+        // Like the age filters, a link without an mtime counts as brand new.
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or(Duration::ZERO);
+        dangling
+            .grace
+            .is_some_and(|grace| age < grace)
+            .then_some(KeepReason::RecentlyBroken)
+    }
+
+    fn reference_keep_reason(&self, relative: &Path, metadata: &Metadata) -> Option<KeepReason> {
+        let comparison = self.compare_to.as_ref()?;
+        let reference = comparison.reference.join(relative);
+
+        match std::fs::metadata(&reference) {
+            Ok(counterpart) if counterpart.is_file() => {
+                // Unreadable files count as different, so they are kept.
+                let identical = counterpart.len() == metadata.len()
+                    && same_contents(&comparison.target.join(relative), &reference)
+                        .unwrap_or(false);
+                (!identical).then_some(KeepReason::Different)
+            }
+            _ => Some(KeepReason::NotInReference),
+        }
+    }
+
+    fn metadata_keep_reason(&self, metadata: &Metadata, now: SystemTime) -> Option<KeepReason> {
+        let size = metadata.len();
+        // Symlinks and other special files have no contents to be empty of.
+        if self.empty_only && !(metadata.is_file() && size == 0) {
+            return Some(KeepReason::NotEmpty);
+        }
+        if self.min_size.is_some_and(|min| size < min) {
+            return Some(KeepReason::TooSmall);
+        }
+        if self.max_size.is_some_and(|max| size > max) {
+            return Some(KeepReason::TooLarge);
+        }
+
+        if let Some(since) = self.keep_modified_since {
+            // Without an mtime the file counts as brand new here too.
+            if metadata
+                .modified()
+                .map_or(true, |modified| modified >= since)
+            {
+                return Some(KeepReason::ModifiedToday);
+            }
+        }
+
+        if self.older_than.is_none() && self.newer_than.is_none() {
+            return None;
+        }
+
+        // A missing mtime makes the file count as brand new, so age filters keep it.
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or(Duration::ZERO);
+
+        if self.older_than.is_some_and(|older| age < older) {
+            return Some(KeepReason::TooNew);
+        }
+        if self.newer_than.is_some_and(|newer| age >= newer) {
+            return Some(KeepReason::TooOld);
+        }
+
+        None
+    }
+}
+
+/// Compares two files byte by byte. Cheaper than hashing both, as it stops at the
+/// first difference.
+pub(crate) fn same_contents(a: &Path, b: &Path) -> Result<bool, std::io::Error> {
+    let mut a = File::open(a)?;
+    let mut b = File::open(b)?;
+    let mut chunk_a = vec![0u8; 64 * 1024];
+    let mut chunk_b = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = read_full(&mut a, &mut chunk_a)?;
+        let read_b = read_full(&mut b, &mut chunk_b)?;
+        rate_limit::consume((read + read_b) as u64);
+        if read_b != read || chunk_a[..read] != chunk_b[..read] {
+            return Ok(false);
+        }
+        if read == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Reads until `buffer` is full or the file ends, returning how much was read.
+pub(crate) fn read_full(file: &mut File, buffer: &mut [u8]) -> Result<usize, std::io::Error> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match file.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod filter_should {
+    use std::{io::Write, sync::atomic::AtomicU8};
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn metadata_for_file_of_size(size: usize) -> Metadata {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/filter-dir-{unique}");
+        let file = format!("{dir}/file.bin");
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::File::create(&file)
+            .unwrap()
+            .write_all(&vec![0; size])
+            .unwrap();
+
+        let metadata = std::fs::metadata(&file).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        metadata
+    }
+
+    #[test]
+    fn match_everything_when_empty() {
+        let filter = Filter::default();
+        assert!(!filter.is_active());
+        assert!(filter
+            .keep_reason(
+                Path::new("file.bin"),
+                &metadata_for_file_of_size(10),
+                SystemTime::now()
+            )
+            .is_none());
+    }
+
+    #[test]
+    fn match_sizes_within_bounds() {
+        let filter = Filter {
+            min_size: Some(10),
+            max_size: Some(20),
+            ..Default::default()
+        };
+        let now = SystemTime::now();
+
+        assert!(filter
+            .keep_reason(Path::new("file.bin"), &metadata_for_file_of_size(5), now)
+            .is_some());
+        assert!(filter
+            .keep_reason(Path::new("file.bin"), &metadata_for_file_of_size(15), now)
+            .is_none());
+        assert!(filter
+            .keep_reason(Path::new("file.bin"), &metadata_for_file_of_size(25), now)
+            .is_some());
+    }
+
+    #[test]
+    fn match_by_age() {
+        let metadata = metadata_for_file_of_size(1);
+        let in_an_hour = SystemTime::now() + Duration::from_secs(60 * 60);
+
+        let older = Filter {
+            older_than: Some(Duration::from_secs(30 * 60)),
+            ..Default::default()
+        };
+        assert!(older
+            .keep_reason(Path::new("file.bin"), &metadata, in_an_hour)
+            .is_none());
+        assert!(older
+            .keep_reason(Path::new("file.bin"), &metadata, SystemTime::now())
+            .is_some());
+
+        let newer = Filter {
+            newer_than: Some(Duration::from_secs(30 * 60)),
+            ..Default::default()
+        };
+        assert!(newer
+            .keep_reason(Path::new("file.bin"), &metadata, in_an_hour)
+            .is_some());
+        assert!(newer
+            .keep_reason(Path::new("file.bin"), &metadata, SystemTime::now())
+            .is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn delete_only_dangling_symlinks_broken_for_longer_than_the_grace_period() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/filter-dangling-dir-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("file.txt"), "kept").unwrap();
+        std::os::unix::fs::symlink("file.txt", dir.join("working")).unwrap();
+        std::os::unix::fs::symlink("missing.txt", dir.join("dangling")).unwrap();
+        let filter = Filter {
+            dangling: Some(DanglingLinks {
+                root: dir.clone(),
+                only: true,
+                grace: Some(Duration::from_secs(60 * 60)),
+            }),
+            ..Default::default()
+        };
+
+        let reason_at = |name: &str, now: SystemTime| {
+            let metadata = std::fs::symlink_metadata(dir.join(name)).unwrap();
+            filter.keep_reason(Path::new(name), &metadata, now)
+        };
+        let later = SystemTime::now() + Duration::from_secs(2 * 60 * 60);
+        let new_link = reason_at("dangling", SystemTime::now());
+        let old_link = reason_at("dangling", later);
+        let working = reason_at("working", later);
+        let file = reason_at("file.txt", later);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(filter.is_active());
+        assert_eq!(new_link, Some(KeepReason::RecentlyBroken));
+        assert_eq!(old_link, None);
+        assert_eq!(working, Some(KeepReason::NotDangling));
+        assert_eq!(file, Some(KeepReason::NotDangling));
+    }
+
+    #[test]
+    fn keep_files_modified_since_midnight() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let file = format!("./data/filter-file-{unique}");
+        let midnight = SystemTime::UNIX_EPOCH + Duration::from_secs(1_792_022_400);
+        let filter = Filter {
+            keep_modified_since: Some(midnight),
+            ..Default::default()
+        };
+
+        let reason_at = |modified: SystemTime| {
+            let handle = std::fs::File::create(&file).unwrap();
+            handle.set_modified(modified).unwrap();
+            let metadata = handle.metadata().unwrap();
+            filter.keep_reason(Path::new("app.log"), &metadata, SystemTime::now())
+        };
+        let before = reason_at(midnight - Duration::from_secs(1));
+        let at = reason_at(midnight);
+        let after = reason_at(midnight + Duration::from_secs(1));
+        std::fs::remove_file(&file).unwrap();
+
+        assert!(filter.is_active());
+        assert_eq!(before, None);
+        assert_eq!(at, Some(KeepReason::ModifiedToday));
+        assert_eq!(after, Some(KeepReason::ModifiedToday));
+    }
+
+    #[test]
+    fn match_include_and_exclude_patterns() {
+        let filter = Filter {
+            include: vec![Glob::new("*.log")],
+            exclude: vec![Glob::new("keep")],
+            ..Default::default()
+        };
+
+        assert!(filter.pattern_keep_reason(Path::new("app.log")).is_none());
+        assert!(filter
+            .pattern_keep_reason(Path::new("nested/app.log"))
+            .is_none());
+        assert!(filter.pattern_keep_reason(Path::new("app.txt")).is_some());
+        assert!(filter
+            .pattern_keep_reason(Path::new("keep/app.log"))
+            .is_some());
+        assert!(filter
+            .pattern_keep_reason(Path::new("nested/keep/deeper/app.log"))
+            .is_some());
+    }
+
+    #[test]
+    fn match_extensions_case_insensitively() {
+        let metadata = metadata_for_file_of_size(10);
+        let now = SystemTime::now();
+        let filter = Filter {
+            include_extensions: vec!["log".to_string(), "txt".to_string()],
+            exclude_extensions: vec!["db".to_string()],
+            ..Default::default()
+        };
+
+        let reason = |path: &str| filter.keep_reason(Path::new(path), &metadata, now);
+        assert!(filter.is_active());
+        assert_eq!(reason("app.log"), None);
+        assert_eq!(reason("logs/APP.TXT"), None);
+        assert_eq!(reason("archive.tar.log"), None);
+        assert_eq!(
+            reason("app.log.db"),
+            Some(KeepReason::Excluded("extension db".to_string()))
+        );
+        assert_eq!(reason("Makefile"), Some(KeepReason::NotIncluded));
+        assert_eq!(reason(".log"), Some(KeepReason::NotIncluded));
+        assert_eq!(reason("log"), Some(KeepReason::NotIncluded));
+    }
+
+    #[test]
+    fn combine_extensions_with_globs() {
+        let metadata = metadata_for_file_of_size(10);
+        let now = SystemTime::now();
+        let filter = Filter {
+            include: vec![Glob::new("Makefile")],
+            exclude: vec![Glob::new("keep")],
+            include_extensions: vec!["log".to_string()],
+            exclude_extensions: vec!["db".to_string()],
+            ..Default::default()
+        };
+
+        let reason = |path: &str| filter.keep_reason(Path::new(path), &metadata, now);
+        assert_eq!(reason("Makefile"), None);
+        assert_eq!(reason("app.log"), None);
+        assert_eq!(
+            reason("keep/app.log"),
+            Some(KeepReason::Excluded("keep".to_string()))
+        );
+        assert_eq!(reason("app.txt"), Some(KeepReason::NotIncluded));
+
+        let only_excluded = Filter {
+            exclude_extensions: vec!["db".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            only_excluded.keep_reason(Path::new("data"), &metadata, now),
+            None
+        );
+        assert!(only_excluded
+            .keep_reason(Path::new("cache.DB"), &metadata, now)
+            .is_some());
+    }
+
+    #[test]
+    fn combine_regex_with_include_patterns() {
+        let filter = Filter {
+            include: vec![Glob::case_insensitive("*.TXT")],
+            regex: vec![Regex::new(r"^(tmp|cache)/", true).unwrap()],
+            ..Default::default()
+        };
+
+        assert!(filter
+            .pattern_keep_reason(Path::new("tmp/file.txt"))
+            .is_none());
+        assert!(filter
+            .pattern_keep_reason(Path::new("CACHE/a/FILE.TXT"))
+            .is_none());
+        assert!(filter.pattern_keep_reason(Path::new("file.txt")).is_some());
+        assert!(filter
+            .pattern_keep_reason(Path::new("tmp/file.log"))
+            .is_some());
+    }
+
+    #[test]
+    fn explain_why_files_are_kept() {
+        let metadata = metadata_for_file_of_size(10);
+        let now = SystemTime::now();
+        let filter = Filter {
+            include: vec![Glob::new("*.log")],
+            exclude: vec![Glob::new("keep")],
+            max_size: Some(5),
+            older_than: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        };
+
+        let reason = |path: &str| filter.keep_reason(Path::new(path), &metadata, now);
+        assert_eq!(
+            reason("keep/app.log"),
+            Some(KeepReason::Excluded("keep".to_string()))
+        );
+        assert_eq!(reason("app.txt"), Some(KeepReason::NotIncluded));
+        assert_eq!(reason("app.log"), Some(KeepReason::TooLarge));
+
+        let small = Filter {
+            max_size: None,
+            ..filter
+        };
+        assert_eq!(
+            small.keep_reason(Path::new("app.log"), &metadata, now),
+            Some(KeepReason::TooNew)
+        );
+        assert_eq!(
+            KeepReason::Excluded("keep".to_string()).to_string(),
+            "excluded by keep"
+        );
+    }
+
+    #[test]
+    fn keep_files_that_differ_from_the_reference() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/filter-dir-{unique}");
+        let target = PathBuf::from(format!("{dir}/target"));
+        let reference = PathBuf::from(format!("{dir}/reference"));
+        std::fs::create_dir_all(&target).unwrap();
+        std::fs::create_dir_all(&reference).unwrap();
+        for (name, target_contents, reference_contents) in [
+            ("same.txt", "same", Some("same")),
+            ("changed.txt", "old!", Some("new!")),
+            ("unique.txt", "only here", None),
+        ] {
+            std::fs::write(target.join(name), target_contents).unwrap();
+            if let Some(contents) = reference_contents {
+                std::fs::write(reference.join(name), contents).unwrap();
+            }
+        }
+
+        let filter = Filter {
+            compare_to: Some(Comparison {
+                target: target.clone(),
+                reference,
+            }),
+            ..Default::default()
+        };
+        let reason = |name: &str| {
+            let metadata = std::fs::metadata(target.join(name)).unwrap();
+            filter.keep_reason(Path::new(name), &metadata, SystemTime::now())
+        };
+
+        let same = reason("same.txt");
+        let changed = reason("changed.txt");
+        let unique_file = reason("unique.txt");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(same, None);
+        assert_eq!(changed, Some(KeepReason::Different));
+        assert_eq!(unique_file, Some(KeepReason::NotInReference));
+    }
+
+    #[test]
+    fn never_match_directories() {
+        std::fs::create_dir_all("./data").unwrap();
+        let metadata = std::fs::metadata("./data").unwrap();
+
+        assert!(Filter::default()
+            .keep_reason(Path::new("data"), &metadata, SystemTime::now())
+            .is_some());
+    }
+
+    #[test]
+    fn only_match_paths_over_the_length_limit() {
+        let filter = Filter {
+            path_limit: Some(PathLimit {
+                root: PathBuf::from("/root"),
+                max: 20,
+            }),
+            ..Default::default()
+        };
+
+        // "/root/" plus 14 characters is exactly at the limit.
+        assert_eq!(
+            filter.pattern_keep_reason(Path::new("abcdefghij.txt")),
+            Some(KeepReason::PathWithinLimit)
+        );
+        assert_eq!(
+            filter.pattern_keep_reason(Path::new("short.txt")),
+            Some(KeepReason::PathWithinLimit)
+        );
+        assert!(filter
+            .pattern_keep_reason(Path::new("abcdefghijk.txt"))
+            .is_none());
+        assert!(filter
+            .pattern_keep_reason(Path::new("nested/deeper/file.txt"))
+            .is_none());
+    }
+}
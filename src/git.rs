@@ -0,0 +1,105 @@
+//! The `--refuse-if-git-dirty` guardrail: never delete uncommitted work from a git
+//! working tree.
+//!
+//! The check asks `git status --porcelain` about the target path only, so changes
+//! elsewhere in the repository do not get in the way. Ignored files, like build output
+//! listed in `.gitignore`, are not reported and so do not count as dirty.
+
+use std::{path::Path, process::Command};
+
+/// The uncommitted changes below `dir`, as `git status --porcelain` lines such as
+/// ` M src/main.rs` or `?? notes.txt`, with paths relative to the repository root. None
+/// when `dir` is not inside a git working tree. Errors when git cannot be run.
+pub(crate) fn dirty_files(dir: &Path) -> Result<Option<Vec<String>>, std::io::Error> {
+    let inside = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--is-inside-work-tree"])
+        .output()?;
+    if !inside.status.success() || String::from_utf8_lossy(&inside.stdout).trim() != "true" {
+        return Ok(None);
+    }
+
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["status", "--porcelain", "--untracked-files=all", "--", "."])
+        .output()?;
+    if !status.status.success() {
+        return Err(std::io::Error::other(format!(
+            "git status failed: {}",
+            String::from_utf8_lossy(&status.stderr).trim()
+        )));
+    }
+
+    Ok(Some(
+        String::from_utf8_lossy(&status.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+    ))
+}
+
+#[cfg(test)]
+mod dirty_files_should {
+    use std::{path::PathBuf, sync::atomic::AtomicU8};
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn git(repo: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {args:?} failed");
+    }
+
+    /// Creates a repository with `target/committed.txt` and `other.txt` committed.
+    fn create_repo() -> PathBuf {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let repo = PathBuf::from(format!("./data/git-dir-{unique}"));
+        std::fs::create_dir_all(repo.join("target")).unwrap();
+        std::fs::write(repo.join("target/committed.txt"), "x").unwrap();
+        std::fs::write(repo.join("other.txt"), "x").unwrap();
+
+        git(&repo, &["init", "--quiet"]);
+        git(&repo, &["add", "."]);
+        git(&repo, &["commit", "--quiet", "-m", "initial"]);
+
+        repo
+    }
+
+    #[test]
+    fn find_nothing_in_a_clean_target() {
+        let repo = create_repo();
+        std::fs::write(repo.join("other.txt"), "changed outside the target").unwrap();
+
+        let dirty = dirty_files(&repo.join("target")).unwrap();
+        std::fs::remove_dir_all(&repo).unwrap();
+
+        assert_eq!(dirty, Some(Vec::new()));
+    }
+
+    #[test]
+    fn list_modified_and_untracked_files_in_the_target() {
+        let repo = create_repo();
+        std::fs::write(repo.join("target/committed.txt"), "changed").unwrap();
+        std::fs::write(repo.join("target/new.txt"), "x").unwrap();
+
+        let dirty = dirty_files(&repo.join("target")).unwrap();
+        std::fs::remove_dir_all(&repo).unwrap();
+
+        assert_eq!(
+            dirty,
+            Some(vec![
+                " M target/committed.txt".to_string(),
+                "?? target/new.txt".to_string()
+            ])
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Minimal glob matching for `--include` and `--exclude` patterns.
+//!
+//! Supports `*` (anything but `/`), `**` (anything, including `/`), `?` and
+//! character classes like `[a-z]` or `[!0-9]`. Patterns without a `/` are matched
+//! against the file name, others against the path relative to the target.
+//! Case-insensitive globs lowercase both the pattern and the matched path.
+
+use std::path::Path;
+
+#[derive(Clone, Debug)]
+pub(crate) struct Glob {
+    source: String,
+    chars: Vec<char>,
+    ignore_case: bool,
+}
+
+impl Glob {
+    pub(crate) fn new(pattern: &str) -> Self {
+        Glob {
+            source: pattern.to_string(),
+            chars: pattern.chars().collect(),
+            ignore_case: false,
+        }
+    }
+
+    /// A glob where `*.TXT` also matches `file.txt`.
+    pub(crate) fn case_insensitive(pattern: &str) -> Self {
+        Glob {
+            source: pattern.to_string(),
+            chars: pattern.to_lowercase().chars().collect(),
+            ignore_case: true,
+        }
+    }
+
+    /// The pattern as it was given.
+    pub(crate) fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Matches `relative` by file name, or by full relative path when the pattern has a `/`.
+    pub(crate) fn matches_path(&self, relative: &Path) -> bool {
+        let text = if self.chars.contains(&'/') {
+            relative.to_string_lossy().replace('\\', "/")
+        } else {
+            match relative.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => return false,
+            }
+        };
+
+        let text = if self.ignore_case {
+            text.to_lowercase()
+        } else {
+            text
+        };
+
+        let text: Vec<char> = text.chars().collect();
+        match_from(&self.chars, &text)
+    }
+}
+
+fn match_from(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') if pattern.get(1) == Some(&'*') => {
+            let rest = &pattern[2..];
+            // `**/` may also match no folder at all.
+            if rest.first() == Some(&'/') && match_from(&rest[1..], text) {
+                return true;
+            }
+            (0..=text.len()).any(|i| match_from(rest, &text[i..]))
+        }
+        Some('*') => {
+            for i in 0..=text.len() {
+                if match_from(&pattern[1..], &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some('?') => !text.is_empty() && text[0] != '/' && match_from(&pattern[1..], &text[1..]),
+        Some('[') => match match_class(&pattern[1..], text.first()) {
+            Some((matched, rest)) => matched && match_from(rest, &text[1..]),
+            // No closing bracket, so treat `[` literally.
+            None => text.first() == Some(&'[') && match_from(&pattern[1..], &text[1..]),
+        },
+        Some(c) => text.first() == Some(c) && match_from(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Matches `c` against the class starting right after `[`. Returns whether it matched and
+/// the pattern after the closing `]`, or None when the class is never closed.
+fn match_class<'a>(class: &'a [char], c: Option<&char>) -> Option<(bool, &'a [char])> {
+    let negated = matches!(class.first(), Some('!') | Some('^'));
+    let body_start = usize::from(negated);
+    // A `]` right after the opening bracket is part of the class.
+    let end = class
+        .iter()
+        .skip(body_start + 1)
+        .position(|&ch| ch == ']')
+        .map(|i| i + body_start + 1)?;
+
+    let Some(&c) = c else {
+        return Some((false, &class[end + 1..]));
+    };
+
+    let body = &class[body_start..end];
+    let mut matched = false;
+    let mut i = 0;
+    while i < body.len() {
+        if i + 2 < body.len() && body[i + 1] == '-' {
+            matched |= body[i] <= c && c <= body[i + 2];
+            i += 3;
+        } else {
+            matched |= body[i] == c;
+            i += 1;
+        }
+    }
+
+    Some((matched != negated && c != '/', &class[end + 1..]))
+}
+
+#[cfg(test)]
+mod glob_should {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        Glob::new(pattern).matches_path(Path::new(path))
+    }
+
+    #[test]
+    fn match_file_names_with_wildcards() {
+        assert!(matches("*.log", "app.log"));
+        assert!(matches("*.log", "nested/deeper/app.log"));
+        assert!(!matches("*.log", "app.log.gz"));
+        assert!(matches("file?.txt", "file1.txt"));
+        assert!(!matches("file?.txt", "file10.txt"));
+    }
+
+    #[test]
+    fn match_character_classes() {
+        assert!(matches("file[0-9].txt", "file7.txt"));
+        assert!(!matches("file[!0-9].txt", "file7.txt"));
+        assert!(matches("file[!0-9].txt", "fileA.txt"));
+        assert!(matches("[abc]", "b"));
+        assert!(matches("[", "["));
+    }
+
+    #[test]
+    fn match_relative_paths_when_pattern_has_slash() {
+        assert!(matches("build/*.o", "build/main.o"));
+        assert!(!matches("build/*.o", "build/nested/main.o"));
+        assert!(matches("build/**/*.o", "build/nested/main.o"));
+        assert!(matches("build/**/*.o", "build/main.o"));
+        assert!(matches("**/cache", "a/b/cache"));
+    }
+
+    #[test]
+    fn ignore_case_only_when_asked() {
+        assert!(!matches("*.TXT", "file.txt"));
+        assert!(Glob::case_insensitive("*.TXT").matches_path(Path::new("file.txt")));
+        assert!(Glob::case_insensitive("Build/*.o").matches_path(Path::new("BUILD/main.O")));
+    }
+}
@@ -0,0 +1,94 @@
+//! `--hardlink-stats`: how the files of a tree share their data through hard links.
+//!
+//! Files are told apart by device and inode, so the count of unique inodes is the count
+//! of physical files. Data only goes once every link to it is deleted, so an inode that
+//! is also linked from outside the tree frees nothing. Only Unix exposes inode numbers.
+
+use crate::scan::Entry;
+
+/// The link structure of the regular files in a tree.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct HardlinkStats {
+    /// Directory entries naming a regular file.
+    pub(crate) entries: u64,
+    /// Distinct files behind those entries.
+    pub(crate) inodes: u64,
+    /// Bytes of the files whose every link is inside the tree.
+    pub(crate) freed: u64,
+    /// Bytes that would be counted again for every further link to the same file.
+    pub(crate) shared: u64,
+    /// Files also linked from outside the tree, which stay on disk.
+    pub(crate) linked_outside: u64,
+}
+
+/// Adds up the link structure of the regular files among `entries`.
+#[cfg(unix)]
+pub(crate) fn hardlink_stats(entries: &[Entry]) -> HardlinkStats {
+    use std::{collections::HashMap, os::unix::fs::MetadataExt};
+
+    // Each inode with its size, its total link count and the links seen in the tree.
+    let mut inodes: HashMap<(u64, u64), (u64, u64, u64)> = HashMap::new();
+    for entry in entries.iter().filter(|entry| entry.metadata.is_file()) {
+        let metadata = &entry.metadata;
+        inodes
+            .entry((metadata.dev(), metadata.ino()))
+            .or_insert((metadata.len(), metadata.nlink(), 0))
+            .2 += 1;
+    }
+
+    let mut stats = HardlinkStats::default();
+    for (size, links, seen) in inodes.into_values() {
+        stats.entries += seen;
+        stats.inodes += 1;
+        stats.shared += size * (seen - 1);
+        if seen >= links {
+            stats.freed += size;
+        } else {
+            stats.linked_outside += 1;
+        }
+    }
+    stats
+}
+
+#[cfg(not(unix))]
+pub(crate) fn hardlink_stats(_entries: &[Entry]) -> HardlinkStats {
+    HardlinkStats::default()
+}
+
+#[cfg(all(test, unix))]
+mod hardlink_stats_should {
+    use std::{path::PathBuf, sync::atomic::AtomicU8};
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn count_each_linked_file_once() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/hardlinks-dir-{unique}"));
+        let outside = PathBuf::from(format!("./data/hardlinks-dir-{unique}-outside.bin"));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("shared.bin"), "x".repeat(100)).unwrap();
+        std::fs::hard_link(dir.join("shared.bin"), dir.join("nested/link-1.bin")).unwrap();
+        std::fs::hard_link(dir.join("shared.bin"), dir.join("nested/link-2.bin")).unwrap();
+        std::fs::write(dir.join("alone.txt"), "x".repeat(10)).unwrap();
+        std::fs::write(dir.join("kept.bin"), "x".repeat(7)).unwrap();
+        std::fs::hard_link(dir.join("kept.bin"), &outside).unwrap();
+
+        let stats = hardlink_stats(&crate::scan::scan_dir(&dir).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&outside).unwrap();
+
+        assert_eq!(
+            stats,
+            HardlinkStats {
+                entries: 5,
+                inodes: 3,
+                freed: 110,
+                shared: 200,
+                linked_outside: 1,
+            }
+        );
+    }
+}
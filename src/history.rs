@@ -0,0 +1,97 @@
+//! `--first-run-safety`: remembers which targets were confirmed with `--confirm`, so a
+//! path never deleted before only gets a dry run.
+//!
+//! The history is a plain text file with one canonical path per line, by default
+//! `$XDG_STATE_HOME/rm-dir/confirmed-targets`, falling back to
+//! `~/.local/state/rm-dir/confirmed-targets`, or `%LOCALAPPDATA%\rm-dir\confirmed-targets`
+//! on Windows. Deleting the file resets it.
+
+use std::{
+    collections::HashSet,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+const FILE_NAME: &str = "confirmed-targets";
+
+/// The targets confirmed so far, as read from `path`.
+pub(crate) struct History {
+    path: PathBuf,
+    confirmed: HashSet<PathBuf>,
+}
+
+impl History {
+    /// Reads the history at `path`. A missing file is an empty history.
+    pub(crate) fn load(path: &Path) -> Result<Self, std::io::Error> {
+        let confirmed = match std::fs::read_to_string(path) {
+            Ok(contents) => contents.lines().map(PathBuf::from).collect(),
+            Err(e) if e.kind() == ErrorKind::NotFound => HashSet::new(),
+            Err(e) => return Err(e),
+        };
+
+        Ok(History {
+            path: path.to_path_buf(),
+            confirmed,
+        })
+    }
+
+    pub(crate) fn is_confirmed(&self, dir: &Path) -> bool {
+        self.confirmed.contains(dir)
+    }
+
+    /// Adds `dir` to the file, creating it and its folder when needed.
+    pub(crate) fn confirm(&mut self, dir: &Path) -> Result<(), std::io::Error> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", dir.to_string_lossy())?;
+
+        self.confirmed.insert(dir.to_path_buf());
+        Ok(())
+    }
+}
+
+/// Where the history lives unless `--history-file` says otherwise.
+pub(crate) fn default_path() -> Option<PathBuf> {
+    let state_dir = if cfg!(windows) {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_STATE_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/state")))
+    };
+
+    state_dir.map(|dir| dir.join("rm-dir").join(FILE_NAME))
+}
+
+#[cfg(test)]
+mod history_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn remember_confirmed_targets_across_loads() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/history-dir-{unique}");
+        let path = Path::new(&dir).join("state/confirmed-targets");
+
+        let mut history = History::load(&path).unwrap();
+        assert!(!history.is_confirmed(Path::new("/srv/cache")));
+        history.confirm(Path::new("/srv/cache")).unwrap();
+        let reloaded = History::load(&path).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(history.is_confirmed(Path::new("/srv/cache")));
+        assert!(reloaded.is_confirmed(Path::new("/srv/cache")));
+        assert!(!reloaded.is_confirmed(Path::new("/srv")));
+    }
+}
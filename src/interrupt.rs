@@ -0,0 +1,48 @@
+//! Ctrl-C handling so long running modes can stop cleanly between entries.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// True once Ctrl-C was pressed after `install_handler` ran.
+pub(crate) fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+pub(crate) fn install_handler() {
+    const SIGINT: i32 = 2;
+
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+    }
+
+    extern "C" fn on_sigint(_signum: i32) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    }
+
+    // SAFETY: the handler only stores to an atomic, which is async-signal-safe.
+    unsafe {
+        signal(SIGINT, on_sigint);
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn install_handler() {
+    extern "system" {
+        fn SetConsoleCtrlHandler(handler: Option<extern "system" fn(u32) -> i32>, add: i32) -> i32;
+    }
+
+    extern "system" fn on_ctrl_c(_ctrl_type: u32) -> i32 {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        1
+    }
+
+    // SAFETY: the handler only stores to an atomic.
+    unsafe {
+        SetConsoleCtrlHandler(Some(on_ctrl_c), 1);
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn install_handler() {}
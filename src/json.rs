@@ -0,0 +1,369 @@
+//! Just enough JSON writing for machine readable summaries, and reading for the flat
+//! objects of a `--snapshot` or `--stats-history` file.
+
+/// The fields of a flat JSON object, written by `JsonObject` or described by
+/// `schema::SchemaObject`, so an output and its schema come from the same code.
+pub(crate) trait JsonFields {
+    fn string(&mut self, key: &str, value: &str) -> &mut Self;
+    fn number(&mut self, key: &str, value: u64) -> &mut Self;
+    fn float(&mut self, key: &str, value: f64) -> &mut Self;
+    fn bool(&mut self, key: &str, value: bool) -> &mut Self;
+    /// A string that tells kinds of object apart, always `value` for this kind.
+    fn tag(&mut self, key: &str, value: &str) -> &mut Self;
+}
+
+/// Builds a single flat JSON object, one field at a time.
+pub(crate) struct JsonObject {
+    out: String,
+}
+
+impl JsonObject {
+    pub(crate) fn new() -> Self {
+        JsonObject {
+            out: String::from("{"),
+        }
+    }
+
+    pub(crate) fn string(&mut self, key: &str, value: &str) -> &mut Self {
+        self.key(key);
+        push_string(&mut self.out, value);
+        self
+    }
+
+    pub(crate) fn number(&mut self, key: &str, value: u64) -> &mut Self {
+        self.key(key);
+        self.out.push_str(&value.to_string());
+        self
+    }
+
+    pub(crate) fn float(&mut self, key: &str, value: f64) -> &mut Self {
+        self.key(key);
+        if value.is_finite() {
+            self.out.push_str(&value.to_string());
+        } else {
+            self.out.push_str("null");
+        }
+        self
+    }
+
+    pub(crate) fn bool(&mut self, key: &str, value: bool) -> &mut Self {
+        self.key(key);
+        self.out.push_str(if value { "true" } else { "false" });
+        self
+    }
+
+    /// Writes `json`, already valid JSON, as the value of `key`.
+    pub(crate) fn raw(&mut self, key: &str, json: &str) -> &mut Self {
+        self.key(key);
+        self.out.push_str(json);
+        self
+    }
+
+    pub(crate) fn finish(&mut self) -> String {
+        let mut out = std::mem::take(&mut self.out);
+        out.push('}');
+        out
+    }
+
+    fn key(&mut self, key: &str) {
+        if self.out.len() > 1 {
+            self.out.push(',');
+        }
+        push_string(&mut self.out, key);
+        self.out.push(':');
+    }
+}
+
+impl JsonFields for JsonObject {
+    fn string(&mut self, key: &str, value: &str) -> &mut Self {
+        JsonObject::string(self, key, value)
+    }
+
+    fn number(&mut self, key: &str, value: u64) -> &mut Self {
+        JsonObject::number(self, key, value)
+    }
+
+    fn float(&mut self, key: &str, value: f64) -> &mut Self {
+        JsonObject::float(self, key, value)
+    }
+
+    fn bool(&mut self, key: &str, value: bool) -> &mut Self {
+        JsonObject::bool(self, key, value)
+    }
+
+    fn tag(&mut self, key: &str, value: &str) -> &mut Self {
+        JsonObject::string(self, key, value)
+    }
+}
+
+/// `value` as a quoted JSON string.
+pub(crate) fn quote(value: &str) -> String {
+    let mut out = String::new();
+    push_string(&mut out, value);
+    out
+}
+
+/// A JSON array of `items`, each already valid JSON.
+pub(crate) fn array(items: impl IntoIterator<Item = String>) -> String {
+    format!("[{}]", items.into_iter().collect::<Vec<_>>().join(","))
+}
+
+/// Appends `value` as a quoted JSON string.
+fn push_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A value of a flat JSON object. Numbers are whole and non-negative, as written by
+/// `JsonObject::number`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum JsonValue {
+    String(String),
+    Number(u64),
+    Bool(bool),
+    Null,
+}
+
+/// Parses a JSON array of flat objects, like a `--snapshot` file, into each object's
+/// fields in order. Nested arrays or objects, fractions and negative numbers are errors.
+pub(crate) fn parse_objects(text: &str) -> Result<Vec<Vec<(String, JsonValue)>>, String> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+    };
+    let mut objects = Vec::new();
+
+    parser.expect('[')?;
+    if !parser.eat(']') {
+        loop {
+            objects.push(
+                parser
+                    .object()
+                    .map_err(|e| format!("entry {}: {e}", objects.len() + 1))?,
+            );
+            if parser.eat(']') {
+                break;
+            }
+            parser
+                .expect(',')
+                .map_err(|e| format!("after entry {}: {e}", objects.len()))?;
+        }
+    }
+    parser.skip_whitespace();
+    if parser.chars.peek().is_some() {
+        return Err("unexpected text after the array".to_string());
+    }
+
+    Ok(objects)
+}
+
+/// Parses a single flat object, like a line of a `--stats-history` file, into its fields
+/// in order.
+pub(crate) fn parse_object(text: &str) -> Result<Vec<(String, JsonValue)>, String> {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+    };
+    let fields = parser.object()?;
+    parser.skip_whitespace();
+    if parser.chars.peek().is_some() {
+        return Err("unexpected text after the object".to_string());
+    }
+
+    Ok(fields)
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn object(&mut self) -> Result<Vec<(String, JsonValue)>, String> {
+        let mut fields = Vec::new();
+        self.expect('{')?;
+        if self.eat('}') {
+            return Ok(fields);
+        }
+        loop {
+            self.expect('"')?;
+            let key = self.string()?;
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            if self.eat('}') {
+                return Ok(fields);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn value(&mut self) -> Result<JsonValue, String> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('"') => {
+                self.chars.next();
+                Ok(JsonValue::String(self.string()?))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let mut digits = String::new();
+                while let Some(c) = self.chars.next_if(char::is_ascii_digit) {
+                    digits.push(c);
+                }
+                digits
+                    .parse()
+                    .map(JsonValue::Number)
+                    .map_err(|e| format!("invalid number {digits}: {e}"))
+            }
+            Some(_) => {
+                let mut word = String::new();
+                while let Some(c) = self.chars.next_if(char::is_ascii_alphabetic) {
+                    word.push(c);
+                }
+                match word.as_str() {
+                    "true" => Ok(JsonValue::Bool(true)),
+                    "false" => Ok(JsonValue::Bool(false)),
+                    "null" => Ok(JsonValue::Null),
+                    _ => Err(format!(
+                        "expected a value, found {}",
+                        self.chars.peek().map_or(word, |c| format!("'{c}'"))
+                    )),
+                }
+            }
+            None => Err("expected a value, found the end".to_string()),
+        }
+    }
+
+    /// The rest of a string whose opening quote was already read.
+    fn string(&mut self) -> Result<String, String> {
+        let mut out = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(out),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('/') => out.push('/'),
+                    Some('b') => out.push('\u{8}'),
+                    Some('f') => out.push('\u{c}'),
+                    Some('n') => out.push('\n'),
+                    Some('r') => out.push('\r'),
+                    Some('t') => out.push('\t'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape \\u{hex}"))?;
+                        out.push(c);
+                    }
+                    Some(c) => return Err(format!("invalid escape \\{c}")),
+                    None => return Err("unterminated string".to_string()),
+                },
+                Some(c) => out.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    /// Skips whitespace and reads `expected` if it comes next.
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        self.chars.next_if_eq(&expected).is_some()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) {
+            return Ok(());
+        }
+        match self.chars.peek() {
+            Some(found) => Err(format!("expected '{expected}', found '{found}'")),
+            None => Err(format!("expected '{expected}', found the end")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod json_object_should {
+    use super::*;
+
+    #[test]
+    fn write_fields_in_order_and_escape_strings() {
+        let json = JsonObject::new()
+            .string("path", "C:\\dir \"a\"\n")
+            .number("files", 3)
+            .bool("dry_run", false)
+            .float("secs", 0.5)
+            .finish();
+
+        assert_eq!(
+            json,
+            r#"{"path":"C:\\dir \"a\"\n","files":3,"dry_run":false,"secs":0.5}"#
+        );
+    }
+
+    #[test]
+    fn write_an_empty_object() {
+        assert_eq!(JsonObject::new().finish(), "{}");
+    }
+}
+
+#[cfg(test)]
+mod parse_objects_should {
+    use super::*;
+
+    #[test]
+    fn read_back_what_was_written() {
+        let object = JsonObject::new()
+            .string("path", "dir \"a\"\\b\n")
+            .number("size", 42)
+            .bool("dir", true)
+            .finish();
+
+        let objects = parse_objects(&format!("[\n  {object},\n  {{}}\n]\n")).unwrap();
+
+        assert_eq!(
+            objects,
+            vec![
+                vec![
+                    (
+                        "path".to_string(),
+                        JsonValue::String("dir \"a\"\\b\n".to_string())
+                    ),
+                    ("size".to_string(), JsonValue::Number(42)),
+                    ("dir".to_string(), JsonValue::Bool(true)),
+                ],
+                Vec::new(),
+            ]
+        );
+        assert_eq!(parse_objects(" [ ] "), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn reject_truncated_and_malformed_input() {
+        assert_eq!(
+            parse_objects(r#"[{"path":"a"},"#),
+            Err("entry 2: expected '{', found the end".to_string())
+        );
+        assert_eq!(
+            parse_objects(r#"[{"path":"a} "#),
+            Err("entry 1: unterminated string".to_string())
+        );
+        assert_eq!(
+            parse_objects(r#"[{"size":-1}]"#),
+            Err("entry 1: expected a value, found '-'".to_string())
+        );
+        assert!(parse_objects(r#"[{"path":"a"}] trailing"#).is_err());
+    }
+}
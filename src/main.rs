@@ -1,160 +1,5308 @@
 use core::panic;
 use std::{
-    io::{stdin, stdout, BufRead, Write},
+    collections::{hash_map::RandomState, BTreeMap},
+    fs::OpenOptions,
+    hash::{BuildHasher, Hasher},
+    io::{stderr, stdin, stdout, BufRead, BufReader, BufWriter, ErrorKind, IsTerminal, Write},
     path::{Path, PathBuf},
-    time::Instant,
+    process::ExitCode,
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime},
 };
 
-use clap::Parser;
+use checkpoint::Checkpoint;
+use clap::{builder::ArgPredicate, ArgGroup, Parser, ValueEnum};
+use confirm_file::ConfirmFile;
+use duplicates::DedupeKeep;
+use error::RemoveError;
+use filter::{Comparison, DanglingLinks, Filter, PathLimit};
+use glob::Glob;
+use history::History;
+use messages::Lang;
+use progress::{JsonProgress, NoProgress, Progress, ProgressBar, ProgressFile};
+use regex::Regex;
+use remove::{RemoveOptions, RemoveStats, SortOrder};
+use timed_input::TimedInput;
 
-#[derive(Parser)]
+mod archive;
+mod audit;
+mod benchmark;
+mod checkpoint;
+mod confirm_file;
+mod dedupe;
+mod deflate;
+mod diagnose;
+mod duplicates;
+mod error;
+mod exec;
+mod filter;
+mod git;
+mod glob;
+mod hardlinks;
+mod history;
+mod interrupt;
+mod json;
+mod manifest;
+mod marker;
+mod messages;
+mod mount;
+mod notify;
+mod plan_diff;
+mod priority;
+mod progress;
+mod quote;
+mod rate_limit;
+mod regex;
+mod remove;
+mod rollback;
+mod scan;
+mod scan_cache;
+mod schema;
+mod scope_hash;
+mod snapshot;
+mod stats_history;
+mod throttle;
+mod timed_input;
+mod tty;
+mod units;
+mod unlock;
+mod watch;
+mod wipe;
+
+#[derive(Clone, Parser)]
 #[command(version, about, long_about = None)]
+#[command(group(ArgGroup::new("filters").multiple(true)))]
+#[command(group(ArgGroup::new("regeneration").args(["regenerate", "source_snapshot"])))]
 struct Cli {
-    /// The source directory to operate on.
-    #[arg(value_name = "SOURCE_PATH")]
-    source_dir: String,
+    /// The source directories to operate on, one after the other.
+    #[arg(
+        value_name = "SOURCE_PATH",
+        required_unless_present_any = ["target_from_env", "json_schema"]
+    )]
+    source_dirs: Vec<String>,
+
+    /// Print the JSON Schema of the --summary-json-to summary and the --progress-json
+    /// events, then exit without deleting anything.
+    #[arg(long, action, conflicts_with_all = ["source_dirs", "target_from_env"])]
+    json_schema: bool,
+
+    /// Read the source directory from this environment variable instead of the command
+    /// line, so it never goes through the shell. Fails when the variable is unset or empty.
+    #[arg(long, value_name = "VARNAME", conflicts_with = "source_dirs")]
+    target_from_env: Option<String>,
+
+    /// After processing every target, print the totals across all of them: targets, files,
+    /// folders and bytes removed, elapsed time and failures.
+    #[arg(long, action)]
+    batch_summary: bool,
+
+    /// Once every target is processed, successfully or not, run this command with the
+    /// summary line for {} and the JSON summary on stdin, or POST the JSON to this http://
+    /// URL (needs the webhook feature). A failing notification only gets a warning.
+    #[arg(
+        long,
+        alias = "notify-on-complete",
+        value_name = "COMMAND|URL",
+        value_parser = notify::parse_notifier
+    )]
+    notify: Option<notify::Notifier>,
+
+    /// Process targets naming the same directory only once, e.g. 'Build/' and 'build' on a
+    /// case-insensitive filesystem, warning about each dropped duplicate.
+    #[arg(long, action)]
+    case_preserving_dedupe: bool,
+
+    /// What to do with a target that does not exist, e.g. because an earlier run already
+    /// deleted it: skip it silently, warn and carry on, or fail.
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = MissingPolicy::Warn)]
+    on_missing: MissingPolicy,
+
+    /// Flag to force delete without confirmation. Give it twice (-ff) to also skip the
+    /// --prompt-beyond-depth confirmation.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    force: u8,
+
+    /// Delete without confirmation, as with --force, the targets whose canonical path matches
+    /// this regex, e.g. '/(target|node_modules)$', and ask about every other one as usual.
+    #[arg(long, value_name = "REGEX", value_parser = Regex::parse)]
+    yes_if_matches: Option<Regex>,
+
+    /// Treat the session as interactive even when no terminal is detected, e.g. in an editor
+    /// embedded terminal: the progress bar is drawn whatever stderr is.
+    #[arg(long, action, conflicts_with = "assume_no_tty")]
+    assume_tty: bool,
+
+    /// Treat the session as non-interactive even on a terminal: nothing is asked, so without
+    /// --force the target is refused, and no progress bar is drawn.
+    #[arg(long, action)]
+    assume_no_tty: bool,
+
+    /// Where confirmations are asked: on stdin and stdout, or on the terminal itself, e.g.
+    /// /dev/tty, so they still work while stdin or stdout are piped. Fails without a
+    /// controlling terminal unless nothing has to be asked.
+    #[arg(long, value_enum, value_name = "STREAM", default_value_t = PromptStream::Stdin)]
+    prompt_stream: PromptStream,
+
+    /// Take the answers to prompts from this file instead of a terminal, for a pipeline
+    /// whose approval step creates it: each prompt waits for the file, up to
+    /// --prompt-timeout, reads the answer from it, e.g. 'y', and deletes it.
+    #[arg(long, value_name = "PATH", conflicts_with = "assume_no_tty")]
+    confirm_via_file: Option<PathBuf>,
+
+    /// Stop waiting for an answer to any prompt after this long, e.g. 30s or 5m, and go
+    /// with --prompt-timeout-default instead.
+    #[arg(long, alias = "timeout", value_name = "DURATION", value_parser = units::parse_duration)]
+    prompt_timeout: Option<Duration>,
+
+    /// The answer a prompt gets once --prompt-timeout passes without one: 'n' aborts, 'y'
+    /// lets unattended jobs go ahead when no operator steps in.
+    #[arg(
+        long,
+        value_name = "ANSWER",
+        value_parser = ["y", "n"],
+        default_value = "n",
+        requires = "prompt_timeout"
+    )]
+    prompt_timeout_default: String,
+
+    /// Language of the confirmation prompt, the abort message and the summary lines, e.g. 'de'
+    /// or 'es'. Defaults to the language of LC_ALL, LC_MESSAGES or LANG, falling back to
+    /// English for languages without translations.
+    #[arg(long, value_name = "CODE")]
+    lang: Option<String>,
+
+    /// Ask for an extra confirmation when the tree is more than this many levels deep, even
+    /// with a single --force. The detected depth is always reported.
+    #[arg(long, value_name = "N")]
+    prompt_beyond_depth: Option<usize>,
+
+    /// Ask separately whether to delete the files directly in the target and whether to delete
+    /// its subfolders with their contents, and only delete the approved kinds.
+    #[arg(long, action, conflicts_with_all = ["watch", "checkpoint", "resume"])]
+    confirm_each_type: bool,
+
+    /// Ask to type a fresh random code like DELETE-7F3A instead of 'y', so a habitual 'y'
+    /// cannot delete an irreplaceable directory. --force still skips the prompt.
+    #[arg(long, action, conflicts_with = "confirm_each_type")]
+    confirm_with_checksum: bool,
+
+    /// Ask a terse 'Delete 1204 items (3.1 GiB)? [y/N]' below a one-line header naming the
+    /// target, instead of repeating the path in the question.
+    #[arg(long, action, conflicts_with_all = ["confirm_each_type", "confirm_with_checksum"])]
+    confirm_summary_only: bool,
+
+    /// Go ahead even when the target looks like it is on a read-only filesystem, instead of
+    /// refusing before anything is touched.
+    #[arg(long, action)]
+    continue_on_readonly_fs: bool,
+
+    /// Warn about setuid, setgid, sticky and world-writable entries before deleting (Unix only).
+    /// Requires an extra confirmation, even with --force.
+    #[arg(long, action)]
+    audit_perms: bool,
+
+    /// Only delete files matching this glob, e.g. '*.log'. Can be repeated.
+    #[arg(long, group = "filters", value_name = "GLOB")]
+    include: Vec<String>,
+
+    /// Keep files matching this glob, along with everything inside matching folders.
+    /// Can be repeated.
+    #[arg(long, group = "filters", value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Only delete files with one of these extensions, e.g. 'log txt', matched
+    /// case-insensitively. Adds to --include: a file matching either goes. Can be repeated.
+    #[arg(long, group = "filters", value_name = "EXT", num_args = 1..)]
+    include_extension: Vec<String>,
+
+    /// Keep files with one of these extensions, e.g. 'db sqlite', matched
+    /// case-insensitively. Like --exclude, this wins over the include filters. Can be repeated.
+    #[arg(long, group = "filters", value_name = "EXT", num_args = 1..)]
+    exclude_extension: Vec<String>,
+
+    /// Only delete files whose path relative to the target matches this regular expression,
+    /// e.g. '^cache/.*\.tmp$'. Can be repeated.
+    #[arg(long, group = "filters", value_name = "REGEX", value_parser = Regex::parse)]
+    regex: Vec<Regex>,
+
+    /// Match --include, --exclude and --regex patterns case-insensitively.
+    #[arg(long, action)]
+    ignore_case: bool,
+
+    /// Only delete files of at least this size, e.g. 10M.
+    #[arg(long, group = "filters", value_name = "SIZE", value_parser = units::parse_size)]
+    min_size: Option<u64>,
+
+    /// Only delete files of at most this size, e.g. 1G.
+    #[arg(long, group = "filters", value_name = "SIZE", value_parser = units::parse_size)]
+    max_size: Option<u64>,
+
+    /// Only delete files last modified longer ago than this, e.g. 30d.
+    #[arg(long, group = "filters", value_name = "DURATION", value_parser = units::parse_duration)]
+    older_than: Option<Duration>,
+
+    /// Only delete files last modified at least this many days ago, the same as
+    /// --older-than <N>d.
+    #[arg(
+        long,
+        group = "filters",
+        value_name = "N",
+        conflicts_with = "older_than"
+    )]
+    older_than_days: Option<u64>,
+
+    /// Keep files modified since midnight, local time, and delete the older ones.
+    #[arg(long, group = "filters", action)]
+    exclude_today: bool,
+
+    /// Keep every folder that itself, or anything inside it, was modified within this window,
+    /// e.g. 7d, and delete the stale ones whole. Coarser and cheaper than --older-than for
+    /// caches used a folder at a time.
+    #[arg(long, group = "filters", value_name = "DURATION", value_parser = units::parse_duration)]
+    exclude_recent_dirs: Option<Duration>,
+
+    /// Only delete files last modified more recently than this, e.g. 12h.
+    #[arg(long, group = "filters", value_name = "DURATION", value_parser = units::parse_duration)]
+    newer_than: Option<Duration>,
+
+    /// Only delete files whose full path is longer than N characters, e.g. to get a tree ready
+    /// for a system with a path limit. Also reports the longest path found.
+    #[arg(long, group = "filters", value_name = "N")]
+    max_path_length: Option<usize>,
+
+    /// Only delete empty files, regular files of zero bytes such as failed downloads, and
+    /// the folders that empties. Everything else is kept.
+    #[arg(long, group = "filters", action)]
+    delete_empty_files: bool,
+
+    /// Only delete dangling symlinks, whose target cannot be found, and keep everything else.
+    #[arg(long, group = "filters", action)]
+    dangling_only: bool,
+
+    /// Only delete a dangling symlink once its own mtime is older than this, e.g. 7d, as a
+    /// recently broken link may be mid-setup or point to something unmounted for now.
+    /// Links kept for this are listed.
+    #[arg(long, group = "filters", value_name = "DURATION", value_parser = units::parse_duration)]
+    delete_broken_after: Option<Duration>,
+
+    /// Only delete files byte-identical to the file at the same relative path below this
+    /// directory, keeping whatever is unique to the target.
+    #[arg(long, group = "filters", value_name = "DIR", conflicts_with = "watch")]
+    compare_to: Option<PathBuf>,
+
+    /// Only delete duplicates: keep one copy of every set of byte-identical files, the
+    /// oldest by default, and delete the others along with the folders that empties.
+    #[arg(long, group = "filters", action, conflicts_with = "watch")]
+    dedupe: bool,
+
+    /// Set by clap whenever any of the filters above is given, so `is_filtered` never
+    /// misses one. Not meant to be passed.
+    #[arg(
+        long,
+        hide = true,
+        action,
+        default_value_if("filters", ArgPredicate::IsPresent, "true")
+    )]
+    filtered: bool,
+
+    /// Which copy of a set of duplicates --dedupe keeps, by mtime.
+    #[arg(long, value_enum, value_name = "WHICH", requires = "dedupe")]
+    dedupe_keep: Option<DedupeKeep>,
+
+    /// Keep this file or folder, and the folders needed to hold it, while deleting the rest.
+    /// Has to be inside one of the targets. Can be repeated.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["checkpoint", "resume", "watch"])]
+    preserve: Vec<PathBuf>,
+
+    /// Reuse the scan saved in this file instead of walking the tree again, as long as the
+    /// file is newer than the target's own mtime, e.g. for dry runs tuning filters on a
+    /// huge static tree. Otherwise the tree is walked and the scan saved there.
+    #[arg(long, value_name = "PATH", conflicts_with = "watch")]
+    scan_cache: Option<PathBuf>,
+
+    /// Delete every file but keep the folders up to N levels below the target, emptied, so
+    /// tools expecting e.g. target/debug/ to exist keep working. Deeper folders go.
+    #[arg(
+        long,
+        value_name = "N",
+        conflicts_with_all = ["checkpoint", "resume", "watch", "staged", "recreate"]
+    )]
+    preserve_structure_depth: Option<usize>,
+
+    /// After a filtered delete, remove the folders it left empty (the default). Folders that
+    /// were already empty and the target itself are always kept.
+    #[arg(long, action, overrides_with = "no_delete_empty_after_filter")]
+    delete_empty_after_filter: bool,
+
+    /// After a filtered delete, keep the folders it left empty.
+    #[arg(long, action, overrides_with = "delete_empty_after_filter")]
+    no_delete_empty_after_filter: bool,
+
+    /// Move the matched files into this directory, keeping their relative paths, instead of
+    /// deleting them.
+    #[arg(long, value_name = "DIR")]
+    quarantine: Option<PathBuf>,
+
+    /// Keep the directory and delete whatever gets created inside it until Ctrl-C is pressed.
+    /// Entries already there are left alone until they are deleted or replaced. Only
+    /// --exclude applies to this mode.
+    #[arg(long, action)]
+    watch: bool,
+
+    /// Only dry-run targets that were never confirmed before, and ask to run again with
+    /// --confirm to delete them. Confirmed targets are remembered in a history file.
+    #[arg(long, action, conflicts_with = "dry_run")]
+    first_run_safety: bool,
+
+    /// Delete a target --first-run-safety has not seen before, and remember it.
+    #[arg(long, action, requires = "first_run_safety")]
+    confirm: bool,
+
+    /// Where --first-run-safety keeps the confirmed targets, instead of
+    /// ~/.local/state/rm-dir/confirmed-targets.
+    #[arg(long, value_name = "PATH", requires = "first_run_safety")]
+    history_file: Option<PathBuf>,
+
+    /// List what would be deleted without deleting anything.
+    #[arg(long, action, conflicts_with = "watch")]
+    dry_run: bool,
+
+    /// Write the paths a --dry-run would delete to this file, one per line or NUL separated
+    /// with --output-null, and only print the summary. Every target goes into the same file.
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "dry_run",
+        conflicts_with = "explain"
+    )]
+    dry_run_to: Option<PathBuf>,
+
+    /// Compare what --dry-run would delete with a listing saved earlier by --dry-run-to,
+    /// printing '+' for paths newly included and '-' for paths now excluded, and counting
+    /// the unchanged ones. Meant for checking a filter change on a single target.
+    #[arg(
+        long,
+        value_name = "PATH",
+        requires = "dry_run",
+        conflicts_with_all = ["dry_run_to", "explain", "output_null"]
+    )]
+    dry_run_compare: Option<PathBuf>,
+
+    /// Make --dry-run a "needs cleanup" check: exit 0 when nothing would be deleted and 10
+    /// when something would. A failed target still exits 1.
+    #[arg(long, action, requires = "dry_run")]
+    dry_run_exit_code: bool,
+
+    /// With --dry-run, list every file as DELETE or KEEP along with the filter that kept it.
+    #[arg(long, action, requires = "dry_run")]
+    explain: bool,
+
+    /// Print more while deleting, repeat for more detail: -v lists every removed folder, -vv
+    /// every removed file and folder, -vvv adds each file's size and mtime and lists the files
+    /// a filter kept with the reason.
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Set the --verbose level, 0 to 3, by number instead of by repeating -v.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u8).range(0..=3), conflicts_with = "verbose")]
+    verbose_level: Option<u8>,
+
+    /// List at most N paths in a --dry-run or --verbose listing, followed by how many more
+    /// there were. 0 lists them all. The summary always counts everything, and --dry-run-to
+    /// and --output-null listings are never cut short.
+    #[arg(long, value_name = "N", default_value_t = 1000)]
+    preview_limit: usize,
+
+    /// List at most one removed path every MS milliseconds, 100 when not given, the one
+    /// removed right then, so printing millions of lines does not slow down a huge delete.
+    /// Lists files and folders as with -vv unless a --verbose level is given. MS has to be
+    /// given as --throttled-verbose=MS.
+    #[arg(
+        long,
+        value_name = "MS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "100",
+        conflicts_with = "output_null"
+    )]
+    throttled_verbose: Option<u64>,
+
+    /// Single-quote the paths listed by --verbose and --dry-run, so the output can be fed
+    /// back into a shell. Paths are printed as they are by default.
+    #[arg(long, action)]
+    shell_escape_paths: bool,
+
+    /// End every path listed by --verbose and --dry-run with a NUL byte instead of a newline,
+    /// leaving out the rest of the line, for piping into `xargs -0`. Notes, prompts and the
+    /// summary move to stderr or are left out, so stdout only holds the paths.
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["explain", "shell_escape_paths", "group_by_extension", "report_largest"]
+    )]
+    output_null: bool,
+
+    /// List paths under --verbose and --dry-run relative to BASE, given as --relative-to=BASE,
+    /// or to the target when no BASE is given. Paths outside BASE stay absolute; what gets
+    /// deleted does not change.
+    #[arg(long, value_name = "BASE", num_args = 0..=1, require_equals = true)]
+    relative_to: Option<Option<PathBuf>>,
+
+    /// After the summary, list the removed files grouped by extension with their count and
+    /// total size, largest first.
+    #[arg(long, action)]
+    group_by_extension: bool,
+
+    /// After the summary, list the N largest files that were, or on --dry-run would be,
+    /// deleted, with their sizes.
+    #[arg(long, value_name = "N")]
+    report_largest: Option<usize>,
+
+    /// Draw a progress bar with percentage, rate and ETA on stderr. Skipped when stderr is not
+    /// a terminal.
+    #[arg(long, action)]
+    progress_bar: bool,
+
+    /// Report progress on stderr as one JSON object per line for a wrapping program:
+    /// '{"type":"progress","removed":N,"total":M}' now and then, and a final
+    /// '{"type":"done",...}'.
+    #[arg(long, action, conflicts_with = "progress_bar")]
+    progress_json: bool,
+
+    /// Keep a JSON snapshot of the progress in this file for a supervisor to poll, rewritten
+    /// every 250ms while deleting: removed, total, current_path and elapsed_secs. Removed
+    /// once the deletion went through.
+    #[arg(long, value_name = "PATH")]
+    progress_file: Option<PathBuf>,
+
+    /// With --verbose, keep the progress bar at the bottom and print each removed path above
+    /// it. Falls back to plain interleaved output when stdout is not a terminal.
+    #[arg(long, action, requires = "progress_bar")]
+    merge_output: bool,
+
+    /// Scan the folders directly in the target on several threads at once, which speeds up
+    /// the scan behind filters, dry runs and counts on big trees. See --jobs.
+    #[arg(long, action)]
+    parallel_scan: bool,
+
+    /// Number of threads for --parallel-scan. Defaults to the number of CPUs.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+
+    /// At most this many --parallel-scan threads on the same device at once. By default a
+    /// spinning disk, as reported on Linux, gets one and other devices are not limited.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..), requires = "parallel_scan")]
+    max_concurrency_per_device: Option<u16>,
+
+    /// Run with idle I/O priority and the lowest CPU priority, so a background cleanup does not
+    /// slow down everything else. Warns and carries on where that is not supported.
+    #[arg(long, action)]
+    low_priority: bool,
+
+    /// Flush every folder something was deleted from to disk once done, so the deletion
+    /// survives a crash or power loss. Slower, and a no-op outside Unix.
+    #[arg(long, action)]
+    fsync: bool,
+
+    /// Slow deleting down while the system load average is above --max-load and speed up again
+    /// once it drops. Best effort and Unix only, elsewhere nothing is throttled.
+    #[arg(long, action)]
+    throttle_on_load: bool,
+
+    /// Load average --throttle-on-load aims to stay under. Defaults to the number of CPUs.
+    #[arg(long, value_name = "N", requires = "throttle_on_load")]
+    max_load: Option<f64>,
+
+    /// Cap the bytes per second read and written going through file contents, e.g. 50M:
+    /// overwriting with --secure, writing an --archive and comparing with --compare-to or
+    /// --dedupe.
+    #[arg(long, value_name = "BYTES_PER_SEC", value_parser = rate_limit::parse_rate)]
+    limit_rate_bytes: Option<u64>,
+
+    /// Stop deleting once the clock passes this RFC 3339 timestamp, e.g.
+    /// 2026-10-15T06:00:00+02:00, reporting what was removed so far and exiting non-zero.
+    #[arg(
+        long,
+        value_name = "TIMESTAMP",
+        value_parser = units::parse_timestamp,
+        conflicts_with_all = ["checkpoint", "resume", "watch"]
+    )]
+    deadline: Option<SystemTime>,
+
+    /// Also write the run's stats as a JSON object to this file, keeping the usual output, one
+    /// line per target. A file that cannot be written only gets a warning.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["checkpoint", "resume", "watch"])]
+    summary_json_to: Option<PathBuf>,
+
+    /// Append the stats of every deleted target as a line of JSON to this file, and compare
+    /// the bytes freed with the last run recorded for the same target.
+    #[arg(
+        long,
+        alias = "stats-compare",
+        value_name = "PATH",
+        conflicts_with_all = ["dry_run", "checkpoint", "resume", "watch", "delete_manifest"]
+    )]
+    stats_history: Option<PathBuf>,
+
+    /// Only print prompts and errors, no progress or summary.
+    #[arg(short, long, action)]
+    quiet: bool,
+
+    /// Order files are listed and deleted in. 'score' evicts the files with the highest size
+    /// in bytes times age in seconds first. Needs the whole scan in memory, so the default of
+    /// filesystem order is cheaper on huge trees.
+    #[arg(long, alias = "delete-order", value_enum, value_name = "ORDER")]
+    sort: Option<SortOrder>,
+
+    /// When SOURCE_PATH itself is a symlink to a directory, delete the linked directory instead
+    /// of only removing the link.
+    #[arg(long, action)]
+    dereference_root: bool,
+
+    /// Descend into symlinked directories outside the target and delete their contents, then the
+    /// link itself. Without it a symlink is always removed as a link. Fails before deleting
+    /// anything at the first such link unless --allow-symlink-escape is given.
+    #[arg(long, action, conflicts_with_all = ["checkpoint", "resume", "watch"])]
+    follow_symlinks: bool,
+
+    /// On Windows, descend into directory junctions and delete the contents of their targets,
+    /// even outside the target, then the junction. Without it a junction, like any reparse
+    /// point, is removed as a link and what it points to is left alone.
+    #[arg(long, action, conflicts_with_all = ["checkpoint", "resume", "watch"])]
+    follow_junctions: bool,
+
+    /// With --follow-symlinks, abort with a security error as soon as a link resolves outside
+    /// the target, before anything is deleted. The default, spelled out.
+    #[arg(long, action, requires = "follow_symlinks")]
+    fail_fast_on_symlink_escape: bool,
+
+    /// With --follow-symlinks, really descend into links resolving outside the target and
+    /// delete what they point to.
+    #[arg(
+        long,
+        action,
+        requires = "follow_symlinks",
+        conflicts_with = "fail_fast_on_symlink_escape"
+    )]
+    allow_symlink_escape: bool,
+
+    /// Count the size of content reached through followed symlinks in the freed bytes. By
+    /// default only the links themselves count.
+    #[arg(long, action, requires = "follow_symlinks")]
+    count_symlink_targets: bool,
+
+    /// Report the bytes freed as the blocks files take up on disk rather than their apparent
+    /// size, which overstates sparse files such as VM images. Unix only, elsewhere the
+    /// apparent size is used.
+    #[arg(long, action)]
+    sparse_aware: bool,
+
+    /// Leave every filesystem mounted below the target intact, along with the folders holding
+    /// it, instead of descending into it. Uses /proc/self/mounts on Linux and device ids on
+    /// other Unix systems.
+    #[arg(long, action, conflicts_with_all = ["checkpoint", "resume"])]
+    exclude_mount_points: bool,
+
+    /// Leave files that any process holds open in place, along with the folders holding
+    /// them, and list them. Looks through /proc/*/fd, so only works on Linux; elsewhere
+    /// open files are deleted like any other, with a warning.
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["checkpoint", "resume", "staged", "recreate", "delete_manifest"]
+    )]
+    exclude_open_files: bool,
+
+    /// Check the mtime of every file again right before deleting it and keep the file, and
+    /// the folders holding it, when it changed since the scan, e.g. as a process wrote to it.
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["checkpoint", "resume", "staged", "delete_manifest"]
+    )]
+    skip_if_modified_since_scan: bool,
+
+    /// Refuse to delete unless the target, or a folder above it, holds this file or folder,
+    /// e.g. Cargo.toml or .git. The search stops below the home directory.
+    #[arg(long, value_name = "NAME")]
+    require_marker: Option<String>,
+
+    /// Refuse to delete a target inside a git working tree while it holds uncommitted or
+    /// untracked changes, listing them. Ignored files do not count. --force deletes anyway.
+    #[arg(long, action)]
+    refuse_if_git_dirty: bool,
+
+    /// Refuse to delete anything if a file that would go was modified within this window,
+    /// e.g. 10m, as a guard against wiping something just created. Unlike --older-than it
+    /// does not skip those files but stops the run. --force deletes anyway.
+    #[arg(long, value_name = "DURATION", value_parser = units::parse_duration)]
+    protect_newer_than: Option<Duration>,
+
+    /// Refuse to delete the target unless the hash over the sorted paths and sizes in it is
+    /// this one, as printed by --dry-run, so a tree that changed since it was reviewed is left
+    /// alone. The actual hash is printed on a mismatch. --force does not override it.
+    #[arg(
+        long,
+        alias = "confirm-scope-hash",
+        value_name = "HASH",
+        conflicts_with = "delete_manifest"
+    )]
+    expect_scope_hash: Option<String>,
+
+    /// List every symlink in the target with where it points, flagging links outside the tree
+    /// and dangling ones, and ask before deleting, even with --force. Only lists on --dry-run.
+    #[arg(long, action)]
+    symlink_report: bool,
+
+    /// Before asking to delete, print the total size of every folder directly in the target,
+    /// largest first, like `du -sh */`. Nothing is deleted without the usual confirmation,
+    /// and nothing at all on --dry-run.
+    #[arg(
+        long,
+        alias = "depth-limited-size-report",
+        action,
+        conflicts_with = "output_null"
+    )]
+    size_by_subdir: bool,
+
+    /// Before asking to delete, report how many regular files the target holds against how
+    /// many unique inodes they are, the bytes deleting them frees and the bytes hard links
+    /// share. Unix only, elsewhere every file counts as its own.
+    #[arg(
+        long,
+        alias = "count-hardlinks-separately",
+        action,
+        conflicts_with = "output_null"
+    )]
+    hardlink_stats: bool,
+
+    /// When a folder is still there after deleting everything in it, list what is left below
+    /// it and which processes hold those entries open, from /proc. Linux only.
+    #[arg(long, action)]
+    diagnose: bool,
+
+    /// Scan the target twice before deleting and refuse when the two scans count a different
+    /// number of files, a sign of something writing to it. --force only warns.
+    #[arg(long, action)]
+    paranoid: bool,
+
+    /// Recreate the target empty once it is deleted, with the permissions it had before, to
+    /// reset e.g. a build output directory.
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["filters", "quarantine", "watch", "confirm_each_type", "preserve"]
+    )]
+    recreate: bool,
+
+    /// Give the recreated target this octal mode, e.g. 0750, instead of the one it had. Set
+    /// exactly, whatever the umask. On Windows only the write bits count, as read-only.
+    #[arg(long, value_name = "OCTAL", value_parser = units::parse_mode, requires = "recreate")]
+    mode: Option<u32>,
+
+    /// Advanced interactive mode: delete the files directly in the target first, show what is
+    /// left and ask before going one level deeper, and so on. --force deletes in one pass.
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["filters", "dry_run", "quarantine", "watch", "checkpoint", "resume", "confirm_each_type"]
+    )]
+    staged: bool,
+
+    /// All or nothing: move the target aside to a hidden sibling in one rename before
+    /// deleting the moved copy, so a failure never leaves it half deleted in place. The
+    /// rename has to stay on one filesystem, so a target that is a mount point is refused.
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["filters", "preserve", "preserve_structure_depth", "quarantine", "exclude_mount_points", "exclude_open_files", "skip_errors", "ignore_errors_matching", "exec", "watch", "checkpoint", "resume", "staged", "benchmark", "delete_manifest", "confirm_each_type"]
+    )]
+    rollback_on_error: bool,
+
+    /// Benchmark deleting: delete the target N times, recreating it in between with
+    /// --regenerate or --source-snapshot, then report the min, median and max times and the
+    /// files deleted per second. Only the deletes are timed.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "regeneration",
+        conflicts_with_all = ["filters", "dry_run", "quarantine", "watch", "checkpoint", "resume", "staged", "recreate", "confirm_each_type"]
+    )]
+    benchmark: Option<u32>,
+
+    /// With --benchmark, run this command to recreate the target between deletes, e.g.
+    /// 'tar -xf tree.tar'. '{}' stands for the target. Nothing goes through a shell.
+    #[arg(long, value_name = "TEMPLATE", value_parser = exec::parse_template, requires = "benchmark")]
+    regenerate: Option<exec::ExecTemplate>,
+
+    /// With --benchmark, recreate the target between deletes as a copy of this folder.
+    #[arg(long, value_name = "DIR", requires = "benchmark")]
+    source_snapshot: Option<PathBuf>,
+
+    /// Scan the target before prompting, and say how big it is in the prompt: small below this
+    /// size, e.g. 1G, and with a warning from it on.
+    #[arg(long, value_name = "SIZE", value_parser = units::parse_size)]
+    human_readable_prompt_threshold: Option<u64>,
+
+    /// Exit non-zero when the target held no files or folders before the run, whether or not
+    /// the delete itself succeeded. Works with --dry-run too.
+    #[arg(long, action)]
+    fail_if_empty: bool,
+
+    /// Only delete the target when it is already empty, refusing and exiting non-zero when it
+    /// holds any files or folders. Checked on --dry-run too.
+    #[arg(long, action, conflicts_with = "fail_if_empty")]
+    refuse_non_empty: bool,
+
+    /// Run this command for every deleted file before deleting it, with {} replaced by the
+    /// path, e.g. 'shred -u {}'. Spawns one process per file and runs without a shell.
+    #[arg(long, value_name = "TEMPLATE", value_parser = exec::parse_template,
+          conflicts_with_all = ["checkpoint", "resume", "watch"])]
+    exec: Option<exec::ExecTemplate>,
+
+    /// Leave deleting each file to the --exec command instead of deleting it afterwards.
+    #[arg(long, action, requires = "exec")]
+    exec_replace: bool,
+
+    /// Before deleting, give every folder you own in the tree owner read, write and execute
+    /// permission, so locked down folders like mode 000 ones can be emptied. Unix only.
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["filters", "preserve", "preserve_structure_depth", "exclude_mount_points", "watch"]
+    )]
+    ignore_permissions: bool,
+
+    /// Report entries that cannot be deleted, or whose --exec command fails, and carry on
+    /// with the rest. The run still exits non-zero.
+    #[arg(long, action, conflicts_with_all = ["checkpoint", "resume", "watch"])]
+    skip_errors: bool,
+
+    /// With --skip-errors, also write every path that could not be deleted for lack of
+    /// permission to this file, one per line, e.g. to hand to an admin. Other errors are
+    /// only reported as usual.
+    #[arg(long, value_name = "PATH", requires = "skip_errors")]
+    denied_to: Option<PathBuf>,
+
+    /// With --skip-errors, print the errors only at the end, grouped by kind with up to 10
+    /// paths each, e.g. 'Permission denied (231)', instead of as they happen.
+    #[arg(long, action, requires = "skip_errors")]
+    keep_going_summary: bool,
+
+    /// Quietly carry on past errors whose 'PATH: MESSAGE' matches this regex, e.g.
+    /// 'permission denied', without counting them as failures. Other errors still stop the
+    /// run, or are reported under --skip-errors. Can be repeated.
+    #[arg(
+        long,
+        value_name = "REGEX",
+        value_parser = Regex::parse,
+        conflicts_with_all = ["checkpoint", "resume", "watch"]
+    )]
+    ignore_errors_matching: Vec<Regex>,
+
+    /// Overwrite every regular file with random bytes before deleting it. Best effort only:
+    /// copy-on-write and journaling filesystems, SSDs and snapshots can keep the old data.
+    #[arg(long, action, conflicts_with_all = ["quarantine", "exec_replace", "checkpoint", "resume", "watch"])]
+    secure: bool,
+
+    /// How many times --secure overwrites each file.
+    #[arg(long, value_name = "N", default_value_t = 1, requires = "secure",
+          value_parser = clap::value_parser!(u32).range(1..))]
+    secure_passes: u32,
+
+    /// Before deleting, write every path below the target with its type, size and mtime to
+    /// this file as JSON. Nothing is deleted if it cannot be written.
+    #[arg(long, value_name = "PATH", conflicts_with = "watch")]
+    snapshot: Option<PathBuf>,
+
+    /// Before deleting, stream the whole tree into this archive, a .tar, .tar.gz, .tgz or
+    /// .zip by its extension, and report its size. Nothing is deleted if archiving fails.
+    #[arg(
+        long,
+        value_name = "PATH",
+        value_parser = archive::parse_archive_path,
+        conflicts_with_all = ["filters", "quarantine", "watch", "checkpoint", "resume", "staged", "delete_manifest", "preserve", "benchmark", "dry_run"]
+    )]
+    archive: Option<PathBuf>,
+
+    /// Delete exactly the paths listed in this --snapshot file, relative to the target, and
+    /// nothing else: files first, then their folders once empty. Listed paths that are gone
+    /// are handled as --on-missing says. Works with --dry-run.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["filters", "quarantine", "watch", "checkpoint", "resume", "staged", "recreate", "benchmark", "confirm_each_type", "preserve", "snapshot", "refuse_non_empty"]
+    )]
+    delete_manifest: Option<PathBuf>,
+
+    /// Record deleted top-level entries in this file so an interrupted delete can be resumed.
+    /// The file is removed once the delete completes.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["filters", "quarantine", "watch"])]
+    checkpoint: Option<PathBuf>,
+
+    /// Skip the top-level entries recorded in this checkpoint file, then keep updating it.
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["filters", "quarantine", "watch"])]
+    resume: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let opts = Cli::parse();
+
+    if opts.json_schema {
+        println!("{}", schema::json_schema());
+        return ExitCode::SUCCESS;
+    }
+
+    if opts.parallel_scan {
+        scan::set_jobs(opts.jobs.map_or_else(default_jobs, usize::from));
+        if let Some(limit) = opts.max_concurrency_per_device {
+            scan::set_device_limit(usize::from(limit));
+        }
+    }
+
+    if opts.low_priority {
+        if let Err(e) = priority::lower_priority() {
+            println!("Warning: could not fully lower priority: {}", e);
+        }
+    }
+
+    if let Some(rate) = opts.limit_rate_bytes {
+        if !opts.secure && opts.archive.is_none() && opts.compare_to.is_none() && !opts.dedupe {
+            println!(
+                "Warning: --limit-rate-bytes only slows down --secure, --archive, --compare-to and --dedupe"
+            );
+        }
+        rate_limit::set_limit(rate);
+    }
+
+    if opts.follow_junctions && !cfg!(windows) {
+        println!("Warning: junctions only exist on Windows, --follow-junctions does nothing here");
+    }
+
+    if opts.exclude_open_files && !cfg!(target_os = "linux") {
+        println!("Warning: cannot tell which files are open on this platform, --exclude-open-files deletes them too");
+    }
+
+    let batch = run_all(&opts);
+
+    if opts.batch_summary {
+        print_batch_summary(&batch, opts.dry_run);
+    }
+    if let Some(notifier) = &opts.notify {
+        notify_completion(notifier, &batch, opts.dry_run);
+    }
+
+    ExitCode::from(exit_code(&opts, &batch))
+}
+
+fn default_jobs() -> usize {
+    std::thread::available_parallelism().map_or(1, |cpus| cpus.get())
+}
+
+/// Exit code of a dry run under --dry-run-exit-code when something would be deleted.
+const WORK_PENDING: u8 = 10;
+
+/// 1 when any target failed. Otherwise 0, unless --dry-run-exit-code found work pending.
+fn exit_code(opts: &Cli, batch: &BatchTotals) -> u8 {
+    if batch.failures > 0 {
+        return 1;
+    }
+
+    let pending = batch.stats.files + batch.stats.dirs > 0;
+    if opts.dry_run_exit_code && pending {
+        WORK_PENDING
+    } else {
+        0
+    }
+}
+
+/// How a target that does not exist is handled.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum MissingPolicy {
+    /// Carry on without a word, exiting 0.
+    Skip,
+    /// Print a warning to stderr and carry on, exiting 0.
+    Warn,
+    /// Print an error and exit non-zero.
+    Error,
+}
+
+/// Where the confirmation prompts read and write.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum PromptStream {
+    /// Read answers from stdin and ask on stdout.
+    Stdin,
+    /// Read and ask on the controlling terminal, /dev/tty or the Windows console.
+    Tty,
+}
+
+/// Totals across every target of a run.
+#[derive(Default)]
+struct BatchTotals {
+    targets: u64,
+    failures: u64,
+    /// Targets that did not exist, whatever the --on-missing policy.
+    missing: u64,
+    stats: RemoveStats,
+    elapsed: Duration,
+}
+
+/// Processes the targets one after the other, carrying on past failed ones.
+fn run_all(opts: &Cli) -> BatchTotals {
+    let started = Instant::now();
+    let mut batch = BatchTotals::default();
+
+    let source_dirs = match &opts.target_from_env {
+        Some(name) => match target_from_env(name) {
+            Ok(target) => vec![target],
+            Err(e) => {
+                println!("Error: {e}");
+                batch.failures += 1;
+                return batch;
+            }
+        },
+        None => opts.source_dirs.clone(),
+    };
+    let source_dirs = if opts.case_preserving_dedupe {
+        unique_targets(&source_dirs)
+    } else {
+        source_dirs
+    };
+
+    // Every target keeps the preserved paths inside it, so a mistyped one fails the run
+    // before anything is deleted.
+    let resolved;
+    let opts = if opts.preserve.is_empty() {
+        opts
+    } else {
+        match resolve_preserved(&opts.preserve, &source_dirs) {
+            Ok(preserve) => {
+                resolved = Cli {
+                    preserve,
+                    ..opts.clone()
+                };
+                &resolved
+            }
+            Err(e) => {
+                println!("Error: {e}");
+                batch.failures += 1;
+                return batch;
+            }
+        }
+    };
+
+    // Fail before scanning anything when the listing has nowhere to go. Each target then
+    // appends to the emptied file.
+    if let Some(path) = &opts.dry_run_to {
+        if let Err(e) = std::fs::File::create(path) {
+            println!(
+                "Error: Failed to create {}. Error: {}",
+                path.to_string_lossy(),
+                e
+            );
+            batch.failures += 1;
+            return batch;
+        }
+    }
+
+    // Every target appends the paths it was denied to the emptied file.
+    if let Some(path) = &opts.denied_to {
+        if let Err(e) = std::fs::File::create(path) {
+            println!(
+                "Error: Failed to create {}. Error: {}",
+                path.to_string_lossy(),
+                e
+            );
+            batch.failures += 1;
+            return batch;
+        }
+    }
+
+    // Every target appends its summary line to the emptied file. Only a warning, as the
+    // summary never fails a run.
+    if let Some(path) = &opts.summary_json_to {
+        if let Err(e) = std::fs::File::create(path) {
+            println!(
+                "Warning: failed to write summary to {}. Error: {}",
+                path.to_string_lossy(),
+                e
+            );
+        }
+    }
+
+    for source_dir in &source_dirs {
+        batch.targets += 1;
+
+        if std::fs::symlink_metadata(source_dir).is_err_and(|e| e.kind() == ErrorKind::NotFound) {
+            batch.missing += 1;
+            if !handle_missing(source_dir, opts.on_missing) {
+                batch.failures += 1;
+            }
+            continue;
+        }
+
+        let (succeeded, stats) = run_target(opts, source_dir);
+
+        if !succeeded {
+            batch.failures += 1;
+        }
+        if let Some(stats) = stats {
+            batch.stats.accumulate(&stats);
+        }
+    }
+
+    batch.elapsed = started.elapsed();
+    batch
+}
+
+/// Reads the target out of the `name` environment variable. An unset or empty variable is
+/// an error rather than falling back to anything, like the current directory.
+fn target_from_env(name: &str) -> Result<String, String> {
+    match std::env::var_os(name) {
+        None => Err(format!("The environment variable {name} is not set")),
+        Some(value) if value.is_empty() => Err(format!("The environment variable {name} is empty")),
+        Some(value) => value
+            .into_string()
+            .map_err(|_| format!("The environment variable {name} is not valid UTF-8")),
+    }
+}
+
+/// Reports a target that does not exist as `policy` asks. Returns whether that is fine.
+fn handle_missing(source_dir: &str, policy: MissingPolicy) -> bool {
+    match policy {
+        MissingPolicy::Skip => true,
+        MissingPolicy::Warn => {
+            eprintln!("Warning: {} does not exist, skipping it.", source_dir);
+            true
+        }
+        MissingPolicy::Error => {
+            println!("Error: {} does not exist", source_dir);
+            false
+        }
+    }
+}
+
+/// Drops targets naming a directory that an earlier one already names.
+fn unique_targets(source_dirs: &[String]) -> Vec<String> {
+    let (unique, duplicates) = dedupe::dedupe_targets(source_dirs);
+    for duplicate in duplicates {
+        println!(
+            "Warning: {} is the same directory as {}, processing it once.",
+            duplicate.target, duplicate.same_as
+        );
+    }
+    unique
+}
+
+fn print_batch_summary(batch: &BatchTotals, dry_run: bool) {
+    let verb = if dry_run { "Would remove" } else { "Removed" };
+
+    println!("Batch summary:");
+    println!("  Targets processed: {}", batch.targets);
+    println!(
+        "  {}: {} files ({}) and {} folders",
+        verb,
+        batch.stats.files,
+        units::format_size(batch.stats.bytes),
+        batch.stats.dirs
+    );
+    println!("  Missing: {}", batch.missing);
+    println!("  Failures: {}", batch.failures);
+    println!("  Elapsed: {}s", batch.elapsed.as_secs_f32());
+    print_extension_report(&batch.stats);
+    print_largest_report(&batch.stats);
+}
+
+/// Sends the --notify notification for the finished run, only warning when that fails.
+fn notify_completion(notifier: &notify::Notifier, batch: &BatchTotals, dry_run: bool) {
+    let verb = if dry_run { "would remove" } else { "removed" };
+    let outcome = if batch.failures == 0 {
+        format!("rm-dir finished: {} targets", batch.targets)
+    } else {
+        format!(
+            "rm-dir failed on {} of {} targets",
+            batch.failures, batch.targets
+        )
+    };
+    let summary = format!(
+        "{}, {} {} files ({}) and {} folders",
+        outcome,
+        verb,
+        batch.stats.files,
+        units::format_size(batch.stats.bytes),
+        batch.stats.dirs
+    );
+    let json = json::JsonObject::new()
+        .bool("succeeded", batch.failures == 0)
+        .bool("dry_run", dry_run)
+        .number("targets", batch.targets)
+        .number("failures", batch.failures)
+        .number("missing", batch.missing)
+        .number("files", batch.stats.files)
+        .number("dirs", batch.stats.dirs)
+        .number("bytes", batch.stats.bytes)
+        .float("elapsed_secs", batch.elapsed.as_secs_f64())
+        .finish();
+
+    if let Err(e) = notifier.send(&summary, &json) {
+        println!("Warning: failed to send the notification. Error: {}", e);
+    }
+}
+
+/// Lets a run on `dir` go ahead when --first-run-safety saw it confirmed before or it is
+/// being confirmed now. Otherwise only dry-runs it and returns false.
+fn check_first_run(opts: &Cli, dir: &Path) -> bool {
+    let Some((_, history)) = first_run_history(opts) else {
+        return false;
+    };
+
+    // The target is only remembered once its delete was confirmed and went through.
+    if history.is_confirmed(dir) || opts.confirm {
+        return true;
+    }
+
+    // The dry run only lists, whatever else was asked for.
+    let options = match remove_options(opts, dir) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("Error: {}", e);
+            return false;
+        }
+    };
+    let _result = handle_dry_run(dir, &options, None, None);
+    println!(
+        "First run on {}, nothing was deleted. Run again with --confirm to delete it.",
+        dir.to_string_lossy()
+    );
+    false
+}
+
+/// The --first-run-safety history and where it lives. Reports and returns None when it
+/// cannot be read.
+fn first_run_history(opts: &Cli) -> Option<(PathBuf, History)> {
+    let Some(path) = opts.history_file.clone().or_else(history::default_path) else {
+        println!("Error: no home directory to keep the --first-run-safety history in");
+        return None;
+    };
+    match History::load(&path) {
+        Ok(history) => Some((path, history)),
+        Err(e) => {
+            println!(
+                "Error: Failed to read {}. Error: {}",
+                path.to_string_lossy(),
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Remembers `dir` in the --first-run-safety history after a delete confirmed with
+/// --confirm. A history that cannot be written only gets a warning, the delete itself
+/// went fine.
+fn remember_first_run(opts: &Cli, dir: &Path) {
+    if !opts.first_run_safety || !opts.confirm {
+        return;
+    }
+    let Some((path, mut history)) = first_run_history(opts) else {
+        return;
+    };
+    if history.is_confirmed(dir) {
+        return;
+    }
+    if let Err(e) = history.confirm(dir) {
+        println!(
+            "Warning: failed to write {}. Error: {}",
+            path.to_string_lossy(),
+            e
+        );
+    }
+}
+
+/// Prints the `--report-largest` list, e.g. `  1. target/debug/app 420.0 MiB`.
+fn print_largest_report(stats: &RemoveStats) {
+    let largest = stats.largest.sorted();
+    if largest.is_empty() {
+        return;
+    }
+
+    println!("Largest files:");
+    for (rank, (bytes, path)) in largest.iter().enumerate() {
+        println!(
+            "  {}. {} {}",
+            rank + 1,
+            path.to_string_lossy(),
+            units::format_size(*bytes)
+        );
+    }
+}
+
+/// Prints the `--keep-going-summary` errors, e.g. `Permission denied (231):` followed by
+/// its first paths and `... and 221 more`.
+fn print_error_groups(stats: &RemoveStats) {
+    print!("{}", format_error_groups(stats));
+}
+
+fn format_error_groups(stats: &RemoveStats) -> String {
+    let mut report = String::new();
+    for (kind, group) in &stats.errors_by_kind {
+        report.push_str(&format!("{kind} ({}):\n", group.count));
+        for path in &group.paths {
+            report.push_str(&format!("  {}\n", path.to_string_lossy()));
+        }
+        let unlisted = group.count - group.paths.len() as u64;
+        if unlisted > 0 {
+            report.push_str(&format!("  ... and {unlisted} more\n"));
+        }
+    }
+    report
+}
+
+/// Prints the `--group-by-extension` table, e.g. `  .o  2.1 GiB (4012 files)`.
+fn print_extension_report(stats: &RemoveStats) {
+    if stats.by_extension.is_empty() {
+        return;
+    }
+
+    println!("By extension:");
+    for (extension, totals) in stats.extensions_by_size() {
+        let extension = if extension.is_empty() {
+            "(none)"
+        } else {
+            extension
+        };
+        println!(
+            "  {:<12} {:>10} ({} files)",
+            extension,
+            units::format_size(totals.bytes),
+            totals.files
+        );
+    }
+}
+
+/// Runs the whole prompt and delete sequence for one target. Returns whether it succeeded,
+/// along with its stats when the tree was walked.
+fn run_target(opts: &Cli, source_dir: &str) -> (bool, Option<RemoveStats>) {
+    let source = Path::new(source_dir);
+    if !opts.dereference_root && source.is_symlink() {
+        return (handle_symlink_root(opts, source), None);
+    }
+
+    let dir_to_remove = match canonicalize_target(source) {
+        Ok(dir) => dir,
+        Err(e) => {
+            println!("Error: {}", e);
+            return (false, None);
+        }
+    };
+
+    let auto_confirmed;
+    let opts = if opts.force == 0 && is_auto_confirmed(opts, &dir_to_remove) {
+        auto_confirmed = Cli {
+            force: 1,
+            ..opts.clone()
+        };
+        &auto_confirmed
+    } else {
+        opts
+    };
+
+    if let Some(marker) = &opts.require_marker {
+        if !has_marker(&dir_to_remove, marker) {
+            return (false, None);
+        }
+    }
+
+    if opts.refuse_if_git_dirty && opts.force == 0 && !is_git_clean(&dir_to_remove) {
+        return (false, None);
+    }
+
+    let mut options = match remove_options(opts, &dir_to_remove) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("Error: {}", e);
+            return (false, None);
+        }
+    };
+
+    if let (Some(window), 0) = (opts.protect_newer_than, opts.force) {
+        if !is_old_enough(&dir_to_remove, &options, window) {
+            return (false, None);
+        }
+    }
+
+    if opts.refuse_non_empty && !is_empty_target(&dir_to_remove) {
+        println!(
+            "Error: {} is not empty, refusing to delete it",
+            dir_to_remove.to_string_lossy()
+        );
+        return (false, None);
+    }
+
+    if let Some(manifest) = &opts.delete_manifest {
+        return delete_manifest(opts, &dir_to_remove, manifest);
+    }
+
+    if (opts.dry_run || opts.expect_scope_hash.is_some()) && !check_scope_hash(opts, &dir_to_remove)
+    {
+        return (false, None);
+    }
+
+    let was_empty = opts.fail_if_empty && is_empty_target(&dir_to_remove);
+
+    if opts.size_by_subdir {
+        print!("{}", subdir_size_report(&dir_to_remove));
+    }
+    if opts.hardlink_stats {
+        print!("{}", hardlink_report(&dir_to_remove));
+    }
+
+    if opts.dry_run {
+        if opts.symlink_report {
+            let Some(report) = checked_symlink_report(&dir_to_remove) else {
+                return (false, None);
+            };
+            print!("{report}");
+        }
+        let result = handle_dry_run(
+            &dir_to_remove,
+            &options,
+            opts.dry_run_to.as_deref(),
+            opts.dry_run_compare.as_deref(),
+        );
+        let succeeded = succeeded(result.is_ok(), was_empty, &dir_to_remove);
+        return (succeeded, result.ok());
+    }
+
+    if opts.first_run_safety && !check_first_run(opts, &dir_to_remove) {
+        return (false, None);
+    }
+
+    if !opts.continue_on_readonly_fs && mount::is_read_only(&dir_to_remove) {
+        println!(
+            "Error: {} is on a read-only filesystem; cannot delete",
+            dir_to_remove.to_string_lossy()
+        );
+        return (false, None);
+    }
+
+    // Answers piped into stdin are fine, so only --assume-no-tty rules out prompting.
+    if opts.force == 0 && !is_interactive(opts, true) {
+        println!(
+            "Error: not asking whether to delete {} without a terminal, pass --force to delete it",
+            dir_to_remove.to_string_lossy()
+        );
+        return (false, None);
+    }
+
+    let Some((mut input, mut output)) = prompt_streams(opts) else {
+        return (false, None);
+    };
+
+    if opts.symlink_report && !confirm_symlinks(&dir_to_remove, &mut input, &mut output) {
+        return (false, None);
+    }
+
+    if opts.audit_perms && !confirm_audit(&dir_to_remove, &mut input, &mut output) {
+        return (false, None);
+    }
+
+    if let Some(threshold) = opts.prompt_beyond_depth {
+        let doubly_forced = opts.force > 1;
+        if !confirm_depth(
+            &dir_to_remove,
+            threshold,
+            doubly_forced,
+            &mut input,
+            &mut output,
+        ) {
+            return (false, None);
+        }
+    }
+
+    if opts.paranoid && !check_stable(&dir_to_remove, opts.force > 0, count_files) {
+        return (false, None);
+    }
+
+    if source.is_symlink() {
+        println!(
+            "{} is a symlink, removing linked directory {}",
+            source.to_string_lossy(),
+            dir_to_remove.to_string_lossy()
+        );
+    }
+
+    let confirmation = if opts.confirm_each_type {
+        let kept = confirm_each_type(&dir_to_remove, opts.force > 0, &mut input, &mut output)
+            .and_then(|(files, dirs)| {
+                Ok((files || dirs, kept_by_type(&dir_to_remove, files, dirs)?))
+            });
+        match kept {
+            Ok((approved, kept)) => {
+                options.preserve.extend(kept);
+                if approved { "y" } else { "n" }.to_string()
+            }
+            Err(e) => {
+                println!(
+                    "Error: Failed to read {}. Error: {}",
+                    dir_to_remove.to_string_lossy(),
+                    e
+                );
+                return (false, None);
+            }
+        }
+    } else if opts.confirm_with_checksum && opts.force == 0 {
+        let code = confirmation_code();
+        if !confirm_with_code(&dir_to_remove, &code, &mut input, &mut output) {
+            return (false, None);
+        }
+        "y".to_string()
+    } else if opts.output_null && opts.force > 0 {
+        // Announcing the forced run would mix with the listed paths on stdout.
+        "y".to_string()
+    } else {
+        let mut errors = stderr();
+        let mut prompt_output: &mut dyn Write =
+            if opts.output_null && opts.prompt_stream == PromptStream::Stdin {
+                &mut errors
+            } else {
+                &mut output
+            };
+        let summary = if opts.confirm_summary_only && opts.force == 0 {
+            get_summary_confirmation(
+                &dir_to_remove,
+                opts.human_readable_prompt_threshold,
+                &mut input,
+                &mut prompt_output,
+            )
+        } else {
+            None
+        };
+        summary
+            .unwrap_or_else(|| {
+                let hint = match opts.human_readable_prompt_threshold {
+                    Some(threshold) if opts.force == 0 => scope_hint(&dir_to_remove, threshold),
+                    _ => None,
+                };
+                get_user_confirmation(
+                    &dir_to_remove,
+                    opts.force > 0,
+                    hint.as_deref(),
+                    options.lang,
+                    &mut input,
+                    &mut prompt_output,
+                )
+            })
+            .trim()
+            .to_lowercase()
+    };
+
+    if opts.watch {
+        let result = handle_watch(&confirmation, &dir_to_remove, &options.filter);
+        return (result.is_ok(), None);
+    }
+
+    if confirmation == "y" {
+        if let Some(snapshot_path) = &opts.snapshot {
+            let snapshot_path = match check_outside_target(snapshot_path.clone(), &dir_to_remove) {
+                Ok(path) => path,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return (false, None);
+                }
+            };
+            if !take_snapshot(&dir_to_remove, &snapshot_path) {
+                return (false, None);
+            }
+        }
+        if let Some(archive_path) = &opts.archive {
+            let archive_path = match check_outside_target(archive_path.clone(), &dir_to_remove) {
+                Ok(path) => path,
+                Err(e) => {
+                    println!("Error: {}", e);
+                    return (false, None);
+                }
+            };
+            if !write_archive(&dir_to_remove, &archive_path) {
+                return (false, None);
+            }
+        }
+    }
+
+    if let (Some(iterations), "y") = (opts.benchmark, confirmation.as_str()) {
+        return (run_benchmark(opts, &dir_to_remove, iterations), None);
+    }
+
+    if opts.ignore_permissions && confirmation == "y" {
+        unlock_target(&dir_to_remove, opts.quiet);
+    }
+
+    if opts.staged && opts.force == 0 && confirmation == "y" {
+        let result = remove_staged(&dir_to_remove, &mut input, &mut output);
+        match &result {
+            Ok(_) => remember_first_run(opts, &dir_to_remove),
+            Err(e) => println!("Error: {}", e),
+        }
+        let succeeded = succeeded(result.is_ok(), was_empty, &dir_to_remove);
+        return (succeeded, result.ok());
+    }
+
+    let permissions = opts
+        .recreate
+        .then(|| {
+            std::fs::metadata(&dir_to_remove).map(|metadata| match opts.mode {
+                Some(mode) => with_mode(metadata.permissions(), mode),
+                None => metadata.permissions(),
+            })
+        })
+        .transpose();
+    let permissions = match permissions {
+        Ok(permissions) => permissions,
+        Err(e) => {
+            println!(
+                "Error: Failed to read the permissions of {}. Error: {}",
+                dir_to_remove.to_string_lossy(),
+                e
+            );
+            return (false, None);
+        }
+    };
+
+    let result = if opts.rollback_on_error && confirmation == "y" {
+        let result = rollback::delete_staged(&dir_to_remove, &mut stdout(), |staged| {
+            handle_confirmation(&confirmation, staged, &options)
+        });
+        if let Err(e @ RemoveError::Delete { path, .. }) = &result {
+            if path == &dir_to_remove {
+                println!("Error: {}", e);
+            }
+        }
+        result
+    } else {
+        handle_confirmation(&confirmation, &dir_to_remove, &options)
+    };
+    if let (true, Err(e)) = (opts.diagnose, &result) {
+        diagnose_failure(e);
+    }
+    if confirmation == "y" && result.is_ok() {
+        remember_first_run(opts, &dir_to_remove);
+    }
+    let mut succeeded = succeeded(result.is_ok(), was_empty, &dir_to_remove);
+    if let (Some(history), Ok(Some(stats))) = (&opts.stats_history, &result) {
+        record_stats(history, &dir_to_remove, stats, opts.quiet);
+    }
+
+    if let Some(permissions) = permissions {
+        if confirmation == "y" && result.is_ok() && !recreate_dir(&dir_to_remove, permissions) {
+            succeeded = false;
+        }
+    }
+
+    (succeeded, result.ok().flatten())
+}
+
+/// Appends the stats of deleting `dir` to the --stats-history file, printing how they
+/// compare to the last run recorded for it. A history that cannot be read or written only
+/// gets a warning, the delete itself went fine.
+fn record_stats(history: &Path, dir: &Path, stats: &RemoveStats, quiet: bool) {
+    let record = stats_history::Record::new(dir, stats, SystemTime::now());
+    let previous = stats_history::last_record(history, &record.target);
+    let appended = stats_history::append(history, &record);
+
+    match previous {
+        Ok(Some(previous)) if !quiet => {
+            println!("{}", stats_history::compare(&previous, &record))
+        }
+        Ok(None) if !quiet => println!(
+            "No earlier run of {} in {} to compare with",
+            record.target,
+            history.to_string_lossy()
+        ),
+        Ok(_) => {}
+        Err(e) => println!(
+            "Warning: failed to read {}. Error: {}",
+            history.to_string_lossy(),
+            e
+        ),
+    }
+    if let Err(e) = appended {
+        println!(
+            "Warning: failed to write {}. Error: {}",
+            history.to_string_lossy(),
+            e
+        );
+    }
+}
+
+/// Whether --yes-if-matches confirms deleting `dir` up front.
+fn is_auto_confirmed(opts: &Cli, dir: &Path) -> bool {
+    opts.yes_if_matches
+        .as_ref()
+        .is_some_and(|regex| regex.is_match(&dir.to_string_lossy()))
+}
+
+/// The streams every prompt about a target reads answers from and asks on: stdin and
+/// stdout, the terminal with --prompt-stream tty, or the --confirm-via-file file. A run
+/// asking nothing keeps to stdin and stdout, so it needs no terminal. Reports and returns
+/// None when none can be opened.
+fn prompt_streams(opts: &Cli) -> Option<(Box<dyn BufRead>, Box<dyn Write>)> {
+    static STDIN_LINES: OnceLock<timed_input::Lines> = OnceLock::new();
+    static TERMINAL_LINES: OnceLock<timed_input::Lines> = OnceLock::new();
+
+    // --audit-perms and --symlink-report ask even with --force.
+    let asks = opts.force == 0
+        || opts.audit_perms
+        || opts.symlink_report
+        || (opts.force == 1 && opts.prompt_beyond_depth.is_some());
+    if let Some(path) = opts.confirm_via_file.clone().filter(|_| asks) {
+        let input = ConfirmFile::new(
+            path,
+            opts.prompt_timeout,
+            &opts.prompt_timeout_default,
+            stdout(),
+        );
+        return Some((Box::new(input), Box::new(stdout())));
+    }
+    if opts.prompt_stream == PromptStream::Stdin || !asks {
+        let Some(timeout) = opts.prompt_timeout else {
+            return Some((Box::new(stdin().lock()), Box::new(stdout())));
+        };
+        let lines = STDIN_LINES.get_or_init(|| timed_input::spawn_lines(BufReader::new(stdin())));
+        let input = TimedInput::new(
+            lines.clone(),
+            timeout,
+            &opts.prompt_timeout_default,
+            stdout(),
+        );
+        return Some((Box::new(input), Box::new(stdout())));
+    }
+
+    match tty::open_terminal() {
+        Ok((input, output)) => match (opts.prompt_timeout, output.try_clone()) {
+            (None, _) => Some((Box::new(input), Box::new(output))),
+            (Some(timeout), Ok(notice)) => {
+                let lines = TERMINAL_LINES.get_or_init(|| timed_input::spawn_lines(input));
+                let input =
+                    TimedInput::new(lines.clone(), timeout, &opts.prompt_timeout_default, notice);
+                Some((Box::new(input), Box::new(output)))
+            }
+            (Some(_), Err(e)) => {
+                println!("Error: failed to share the terminal for --prompt-timeout: {e}");
+                None
+            }
+        },
+        Err(e) => {
+            println!("Error: --prompt-stream tty needs a controlling terminal to ask on: {e}");
+            None
+        }
+    }
+}
+
+/// Runs --benchmark on `dir`, returning whether every iteration went through.
+fn run_benchmark(opts: &Cli, dir: &Path, iterations: u32) -> bool {
+    let regenerate = match (&opts.regenerate, &opts.source_snapshot) {
+        (Some(command), _) => benchmark::Regenerate::Command(command.clone()),
+        (None, Some(snapshot)) => benchmark::Regenerate::Snapshot(snapshot.clone()),
+        (None, None) => unreachable!("--benchmark requires a way to regenerate the target"),
+    };
+
+    match benchmark::run(dir, iterations, &regenerate, &mut stdout()) {
+        Ok(timings) => {
+            println!("Benchmark: {}", benchmark::summarize(&timings));
+            true
+        }
+        Err(e) => {
+            println!(
+                "Error: Benchmark of {} stopped: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Runs --delete-manifest on `dir`, asking first unless forced or on --dry-run.
+fn delete_manifest(opts: &Cli, dir: &Path, manifest_path: &Path) -> (bool, Option<RemoveStats>) {
+    let entries = match manifest::read_manifest(manifest_path) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!("Error: Failed to read manifest. Error: {}", e);
+            return (false, None);
+        }
+    };
+
+    if !opts.dry_run && opts.force == 0 {
+        if !is_interactive(opts, true) {
+            println!(
+                "Error: not asking whether to delete the paths listed in {} without a terminal, pass --force to delete them",
+                manifest_path.to_string_lossy()
+            );
+            return (false, None);
+        }
+        let Some((mut input, mut output)) = prompt_streams(opts) else {
+            return (false, None);
+        };
+        let prompt = format!(
+            "Are you sure you want to delete the {} paths listed in {} from {}? (y/n) ",
+            entries.len(),
+            manifest_path.to_string_lossy(),
+            dir.to_string_lossy()
+        );
+        let confirmation = prompt_user(&prompt, &mut input, &mut output).to_lowercase();
+        if confirmation != "y" {
+            println!("{}", language(opts).aborting(&confirmation));
+            return (true, None);
+        }
+    }
+
+    let outcome = match manifest::delete_listed(dir, &entries, opts.dry_run, &mut stdout()) {
+        Ok(outcome) => outcome,
+        Err(e) => {
+            println!("Error: {}", e);
+            return (false, None);
+        }
+    };
+
+    let mut succeeded = true;
+    for missing in &outcome.missing {
+        if !handle_missing(&dir.join(missing).to_string_lossy(), opts.on_missing) {
+            succeeded = false;
+        }
+    }
+
+    if !opts.quiet {
+        let stats = &outcome.stats;
+        let verb = if opts.dry_run {
+            "Dry run, nothing was deleted. Would remove"
+        } else {
+            "Removed"
+        };
+        println!(
+            "{} {} files ({}) and {} folders listed in {} from {}",
+            verb,
+            stats.files,
+            units::format_size(stats.bytes),
+            stats.dirs,
+            manifest_path.to_string_lossy(),
+            dir.to_string_lossy()
+        );
+    }
+
+    (succeeded, Some(outcome.stats))
+}
+
+/// Explains for --diagnose a folder that could not be removed as it was not empty, or was
+/// still there after the delete, by listing what is left and who holds it open.
+fn diagnose_failure(error: &RemoveError) {
+    let dir = match error {
+        RemoveError::Delete { path, source } if source.kind() == ErrorKind::DirectoryNotEmpty => {
+            path
+        }
+        RemoveError::Verify { path } => path,
+        _ => return,
+    };
+
+    let remaining = match diagnose::remaining_entries(dir) {
+        Ok(remaining) => remaining,
+        Err(e) => {
+            println!(
+                "Diagnosis: could not look into {}: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            return;
+        }
+    };
+
+    println!(
+        "Diagnosis: {} still holds {} entries after the delete:",
+        dir.to_string_lossy(),
+        remaining.len()
+    );
+    for entry in &remaining {
+        let holders = if entry.holders.is_empty() {
+            "not open in any process this user can see".to_string()
+        } else {
+            let holders: Vec<String> = entry
+                .holders
+                .iter()
+                .map(|holder| format!("{} {}", holder.pid, holder.command))
+                .collect();
+            format!("held open by {}", holders.join(", "))
+        };
+        println!("  {} ({})", entry.path.to_string_lossy(), holders);
+    }
+}
+
+/// Opens up the locked folders in `dir` for --ignore-permissions, warning about those left
+/// locked. Whatever still cannot be deleted gets reported by the delete itself.
+fn unlock_target(dir: &Path, quiet: bool) {
+    let unlocked = unlock::unlock_tree(dir);
+    if !quiet && unlocked.changed > 0 {
+        println!(
+            "Made {} locked folders in {} writable",
+            unlocked.changed,
+            dir.to_string_lossy()
+        );
+    }
+    for (path, reason) in &unlocked.locked {
+        println!(
+            "Warning: could not unlock {}: {}",
+            path.to_string_lossy(),
+            reason
+        );
+    }
+}
+
+/// `permissions` changed to the octal `mode`. Without Unix modes, only read-only is kept
+/// track of, set when `mode` gives nobody write permission.
+fn with_mode(mut permissions: std::fs::Permissions, mode: u32) -> std::fs::Permissions {
+    #[cfg(unix)]
+    std::os::unix::fs::PermissionsExt::set_mode(&mut permissions, mode);
+    #[cfg(not(unix))]
+    permissions.set_readonly(mode & 0o222 == 0);
+    permissions
+}
+
+/// Creates `dir` again, empty, with the `permissions` it had. Returns whether that worked.
+fn recreate_dir(dir: &Path, permissions: std::fs::Permissions) -> bool {
+    let recreated =
+        std::fs::create_dir(dir).and_then(|_| std::fs::set_permissions(dir, permissions));
+
+    match recreated {
+        Ok(()) => {
+            println!("Recreated {} empty", dir.to_string_lossy());
+            true
+        }
+        Err(e) => {
+            println!(
+                "Error: {} was deleted but could not be recreated: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            false
+        }
+    }
+}
+
+/// True when any of the "filters" group is given, so only part of the target goes.
+fn is_filtered(opts: &Cli) -> bool {
+    opts.filtered
+}
+
+/// Removes only the link when SOURCE_PATH is a symlink and --dereference-root was not given.
+fn handle_symlink_root(opts: &Cli, link: &Path) -> bool {
+    let target = match std::fs::read_link(link) {
+        Ok(target) => target,
+        Err(e) => {
+            println!(
+                "Error: Failed to read the symlink {}. Error: {}",
+                link.to_string_lossy(),
+                e
+            );
+            return false;
+        }
+    };
+
+    let walks_target =
+        is_filtered(opts) || !opts.preserve.is_empty() || opts.quarantine.is_some() || opts.watch;
+    if walks_target {
+        println!(
+            "Error: {} is a symlink, pass --dereference-root to work inside the linked directory",
+            link.to_string_lossy()
+        );
+        return false;
+    }
+
+    if opts.dry_run {
+        println!(
+            "Dry run, nothing was deleted. Would remove symlink {} and keep the linked directory {}",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        );
+        return true;
+    }
+
+    let Some((mut input, mut output)) = prompt_streams(opts) else {
+        return false;
+    };
+    let confirmation =
+        get_link_confirmation(link, &target, opts.force > 0, &mut input, &mut output)
+            .to_lowercase();
+
+    handle_link_confirmation(&confirmation, link).is_ok()
+}
+
+fn get_link_confirmation(
+    link: &Path,
+    target: &Path,
+    force: bool,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> String {
+    if force {
+        println!("Running delete without confirmation.");
+        println!(
+            "Removing symlink {}, the linked directory {} is kept.",
+            link.to_string_lossy(),
+            target.to_string_lossy()
+        );
+        return "y".to_string();
+    }
+
+    let prompt = format!(
+        "{} is a symlink to {}. Are you sure you want to remove the symlink? The linked directory is kept. (y/n) ",
+        link.to_string_lossy(),
+        target.to_string_lossy()
+    );
+
+    prompt_user(&prompt, input, output)
+}
+
+fn handle_link_confirmation(confirmation: &str, link: &Path) -> Result<(), std::io::Error> {
+    if confirmation != "y" {
+        println!("Aborting as user input '{confirmation}' was not 'y'");
+        return Ok(());
+    }
+
+    let result = remove::remove_symlink(link);
+
+    match &result {
+        Ok(_) => println!("Removed symlink {}", link.to_string_lossy()),
+        Err(e) => println!("Error: {}", e),
+    }
+
+    result
+}
+
+fn succeeded(ok: bool, was_empty: bool, dir: &Path) -> bool {
+    if was_empty {
+        println!(
+            "Failing as {} was empty before the run",
+            dir.to_string_lossy()
+        );
+        return false;
+    }
+
+    ok
+}
+
+fn has_marker(dir: &Path, marker: &str) -> bool {
+    let found = marker::find_marker(dir, marker, marker::home_dir().as_deref()).is_some();
+    if !found {
+        println!(
+            "Refusing: no {} found at or above {}",
+            marker,
+            dir.to_string_lossy()
+        );
+    }
+
+    found
+}
+
+/// True unless `dir` holds uncommitted changes in a git working tree, which are listed.
+/// A failure to ask git counts as dirty.
+fn is_git_clean(dir: &Path) -> bool {
+    match git::dirty_files(dir) {
+        Ok(None) => true,
+        Ok(Some(dirty)) if dirty.is_empty() => true,
+        Ok(Some(dirty)) => {
+            println!(
+                "Refusing: {} holds {} uncommitted changes, pass --force to delete them:",
+                dir.to_string_lossy(),
+                dirty.len()
+            );
+            for line in dirty {
+                println!("  {line}");
+            }
+            false
+        }
+        Err(e) => {
+            println!(
+                "Refusing: could not check {} for uncommitted changes. Error: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            false
+        }
+    }
+}
+
+/// True unless a file that would be deleted from `dir` was modified within `window`. A
+/// failed scan counts as recent.
+fn is_old_enough(dir: &Path, options: &RemoveOptions, window: Duration) -> bool {
+    let plan = match remove::plan(dir, options) {
+        Ok(plan) => plan,
+        Err(e) => {
+            println!(
+                "Refusing: could not check {} for recently modified files. Error: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            return false;
+        }
+    };
+
+    let now = SystemTime::now();
+    let recent = plan
+        .files
+        .iter()
+        .filter(|entry| {
+            entry
+                .metadata
+                .modified()
+                .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() < window)
+        })
+        .count();
+    if recent > 0 {
+        println!(
+            "Refusing to delete {} files modified in the last {}; pass --force to override.",
+            recent,
+            units::format_duration(window)
+        );
+    }
+
+    recent == 0
+}
+
+/// Prints the scope hash of `dir` on a dry run and checks it against --expect-scope-hash.
+/// True when it matches or nothing was expected; a tree that cannot be hashed fails.
+fn check_scope_hash(opts: &Cli, dir: &Path) -> bool {
+    let actual = match scope_hash::scope_hash(dir) {
+        Ok(actual) => actual,
+        Err(e) => {
+            println!(
+                "Refusing: could not hash the scope of {}. Error: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            return false;
+        }
+    };
+
+    if opts.dry_run && !opts.quiet {
+        let line = format!("Scope hash of {}: {}", dir.to_string_lossy(), actual);
+        // The listing owns stdout with --output-null, so notes go to stderr.
+        if opts.output_null {
+            eprintln!("{line}");
+        } else {
+            println!("{line}");
+        }
+    }
+
+    match &opts.expect_scope_hash {
+        Some(expected) if !scope_hash::matches(expected, &actual) => {
+            println!(
+                "Refusing to delete {} as its scope hash is {}, not the expected {}; it changed since it was reviewed.",
+                dir.to_string_lossy(),
+                actual,
+                expected.trim()
+            );
+            false
+        }
+        _ => true,
+    }
+}
+
+/// True when `dir` holds no files or folders at all. Unreadable directories count as not empty.
+fn is_empty_target(dir: &Path) -> bool {
+    std::fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_none())
+}
+
+/// The options to delete `dir_to_remove` with. Fails on a path given on the command line
+/// that does not suit this target.
+fn remove_options(opts: &Cli, dir_to_remove: &Path) -> Result<RemoveOptions, RemoveError> {
+    let quarantine = opts
+        .quarantine
+        .as_ref()
+        .map(|dir| prepare_quarantine(dir, dir_to_remove))
+        .transpose()?;
+
+    Ok(RemoveOptions {
+        filter: Filter {
+            include: opts
+                .include
+                .iter()
+                .map(|p| glob(p, opts.ignore_case))
+                .collect(),
+            exclude: opts
+                .exclude
+                .iter()
+                .map(|p| glob(p, opts.ignore_case))
+                .collect(),
+            include_extensions: extensions(&opts.include_extension),
+            exclude_extensions: extensions(&opts.exclude_extension),
+            regex: opts
+                .regex
+                .iter()
+                .map(|regex| regex.clone().ignoring_case(opts.ignore_case))
+                .collect(),
+            min_size: opts.min_size,
+            max_size: opts.max_size,
+            older_than: opts.older_than.or(opts
+                .older_than_days
+                .map(|days| Duration::from_secs(days * 86400))),
+            keep_modified_since: opts
+                .exclude_today
+                .then(|| units::local_midnight(SystemTime::now())),
+            keep_recent_dirs: opts.exclude_recent_dirs,
+            newer_than: opts.newer_than,
+            path_limit: opts.max_path_length.map(|max| PathLimit {
+                root: dir_to_remove.to_path_buf(),
+                max,
+            }),
+            dangling: (opts.dangling_only || opts.delete_broken_after.is_some()).then(|| {
+                DanglingLinks {
+                    root: dir_to_remove.to_path_buf(),
+                    only: opts.dangling_only,
+                    grace: opts.delete_broken_after,
+                }
+            }),
+            empty_only: opts.delete_empty_files,
+            compare_to: opts
+                .compare_to
+                .as_ref()
+                .map(|reference| {
+                    prepare_reference(reference, dir_to_remove).map(|reference| Comparison {
+                        target: dir_to_remove.to_path_buf(),
+                        reference,
+                    })
+                })
+                .transpose()?,
+        },
+        quarantine,
+        keep_emptied_dirs: opts.no_delete_empty_after_filter,
+        checkpoint: opts
+            .checkpoint
+            .clone()
+            .or_else(|| opts.resume.clone())
+            .map(|path| check_outside_target(path, dir_to_remove))
+            .transpose()?,
+        resume: opts.resume.is_some(),
+        verbosity: match opts.verbose_level.unwrap_or(opts.verbose) {
+            0 if opts.throttled_verbose.is_some() => 2,
+            verbosity => verbosity,
+        },
+        verbose_interval: opts.throttled_verbose.map(Duration::from_millis),
+        preview_limit: Some(opts.preview_limit).filter(|limit| *limit > 0),
+        sort: opts.sort,
+        follow_symlinks: opts.follow_symlinks,
+        follow_junctions: opts.follow_junctions,
+        fail_on_symlink_escape: opts.follow_symlinks && !opts.allow_symlink_escape,
+        count_symlink_targets: opts.count_symlink_targets,
+        sparse_aware: opts.sparse_aware,
+        report_progress: show_progress_bar(opts) || opts.progress_json,
+        progress_json: opts.progress_json,
+        progress_file: opts
+            .progress_file
+            .clone()
+            .map(|path| check_outside_target(path, dir_to_remove))
+            .transpose()?,
+        merge_output: opts.merge_output && is_interactive(opts, stdout().is_terminal()),
+        quiet: opts.quiet || opts.output_null,
+        skip_mount_points: opts.exclude_mount_points,
+        exec: opts.exec.clone(),
+        exec_replace: opts.exec_replace,
+        skip_errors: opts.skip_errors,
+        lang: language(opts),
+        group_errors: opts.keep_going_summary,
+        denied_to: opts
+            .denied_to
+            .clone()
+            .map(|path| check_outside_target(path, dir_to_remove))
+            .transpose()?,
+        ignore_errors: opts
+            .ignore_errors_matching
+            .iter()
+            .map(|regex| regex.clone().ignoring_case(opts.ignore_case))
+            .collect(),
+        secure_passes: opts.secure.then_some(opts.secure_passes),
+        explain: opts.explain,
+        preserve: opts
+            .preserve
+            .iter()
+            .filter_map(|path| preserved_path(path, dir_to_remove))
+            .collect(),
+        summary_json_to: opts.summary_json_to.clone(),
+        collect_stats: opts.batch_summary || opts.stats_history.is_some() || opts.notify.is_some(),
+        deadline: opts.deadline,
+        shell_escape_paths: opts.shell_escape_paths,
+        output_null: opts.output_null,
+        bare_listing: opts.dry_run_to.is_some() || opts.dry_run_compare.is_some(),
+        keep_structure_depth: opts.preserve_structure_depth,
+        relative_to: opts.relative_to.as_ref().map(|base| match base {
+            // Listed paths are canonical, so the base has to be too.
+            Some(base) => std::fs::canonicalize(base)
+                .or_else(|_| std::path::absolute(base))
+                .unwrap_or_else(|_| base.clone()),
+            None => dir_to_remove.to_path_buf(),
+        }),
+        group_by_extension: opts.group_by_extension,
+        report_largest: opts.report_largest,
+        fsync: opts.fsync,
+        exclude_open_files: opts.exclude_open_files,
+        skip_modified_since_scan: opts.skip_if_modified_since_scan,
+        dedupe: opts
+            .dedupe
+            .then(|| opts.dedupe_keep.unwrap_or(DedupeKeep::Oldest)),
+        scan_cache: opts
+            .scan_cache
+            .clone()
+            .map(|path| check_outside_target(path, dir_to_remove))
+            .transpose()?,
+        throttle_load: opts
+            .throttle_on_load
+            .then(|| opts.max_load.unwrap_or_else(throttle::default_max_load)),
+    })
+}
+
+/// The --lang language, or the environment's when not given.
+fn language(opts: &Cli) -> Lang {
+    opts.lang
+        .as_deref()
+        .map_or_else(Lang::from_env, Lang::from_code)
+}
+
+fn glob(pattern: &str, ignore_case: bool) -> Glob {
+    if ignore_case {
+        Glob::case_insensitive(pattern)
+    } else {
+        Glob::new(pattern)
+    }
+}
+
+/// Normalises extensions given as 'log' or '.LOG' for case-insensitive matching.
+fn extensions(extensions: &[String]) -> Vec<String> {
+    extensions
+        .iter()
+        .map(|extension| extension.trim_start_matches('.').to_lowercase())
+        .collect()
+}
+
+fn show_progress_bar(opts: &Cli) -> bool {
+    opts.progress_bar && !opts.quiet && is_interactive(opts, std::io::stderr().is_terminal())
+}
+
+/// Whether there is someone at a terminal, going by `detected` unless --assume-tty or
+/// --assume-no-tty override it.
+fn is_interactive(opts: &Cli, detected: bool) -> bool {
+    if opts.assume_tty {
+        true
+    } else if opts.assume_no_tty {
+        false
+    } else {
+        detected
+    }
+}
+
+/// Fails when `path` would be deleted along with the target.
+fn check_outside_target(path: PathBuf, dir_to_remove: &Path) -> Result<PathBuf, RemoveError> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let inside =
+        std::fs::canonicalize(parent).is_ok_and(|parent| parent.starts_with(dir_to_remove));
+    if inside {
+        return Err(RemoveError::InsideTarget {
+            path,
+            target: dir_to_remove.to_path_buf(),
+        });
+    }
+
+    Ok(path)
+}
+
+/// Makes sure the quarantine directory is not inside the directory being emptied. It is
+/// only created by the first move into it, so a dry run leaves no trace.
+fn prepare_quarantine(quarantine_dir: &Path, dir_to_remove: &Path) -> Result<PathBuf, RemoveError> {
+    let quarantine_dir =
+        resolve_nonexistent(quarantine_dir).map_err(|source| RemoveError::Canonicalize {
+            path: quarantine_dir.to_path_buf(),
+            source,
+        })?;
+
+    if quarantine_dir.starts_with(dir_to_remove) {
+        return Err(RemoveError::InsideTarget {
+            path: quarantine_dir,
+            target: dir_to_remove.to_path_buf(),
+        });
+    }
+
+    Ok(quarantine_dir)
+}
+
+/// The canonical form of `path`, which may not exist yet: its deepest existing ancestor is
+/// canonicalized and the missing rest appended.
+fn resolve_nonexistent(path: &Path) -> Result<PathBuf, std::io::Error> {
+    let absolute = std::path::absolute(path)?;
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+    loop {
+        match std::fs::canonicalize(existing) {
+            Ok(canonical) => {
+                return Ok(missing
+                    .iter()
+                    .rev()
+                    .fold(canonical, |path, part| path.join(part)))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                match (existing.file_name(), existing.parent()) {
+                    (Some(name), Some(parent)) => {
+                        missing.push(name);
+                        existing = parent;
+                    }
+                    _ => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Makes sure the --compare-to reference exists and does not overlap the target, where
+/// files would be compared to themselves.
+fn prepare_reference(reference: &Path, dir_to_remove: &Path) -> Result<PathBuf, RemoveError> {
+    let reference =
+        std::fs::canonicalize(reference).map_err(|source| RemoveError::Canonicalize {
+            path: reference.to_path_buf(),
+            source,
+        })?;
+
+    if reference.starts_with(dir_to_remove) || dir_to_remove.starts_with(&reference) {
+        return Err(RemoveError::Overlaps {
+            path: reference,
+            target: dir_to_remove.to_path_buf(),
+        });
+    }
+
+    Ok(reference)
+}
+
+/// Resolves the --preserve paths once for the whole batch, failing on one that does not
+/// exist or is not inside any of `targets`.
+fn resolve_preserved(preserve: &[PathBuf], targets: &[String]) -> Result<Vec<PathBuf>, String> {
+    let targets: Vec<PathBuf> = targets
+        .iter()
+        .filter_map(|target| std::fs::canonicalize(target).ok())
+        .collect();
+
+    preserve
+        .iter()
+        .map(|path| {
+            let canonical = std::fs::canonicalize(path).map_err(|e| {
+                format!(
+                    "Failed to find preserved path {}. Error: {}",
+                    path.to_string_lossy(),
+                    e
+                )
+            })?;
+            if !targets
+                .iter()
+                .any(|target| preserved_path(&canonical, target).is_some())
+            {
+                return Err(format!(
+                    "Preserved path {} is not inside any target",
+                    canonical.to_string_lossy()
+                ));
+            }
+            Ok(canonical)
+        })
+        .collect()
+}
+
+/// Where the resolved --preserve path `canonical` lies relative to the target, None when
+/// it is not inside it.
+fn preserved_path(canonical: &Path, dir_to_remove: &Path) -> Option<PathBuf> {
+    canonical
+        .strip_prefix(dir_to_remove)
+        .ok()
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+}
+
+/// Asks whether to delete everything in `source_dir`, adding `scope_hint` to the prompt.
+fn get_user_confirmation(
+    source_dir: &Path,
+    force: bool,
+    scope_hint: Option<&str>,
+    lang: Lang,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> String {
+    if force {
+        println!("{}", lang.forced(&source_dir.to_string_lossy()));
+        return "y".to_string();
+    }
+
+    let prompt = lang.confirm_prompt(&source_dir.to_string_lossy(), scope_hint);
+
+    prompt_user(&prompt, input, output)
+}
+
+/// Scans `dir` and asks `--confirm-summary-only`'s `Delete N items (SIZE)? [y/N]` after a
+/// header naming it. From `threshold` bytes on the question starts with a warning. None,
+/// asking nothing, when the tree cannot be scanned.
+fn get_summary_confirmation(
+    dir: &Path,
+    threshold: Option<u64>,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Option<String> {
+    let entries = scan::scan_dir(dir).ok()?;
+    let bytes = entries
+        .iter()
+        .filter(|entry| !entry.metadata.is_dir())
+        .map(|entry| entry.metadata.len())
+        .sum();
+    let warning = if threshold.is_some_and(|threshold| bytes >= threshold) {
+        "WARNING: "
+    } else {
+        ""
+    };
+
+    writeln!(output, "Target: {}", dir.to_string_lossy())
+        .unwrap_or_else(|e| panic!("Failed to write prompt Error: {}", e));
+    let prompt = format!(
+        "{warning}Delete {} items ({})? [y/N] ",
+        units::format_count(entries.len() as u64),
+        units::format_size(bytes)
+    );
+    Some(prompt_user(&prompt, input, output))
+}
+
+/// A code like `DELETE-7F3A` that differs between runs, as the hasher keys are random.
+fn confirmation_code() -> String {
+    let random = RandomState::new().build_hasher().finish();
+    format!("DELETE-{:04X}", random & 0xFFFF)
+}
+
+/// Asks to type `code` to delete everything in `dir`. Only an exact match confirms.
+fn confirm_with_code(
+    dir: &Path,
+    code: &str,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> bool {
+    let prompt = format!(
+        "This deletes all files and folders in {}. Type {} to confirm: ",
+        dir.to_string_lossy(),
+        code
+    );
+    let answer = prompt_user(&prompt, input, output);
+    if answer != code {
+        println!("Aborting as user input '{answer}' was not '{code}'");
+        return false;
+    }
+
+    true
+}
+
+/// Scans `dir` for the `--human-readable-prompt-threshold` hint. None when it cannot be read.
+fn scope_hint(dir: &Path, threshold: u64) -> Option<String> {
+    let entries = scan::scan_dir(dir).ok()?;
+    let files = entries.iter().filter(|entry| !entry.metadata.is_dir());
+    let (count, bytes) = files.fold((0, 0), |(count, bytes), entry| {
+        (count + 1, bytes + entry.metadata.len())
+    });
+
+    Some(format_scope_hint(count, bytes, threshold))
+}
+
+/// E.g. `(this directory is small: 3 files, 12.0 KiB)` below `threshold` bytes and
+/// `(WARNING: 2.3 GiB, 1.2M files)` from it on.
+fn format_scope_hint(files: u64, bytes: u64, threshold: u64) -> String {
+    if bytes < threshold {
+        format!(
+            "(this directory is small: {} files, {})",
+            units::format_count(files),
+            units::format_size(bytes)
+        )
+    } else {
+        format!(
+            "(WARNING: {}, {} files)",
+            units::format_size(bytes),
+            units::format_count(files)
+        )
+    }
+}
+
+/// Asks once for the files directly in `dir` and once for its subfolders. Returns whether
+/// each kind may go; --force approves both. Fails when `dir` cannot be listed.
+fn confirm_each_type(
+    dir: &Path,
+    force: bool,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<(bool, bool), std::io::Error> {
+    let (files, dirs) = top_level_entries(dir)?;
+    if force {
+        println!("Running delete without confirmation.");
+        return Ok((true, true));
+    }
+
+    let mut ask = |prompt: String, count: usize| {
+        count > 0 && prompt_user(&prompt, input, output).to_lowercase() == "y"
+    };
+    let delete_files = ask(
+        format!(
+            "Delete {} files in {}? (y/n) ",
+            files.len(),
+            dir.to_string_lossy()
+        ),
+        files.len(),
+    );
+    let delete_dirs = ask(
+        format!(
+            "Delete {} subdirectories of {}, including their contents? (y/n) ",
+            dirs.len(),
+            dir.to_string_lossy()
+        ),
+        dirs.len(),
+    );
+
+    Ok((delete_files, delete_dirs))
+}
+
+/// Splits the entries directly in `dir` into files, symlinks included, and folders.
+/// An entry that cannot be read fails the whole listing, as leaving it out would get it
+/// deleted whatever was approved.
+fn top_level_entries(dir: &Path) -> Result<(Vec<PathBuf>, Vec<PathBuf>), std::io::Error> {
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            dirs.push(PathBuf::from(entry.file_name()));
+        } else {
+            files.push(PathBuf::from(entry.file_name()));
+        }
+    }
+
+    Ok((files, dirs))
+}
+
+/// The entries to preserve so only the approved kinds get deleted.
+fn kept_by_type(
+    dir: &Path,
+    delete_files: bool,
+    delete_dirs: bool,
+) -> Result<Vec<PathBuf>, std::io::Error> {
+    let (files, dirs) = top_level_entries(dir)?;
+    let mut kept = Vec::new();
+
+    if !delete_files {
+        kept.extend(files);
+    }
+    if !delete_dirs {
+        kept.extend(dirs);
+    }
+
+    Ok(kept)
+}
+
+fn prompt_user(prompt: &str, input: &mut impl BufRead, output: &mut impl Write) -> String {
+    write!(output, "{prompt}").unwrap_or_else(|e| panic!("Failed to write prompt Error: {}", e));
+
+    output
+        .flush()
+        .unwrap_or_else(|e| panic!("Failed to flush output. Error: {}", e));
+
+    let mut user_input = String::new();
+    input
+        .read_line(&mut user_input)
+        .unwrap_or_else(|e| panic!("Failed to read user input {}", e));
+
+    user_input.trim().to_string()
+}
+
+/// How many levels deep the tree below `dir` goes. Entries directly inside it are level 1.
+fn tree_depth(dir: &Path) -> Result<usize, std::io::Error> {
+    let entries = scan::scan_dir(dir)?;
+
+    Ok(entries
+        .iter()
+        .filter_map(|entry| entry.path.strip_prefix(dir).ok())
+        .map(|relative| relative.components().count())
+        .max()
+        .unwrap_or(0))
+}
+
+/// Reports the depth of the tree and asks whether to carry on when it is deeper than
+/// `threshold`. Returns true when it is not, the user answered 'y' or `doubly_forced`, and
+/// false when the tree cannot be scanned.
+fn confirm_depth(
+    dir: &Path,
+    threshold: usize,
+    doubly_forced: bool,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> bool {
+    let depth = match tree_depth(dir) {
+        Ok(depth) => depth,
+        Err(e) => {
+            println!(
+                "Error: Failed to scan {}. Error: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            return false;
+        }
+    };
+    println!("{} is {} levels deep", dir.to_string_lossy(), depth);
+
+    if depth <= threshold {
+        return true;
+    }
+
+    if doubly_forced {
+        println!(
+            "Deeper than the threshold of {threshold}, continuing as --force was given twice."
+        );
+        return true;
+    }
+
+    let prompt = format!(
+        "This tree is {depth} levels deep, deeper than your threshold of {threshold}. Continue? (y/n) "
+    );
+    let confirmation = prompt_user(&prompt, input, output).to_lowercase();
+    if confirmation != "y" {
+        println!("Aborting as user input '{confirmation}' was not 'y'");
+        return false;
+    }
+
+    true
+}
+
+/// Deletes the files of `dir` one level at a time, starting with those directly inside it,
+/// and asks before each deeper level with a summary of what remains. Folders, and `dir`
+/// itself, only go once every level was approved.
+fn remove_staged(
+    dir: &Path,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> Result<RemoveStats, std::io::Error> {
+    let entries = scan::scan_dir(dir)?;
+    let level = |entry: &scan::Entry| {
+        entry
+            .path
+            .strip_prefix(dir)
+            .map_or(0, |relative| relative.components().count())
+    };
+    let is_file = |entry: &scan::Entry| !entry.metadata.is_dir() || entry.metadata.is_symlink();
+    let deepest = entries.iter().map(level).max().unwrap_or(0);
+
+    let mut stats = RemoveStats::default();
+
+    for depth in 1..=deepest {
+        let mut removed = 0;
+        for entry in entries.iter().filter(|e| is_file(e) && level(e) == depth) {
+            if entry.metadata.is_symlink() {
+                remove::remove_symlink(&entry.path)?;
+            } else {
+                std::fs::remove_file(&entry.path)?;
+            }
+            removed += 1;
+            stats.files += 1;
+            stats.bytes += entry.metadata.len();
+        }
+
+        let remaining: Vec<&scan::Entry> = entries
+            .iter()
+            .filter(|e| is_file(e) && level(e) > depth)
+            .collect();
+        if remaining.is_empty() {
+            break;
+        }
+
+        let bytes: u64 = remaining.iter().map(|entry| entry.metadata.len()).sum();
+        let prompt = format!(
+            "Removed {} files at level {}. {} files ({}) remain below, down to level {}. Continue with level {}? (y/n) ",
+            removed,
+            depth,
+            remaining.len(),
+            units::format_size(bytes),
+            deepest,
+            depth + 1
+        );
+        let confirmation = prompt_user(&prompt, input, output).to_lowercase();
+        if confirmation != "y" {
+            println!(
+                "Stopping as user input '{confirmation}' was not 'y', keeping the rest of {}",
+                dir.to_string_lossy()
+            );
+            return Ok(stats);
+        }
+    }
+
+    // Scanned parents come before their children, so reversed the deepest go first.
+    for entry in entries.iter().rev().filter(|e| !is_file(e)) {
+        std::fs::remove_dir(&entry.path)?;
+        stats.dirs += 1;
+    }
+    std::fs::remove_dir(dir)?;
+
+    println!(
+        "Removed all files and folders from {} ({} files, {}, {} folders)",
+        dir.to_string_lossy(),
+        stats.files,
+        units::format_size(stats.bytes),
+        stats.dirs
+    );
+
+    Ok(stats)
+}
+
+/// Counts the files below `dir`, folders not included.
+fn count_files(dir: &Path) -> Result<usize, std::io::Error> {
+    let entries = scan::scan_dir(dir)?;
+    Ok(entries
+        .iter()
+        .filter(|entry| !entry.metadata.is_dir())
+        .count())
+}
+
+/// Counts the files of `dir` twice with `count` and compares. Returns true when both
+/// agree, or when they do not but `forced`.
+fn check_stable(
+    dir: &Path,
+    forced: bool,
+    mut count: impl FnMut(&Path) -> Result<usize, std::io::Error>,
+) -> bool {
+    let counts = count(dir).and_then(|first| count(dir).map(|second| (first, second)));
+    let (first, second) = match counts {
+        Ok(counts) => counts,
+        Err(e) => {
+            println!("Error: failed to scan {}: {}", dir.to_string_lossy(), e);
+            return false;
+        }
+    };
+
+    if first == second {
+        println!(
+            "Scanned {} twice, both found {} files",
+            dir.to_string_lossy(),
+            first
+        );
+        return true;
+    }
+
+    println!(
+        "Warning: {} changed between two scans, from {} to {} files. Something may be writing to it.",
+        dir.to_string_lossy(),
+        first,
+        second
+    );
+    if forced {
+        println!("Continuing as --force was given.");
+        return true;
+    }
+
+    println!("Refusing to delete, pass --force to delete anyway.");
+    false
+}
+
+/// Lists entries with flagged permissions and asks whether to carry on.
+/// Returns true when nothing was flagged or the user answered 'y', and false when the tree
+/// cannot be scanned, as what it holds is unknown.
+fn confirm_audit(dir: &Path, input: &mut impl BufRead, output: &mut impl Write) -> bool {
+    let entries = match scan::scan_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            println!(
+                "Error: Failed to scan {}. Error: {}",
+                dir.to_string_lossy(),
+                e
+            );
+            return false;
+        }
+    };
+    let findings = audit::audit_permissions(&entries);
+
+    if findings.is_empty() {
+        return true;
+    }
+
+    let mut warning = format!(
+        "Warning: {} entries in {} have unusual permissions:\n",
+        findings.len(),
+        dir.to_string_lossy()
+    );
+    for finding in &findings {
+        warning.push_str(&format!(
+            "  {:o} {} ({})\n",
+            finding.mode,
+            finding.path.to_string_lossy(),
+            finding.reasons().join(", ")
+        ));
+    }
+    warning.push_str("Delete them anyway? (y/n) ");
+
+    let confirmation = prompt_user(&warning, input, output).to_lowercase();
+    if confirmation != "y" {
+        println!("Aborting as user input '{confirmation}' was not 'y'");
+        return false;
+    }
+
+    true
+}
+
+/// Lists the folders directly in `dir` with their sizes, largest first, for --size-by-subdir.
+fn subdir_size_report(dir: &Path) -> String {
+    let sizes = match subdir_sizes(dir) {
+        Ok(sizes) => sizes,
+        Err(e) => {
+            return format!(
+                "Error: Failed to size the folders in {}. Error: {}\n",
+                dir.to_string_lossy(),
+                e
+            )
+        }
+    };
+
+    if sizes.is_empty() {
+        return format!("No folders in {}\n", dir.to_string_lossy());
+    }
+
+    let mut report = format!("Size by folder in {}:\n", dir.to_string_lossy());
+    for (name, bytes) in &sizes {
+        report.push_str(&format!(
+            "  {:>10}  {}/\n",
+            units::format_size(*bytes),
+            name
+        ));
+    }
+    report
+}
+
+/// Sums up how the files below `dir` share their data through hard links.
+fn hardlink_report(dir: &Path) -> String {
+    if !cfg!(unix) {
+        return "Warning: hard links cannot be told apart on this platform, skipping --hardlink-stats\n".to_string();
+    }
+
+    let stats = match scan::scan_dir(dir) {
+        Ok(entries) => hardlinks::hardlink_stats(&entries),
+        Err(e) => {
+            return format!(
+                "Error: Failed to scan {}. Error: {}\n",
+                dir.to_string_lossy(),
+                e
+            )
+        }
+    };
+
+    let mut report = format!(
+        "Hard links in {}: {} entries, {} unique inodes, {} would be freed, {} saved by hard links\n",
+        dir.to_string_lossy(),
+        stats.entries,
+        stats.inodes,
+        units::format_size(stats.freed),
+        units::format_size(stats.shared)
+    );
+    if stats.linked_outside > 0 {
+        report.push_str(&format!(
+            "  {} files are also linked from outside and stay on disk\n",
+            stats.linked_outside
+        ));
+    }
+    report
+}
+
+/// The total size of the files below each folder directly in `dir`, largest first. Files
+/// directly in `dir`, and symlinks to folders, belong to no folder and are left out.
+fn subdir_sizes(dir: &Path) -> Result<Vec<(String, u64)>, std::io::Error> {
+    let mut sizes: BTreeMap<String, u64> = BTreeMap::new();
+    for entry in scan::scan_dir(dir)? {
+        let Ok(relative) = entry.path.strip_prefix(dir) else {
+            continue;
+        };
+        let mut components = relative.components();
+        let Some(top) = components.next() else {
+            continue;
+        };
+        let top = top.as_os_str().to_string_lossy().to_string();
+        let nested = components.next().is_some();
+
+        if entry.metadata.is_dir() && !nested {
+            sizes.entry(top).or_insert(0);
+        } else if !entry.metadata.is_dir() && nested {
+            *sizes.entry(top).or_insert(0) += entry.metadata.len();
+        }
+    }
+
+    let mut sizes: Vec<(String, u64)> = sizes.into_iter().collect();
+    sizes.sort_by(|(a_name, a_bytes), (b_name, b_bytes)| {
+        b_bytes.cmp(a_bytes).then_with(|| a_name.cmp(b_name))
+    });
+    Ok(sizes)
+}
+
+/// Lists the symlinks below `dir`, one per line with their target and where that is.
+fn symlink_report(dir: &Path) -> Result<String, std::io::Error> {
+    let entries = scan::scan_dir(dir)?;
+    let links = audit::audit_symlinks(dir, &entries);
+
+    if links.is_empty() {
+        return Ok(format!("No symlinks in {}\n", dir.to_string_lossy()));
+    }
+
+    let mut report = format!("{} symlinks in {}:\n", links.len(), dir.to_string_lossy());
+    for link in &links {
+        report.push_str(&format!(
+            "  {} -> {} ({})\n",
+            link.path.to_string_lossy(),
+            link.target.to_string_lossy(),
+            link.kind
+        ));
+    }
+    Ok(report)
+}
+
+/// The symlink report of `dir`, or None after reporting why it could not be made.
+fn checked_symlink_report(dir: &Path) -> Option<String> {
+    symlink_report(dir)
+        .inspect_err(|e| {
+            println!(
+                "Error: Failed to scan {}. Error: {}",
+                dir.to_string_lossy(),
+                e
+            )
+        })
+        .ok()
+}
+
+/// Prints the symlink report and asks whether to carry on. Returns true when there are no
+/// symlinks or the user answered 'y', and false when the tree cannot be scanned.
+fn confirm_symlinks(dir: &Path, input: &mut impl BufRead, output: &mut impl Write) -> bool {
+    let Some(report) = checked_symlink_report(dir) else {
+        return false;
+    };
+    if report.starts_with("No symlinks") {
+        print!("{report}");
+        return true;
+    }
+
+    let confirmation = prompt_user(
+        &format!("{report}Delete them anyway? (y/n) "),
+        input,
+        output,
+    )
+    .to_lowercase();
+    if confirmation != "y" {
+        println!("Aborting as user input '{confirmation}' was not 'y'");
+        return false;
+    }
+
+    true
+}
+
+/// Deletes the target once confirmed. Returns the stats when the tree was walked.
+fn handle_confirmation(
+    confirmation: &str,
+    dir_to_remove: &Path,
+    options: &RemoveOptions,
+) -> Result<Option<RemoveStats>, RemoveError> {
+    if confirmation != "y" {
+        println!("{}", options.lang.aborting(confirmation));
+        return Ok(None);
+    }
+
+    let now = Instant::now();
+    let result = if let Some(checkpoint_path) = &options.checkpoint {
+        remove_resumable(dir_to_remove, checkpoint_path, options.resume)
+            .map_err(|source| RemoveError::Delete {
+                path: dir_to_remove.to_path_buf(),
+                source,
+            })
+            .map(|_| {
+                if !options.quiet {
+                    println!(
+                        "{}",
+                        options.lang.removed_all(&dir_to_remove.to_string_lossy())
+                    )
+                }
+                None
+            })
+    } else if options.needs_walk() {
+        let stats = if options.progress_json {
+            let mut progress = JsonProgress::new(stderr());
+            remove_walked(dir_to_remove, options, &mut progress)
+        } else if options.report_progress {
+            let mut progress = ProgressBar::new(stderr());
+            if options.merge_output {
+                progress = progress.merging_output();
+            }
+            remove_walked(dir_to_remove, options, &mut progress)
+        } else {
+            remove_walked(dir_to_remove, options, &mut NoProgress)
+        };
+        stats.and_then(|stats| {
+            print_walk_summary(dir_to_remove, options, &stats);
+            print_extension_report(&stats);
+            print_largest_report(&stats);
+            print_error_groups(&stats);
+            write_summary_json(dir_to_remove, options, &stats, false, now.elapsed());
+            Ok(skipped_errors(&stats).map(|_| Some(stats))?)
+        })
+    } else {
+        remove_dir_all(dir_to_remove).map(|_| {
+            if !options.quiet {
+                println!(
+                    "{}",
+                    options.lang.removed_all(&dir_to_remove.to_string_lossy())
+                )
+            }
+            None
+        })
+    };
+
+    if let Err(e) = &result {
+        println!("Error: {}", e);
+    }
+
+    if !options.quiet {
+        println!("{}", options.lang.done_in(now.elapsed().as_secs_f32()));
+    }
+
+    result
+}
+
+/// Removes `dir` entry by entry, keeping the --progress-file up to date along with
+/// `progress` when asked to. The file goes once the removal went through, and stays
+/// with its last snapshot when it stopped early.
+fn remove_walked(
+    dir: &Path,
+    options: &RemoveOptions,
+    progress: &mut impl Progress,
+) -> Result<RemoveStats, RemoveError> {
+    let Some(path) = &options.progress_file else {
+        return remove::remove_entries(dir, options, &mut stdout(), progress);
+    };
+
+    let mut progress = ProgressFile::new(path.clone(), progress);
+    let stats = remove::remove_entries(dir, options, &mut stdout(), &mut progress)?;
+    if let Err(e) = std::fs::remove_file(path) {
+        println!(
+            "Warning: failed to remove the progress file {}. Error: {}",
+            path.to_string_lossy(),
+            e
+        );
+    }
+    Ok(stats)
+}
+
+/// Turns errors skipped during the walk into a failure once the summary is out.
+fn skipped_errors(stats: &RemoveStats) -> Result<(), std::io::Error> {
+    if stats.errors == 0 {
+        return Ok(());
+    }
+
+    Err(std::io::Error::other(format!(
+        "{} entries could not be removed",
+        stats.errors
+    )))
+}
+
+fn dry_run_summary(dir_to_remove: &Path, options: &RemoveOptions, stats: &RemoveStats) -> String {
+    options.lang.dry_run_summary(
+        &dir_to_remove.to_string_lossy(),
+        stats.files,
+        &freed_size(options, stats.bytes),
+        stats.dirs,
+    )
+}
+
+/// `bytes` for the summaries, marked as `on disk` when --sparse-aware counted blocks.
+fn freed_size(options: &RemoveOptions, bytes: u64) -> String {
+    if options.sparse_aware {
+        format!("{} on disk", units::format_size(bytes))
+    } else {
+        units::format_size(bytes)
+    }
+}
+
+fn print_walk_summary(dir_to_remove: &Path, options: &RemoveOptions, stats: &RemoveStats) {
+    if options.quiet {
+        return;
+    }
+
+    match &options.quarantine {
+        Some(quarantine_dir) => println!(
+            "Quarantined {} files ({}) from {} into {}",
+            stats.files,
+            freed_size(options, stats.bytes),
+            dir_to_remove.to_string_lossy(),
+            quarantine_dir.to_string_lossy()
+        ),
+        None if options.filter.compare_to.is_some() => println!(
+            "Removed {} files ({}) identical to the reference and {} emptied folders from {}, kept {} that differ or are missing from it",
+            stats.files,
+            freed_size(options, stats.bytes),
+            stats.dirs,
+            dir_to_remove.to_string_lossy(),
+            stats.kept_different
+        ),
+        None if options.is_partial() => println!(
+            "Removed {} files ({}) and {} emptied folders from {}",
+            stats.files,
+            freed_size(options, stats.bytes),
+            stats.dirs,
+            dir_to_remove.to_string_lossy()
+        ),
+        None if !options.preserve.is_empty() => println!(
+            "Removed {} files ({}) and {} folders from {}, preserving {} paths",
+            stats.files,
+            freed_size(options, stats.bytes),
+            stats.dirs,
+            dir_to_remove.to_string_lossy(),
+            options.preserve.len()
+        ),
+        None if options.keep_structure_depth.is_some() => println!(
+            "Removed {} files ({}) and {} folders from {}, keeping the folders up to depth {}",
+            stats.files,
+            freed_size(options, stats.bytes),
+            stats.dirs,
+            dir_to_remove.to_string_lossy(),
+            options.keep_structure_depth.unwrap_or_default()
+        ),
+        None if stats.skipped_mount_points > 0 => println!(
+            "Removed {} files ({}) and {} folders from {}, keeping {} mount points",
+            stats.files,
+            freed_size(options, stats.bytes),
+            stats.dirs,
+            dir_to_remove.to_string_lossy(),
+            stats.skipped_mount_points
+        ),
+        None if stats.modified_since_scan > 0 => println!(
+            "Removed {} files ({}) and {} folders from {}, keeping {} files modified since the scan",
+            stats.files,
+            freed_size(options, stats.bytes),
+            stats.dirs,
+            dir_to_remove.to_string_lossy(),
+            stats.modified_since_scan
+        ),
+        None if stats.skipped_open_files > 0 => println!(
+            "Removed {} files ({}) and {} folders from {}, keeping {} open files",
+            stats.files,
+            freed_size(options, stats.bytes),
+            stats.dirs,
+            dir_to_remove.to_string_lossy(),
+            stats.skipped_open_files
+        ),
+        None => println!(
+            "{}",
+            options.lang.removed_all_counted(
+                &dir_to_remove.to_string_lossy(),
+                stats.files,
+                &freed_size(options, stats.bytes),
+                stats.dirs
+            )
+        ),
+    }
+}
+
+/// Writes the --snapshot file, returning false when the delete has to be aborted.
+fn take_snapshot(dir_to_remove: &Path, snapshot_path: &Path) -> bool {
+    match snapshot::write_snapshot(dir_to_remove, snapshot_path) {
+        Ok(entries) => {
+            println!(
+                "Recorded {} entries of {} in {}",
+                entries,
+                dir_to_remove.to_string_lossy(),
+                snapshot_path.to_string_lossy()
+            );
+            true
+        }
+        Err(e) => {
+            println!(
+                "Aborting as the snapshot {} could not be written. Error: {}",
+                snapshot_path.to_string_lossy(),
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Writes the --archive file, returning false when the delete has to be aborted.
+fn write_archive(dir_to_remove: &Path, archive_path: &Path) -> bool {
+    match archive::archive_tree(dir_to_remove, archive_path) {
+        Ok(size) => {
+            println!(
+                "Archived {} into {} ({})",
+                dir_to_remove.to_string_lossy(),
+                archive_path.to_string_lossy(),
+                units::format_size(size)
+            );
+            true
+        }
+        Err(e) => {
+            println!(
+                "Aborting as the archive {} could not be written. Error: {}",
+                archive_path.to_string_lossy(),
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Writes the stats to --summary-json-to, if given. Failing to do so only warns, as the delete
+/// itself already happened.
+fn write_summary_json(
+    dir_to_remove: &Path,
+    options: &RemoveOptions,
+    stats: &RemoveStats,
+    dry_run: bool,
+    elapsed: Duration,
+) {
+    let Some(path) = &options.summary_json_to else {
+        return;
+    };
+
+    let json = stats.to_json(dir_to_remove, dry_run, elapsed);
+    let written = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{json}"));
+    if let Err(e) = written {
+        println!(
+            "Warning: failed to write summary to {}. Error: {}",
+            path.to_string_lossy(),
+            e
+        );
+    }
+}
+
+fn handle_dry_run(
+    dir_to_remove: &Path,
+    options: &RemoveOptions,
+    list_to: Option<&Path>,
+    compare_with: Option<&Path>,
+) -> Result<RemoveStats, std::io::Error> {
+    let now = Instant::now();
+    let result = match (list_to, compare_with) {
+        (Some(path), _) => OpenOptions::new().append(true).open(path).and_then(|file| {
+            let mut file = BufWriter::new(file);
+            let stats = remove::dry_run(dir_to_remove, options, &mut file)?;
+            file.flush()?;
+            Ok(stats)
+        }),
+        (None, Some(previous)) => compare_plan(dir_to_remove, options, previous),
+        (None, None) => remove::dry_run(dir_to_remove, options, &mut stdout()),
+    };
+    if let Ok(stats) = &result {
+        write_summary_json(dir_to_remove, options, stats, true, now.elapsed());
+    }
+
+    match &result {
+        Ok(stats) if options.output_null && list_to.is_none() => {
+            eprintln!("{}", dry_run_summary(dir_to_remove, options, stats))
+        }
+        Ok(stats) => println!("{}", dry_run_summary(dir_to_remove, options, stats)),
+        Err(e) => println!("Error: {}", e),
+    }
+    if let Ok(stats) = &result {
+        print_extension_report(stats);
+        print_largest_report(stats);
+    }
+
+    result
+}
+
+/// Dry-runs `dir_to_remove` into memory and prints how its listing differs from the
+/// `previous` one saved by --dry-run-to.
+fn compare_plan(
+    dir_to_remove: &Path,
+    options: &RemoveOptions,
+    previous: &Path,
+) -> Result<RemoveStats, std::io::Error> {
+    let old = std::fs::read(previous).map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!(
+                "Failed to read {}. Error: {}",
+                previous.to_string_lossy(),
+                e
+            ),
+        )
+    })?;
+    let mut listing = Vec::new();
+    let stats = remove::dry_run(dir_to_remove, options, &mut listing)?;
+
+    let diff = plan_diff::diff(
+        &plan_diff::parse_listing(&old),
+        &plan_diff::parse_listing(&listing),
+    );
+    for path in &diff.added {
+        println!("+ {path}");
+    }
+    for path in &diff.removed {
+        println!("- {path}");
+    }
+    println!(
+        "Compared with {}: {} newly included (+), {} now excluded (-), {} unchanged",
+        previous.to_string_lossy(),
+        diff.added.len(),
+        diff.removed.len(),
+        diff.unchanged
+    );
+
+    Ok(stats)
+}
+
+fn handle_watch(confirmation: &str, dir: &Path, filter: &Filter) -> Result<(), std::io::Error> {
+    if confirmation != "y" {
+        println!("Aborting as user input '{confirmation}' was not 'y'");
+        return Ok(());
+    }
+
+    println!("Watching {}, press Ctrl-C to stop.", dir.to_string_lossy());
+
+    let result = watch::watch(dir, filter);
+
+    match &result {
+        Ok(removed) => println!(
+            "Stopped watching {}, removed {} entries",
+            dir.to_string_lossy(),
+            removed
+        ),
+        Err(e) => println!("Error: {}", e),
+    }
+
+    result.map(|_| ())
+}
+
+fn remove_resumable(
+    dir_to_remove: &Path,
+    checkpoint_path: &Path,
+    resume: bool,
+) -> Result<(), std::io::Error> {
+    let mut checkpoint = if resume {
+        let checkpoint = Checkpoint::load(checkpoint_path, dir_to_remove)?;
+        println!(
+            "Resuming, skipping {} entries recorded in {}",
+            checkpoint.done.len(),
+            checkpoint_path.to_string_lossy()
+        );
+        checkpoint
+    } else {
+        Checkpoint::new(dir_to_remove)
+    };
+
+    checkpoint::remove_resumable(dir_to_remove, &mut checkpoint, checkpoint_path)
+}
+
+/// Deletes the whole tree in one go, then makes sure it is really gone.
+fn remove_dir_all(dir_to_remove: &Path) -> Result<(), RemoveError> {
+    std::fs::remove_dir_all(dir_to_remove).map_err(|source| RemoveError::Delete {
+        path: dir_to_remove.to_path_buf(),
+        source,
+    })?;
+
+    if std::fs::symlink_metadata(dir_to_remove).is_ok() {
+        return Err(RemoveError::Verify {
+            path: dir_to_remove.to_path_buf(),
+        });
+    }
+
+    Ok(())
+}
+
+fn canonicalize_target(source: &Path) -> Result<PathBuf, RemoveError> {
+    std::fs::canonicalize(source).map_err(|e| RemoveError::Canonicalize {
+        path: source.to_path_buf(),
+        source: e,
+    })
+}
+
+#[cfg(test)]
+mod remove_dir_all_should {
+    use super::*;
+
+    #[test]
+    fn tell_resolving_from_deleting_failures() {
+        let missing = Path::new("./data/remove-dir-all-missing");
+
+        let resolved = canonicalize_target(missing);
+        let deleted = remove_dir_all(missing);
+
+        assert!(
+            matches!(&resolved, Err(RemoveError::Canonicalize { path, source })
+                if path == missing && source.kind() == ErrorKind::NotFound),
+            "{resolved:?}"
+        );
+        assert!(
+            matches!(&deleted, Err(RemoveError::Delete { path, source })
+                if path == missing && source.kind() == ErrorKind::NotFound),
+            "{deleted:?}"
+        );
+    }
+
+    #[test]
+    fn delete_and_verify_the_whole_tree() {
+        let dir = PathBuf::from("./data/remove-dir-all-dir");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/file.txt"), "x").unwrap();
+
+        let deleted = remove_dir_all(&dir);
+
+        assert!(deleted.is_ok(), "{deleted:?}");
+        assert!(!dir.exists());
+    }
+}
+
+#[cfg(test)]
+mod get_user_confirmation_should {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    #[test]
+    fn return_user_input() {
+        let mut input = "y\n".as_bytes();
+        let mut output = Vec::new();
+        let dir = PathBuf::from("./test-dir");
+
+        let confirmation =
+            get_user_confirmation(&dir, false, None, Lang::English, &mut input, &mut output);
+        assert_eq!(confirmation, "y");
+
+        let output = String::from_utf8(output).unwrap();
+        let expected = format!(
+            "Are you sure you want to delete all files and folders in {}? (y/n) ",
+            dir.to_string_lossy()
+        );
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn return_y_when_force() {
+        let mut input = "n\n".as_bytes();
+        let mut output = Vec::new();
+
+        let dir = PathBuf::from("./test-dir-other");
+
+        let confirmation =
+            get_user_confirmation(&dir, true, None, Lang::English, &mut input, &mut output);
+        assert_eq!(confirmation, "y"); // Is y, even though we gave n
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output, "",
+            "Output should be empty since prompting gets skipped when force is true"
+        );
+    }
+
+    #[test]
+    fn add_the_scope_hint_to_the_prompt() {
+        let mut input = "y\n".as_bytes();
+        let mut output = Vec::new();
+        let dir = PathBuf::from("./test-dir");
+
+        get_user_confirmation(
+            &dir,
+            false,
+            Some("(WARNING: 2.0 GiB, 5 files)"),
+            Lang::English,
+            &mut input,
+            &mut output,
+        );
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            "Are you sure you want to delete all files and folders in ./test-dir (WARNING: 2.0 GiB, 5 files)? (y/n) "
+        );
+    }
+
+    #[test]
+    fn ask_in_the_selected_language() {
+        let dir = PathBuf::from("./test-dir");
+        let prompts: Vec<String> = [Lang::Spanish, Lang::German, Lang::from_code("fr")]
+            .into_iter()
+            .map(|lang| {
+                let mut output = Vec::new();
+                get_user_confirmation(&dir, false, None, lang, &mut "y\n".as_bytes(), &mut output);
+                String::from_utf8(output).unwrap()
+            })
+            .collect();
+
+        assert_eq!(
+            prompts,
+            vec![
+                "¿Seguro que quieres borrar todos los archivos y carpetas de ./test-dir? (y/n) ",
+                "Sollen wirklich alle Dateien und Ordner in ./test-dir gelöscht werden? (y/n) ",
+                "Are you sure you want to delete all files and folders in ./test-dir? (y/n) ",
+            ]
+        );
+    }
+
+    #[test]
+    fn describe_small_and_large_trees() {
+        let threshold = 1024 * 1024 * 1024;
+        assert_eq!(
+            format_scope_hint(3, 12 * 1024, threshold),
+            "(this directory is small: 3 files, 12.0 KiB)"
+        );
+        assert_eq!(
+            format_scope_hint(1_234_567, 2_469_606_195, threshold),
+            "(WARNING: 2.3 GiB, 1.2M files)"
+        );
+    }
+
+    #[test]
+    fn scan_the_tree_for_the_hint() {
+        let dir = PathBuf::from("./data/scope-hint-dir");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/file.txt"), "12345").unwrap();
+        std::fs::write(dir.join("other.txt"), "12345").unwrap();
+
+        let small = scope_hint(&dir, 1024);
+        let large = scope_hint(&dir, 10);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            small.as_deref(),
+            Some("(this directory is small: 2 files, 10 B)")
+        );
+        assert_eq!(large.as_deref(), Some("(WARNING: 10 B, 2 files)"));
+    }
+
+    #[test]
+    fn return_n_when_given_n() {
+        let mut input = "n\n".as_bytes();
+        let mut output = Vec::new();
+
+        let dir = PathBuf::from("./test-dir");
+        let confirmation =
+            get_user_confirmation(&dir, false, None, Lang::English, &mut input, &mut output);
+        assert_eq!(confirmation, "n");
+    }
+}
+
+#[cfg(test)]
+mod get_summary_confirmation_should {
+    use super::*;
+
+    #[test]
+    fn ask_with_the_item_count_and_size_only() {
+        let dir = PathBuf::from("./data/summary-confirmation-dir");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/file.txt"), "x".repeat(1024)).unwrap();
+        std::fs::write(dir.join("other.txt"), "x".repeat(1024)).unwrap();
+
+        let mut output = Vec::new();
+        let answer = get_summary_confirmation(&dir, None, &mut "y\n".as_bytes(), &mut output);
+        let mut warned = Vec::new();
+        get_summary_confirmation(&dir, Some(1024), &mut "n\n".as_bytes(), &mut warned);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(answer.as_deref(), Some("y"));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Target: ./data/summary-confirmation-dir\nDelete 3 items (2.0 KiB)? [y/N] "
+        );
+        assert!(String::from_utf8(warned)
+            .unwrap()
+            .ends_with("\nWARNING: Delete 3 items (2.0 KiB)? [y/N] "));
+    }
+}
+
+#[cfg(test)]
+mod confirm_with_code_should {
+    use super::*;
+
+    #[test]
+    fn proceed_on_the_displayed_code() {
+        let code = confirmation_code();
+        let input = format!("{code}\n");
+        let mut output = Vec::new();
+
+        let confirmed = confirm_with_code(
+            Path::new("./irreplaceable"),
+            &code,
+            &mut input.as_bytes(),
+            &mut output,
+        );
+
+        assert!(confirmed);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "This deletes all files and folders in ./irreplaceable. Type {code} to confirm: "
+            )
+        );
+    }
+
+    #[test]
+    fn abort_on_a_wrong_code_or_a_plain_y() {
+        for answer in ["y\n", "DELETE-0000\n", "delete-7f3a\n"] {
+            let confirmed = confirm_with_code(
+                Path::new("./irreplaceable"),
+                "DELETE-7F3A",
+                &mut answer.as_bytes(),
+                &mut Vec::new(),
+            );
+            assert!(!confirmed, "{answer:?} confirmed");
+        }
+    }
+
+    #[test]
+    fn generate_codes_of_the_displayed_shape() {
+        let code = confirmation_code();
+        let hex = code.strip_prefix("DELETE-").unwrap();
+        assert_eq!(hex.len(), 4);
+        assert!(hex
+            .chars()
+            .all(|c| c.is_ascii_hexdigit() && !c.is_ascii_lowercase()));
+    }
+}
+
+#[cfg(test)]
+mod is_interactive_should {
+    use super::*;
+
+    #[test]
+    fn let_the_overrides_win_over_detection() {
+        let detected = Cli::parse_from(["rm-dir", "dir"]);
+        let tty = Cli::parse_from(["rm-dir", "--assume-tty", "dir"]);
+        let no_tty = Cli::parse_from(["rm-dir", "--assume-no-tty", "dir"]);
+
+        assert!(is_interactive(&detected, true));
+        assert!(!is_interactive(&detected, false));
+        assert!(is_interactive(&tty, false));
+        assert!(!is_interactive(&no_tty, true));
+        assert!(Cli::try_parse_from(["rm-dir", "--assume-tty", "--assume-no-tty", "dir"]).is_err());
+    }
+
+    #[test]
+    fn refuse_to_prompt_without_a_terminal() {
+        let dir = "./data/assume-no-tty-dir";
+        std::fs::create_dir_all(dir).unwrap();
+
+        let refused = run_all(&Cli::parse_from(["rm-dir", "--assume-no-tty", dir]));
+        let kept = Path::new(dir).exists();
+        let forced = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--assume-no-tty",
+            "--force",
+            dir,
+        ]));
+
+        assert_eq!(refused.failures, 1);
+        assert!(kept);
+        assert_eq!(forced.failures, 0);
+        assert!(!Path::new(dir).exists());
+    }
+}
+
+#[cfg(test)]
+mod prompt_streams_should {
+    use super::*;
+
+    #[test]
+    fn need_no_terminal_when_nothing_is_asked() {
+        let forced = Cli::parse_from(["rm-dir", "--prompt-stream", "tty", "-f", "./data"]);
+        let doubly_forced = Cli::parse_from([
+            "rm-dir",
+            "--prompt-stream",
+            "tty",
+            "-ff",
+            "--prompt-beyond-depth",
+            "3",
+            "./data",
+        ]);
+
+        assert!(prompt_streams(&forced).is_some());
+        assert!(prompt_streams(&doubly_forced).is_some());
+    }
+}
+
+#[cfg(all(test, unix))]
+mod confirm_symlinks_should {
+    use std::{os::unix::fs::symlink, sync::atomic::AtomicU8};
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn list_the_links_and_ask() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/symlink-report-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+        let dir = std::fs::canonicalize(&dir).unwrap();
+        symlink("missing.txt", dir.join("dangling")).unwrap();
+
+        let mut input = "n\n".as_bytes();
+        let mut output = Vec::new();
+        let confirmed = confirm_symlinks(&dir, &mut input, &mut output);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!confirmed);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!(
+                "1 symlinks in {0}:\n  {0}/dangling -> missing.txt (dangling)\nDelete them anyway? (y/n) ",
+                dir.to_string_lossy()
+            )
+        );
+    }
+
+    #[test]
+    fn not_ask_without_links() {
+        let dir = PathBuf::from("./data/symlink-report-dir-empty");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut output = Vec::new();
+        let confirmed = confirm_symlinks(&dir, &mut "n\n".as_bytes(), &mut output);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(confirmed);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn refuse_a_tree_that_cannot_be_scanned() {
+        let dir = PathBuf::from("./data/symlink-report-dir-missing");
+
+        let mut output = Vec::new();
+        let confirmed = confirm_symlinks(&dir, &mut "y\n".as_bytes(), &mut output);
+
+        assert!(!confirmed);
+        assert!(output.is_empty(), "Nothing should be asked");
+    }
+}
+
+#[cfg(test)]
+mod check_stable_should {
+    use super::*;
+
+    fn counts(counts: &[usize]) -> impl FnMut(&Path) -> Result<usize, std::io::Error> + '_ {
+        let mut counts = counts.iter();
+        move |_| Ok(*counts.next().unwrap())
+    }
+
+    #[test]
+    fn pass_when_both_scans_agree() {
+        assert!(check_stable(Path::new("dir"), false, counts(&[3, 3])));
+    }
+
+    #[test]
+    fn refuse_a_changing_tree_unless_forced() {
+        assert!(!check_stable(Path::new("dir"), false, counts(&[3, 4])));
+        assert!(check_stable(Path::new("dir"), true, counts(&[3, 4])));
+    }
+
+    #[test]
+    fn refuse_when_a_scan_fails() {
+        let failing = |_: &Path| Err(std::io::Error::other("gone"));
+        assert!(!check_stable(Path::new("dir"), true, failing));
+    }
+
+    #[test]
+    fn count_files_without_folders() {
+        let dir = PathBuf::from("./data/check-stable-dir");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/file.txt"), "x").unwrap();
+
+        let count = count_files(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(count.unwrap(), 1);
+    }
+}
+
+#[cfg(test)]
+mod remove_staged_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Creates one file of 1 byte on each of three levels.
+    fn create_tree() -> PathBuf {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/staged-dir-{unique}"));
+
+        std::fs::create_dir_all(dir.join("one/two")).unwrap();
+        std::fs::write(dir.join("root.txt"), "a").unwrap();
+        std::fs::write(dir.join("one/first.txt"), "b").unwrap();
+        std::fs::write(dir.join("one/two/second.txt"), "c").unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn ask_before_every_deeper_level() {
+        let dir = create_tree();
+        let mut input = "y\ny\n".as_bytes();
+        let mut output = Vec::new();
+
+        let stats = remove_staged(&dir, &mut input, &mut output).unwrap();
+
+        assert!(!dir.exists());
+        assert_eq!(
+            stats,
+            RemoveStats {
+                files: 3,
+                bytes: 3,
+                dirs: 2,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Removed 1 files at level 1. 2 files (2 B) remain below, down to level 3. Continue with level 2? (y/n) \
+             Removed 1 files at level 2. 1 files (1 B) remain below, down to level 3. Continue with level 3? (y/n) "
+        );
+    }
+
+    #[test]
+    fn keep_the_deeper_levels_when_stopped() {
+        let dir = create_tree();
+        let mut input = "y\nn\n".as_bytes();
+        let mut output = Vec::new();
+
+        let stats = remove_staged(&dir, &mut input, &mut output).unwrap();
+        let root_file = dir.join("root.txt").exists();
+        let first = dir.join("one/first.txt").exists();
+        let second = dir.join("one/two/second.txt").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.dirs, 0);
+        assert!(!root_file && !first);
+        assert!(second, "The level that was not approved should be kept");
+        assert_eq!(
+            String::from_utf8(output).unwrap().matches("(y/n)").count(),
+            2
+        );
+    }
+}
+
+#[cfg(test)]
+mod confirm_depth_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Creates a tree three levels deep.
+    fn create_deep_dir() -> String {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/depth-dir-{unique}");
+
+        std::fs::create_dir_all(format!("{dir}/a/b")).unwrap();
+        std::fs::File::create(format!("{dir}/a/b/file.txt")).unwrap();
+        std::fs::File::create(format!("{dir}/top.txt")).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn measure_the_deepest_entry() {
+        let dir = create_deep_dir();
+
+        let depth = tree_depth(Path::new(&dir)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(depth, 3);
+    }
+
+    #[test]
+    fn refuse_a_tree_that_cannot_be_scanned() {
+        let mut output = Vec::new();
+
+        let confirmed = confirm_depth(
+            Path::new("./data/depth-missing"),
+            3,
+            true,
+            &mut "y\n".as_bytes(),
+            &mut output,
+        );
+
+        assert!(!confirmed);
+        assert!(output.is_empty(), "Nothing should be asked");
+    }
+
+    #[test]
+    fn ask_only_beyond_the_threshold() {
+        let dir = create_deep_dir();
+        let mut output = Vec::new();
+
+        let shallow_enough =
+            confirm_depth(Path::new(&dir), 3, false, &mut "".as_bytes(), &mut output);
+        let aborted = !confirm_depth(
+            Path::new(&dir),
+            2,
+            false,
+            &mut "n\n".as_bytes(),
+            &mut output,
+        );
+        let doubly_forced =
+            confirm_depth(Path::new(&dir), 2, true, &mut "".as_bytes(), &mut output);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(shallow_enough);
+        assert!(aborted, "Should abort on n");
+        assert!(doubly_forced);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output, "This tree is 3 levels deep, deeper than your threshold of 2. Continue? (y/n) ",
+            "Expected a single prompt"
+        );
+    }
+}
+
+#[cfg(all(test, unix))]
+mod confirm_audit_should {
+    use std::{os::unix::fs::PermissionsExt, sync::atomic::AtomicU8};
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn create_dir_with_setuid_file() -> String {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/confirm-audit-dir-{unique}");
+        let file = format!("{dir}/setuid");
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::File::create(&file).unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o4755)).unwrap();
+
+        dir
+    }
+
+    #[test]
+    fn warn_and_abort_when_given_n() {
+        let dir = create_dir_with_setuid_file();
+        let mut input = "n\n".as_bytes();
+        let mut output = Vec::new();
+
+        let confirmed = confirm_audit(Path::new(&dir), &mut input, &mut output);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!confirmed, "Audit should abort on n");
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("4755"), "Mode missing from {output}");
+        assert!(output.contains("setuid"), "Reason missing from {output}");
+    }
+
+    #[test]
+    fn continue_when_given_y() {
+        let dir = create_dir_with_setuid_file();
+        let mut input = "y\n".as_bytes();
+        let mut output = Vec::new();
+
+        let confirmed = confirm_audit(Path::new(&dir), &mut input, &mut output);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(confirmed, "Audit should continue on y");
+    }
+
+    #[test]
+    fn refuse_a_tree_that_cannot_be_scanned() {
+        let mut output = Vec::new();
+
+        let confirmed = confirm_audit(
+            Path::new("./data/confirm-audit-missing"),
+            &mut "y\n".as_bytes(),
+            &mut output,
+        );
+
+        assert!(!confirmed);
+        assert!(output.is_empty(), "Nothing should be asked");
+    }
+
+    #[test]
+    fn not_prompt_when_nothing_flagged() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/confirm-audit-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut input = "".as_bytes();
+        let mut output = Vec::new();
+
+        let confirmed = confirm_audit(Path::new(&dir), &mut input, &mut output);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(confirmed);
+        assert!(output.is_empty(), "Prompted without findings");
+    }
+}
+
+#[cfg(all(test, unix))]
+mod handle_link_confirmation_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Creates a directory holding a file and a symlink next to it pointing at it.
+    fn create_linked_dir() -> (String, String) {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/link-root-dir-{unique}");
+        let link = format!("{dir}-link");
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::File::create(format!("{dir}/file.txt")).unwrap();
+        std::os::unix::fs::symlink(std::fs::canonicalize(&dir).unwrap(), &link).unwrap();
+
+        (dir, link)
+    }
+
+    #[test]
+    fn remove_only_the_link_by_default() {
+        let (dir, link) = create_linked_dir();
+
+        let result = handle_link_confirmation("y", Path::new(&link));
+
+        assert!(result.is_ok(), "Error when removing link");
+        assert!(!Path::new(&link).is_symlink(), "Link was not removed");
+        assert!(
+            Path::new(&format!("{dir}/file.txt")).exists(),
+            "Linked directory was touched"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keep_the_link_when_n() {
+        let (dir, link) = create_linked_dir();
+
+        let result = handle_link_confirmation("n", Path::new(&link));
+
+        assert!(result.is_ok());
+        assert!(Path::new(&link).is_symlink(), "Link was removed");
+
+        std::fs::remove_file(&link).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn refuse_a_filtered_run_instead_of_removing_the_link() {
+        for filter in [
+            &["--include", "*.txt"][..],
+            &["--delete-empty-files"],
+            &["--dangling-only"],
+            &["--delete-broken-after", "7d"],
+            &["--dedupe"],
+        ] {
+            let (dir, link) = create_linked_dir();
+            let mut args = vec!["rm-dir", "--force"];
+            args.extend(filter);
+            args.push(&link);
+            let opts = Cli::parse_from(args);
+
+            let refused = !handle_symlink_root(&opts, Path::new(&link));
+            let link_kept = Path::new(&link).is_symlink();
+            let file_kept = Path::new(&format!("{dir}/file.txt")).exists();
+            std::fs::remove_file(&link).unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+
+            assert!(refused, "{filter:?} removed the link");
+            assert!(link_kept && file_kept, "{filter:?} touched the tree");
+        }
+    }
+
+    #[test]
+    fn remove_the_linked_directory_when_dereferenced() {
+        let (dir, link) = create_linked_dir();
+        let dereferenced = std::fs::canonicalize(&link).unwrap();
+
+        let result = handle_confirmation("y", &dereferenced, &RemoveOptions::default());
+
+        assert!(result.is_ok(), "Error when removing linked directory");
+        assert!(
+            !Path::new(&dir).exists(),
+            "Linked directory was not removed"
+        );
+        assert!(Path::new(&link).is_symlink(), "Link itself should be left");
+
+        std::fs::remove_file(&link).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod confirm_each_type_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn delete_files_but_keep_directories() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/each-type-dir-{unique}");
+
+        std::fs::create_dir_all(format!("{dir}/sub")).unwrap();
+        std::fs::File::create(format!("{dir}/file1.txt")).unwrap();
+        std::fs::File::create(format!("{dir}/file2.txt")).unwrap();
+        std::fs::File::create(format!("{dir}/sub/nested.txt")).unwrap();
+        let canonicalized = std::fs::canonicalize(&dir).unwrap();
+
+        let mut input = "y\nn\n".as_bytes();
+        let mut output = Vec::new();
+        let (files, dirs) =
+            confirm_each_type(&canonicalized, false, &mut input, &mut output).unwrap();
+        assert_eq!((files, dirs), (true, false));
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Delete 2 files in"), "Got {output}");
+        assert!(
+            output.contains("Delete 1 subdirectories of"),
+            "Got {output}"
+        );
+
+        let options = RemoveOptions {
+            preserve: kept_by_type(&canonicalized, files, dirs).unwrap(),
+            ..Default::default()
+        };
+        let result = handle_confirmation("y", &canonicalized, &options);
+
+        assert!(result.is_ok(), "Error when removing files");
+        assert!(!Path::new(&format!("{dir}/file1.txt")).exists());
+        assert!(!Path::new(&format!("{dir}/file2.txt")).exists());
+        assert!(Path::new(&format!("{dir}/sub/nested.txt")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn approve_both_kinds_when_forced() {
+        let mut input = "".as_bytes();
+        let mut output = Vec::new();
+
+        std::fs::create_dir_all("./data").unwrap();
+        let approved = confirm_each_type(Path::new("./data"), true, &mut input, &mut output);
+
+        assert_eq!(approved.unwrap(), (true, true));
+        assert!(output.is_empty(), "Nothing should be asked with --force");
+    }
+
+    #[test]
+    fn fail_when_the_directory_cannot_be_listed() {
+        let mut output = Vec::new();
+
+        let approved = confirm_each_type(
+            Path::new("./data/each-type-missing"),
+            false,
+            &mut "y\ny\n".as_bytes(),
+            &mut output,
+        );
+
+        assert!(approved.is_err());
+        assert!(output.is_empty(), "Nothing should be asked");
+    }
+}
+
+#[cfg(test)]
+mod subdir_sizes_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn add_up_each_top_level_folder_largest_first() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/subdir-sizes-dir-{unique}"));
+        std::fs::create_dir_all(dir.join("small")).unwrap();
+        std::fs::create_dir_all(dir.join("big/deeper")).unwrap();
+        std::fs::create_dir_all(dir.join("empty")).unwrap();
+        std::fs::write(dir.join("small/file.txt"), "x").unwrap();
+        std::fs::write(dir.join("big/file.txt"), "x".repeat(10)).unwrap();
+        std::fs::write(dir.join("big/deeper/file.txt"), "x".repeat(20)).unwrap();
+        std::fs::write(dir.join("top.txt"), "x".repeat(100)).unwrap();
+
+        let sizes = subdir_sizes(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            sizes,
+            [
+                ("big".to_string(), 30),
+                ("small".to_string(), 1),
+                ("empty".to_string(), 0)
+            ]
+        );
+    }
+}
+
+#[cfg(test)]
+mod format_error_groups_should {
+    use super::*;
+    use remove::ErrorGroup;
+
+    #[test]
+    fn list_each_kind_with_its_count_and_cap_the_paths() {
+        let mut stats = RemoveStats::default();
+        stats.errors_by_kind.insert(
+            "Not a directory".to_string(),
+            ErrorGroup {
+                count: 1,
+                paths: vec![PathBuf::from("/t/a")],
+            },
+        );
+        stats.errors_by_kind.insert(
+            "Permission denied".to_string(),
+            ErrorGroup {
+                count: 231,
+                paths: vec![PathBuf::from("/t/b"), PathBuf::from("/t/c")],
+            },
+        );
+
+        assert_eq!(
+            format_error_groups(&stats),
+            "Not a directory (1):\n  /t/a\nPermission denied (231):\n  /t/b\n  /t/c\n  ... and 229 more\n"
+        );
+    }
+}
+
+#[cfg(test)]
+mod target_from_env_should {
+    use super::*;
+
+    #[test]
+    fn read_the_target_from_a_set_variable() {
+        std::env::set_var("RM_DIR_TEST_TARGET_SET", "./data/some dir; rm -rf ~");
+
+        assert_eq!(
+            target_from_env("RM_DIR_TEST_TARGET_SET"),
+            Ok("./data/some dir; rm -rf ~".to_string())
+        );
+    }
+
+    #[test]
+    fn refuse_an_unset_or_empty_variable() {
+        std::env::remove_var("RM_DIR_TEST_TARGET_UNSET");
+        std::env::set_var("RM_DIR_TEST_TARGET_EMPTY", "");
+
+        assert_eq!(
+            target_from_env("RM_DIR_TEST_TARGET_UNSET"),
+            Err("The environment variable RM_DIR_TEST_TARGET_UNSET is not set".to_string())
+        );
+        assert_eq!(
+            target_from_env("RM_DIR_TEST_TARGET_EMPTY"),
+            Err("The environment variable RM_DIR_TEST_TARGET_EMPTY is empty".to_string())
+        );
+    }
+}
+
+#[cfg(test)]
+mod run_all_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn refuse_a_target_with_files_modified_within_the_protection_window() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/old.txt"), "old").unwrap();
+        std::fs::write(format!("{dir}/nested/new.txt"), "new").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(format!("{dir}/old.txt"))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+            .unwrap();
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--assume-tty",
+            "--protect-newer-than",
+            "1h",
+            &dir,
+        ]));
+        let kept = Path::new(&format!("{dir}/old.txt")).exists()
+            && Path::new(&format!("{dir}/nested/new.txt")).exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(batch.failures, 1);
+        assert!(kept);
+    }
+
+    #[test]
+    fn delete_once_the_confirmation_file_appears() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let confirmation = format!("{dir}-approved");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "approved").unwrap();
+        let approval = {
+            let confirmation = confirmation.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(300));
+                std::fs::write(confirmation, "y\n").unwrap();
+            })
+        };
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--assume-tty",
+            "--confirm-via-file",
+            &confirmation,
+            "--timeout",
+            "10s",
+            &dir,
+        ]));
+        approval.join().unwrap();
+
+        assert_eq!(batch.failures, 0);
+        assert!(!Path::new(&dir).exists());
+        assert!(!Path::new(&confirmation).exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn take_the_audit_answer_from_the_confirmation_file_under_force() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let confirmation = format!("{dir}-approved");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(format!("{dir}/setuid"), "audited").unwrap();
+        std::fs::set_permissions(
+            format!("{dir}/setuid"),
+            std::fs::Permissions::from_mode(0o4755),
+        )
+        .unwrap();
+        std::fs::write(&confirmation, "y\n").unwrap();
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--audit-perms",
+            "--confirm-via-file",
+            &confirmation,
+            "--timeout",
+            "10s",
+            &dir,
+        ]));
+
+        assert_eq!(batch.failures, 0);
+        assert!(!Path::new(&dir).exists());
+        assert!(!Path::new(&confirmation).exists());
+    }
+
+    #[test]
+    fn remove_the_progress_file_once_the_deletion_went_through() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let progress_file = format!("{dir}-progress.json");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "watched").unwrap();
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--progress-file",
+            &progress_file,
+            &dir,
+        ]));
+
+        assert_eq!(batch.failures, 0);
+        assert!(!Path::new(&dir).exists());
+        assert!(!Path::new(&progress_file).exists());
+    }
+
+    #[test]
+    fn delete_a_target_whose_scope_hash_matches() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "reviewed").unwrap();
+        let reviewed = scope_hash::scope_hash(&std::fs::canonicalize(&dir).unwrap()).unwrap();
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--expect-scope-hash",
+            &reviewed,
+            &dir,
+        ]));
+
+        assert_eq!(batch.failures, 0);
+        assert!(!Path::new(&dir).exists());
+    }
+
+    #[test]
+    fn refuse_a_target_whose_scope_changed_since_its_hash_was_taken() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "reviewed").unwrap();
+        let reviewed = scope_hash::scope_hash(&std::fs::canonicalize(&dir).unwrap()).unwrap();
+        std::fs::write(format!("{dir}/nested/added.txt"), "unexpected").unwrap();
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--expect-scope-hash",
+            &reviewed,
+            &dir,
+        ]));
+        let kept = Path::new(&format!("{dir}/nested/file.txt")).exists()
+            && Path::new(&format!("{dir}/nested/added.txt")).exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(batch.failures, 1);
+        assert!(kept);
+    }
+
+    #[test]
+    fn archive_the_target_before_deleting_it() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let archive = format!("{dir}-archive.tar");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "archived contents").unwrap();
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--archive",
+            &archive,
+            &dir,
+        ]));
+        let tar = std::fs::read(&archive).unwrap();
+        std::fs::remove_file(&archive).unwrap();
+
+        let contains = |needle: &str| tar.windows(needle.len()).any(|w| w == needle.as_bytes());
+        assert_eq!(batch.failures, 0);
+        assert!(!Path::new(&dir).exists());
+        assert!(contains(&format!("batch-dir-{unique}/nested/file.txt")));
+        assert!(contains("archived contents"));
+    }
+
+    #[test]
+    fn keep_the_target_when_the_archive_cannot_be_written() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let archive = format!("{dir}-missing/archive.zip");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "kept").unwrap();
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--archive",
+            &archive,
+            &dir,
+        ]));
+        let kept = Path::new(&format!("{dir}/nested/file.txt")).exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(batch.failures, 1);
+        assert!(kept);
+    }
+
+    #[test]
+    fn compare_a_second_run_with_the_first_in_the_stats_history() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let history = format!("{dir}-history.jsonl");
+        let args = ["rm-dir", "--force", "--stats-history", &history, &dir];
+
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "12345").unwrap();
+        let target = std::fs::canonicalize(&dir).unwrap();
+        let first = run_all(&Cli::parse_from(args));
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "1234567890").unwrap();
+        let second = run_all(&Cli::parse_from(args));
+
+        let contents = std::fs::read_to_string(&history).unwrap();
+        std::fs::remove_file(&history).unwrap();
+        let records: Vec<_> = contents
+            .lines()
+            .map(|line| json::parse_object(line).unwrap())
+            .collect();
+
+        assert_eq!((first.failures, second.failures), (0, 0));
+        assert!(!Path::new(&dir).exists());
+        assert_eq!(records.len(), 2, "{contents}");
+        for (record, bytes) in records.iter().zip([5, 10]) {
+            assert!(record.contains(&(
+                "target".to_string(),
+                json::JsonValue::String(target.to_string_lossy().into_owned())
+            )));
+            assert!(record.contains(&("bytes".to_string(), json::JsonValue::Number(bytes))));
+        }
+    }
+
+    #[test]
+    fn add_up_every_target_and_count_failures() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dirs: Vec<String> = (0..3)
+            .map(|i| format!("./data/batch-dir-{unique}-{i}"))
+            .collect();
+        for (i, dir) in dirs.iter().enumerate() {
+            std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+            std::fs::write(format!("{dir}/nested/file.txt"), "x".repeat(i + 1)).unwrap();
+        }
+        let missing = format!("./data/batch-dir-{unique}-missing");
 
-    /// Flag to force delete without confirmation.
-    #[arg(short, long, action)]
-    force: bool,
-}
+        let mut args = vec![
+            "rm-dir",
+            "--force",
+            "--quiet",
+            "--batch-summary",
+            "--on-missing",
+            "error",
+        ];
+        args.extend(dirs.iter().map(String::as_str));
+        args.push(&missing);
+        let batch = run_all(&Cli::parse_from(args));
 
-fn main() {
-    let opts = Cli::parse();
+        assert!(dirs.iter().all(|dir| !Path::new(dir).exists()));
+        assert_eq!(batch.targets, 4);
+        assert_eq!(batch.failures, 1, "The missing target should fail");
+        assert_eq!(batch.missing, 1);
+        assert_eq!(
+            batch.stats,
+            RemoveStats {
+                files: 3,
+                dirs: 3,
+                bytes: 6,
+                ..Default::default()
+            }
+        );
+    }
 
-    let dir_to_remove = std::fs::canonicalize(opts.source_dir).unwrap_or_else(|e| {
-        panic!("{}", e);
-    });
+    #[test]
+    fn remove_the_target_named_by_an_environment_variable() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/env-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "x").unwrap();
+        std::env::set_var("RM_DIR_TEST_RUN_ALL_TARGET", &dir);
+        std::env::remove_var("RM_DIR_TEST_RUN_ALL_UNSET");
 
-    let confirmation = get_user_confirmation(
-        &dir_to_remove,
-        opts.force,
-        &mut stdin().lock(),
-        &mut stdout(),
-    )
-    .trim()
-    .to_lowercase();
+        let args = ["rm-dir", "--force", "--quiet", "--target-from-env"];
+        let removed = run_all(&Cli::parse_from(
+            args.iter().chain(&["RM_DIR_TEST_RUN_ALL_TARGET"]),
+        ));
+        let unset = run_all(&Cli::parse_from(
+            args.iter().chain(&["RM_DIR_TEST_RUN_ALL_UNSET"]),
+        ));
 
-    let _result = handle_confirmation(&confirmation, &dir_to_remove);
-}
+        assert!(!Path::new(&dir).exists());
+        assert_eq!((removed.targets, removed.failures), (1, 0));
+        assert_eq!((unset.targets, unset.failures), (0, 1));
+    }
 
-fn get_user_confirmation(
-    source_dir: &Path,
-    force: bool,
-    input: &mut impl BufRead,
-    output: &mut impl Write,
-) -> String {
-    if force {
-        println!("Running delete without confirmation.");
-        println!(
-            "Deleting all files and folders in {}.",
-            source_dir.to_string_lossy()
-        );
-        return "y".to_string();
+    #[test]
+    fn delete_only_the_paths_in_a_manifest_and_fail_on_missing_ones() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/manifest-run-dir-{unique}");
+        let manifest = format!("{dir}.json");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "x").unwrap();
+        snapshot::write_snapshot(Path::new(&dir), Path::new(&manifest)).unwrap();
+        std::fs::write(format!("{dir}/unlisted.txt"), "x").unwrap();
+        std::fs::remove_file(format!("{dir}/nested/file.txt")).unwrap();
+
+        let args = ["rm-dir", "--force", "--quiet", "--on-missing", "error"];
+        let batch = run_all(&Cli::parse_from(args.iter().copied().chain([
+            "--delete-manifest",
+            &manifest,
+            &dir,
+        ])));
+        let nested_removed = !Path::new(&format!("{dir}/nested")).exists();
+        let unlisted_kept = Path::new(&format!("{dir}/unlisted.txt")).exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&manifest).unwrap();
+
+        assert!(nested_removed);
+        assert!(unlisted_kept);
+        assert_eq!(batch.failures, 1, "The missing file should fail");
+        assert_eq!((batch.stats.files, batch.stats.dirs), (0, 1));
     }
 
-    let prompt = format!(
-        "Are you sure you want to delete all files and folders in {}? (y/n) ",
-        source_dir.to_string_lossy()
-    );
+    #[test]
+    fn list_what_a_dry_run_would_delete_into_a_file() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dirs: Vec<String> = (0..2)
+            .map(|i| format!("./data/batch-dir-{unique}-{i}"))
+            .collect();
+        for dir in &dirs {
+            std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+            std::fs::write(format!("{dir}/nested/file.txt"), "x").unwrap();
+            std::fs::write(format!("{dir}/top.txt"), "x").unwrap();
+        }
+        let list = format!("./data/batch-dir-{unique}-list.txt");
 
-    write!(output, "{prompt}").unwrap_or_else(|e| panic!("Failed to write prompt Error: {}", e));
+        let mut args = vec!["rm-dir", "--dry-run", "--dry-run-to", &list];
+        args.extend(dirs.iter().map(String::as_str));
+        let batch = run_all(&Cli::parse_from(args));
 
-    output
-        .flush()
-        .unwrap_or_else(|e| panic!("Failed to flush output. Error: {}", e));
+        let mut scanned: Vec<String> = dirs
+            .iter()
+            .flat_map(|dir| scan::scan_dir(&std::fs::canonicalize(dir).unwrap()).unwrap())
+            .map(|entry| entry.path.to_string_lossy().to_string())
+            .collect();
+        scanned.sort();
+        let mut listed: Vec<String> = std::fs::read_to_string(&list)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect();
+        listed.sort();
 
-    let mut user_input = String::new();
-    input
-        .read_line(&mut user_input)
-        .unwrap_or_else(|e| panic!("Failed to read user input {}", e));
+        for dir in &dirs {
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+        std::fs::remove_file(&list).unwrap();
 
-    user_input.trim().to_string()
-}
+        assert_eq!(batch.failures, 0);
+        assert_eq!(listed.len(), 6);
+        assert_eq!(listed, scanned);
+    }
 
-fn handle_confirmation(confirmation: &str, dir_to_remove: &Path) -> Result<(), std::io::Error> {
-    if confirmation != "y" {
-        println!("Aborting as user input '{confirmation}' was not 'y'");
-        return Ok(());
+    #[test]
+    fn write_a_summary_line_for_every_target() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dirs: Vec<String> = (0..2)
+            .map(|i| format!("./data/batch-dir-{unique}-{i}"))
+            .collect();
+        for dir in &dirs {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(format!("{dir}/file.txt"), "x").unwrap();
+        }
+        let summary = format!("./data/batch-dir-{unique}-summary.json");
+        std::fs::write(&summary, "left from an earlier run\n").unwrap();
+
+        let mut args = vec!["rm-dir", "--force", "--summary-json-to", &summary];
+        args.extend(dirs.iter().map(String::as_str));
+        let batch = run_all(&Cli::parse_from(args));
+        let json = std::fs::read_to_string(&summary).unwrap();
+        std::fs::remove_file(&summary).unwrap();
+
+        assert_eq!(batch.failures, 0);
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(lines.len(), 2, "Unexpected summary {json}");
+        for (line, dir) in lines.iter().zip(&dirs) {
+            let target = dir.trim_start_matches("./data/");
+            assert!(line.starts_with(r#"{"target":"#), "Unexpected line {line}");
+            assert!(line.contains(target), "{line} is not about {target}");
+        }
     }
 
-    let now = Instant::now();
-    let result = remove_dir_all(dir_to_remove);
+    #[test]
+    fn fail_before_scanning_when_the_list_cannot_be_written() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+        let list = format!("./data/batch-dir-{unique}-missing/list.txt");
 
-    match &result {
-        Ok(_) => println!(
-            "Removed all files and folders from {}",
-            dir_to_remove.to_string_lossy()
-        ),
-        Err(e) => println!("Error: {}", e),
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--dry-run",
+            "--dry-run-to",
+            &list,
+            &dir,
+        ]));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(batch.failures, 1);
+        assert_eq!(batch.targets, 0, "No target should have been scanned");
     }
 
-    println!("Done in {}s", now.elapsed().as_secs_f32());
+    #[test]
+    fn only_dry_run_unconfirmed_targets_on_first_run_safety() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let history = format!("./data/batch-dir-{unique}-history");
+        let run = |confirm: bool| {
+            std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+            let mut args = vec![
+                "rm-dir",
+                "--force",
+                "--first-run-safety",
+                "--history-file",
+                &history,
+                &dir,
+            ];
+            if confirm {
+                args.push("--confirm");
+            }
+            let batch = run_all(&Cli::parse_from(args));
+            (batch.failures, Path::new(&dir).exists())
+        };
 
-    result
-}
+        let first = run(false);
+        let again_unconfirmed = run(false);
+        let confirmed = run(true);
+        let later = run(false);
 
-fn remove_dir_all(dir_to_remove: &Path) -> Result<(), std::io::Error> {
-    std::fs::remove_dir_all(dir_to_remove)
-}
+        std::fs::remove_file(&history).unwrap();
 
-#[cfg(test)]
-mod get_user_confirmation_should {
-    use super::*;
+        assert_eq!(first, (1, true), "The first run must only dry-run");
+        assert_eq!(again_unconfirmed, (1, true));
+        assert_eq!(confirmed, (0, false));
+        assert_eq!(later, (0, false), "A confirmed target proceeds normally");
+    }
 
     #[test]
-    fn return_user_input() {
-        let mut input = "y\n".as_bytes();
-        let mut output = Vec::new();
-        let dir = PathBuf::from("./test-dir");
+    fn not_remember_a_first_run_target_whose_delete_was_declined() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let history = format!("./data/batch-dir-{unique}-history");
+        let answer = format!("./data/batch-dir-{unique}-answer");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(&answer, "n\n").unwrap();
 
-        let confirmation = get_user_confirmation(&dir, false, &mut input, &mut output);
-        assert_eq!(confirmation, "y");
+        let declined = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--assume-tty",
+            "--confirm-via-file",
+            &answer,
+            "--first-run-safety",
+            "--confirm",
+            "--history-file",
+            &history,
+            &dir,
+        ]));
+        let remembered = History::load(Path::new(&history))
+            .unwrap()
+            .is_confirmed(&std::fs::canonicalize(&dir).unwrap());
+        let later = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--first-run-safety",
+            "--history-file",
+            &history,
+            &dir,
+        ]));
+        let kept = Path::new(&dir).exists();
+        std::fs::remove_dir_all(&dir).unwrap();
 
-        let output = String::from_utf8(output).unwrap();
-        let expected = format!(
-            "Are you sure you want to delete all files and folders in {}? (y/n) ",
-            dir.to_string_lossy()
-        );
-        assert_eq!(output, expected);
+        assert_eq!(declined.failures, 0);
+        assert!(!remembered, "Declining must not mark the target confirmed");
+        assert_eq!(later.failures, 1, "The next run must only dry-run again");
+        assert!(kept);
+        assert!(!Path::new(&history).exists());
     }
 
     #[test]
-    fn return_y_when_force() {
-        let mut input = "n\n".as_bytes();
-        let mut output = Vec::new();
+    fn only_fail_on_missing_targets_when_asked() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let missing = format!("./data/batch-dir-{unique}-missing");
 
-        let dir = PathBuf::from("./test-dir-other");
+        for (policy, failures) in [("skip", 0), ("warn", 0), ("error", 1)] {
+            let batch = run_all(&Cli::parse_from([
+                "rm-dir",
+                "--force",
+                "--on-missing",
+                policy,
+                &missing,
+            ]));
 
-        let confirmation = get_user_confirmation(&dir, true, &mut input, &mut output);
-        assert_eq!(confirmation, "y"); // Is y, even though we gave n
+            assert_eq!(batch.missing, 1);
+            assert_eq!(batch.failures, failures, "Unexpected failures for {policy}");
+        }
 
-        let output = String::from_utf8(output).unwrap();
+        let default = Cli::parse_from(["rm-dir", &missing]);
+        assert_eq!(default.on_missing, MissingPolicy::Warn);
+    }
+
+    #[test]
+    fn exit_with_10_only_when_a_dry_run_found_work() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let clean = format!("./data/batch-dir-{unique}-clean");
+        let pending = format!("./data/batch-dir-{unique}-pending");
+        std::fs::create_dir_all(&clean).unwrap();
+        std::fs::create_dir_all(&pending).unwrap();
+        std::fs::write(format!("{pending}/file.log"), "x").unwrap();
+
+        let code = |args: &[&str]| {
+            let opts = Cli::parse_from(args);
+            exit_code(&opts, &run_all(&opts))
+        };
+        let clean_code = code(&["rm-dir", "--dry-run", "--dry-run-exit-code", &clean]);
+        let pending_code = code(&["rm-dir", "--dry-run", "--dry-run-exit-code", &pending]);
+        let filtered_code = code(&[
+            "rm-dir",
+            "--dry-run",
+            "--dry-run-exit-code",
+            "--include",
+            "*.tmp",
+            &pending,
+        ]);
+        let plain_code = code(&["rm-dir", "--dry-run", &pending]);
+        let kept = Path::new(&pending).join("file.log").exists();
+        std::fs::remove_dir_all(&clean).unwrap();
+        std::fs::remove_dir_all(&pending).unwrap();
+
+        assert_eq!(clean_code, 0);
+        assert_eq!(pending_code, WORK_PENDING);
+        assert_eq!(filtered_code, 0, "Nothing matches the filter");
+        assert_eq!(plain_code, 0);
+        assert!(kept);
+        assert!(Cli::try_parse_from(["rm-dir", "--dry-run-exit-code", "dir"]).is_err());
+    }
+
+    #[test]
+    fn keep_the_target_after_a_bare_throttled_verbose() {
+        let bare = Cli::parse_from(["rm-dir", "--throttled-verbose", "123"]);
+        let with_interval = Cli::parse_from(["rm-dir", "--throttled-verbose=50", "123"]);
+
+        assert_eq!(bare.throttled_verbose, Some(100));
+        assert_eq!(bare.source_dirs, ["123"]);
+        assert_eq!(with_interval.throttled_verbose, Some(50));
+        assert_eq!(with_interval.source_dirs, ["123"]);
+    }
+
+    #[test]
+    fn keep_the_target_after_a_bare_relative_to() {
+        let bare = Cli::parse_from(["rm-dir", "--relative-to", "build/"]);
+        let with_base = Cli::parse_from(["rm-dir", "--relative-to=base", "build/"]);
+
+        assert_eq!(bare.relative_to, Some(None));
+        assert_eq!(bare.source_dirs, ["build/"]);
+        assert_eq!(with_base.relative_to, Some(Some(PathBuf::from("base"))));
+        assert_eq!(with_base.source_dirs, ["build/"]);
+    }
+
+    #[test]
+    fn recreate_the_target_empty_with_its_permissions() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "x").unwrap();
+        let mut permissions = std::fs::metadata(&dir).unwrap().permissions();
+        #[cfg(unix)]
+        std::os::unix::fs::PermissionsExt::set_mode(&mut permissions, 0o750);
+        std::fs::set_permissions(&dir, permissions).unwrap();
+        let permissions = std::fs::metadata(&dir).unwrap().permissions();
+
+        let batch = run_all(&Cli::parse_from(["rm-dir", "--force", "--recreate", &dir]));
+        let empty = is_empty_target(Path::new(&dir));
+        let recreated = std::fs::metadata(&dir).unwrap().permissions();
+        std::fs::remove_dir(&dir).unwrap();
+
+        assert_eq!(batch.failures, 0);
+        assert!(empty);
+        assert_eq!(recreated, permissions);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recreate_the_target_with_an_explicit_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--quiet",
+            "--recreate",
+            "--mode",
+            "0751",
+            &dir,
+        ]));
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode() & 0o7777;
+        std::fs::remove_dir(&dir).unwrap();
+
+        assert_eq!(batch.failures, 0);
+        assert_eq!(mode, 0o751);
+    }
+
+    #[test]
+    fn only_skip_the_prompt_for_targets_matching_yes_if_matches() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let matching = format!("./data/batch-dir-{unique}-node_modules");
+        let other = format!("./data/batch-dir-{unique}-src");
+        for dir in [&matching, &other] {
+            std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        }
+
+        // Without a terminal a target that has to be asked about is refused.
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--assume-no-tty",
+            "--quiet",
+            "--yes-if-matches",
+            "[-/](node_modules|target)$",
+            &matching,
+            &other,
+        ]));
+        let matching_removed = !Path::new(&matching).exists();
+        let other_kept = Path::new(&other).exists();
+        std::fs::remove_dir_all(&other).unwrap();
+
+        assert!(matching_removed);
+        assert!(other_kept);
+        assert_eq!(batch.failures, 1);
+    }
+
+    #[test]
+    fn carry_on_past_a_target_holding_its_own_progress_file() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let holding = format!("./data/batch-dir-{unique}-holding");
+        let other = format!("./data/batch-dir-{unique}-other");
+        for dir in [&holding, &other] {
+            std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        }
+        let progress_file = format!("{holding}/progress.json");
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--progress-file",
+            &progress_file,
+            &holding,
+            &other,
+        ]));
+        let holding_kept = Path::new(&holding).exists();
+        std::fs::remove_dir_all(&holding).unwrap();
+
+        assert_eq!(batch.failures, 1);
+        assert!(holding_kept);
+        assert!(!Path::new(&other).exists());
+    }
+
+    #[test]
+    fn fail_a_target_with_a_missing_or_overlapping_reference() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "compared").unwrap();
+        let missing = format!("{dir}-missing");
+
+        let run = |reference: &str| {
+            run_all(&Cli::parse_from([
+                "rm-dir",
+                "--force",
+                "--compare-to",
+                reference,
+                &dir,
+                &dir,
+            ]))
+        };
+        let with_missing = run(&missing);
+        let with_overlapping = run(&format!("{dir}/nested"));
+        let kept = Path::new(&format!("{dir}/nested/file.txt")).exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(with_missing.failures, 2);
+        assert_eq!(with_overlapping.failures, 2);
+        assert!(kept);
+    }
+
+    #[test]
+    fn only_create_the_quarantine_when_moving_into_it() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let quarantine = format!("{dir}-quarantine/nested");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(format!("{dir}/file.txt"), "quarantined").unwrap();
+
+        let dry_run = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--dry-run",
+            "--quarantine",
+            &quarantine,
+            &dir,
+        ]));
+        let created_by_dry_run = Path::new(&format!("{dir}-quarantine")).exists();
+        let inside = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--quarantine",
+            &format!("{dir}/quarantine"),
+            &dir,
+        ]));
+        let kept = Path::new(&format!("{dir}/file.txt")).exists();
+        let moved = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--quarantine",
+            &quarantine,
+            &dir,
+        ]));
+        let quarantined = Path::new(&format!("{quarantine}/file.txt")).exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(format!("{dir}-quarantine")).unwrap();
+
+        assert_eq!(dry_run.failures, 0);
+        assert!(!created_by_dry_run, "The dry run created the quarantine");
+        assert_eq!(inside.failures, 1);
+        assert!(kept);
+        assert_eq!(moved.failures, 0);
+        assert!(quarantined);
+    }
+
+    #[test]
+    fn preserve_paths_only_in_the_target_holding_them() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let holding = format!("./data/batch-dir-{unique}-holding");
+        let other = format!("./data/batch-dir-{unique}-other");
+        for dir in [&holding, &other] {
+            std::fs::create_dir_all(format!("{dir}/keep")).unwrap();
+            std::fs::write(format!("{dir}/file.txt"), "x").unwrap();
+        }
+        let outside = format!("./data/batch-dir-{unique}-outside");
+        std::fs::create_dir_all(&outside).unwrap();
+
+        let preserve = format!("{holding}/keep");
+        let run = |preserve: &str| {
+            run_all(&Cli::parse_from([
+                "rm-dir",
+                "--force",
+                "--preserve",
+                preserve,
+                &holding,
+                &other,
+            ]))
+        };
+        let refused = run(&outside);
+        let nothing_deleted = Path::new(&format!("{holding}/file.txt")).exists()
+            && Path::new(&format!("{other}/file.txt")).exists();
+        let batch = run(&preserve);
+        let preserved = Path::new(&preserve).exists();
+        let holding_file_kept = Path::new(&format!("{holding}/file.txt")).exists();
+        std::fs::remove_dir_all(&holding).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+
+        assert_eq!(refused.failures, 1);
+        assert_eq!(refused.targets, 0, "No target should have been touched");
+        assert!(nothing_deleted);
+        assert_eq!(batch.failures, 0);
+        assert!(preserved);
+        assert!(!holding_file_kept);
+        assert!(!Path::new(&other).exists());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn carry_on_past_a_filtered_symlink_root() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        let link = format!("{dir}-link");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(format!("{dir}/file.txt"), "x").unwrap();
+        std::os::unix::fs::symlink(std::fs::canonicalize(&dir).unwrap(), &link).unwrap();
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--include",
+            "*.txt",
+            &link,
+            &dir,
+        ]));
+        let link_kept = Path::new(&link).is_symlink();
+        let file_kept = Path::new(&format!("{dir}/file.txt")).exists();
+        std::fs::remove_file(&link).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(batch.failures, 1);
+        assert!(link_kept);
+        assert!(!file_kept, "The target after the link was not processed");
+    }
+
+    #[test]
+    fn fail_when_polling_the_watched_directory_fails() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+        let removal = {
+            let dir = dir.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(300));
+                std::fs::remove_dir(dir).unwrap();
+            })
+        };
+
+        let batch = run_all(&Cli::parse_from(["rm-dir", "--force", "--watch", &dir]));
+        removal.join().unwrap();
+
+        assert_eq!(batch.failures, 1, "A failed poll should fail the target");
+    }
+
+    #[test]
+    fn reject_an_invalid_regex_as_a_usage_error() {
+        for flag in ["--regex", "--ignore-errors-matching", "--yes-if-matches"] {
+            let parsed = Cli::try_parse_from(["rm-dir", "--force", flag, "(", "./data"]);
+
+            let kind = parsed.err().map(|error| error.kind());
+            assert_eq!(
+                kind,
+                Some(clap::error::ErrorKind::ValueValidation),
+                "{flag}"
+            );
+        }
+    }
+
+    #[test]
+    fn only_delete_empty_targets_when_refusing_non_empty() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let empty = format!("./data/batch-dir-{unique}-empty");
+        let populated = format!("./data/batch-dir-{unique}-populated");
+        std::fs::create_dir_all(&empty).unwrap();
+        std::fs::create_dir_all(format!("{populated}/nested")).unwrap();
+
+        let run = |dir: &str| {
+            run_all(&Cli::parse_from([
+                "rm-dir",
+                "--force",
+                "--refuse-non-empty",
+                dir,
+            ]))
+        };
+        let emptied = run(&empty);
+        let refused = run(&populated);
+        let populated_kept = Path::new(&populated).join("nested").exists();
+        std::fs::remove_dir_all(&populated).unwrap();
+
+        assert_eq!(emptied.failures, 0);
+        assert!(!Path::new(&empty).exists());
+        assert_eq!(refused.failures, 1);
+        assert!(
+            populated_kept,
+            "Nothing should be deleted from a populated target"
+        );
+    }
+
+    #[test]
+    fn process_duplicate_targets_once_when_deduping() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/batch-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        let same_dir = format!("{dir}/");
+
+        let batch = run_all(&Cli::parse_from([
+            "rm-dir",
+            "--force",
+            "--quiet",
+            "--case-preserving-dedupe",
+            &dir,
+            &same_dir,
+        ]));
+
+        assert!(!Path::new(&dir).exists());
+        assert_eq!(batch.targets, 1);
         assert_eq!(
-            output, "",
-            "Output should be empty since prompting gets skipped when force is true"
+            batch.failures, 0,
+            "The duplicate should not fail as missing"
         );
     }
+}
+
+#[cfg(test)]
+mod is_empty_target_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
 
     #[test]
-    fn return_n_when_given_n() {
-        let mut input = "n\n".as_bytes();
-        let mut output = Vec::new();
+    fn be_true_only_without_any_entries() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/empty-target-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
 
-        let dir = PathBuf::from("./test-dir");
-        let confirmation = get_user_confirmation(&dir, false, &mut input, &mut output);
-        assert_eq!(confirmation, "n");
+        let empty = is_empty_target(Path::new(&dir));
+        std::fs::create_dir(format!("{dir}/nested")).unwrap();
+        let with_folder = is_empty_target(Path::new(&dir));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(empty);
+        assert!(!with_folder, "An empty subfolder still counts");
     }
 }
 
 #[cfg(test)]
-mod handle_confirmation_should {
+mod is_old_enough_should {
     use std::sync::atomic::AtomicU8;
 
     use super::*;
 
     static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
 
+    /// Creates a target holding `old.txt`, last modified two hours ago.
+    fn create_target() -> String {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/old-enough-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/old.txt"), "old").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(format!("{dir}/old.txt"))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+            .unwrap();
+        dir
+    }
+
+    fn check(dir: &str, args: &[&str]) -> bool {
+        let opts = Cli::parse_from(["rm-dir"].iter().chain(args).chain([&dir]));
+        is_old_enough(
+            Path::new(dir),
+            &remove_options(&opts, Path::new(dir)).unwrap(),
+            Duration::from_secs(60 * 60),
+        )
+    }
+
+    #[test]
+    fn pass_a_tree_of_only_old_files() {
+        let dir = create_target();
+
+        let old_enough = check(&dir, &[]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(old_enough);
+    }
+
+    #[test]
+    fn fail_on_a_recent_file_unless_the_filter_keeps_it() {
+        let dir = create_target();
+        std::fs::write(format!("{dir}/nested/new.txt"), "new").unwrap();
+
+        let old_enough = check(&dir, &[]);
+        let filtered = check(&dir, &["--older-than", "1h"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!old_enough);
+        assert!(filtered, "A file --older-than keeps is not deleted anyway");
+    }
+}
+
+#[cfg(test)]
+mod handle_confirmation_should {
+    use std::{path::PathBuf, sync::atomic::AtomicU8};
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
     #[test]
     fn remove_dir_when_confirmation() {
         let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
@@ -174,7 +5322,7 @@ mod handle_confirmation_should {
             );
         }
 
-        let result = handle_confirmation("y", &canonicalized);
+        let result = handle_confirmation("y", &canonicalized, &RemoveOptions::default());
         assert!(result.is_ok(), "Error when removing dir");
         assert!(std::fs::canonicalize(&dir).is_err(), "Dir was not removed");
     }
@@ -187,7 +5335,7 @@ mod handle_confirmation_should {
         std::fs::create_dir_all(&dir).unwrap();
         let canonicalized = std::fs::canonicalize(&dir).unwrap();
 
-        let result = handle_confirmation("n", &canonicalized);
+        let result = handle_confirmation("n", &canonicalized, &RemoveOptions::default());
         assert!(result.is_ok(), "Error when removing dir");
         assert!(
             std::fs::canonicalize(&dir).is_ok(),
@@ -197,13 +5345,57 @@ mod handle_confirmation_should {
         std::fs::remove_dir(&dir).unwrap();
     }
 
+    #[test]
+    fn write_summary_json_next_to_the_text_output() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/test-dir-{unique}");
+        let summary = PathBuf::from(format!("{dir}-summary.json"));
+
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "12345").unwrap();
+        let canonicalized = std::fs::canonicalize(&dir).unwrap();
+
+        let options = RemoveOptions {
+            summary_json_to: Some(summary.clone()),
+            ..Default::default()
+        };
+        let result = handle_confirmation("y", &canonicalized, &options);
+        let json = std::fs::read_to_string(&summary).unwrap();
+        std::fs::remove_file(&summary).unwrap();
+
+        assert!(result.is_ok(), "Error when removing dir");
+        assert!(!Path::new(&dir).exists(), "Dir was not removed");
+        assert!(
+            json.contains(r#""dry_run":false,"files":1,"dirs":1,"bytes":5,"#),
+            "Unexpected summary {json}"
+        );
+    }
+
+    #[test]
+    fn not_fail_when_summary_json_cannot_be_written() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/test-dir-{unique}");
+
+        std::fs::create_dir_all(&dir).unwrap();
+        let canonicalized = std::fs::canonicalize(&dir).unwrap();
+
+        let options = RemoveOptions {
+            summary_json_to: Some(PathBuf::from(format!("{dir}-missing/summary.json"))),
+            ..Default::default()
+        };
+        let result = handle_confirmation("y", &canonicalized, &options);
+
+        assert!(result.is_ok(), "Unwritable summary failed the delete");
+        assert!(!Path::new(&dir).exists(), "Dir was not removed");
+    }
+
     #[test]
     fn error_when_dir_does_not_exist() {
         let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         let dir = format!("./data/test-dir-{unique}");
 
         assert!(
-            handle_confirmation("y", &PathBuf::from(&dir)).is_err(),
+            handle_confirmation("y", &PathBuf::from(&dir), &RemoveOptions::default()).is_err(),
             "Deleted a folder that does not exist???"
         );
     }
@@ -0,0 +1,330 @@
+//! `--delete-manifest`: deletes exactly the paths a `--snapshot` file lists, without
+//! scanning the target for anything else.
+//!
+//! Entries go deepest first, so files leave a folder before the folder itself does, and
+//! folders are only removed once empty: anything below them that the manifest does not
+//! list stays, and keeps its folder with it.
+
+use std::{
+    io::{ErrorKind, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    error::RemoveError,
+    json::{self, JsonValue},
+    remove::RemoveStats,
+};
+
+/// The type a manifest entry was recorded with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum EntryKind {
+    File,
+    Dir,
+    Symlink,
+    Other,
+}
+
+impl EntryKind {
+    fn from_name(name: &str) -> Option<EntryKind> {
+        match name {
+            "file" => Some(EntryKind::File),
+            "dir" => Some(EntryKind::Dir),
+            "symlink" => Some(EntryKind::Symlink),
+            "other" => Some(EntryKind::Other),
+            _ => None,
+        }
+    }
+
+    fn of(file_type: std::fs::FileType) -> EntryKind {
+        if file_type.is_symlink() {
+            EntryKind::Symlink
+        } else if file_type.is_dir() {
+            EntryKind::Dir
+        } else if file_type.is_file() {
+            EntryKind::File
+        } else {
+            EntryKind::Other
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Dir => "folder",
+            EntryKind::Symlink => "symlink",
+            EntryKind::Other => "special file",
+        }
+    }
+}
+
+/// A path listed in the manifest, relative to the target.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ManifestEntry {
+    pub(crate) path: PathBuf,
+    pub(crate) kind: EntryKind,
+}
+
+/// What deleting the listed entries did.
+#[derive(Debug, Default)]
+pub(crate) struct ManifestOutcome {
+    pub(crate) stats: RemoveStats,
+    /// Listed entries that were already gone, relative to the target.
+    pub(crate) missing: Vec<PathBuf>,
+}
+
+/// Reads the entries of a manifest written by `--snapshot`. Errors with `InvalidData`
+/// when it is not a JSON array of entries with a `path` and a known `type`, or when a
+/// path is absolute or climbs out of the target with `..`.
+pub(crate) fn read_manifest(path: &Path) -> Result<Vec<ManifestEntry>, std::io::Error> {
+    let contents = std::fs::read_to_string(path)?;
+    parse_manifest(&contents).map_err(|reason| {
+        std::io::Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "{} is not a valid manifest: {reason}",
+                path.to_string_lossy()
+            ),
+        )
+    })
+}
+
+fn parse_manifest(contents: &str) -> Result<Vec<ManifestEntry>, String> {
+    json::parse_objects(contents)?
+        .into_iter()
+        .enumerate()
+        .map(|(i, fields)| parse_entry(&fields).map_err(|e| format!("entry {}: {e}", i + 1)))
+        .collect()
+}
+
+fn parse_entry(fields: &[(String, JsonValue)]) -> Result<ManifestEntry, String> {
+    let field = |name: &str| match fields.iter().find(|(key, _)| key == name) {
+        Some((_, JsonValue::String(value))) => Ok(value.as_str()),
+        Some(_) => Err(format!("'{name}' is not a string")),
+        None => Err(format!("no '{name}'")),
+    };
+
+    let path = PathBuf::from(field("path")?);
+    let kind = field("type")?;
+    let kind = EntryKind::from_name(kind).ok_or_else(|| format!("unknown type '{kind}'"))?;
+
+    let inside_target = path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if path.as_os_str().is_empty() || !inside_target {
+        return Err(format!(
+            "'{}' is not a path inside the target",
+            path.to_string_lossy()
+        ));
+    }
+
+    Ok(ManifestEntry { path, kind })
+}
+
+/// Deletes the `entries` below `root`, deepest first, or on `dry_run` writes each one
+/// that would go to `output`. Entries that no longer exist are skipped and returned as
+/// missing. Stops at the first entry that cannot be deleted, including one that is now
+/// of another type than recorded, or a folder still holding entries not listed.
+pub(crate) fn delete_listed(
+    root: &Path,
+    entries: &[ManifestEntry],
+    dry_run: bool,
+    output: &mut impl Write,
+) -> Result<ManifestOutcome, RemoveError> {
+    let mut ordered: Vec<&ManifestEntry> = entries.iter().collect();
+    ordered.sort_by(|a, b| {
+        let depth = |entry: &ManifestEntry| entry.path.components().count();
+        depth(b).cmp(&depth(a)).then_with(|| a.path.cmp(&b.path))
+    });
+    ordered.dedup_by(|a, b| a.path == b.path);
+
+    let mut outcome = ManifestOutcome::default();
+    for entry in ordered {
+        let path = root.join(&entry.path);
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                outcome.missing.push(entry.path.clone());
+                continue;
+            }
+            Err(source) => return Err(RemoveError::Delete { path, source }),
+        };
+
+        let found = EntryKind::of(metadata.file_type());
+        if found != entry.kind {
+            return Err(RemoveError::Delete {
+                path,
+                source: std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "the manifest lists a {}, found a {}",
+                        entry.kind.name(),
+                        found.name()
+                    ),
+                ),
+            });
+        }
+
+        if dry_run {
+            writeln!(output, "Would remove {}", path.to_string_lossy())?;
+        } else {
+            let removed = if found == EntryKind::Dir {
+                std::fs::remove_dir(&path)
+            } else {
+                remove_link_or_file(&path)
+            };
+            removed.map_err(|source| RemoveError::Delete {
+                path: path.clone(),
+                source,
+            })?;
+        }
+
+        if found == EntryKind::Dir {
+            outcome.stats.dirs += 1;
+        } else {
+            outcome.stats.files += 1;
+            outcome.stats.bytes += metadata.len();
+        }
+    }
+
+    Ok(outcome)
+}
+
+/// Removes a file or a symlink. A symlink to a folder on Windows has to go as a folder.
+fn remove_link_or_file(path: &Path) -> Result<(), std::io::Error> {
+    match std::fs::remove_file(path) {
+        #[cfg(windows)]
+        Err(_) if path.is_dir() => std::fs::remove_dir(path),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod read_manifest_should {
+    use super::*;
+
+    #[test]
+    fn reject_corrupt_manifests() {
+        let error = |contents: &str| parse_manifest(contents).unwrap_err();
+
+        assert_eq!(
+            error(r#"[{"path":"a.txt","type":"file"}"#),
+            "after entry 1: expected ',', found the end"
+        );
+        assert_eq!(
+            error(r#"[{"path":"a.txt","type":"pipe"}]"#),
+            "entry 1: unknown type 'pipe'"
+        );
+        assert_eq!(error(r#"[{"type":"file"}]"#), "entry 1: no 'path'");
+        assert_eq!(
+            error(r#"[{"path":"../outside","type":"file"}]"#),
+            "entry 1: '../outside' is not a path inside the target"
+        );
+        assert_eq!(
+            error(r#"[{"path":"/etc/passwd","type":"file"}]"#),
+            "entry 1: '/etc/passwd' is not a path inside the target"
+        );
+    }
+}
+
+#[cfg(test)]
+mod delete_listed_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Creates a target with `nested/a.txt` and `nested/b.txt`, 1 and 2 bytes long.
+    fn create_target() -> PathBuf {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/manifest-dir-{unique}"));
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/a.txt"), "x").unwrap();
+        std::fs::write(dir.join("nested/b.txt"), "xx").unwrap();
+        dir
+    }
+
+    #[test]
+    fn delete_what_a_snapshot_recorded_and_nothing_else() {
+        let dir = create_target();
+        let manifest = dir.with_extension("json");
+        crate::snapshot::write_snapshot(&dir, &manifest).unwrap();
+        std::fs::write(dir.join("created-later.txt"), "x").unwrap();
+
+        let entries = read_manifest(&manifest).unwrap();
+        let outcome = delete_listed(&dir, &entries, false, &mut Vec::new()).unwrap();
+        let left: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&manifest).unwrap();
+
+        assert_eq!(left, ["created-later.txt"]);
+        assert_eq!(
+            (outcome.stats.files, outcome.stats.dirs, outcome.stats.bytes),
+            (2, 1, 3)
+        );
+        assert!(outcome.missing.is_empty());
+    }
+
+    #[test]
+    fn keep_a_folder_holding_unlisted_entries() {
+        let dir = create_target();
+        let entries = parse_manifest(
+            r#"[{"path":"nested","type":"dir"},{"path":"nested/a.txt","type":"file"}]"#,
+        )
+        .unwrap();
+
+        let result = delete_listed(&dir, &entries, false, &mut Vec::new());
+        let a_removed = !dir.join("nested/a.txt").exists();
+        let b_kept = dir.join("nested/b.txt").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(
+            matches!(result, Err(RemoveError::Delete { path, .. }) if path.ends_with("nested"))
+        );
+        assert!(a_removed);
+        assert!(b_kept);
+    }
+
+    #[test]
+    fn only_list_entries_on_a_dry_run() {
+        let dir = create_target();
+        let entries = parse_manifest(
+            r#"[{"path":"nested/a.txt","type":"file"},{"path":"gone.txt","type":"file"}]"#,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+
+        let outcome = delete_listed(&dir, &entries, true, &mut output).unwrap();
+        let kept = dir.join("nested/a.txt").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(kept);
+        assert_eq!(outcome.missing, [PathBuf::from("gone.txt")]);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("Would remove "), "{output}");
+        assert!(output.trim_end().ends_with("a.txt"), "{output}");
+    }
+
+    #[test]
+    fn refuse_an_entry_of_another_type() {
+        let dir = create_target();
+        let entries = parse_manifest(r#"[{"path":"nested","type":"file"}]"#).unwrap();
+
+        let result = delete_listed(&dir, &entries, false, &mut Vec::new());
+        let kept = dir.join("nested/a.txt").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            result.map(|_| ()).unwrap_err().to_string(),
+            format!(
+                "Failed to delete {}: the manifest lists a file, found a folder",
+                dir.join("nested").to_string_lossy()
+            )
+        );
+        assert!(kept);
+    }
+}
@@ -0,0 +1,75 @@
+//! The `--require-marker` guardrail: only delete inside directories that look like a
+//! project root.
+
+use std::path::{Path, PathBuf};
+
+/// Looks for `name` in `dir` and then its ancestors, returning the first folder holding
+/// it. When `dir` is below `boundary` the search stops short of it, so a marker in the
+/// home directory, like a dotfiles `.git`, does not bless everything below it.
+pub(crate) fn find_marker(dir: &Path, name: &str, boundary: Option<&Path>) -> Option<PathBuf> {
+    let boundary = boundary.filter(|boundary| dir.starts_with(boundary) && dir != *boundary);
+
+    for ancestor in dir.ancestors() {
+        if boundary.is_some_and(|boundary| ancestor == boundary) {
+            break;
+        }
+
+        if std::fs::symlink_metadata(ancestor.join(name)).is_ok() {
+            return Some(ancestor.to_path_buf());
+        }
+    }
+
+    None
+}
+
+/// The home directory, which marker searches do not reach.
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .and_then(|home| std::fs::canonicalize(home).ok())
+}
+
+#[cfg(test)]
+mod find_marker_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn create_project() -> PathBuf {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/marker-dir-{unique}");
+
+        std::fs::create_dir_all(format!("{dir}/project/target/debug")).unwrap();
+        std::fs::canonicalize(&dir).unwrap()
+    }
+
+    #[test]
+    fn find_the_marker_in_an_ancestor() {
+        let dir = create_project();
+        std::fs::File::create(dir.join("project/Cargo.toml")).unwrap();
+
+        let found = find_marker(&dir.join("project/target/debug"), "Cargo.toml", Some(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(found, Some(dir.join("project")));
+    }
+
+    #[test]
+    fn not_search_past_the_boundary() {
+        let dir = create_project();
+        let target = dir.join("project/target");
+
+        let unmarked = find_marker(&target, "rm-dir-test-marker", None);
+        std::fs::File::create(dir.join("rm-dir-test-marker")).unwrap();
+        let above_boundary = find_marker(&target, "rm-dir-test-marker", Some(&dir));
+        let unrelated_boundary =
+            find_marker(&target, "rm-dir-test-marker", Some(Path::new("/elsewhere")));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(unmarked, None);
+        assert_eq!(above_boundary, None);
+        assert_eq!(unrelated_boundary, Some(dir));
+    }
+}
@@ -0,0 +1,159 @@
+//! `--lang`: the confirmation prompt, the abort message and the summary lines in the
+//! user's language.
+//!
+//! Every message is a method on `Lang` matching on the language, so adding one means
+//! adding a variant, its codes in `from_code` and an arm per message. Answers stay `y`
+//! and `n` in every language, and messages not listed here are English only.
+
+/// A language the messages are available in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) enum Lang {
+    #[default]
+    English,
+    Spanish,
+    German,
+}
+
+impl Lang {
+    /// Picks the language for a code like `de`, `es-MX` or a locale like `es_ES.UTF-8`,
+    /// falling back to English for anything unknown.
+    pub(crate) fn from_code(code: &str) -> Lang {
+        let language = code
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match language.as_str() {
+            "es" => Lang::Spanish,
+            "de" => Lang::German,
+            _ => Lang::English,
+        }
+    }
+
+    /// The language of the environment, from `LC_ALL`, `LC_MESSAGES` or `LANG`, the first
+    /// one set winning as they do for every other program.
+    pub(crate) fn from_env() -> Lang {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|name| std::env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .map_or(Lang::English, |value| Lang::from_code(&value))
+    }
+
+    /// Asks whether to delete everything in `dir`, with `hint` about its scope, if any.
+    pub(crate) fn confirm_prompt(self, dir: &str, hint: Option<&str>) -> String {
+        let hint = hint.map(|hint| format!(" {hint}")).unwrap_or_default();
+        match self {
+            Lang::English => {
+                format!(
+                    "Are you sure you want to delete all files and folders in {dir}{hint}? (y/n) "
+                )
+            }
+            Lang::Spanish => {
+                format!("¿Seguro que quieres borrar todos los archivos y carpetas de {dir}{hint}? (y/n) ")
+            }
+            Lang::German => {
+                format!("Sollen wirklich alle Dateien und Ordner in {dir}{hint} gelöscht werden? (y/n) ")
+            }
+        }
+    }
+
+    /// The two lines announcing a forced delete of everything in `dir`.
+    pub(crate) fn forced(self, dir: &str) -> String {
+        match self {
+            Lang::English => {
+                format!("Running delete without confirmation.\nDeleting all files and folders in {dir}.")
+            }
+            Lang::Spanish => {
+                format!(
+                    "Borrando sin confirmación.\nBorrando todos los archivos y carpetas de {dir}."
+                )
+            }
+            Lang::German => {
+                format!("Löschen ohne Bestätigung.\nLösche alle Dateien und Ordner in {dir}.")
+            }
+        }
+    }
+
+    pub(crate) fn aborting(self, answer: &str) -> String {
+        match self {
+            Lang::English => format!("Aborting as user input '{answer}' was not 'y'"),
+            Lang::Spanish => format!("Cancelado, la respuesta '{answer}' no fue 'y'"),
+            Lang::German => format!("Abgebrochen, da die Eingabe '{answer}' nicht 'y' war"),
+        }
+    }
+
+    pub(crate) fn removed_all(self, dir: &str) -> String {
+        match self {
+            Lang::English => format!("Removed all files and folders from {dir}"),
+            Lang::Spanish => format!("Se borraron todos los archivos y carpetas de {dir}"),
+            Lang::German => format!("Alle Dateien und Ordner in {dir} wurden gelöscht"),
+        }
+    }
+
+    /// Like `removed_all`, with what went, e.g. `(3 files, 1.2 KiB, 1 folders)`.
+    pub(crate) fn removed_all_counted(
+        self,
+        dir: &str,
+        files: u64,
+        size: &str,
+        dirs: u64,
+    ) -> String {
+        match self {
+            Lang::English => format!(
+                "{} ({files} files, {size}, {dirs} folders)",
+                self.removed_all(dir)
+            ),
+            Lang::Spanish => format!(
+                "{} ({files} archivos, {size}, {dirs} carpetas)",
+                self.removed_all(dir)
+            ),
+            Lang::German => format!(
+                "{} ({files} Dateien, {size}, {dirs} Ordner)",
+                self.removed_all(dir)
+            ),
+        }
+    }
+
+    pub(crate) fn dry_run_summary(self, dir: &str, files: u64, size: &str, dirs: u64) -> String {
+        match self {
+            Lang::English => format!(
+                "Dry run, nothing was deleted. Would remove {files} files ({size}) and {dirs} folders from {dir}"
+            ),
+            Lang::Spanish => format!(
+                "Simulación, no se borró nada. Se borrarían {files} archivos ({size}) y {dirs} carpetas de {dir}"
+            ),
+            Lang::German => format!(
+                "Probelauf, nichts wurde gelöscht. {files} Dateien ({size}) und {dirs} Ordner in {dir} würden gelöscht"
+            ),
+        }
+    }
+
+    pub(crate) fn done_in(self, seconds: f32) -> String {
+        match self {
+            Lang::English => format!("Done in {seconds}s"),
+            Lang::Spanish => format!("Terminado en {seconds}s"),
+            Lang::German => format!("Fertig in {seconds}s"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod from_code_should {
+    use super::*;
+
+    #[test]
+    fn read_the_language_out_of_codes_and_locales() {
+        assert_eq!(Lang::from_code("es"), Lang::Spanish);
+        assert_eq!(Lang::from_code("es_ES.UTF-8"), Lang::Spanish);
+        assert_eq!(Lang::from_code("DE-at"), Lang::German);
+        assert_eq!(Lang::from_code("en_US.UTF-8"), Lang::English);
+    }
+
+    #[test]
+    fn fall_back_to_english() {
+        assert_eq!(Lang::from_code("fr_FR"), Lang::English);
+        assert_eq!(Lang::from_code("C"), Lang::English);
+        assert_eq!(Lang::from_code(""), Lang::English);
+    }
+}
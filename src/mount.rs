@@ -0,0 +1,235 @@
+//! Detection of mount points nested inside the target, so a mounted drive that
+//! happens to live below it is never wiped.
+//!
+//! Linux reads the mount table from `/proc/self/mounts`, which also catches bind
+//! mounts of the same filesystem. Everywhere else on Unix a folder whose device
+//! differs from its parent's is treated as a mount point.
+//!
+//! Whether the target itself sits on a read-only filesystem is also read from the mount
+//! table on Linux. Elsewhere, or when the table has no answer, a probe file is written
+//! into the target and a read-only error from that is what counts.
+
+use std::{
+    collections::HashSet,
+    fs::Metadata,
+    path::{Path, PathBuf},
+};
+
+/// Every mount point strictly below `root`, as canonical paths.
+#[cfg(target_os = "linux")]
+pub(crate) fn mount_points_below(root: &Path) -> HashSet<PathBuf> {
+    match std::fs::read_to_string("/proc/self/mounts") {
+        Ok(table) => parse_mount_table(&table)
+            .into_iter()
+            .filter(|mount| mount != root && mount.starts_with(root))
+            .collect(),
+        Err(e) => {
+            println!("Failed to read /proc/self/mounts, only checking devices. Error: {e}");
+            HashSet::new()
+        }
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+pub(crate) fn mount_points_below(_root: &Path) -> HashSet<PathBuf> {
+    HashSet::new()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn mount_points_below(_root: &Path) -> HashSet<PathBuf> {
+    println!("Mount point detection is not supported on this platform, nothing will be skipped.");
+    HashSet::new()
+}
+
+/// True when a folder lives on another device than the folder holding it.
+#[cfg(unix)]
+pub(crate) fn crosses_device(parent: &Metadata, dir: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    parent.dev() != dir.dev()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn crosses_device(_parent: &Metadata, _dir: &Metadata) -> bool {
+    false
+}
+
+/// The device a file or folder lives on, the same for everything on one filesystem.
+#[cfg(unix)]
+pub(crate) fn device_id(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.dev()
+}
+
+/// Devices are not told apart here, everything counts as one.
+#[cfg(not(unix))]
+pub(crate) fn device_id(_metadata: &Metadata) -> u64 {
+    0
+}
+
+/// Whether `device` is spinning media, from the block device's `queue/rotational` in
+/// sysfs, or for a partition its disk's. None when that cannot be told, e.g. for network
+/// or virtual filesystems.
+#[cfg(target_os = "linux")]
+pub(crate) fn is_rotational(device: u64) -> Option<bool> {
+    // The glibc encoding of `dev_t`, 12 bits of major and 20 of minor.
+    let major = ((device >> 8) & 0xfff) | ((device >> 32) & !0xfff);
+    let minor = (device & 0xff) | ((device >> 12) & !0xff);
+    let block = PathBuf::from(format!("/sys/dev/block/{major}:{minor}"));
+
+    [
+        block.join("queue/rotational"),
+        block.join("../queue/rotational"),
+    ]
+    .iter()
+    .find_map(|path| std::fs::read_to_string(path).ok())
+    .map(|flag| flag.trim() == "1")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn is_rotational(_device: u64) -> Option<bool> {
+    None
+}
+
+/// True when `dir` is on a filesystem mounted read-only, so nothing in it can be deleted.
+#[cfg(target_os = "linux")]
+pub(crate) fn is_read_only(dir: &Path) -> bool {
+    std::fs::read_to_string("/proc/self/mounts")
+        .ok()
+        .and_then(|table| read_only_in_table(&table, dir))
+        .unwrap_or_else(|| fails_write_probe(dir))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn is_read_only(dir: &Path) -> bool {
+    fails_write_probe(dir)
+}
+
+/// Whether the mount holding `dir`, the deepest matching mount point with later lines
+/// stacked over earlier ones, has the `ro` option. None when no line matches.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn read_only_in_table(table: &str, dir: &Path) -> Option<bool> {
+    let mut holding: Option<(PathBuf, bool)> = None;
+
+    for line in table.lines() {
+        let mut columns = line.split_whitespace();
+        let (Some(mount), Some(options)) = (columns.nth(1), columns.nth(1)) else {
+            continue;
+        };
+        let mount = PathBuf::from(unescape_octal(mount));
+        if !dir.starts_with(&mount) {
+            continue;
+        }
+
+        let deeper = holding
+            .as_ref()
+            .is_none_or(|(held, _)| mount.components().count() >= held.components().count());
+        if deeper {
+            let read_only = options.split(',').any(|option| option == "ro");
+            holding = Some((mount, read_only));
+        }
+    }
+
+    holding.map(|(_, read_only)| read_only)
+}
+
+/// Creates and removes a file in `dir`. Only a read-only filesystem error counts, a
+/// missing permission is left for the delete itself to report.
+fn fails_write_probe(dir: &Path) -> bool {
+    let probe = dir.join(format!(".rm-dir-write-probe-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            false
+        }
+        Err(e) => e.kind() == std::io::ErrorKind::ReadOnlyFilesystem,
+    }
+}
+
+/// Extracts the mount point column. Whitespace in it is octal escaped, e.g. `\040`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_mount_table(table: &str) -> Vec<PathBuf> {
+    table
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(|mount| PathBuf::from(unescape_octal(mount)))
+        .collect()
+}
+
+fn unescape_octal(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(index) = rest.find('\\') {
+        result.push_str(&rest[..index]);
+        let escaped = rest.get(index + 1..index + 4);
+        match escaped.and_then(|digits| u8::from_str_radix(digits, 8).ok()) {
+            Some(byte) => {
+                result.push(byte as char);
+                rest = &rest[index + 4..];
+            }
+            None => {
+                result.push('\\');
+                rest = &rest[index + 1..];
+            }
+        }
+    }
+
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod parse_mount_table_should {
+    use super::*;
+
+    #[test]
+    fn read_mount_points_with_escaped_whitespace() {
+        let table = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+tmpfs /mnt/my\\040drive tmpfs rw 0 0
+proc /proc proc rw 0 0
+";
+
+        assert_eq!(
+            parse_mount_table(table),
+            vec![
+                PathBuf::from("/"),
+                PathBuf::from("/mnt/my drive"),
+                PathBuf::from("/proc")
+            ]
+        );
+    }
+
+    #[test]
+    fn tell_whether_the_holding_mount_is_read_only() {
+        let table = "\
+/dev/sda1 / ext4 rw,relatime 0 0
+/dev/sr0 /media/cd iso9660 ro,nosuid 0 0
+tmpfs /media/cd/rw\\040overlay tmpfs rw 0 0
+/dev/sdb1 /srv ext4 rw 0 0
+/dev/sdb1 /srv ext4 ro,remount 0 0
+";
+
+        let read_only = |dir: &str| read_only_in_table(table, Path::new(dir));
+        assert_eq!(read_only("/home/user/build"), Some(false));
+        assert_eq!(read_only("/media/cd/build"), Some(true));
+        assert_eq!(read_only("/media/cd/rw overlay/build"), Some(false));
+        assert_eq!(read_only("/media/cdrom"), Some(false));
+        assert_eq!(read_only("/srv/build"), Some(true));
+        assert_eq!(read_only_in_table("", Path::new("/srv")), None);
+    }
+
+    #[test]
+    fn not_flag_a_writable_folder() {
+        std::fs::create_dir_all("./data").unwrap();
+        assert!(!fails_write_probe(Path::new("./data")));
+    }
+
+    #[test]
+    fn keep_lone_backslashes() {
+        assert_eq!(unescape_octal("a\\b"), "a\\b");
+        assert_eq!(unescape_octal("tab\\011end"), "tab\tend");
+    }
+}
@@ -0,0 +1,140 @@
+//! `--notify`: tells a command or a webhook that a run finished, successful or not.
+//!
+//! A command is parsed like an `--exec` template, gets the one line summary for `{}` and
+//! the JSON summary on stdin, e.g. `notify-send rm-dir {}`. An `http://` URL gets the
+//! JSON summary POSTed to it, which needs the `webhook` feature; `https://` is left to a
+//! command like `curl`, as TLS would need more than std.
+
+use std::ffi::OsStr;
+
+use crate::exec::{self, ExecTemplate};
+
+/// Where to send the summary of a finished run.
+#[derive(Clone, Debug)]
+pub(crate) enum Notifier {
+    Command(ExecTemplate),
+    Webhook(String),
+}
+
+/// Parses a `--notify` value: a URL when it starts with `http://` or `https://`, a
+/// command otherwise.
+pub(crate) fn parse_notifier(value: &str) -> Result<Notifier, String> {
+    if value.starts_with("http://") || value.starts_with("https://") {
+        return Ok(Notifier::Webhook(value.to_string()));
+    }
+
+    exec::parse_command(value).map(Notifier::Command)
+}
+
+impl Notifier {
+    /// Sends `summary`, a line of text, and `json`, the same as a JSON object.
+    pub(crate) fn send(&self, summary: &str, json: &str) -> Result<(), std::io::Error> {
+        match self {
+            Notifier::Command(command) => {
+                command.run_with(OsStr::new(summary), Some(json.as_bytes()))
+            }
+            Notifier::Webhook(url) => post(url, json),
+        }
+    }
+}
+
+/// POSTs `body` as JSON to a plain `http://` URL, failing unless the answer is a 2xx.
+#[cfg(feature = "webhook")]
+fn post(url: &str, body: &str) -> Result<(), std::io::Error> {
+    use std::{
+        io::{BufRead, BufReader, Write},
+        net::TcpStream,
+        time::Duration,
+    };
+
+    let invalid = |reason: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, reason);
+    let rest = url.strip_prefix("http://").ok_or_else(|| {
+        invalid("only http:// webhooks can be POSTed to, use a command like curl for https://")
+    })?;
+    let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let path = if path.is_empty() { "/" } else { path };
+    let address = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\nHost: {authority}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    match status_line.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(std::io::Error::other(format!(
+            "the webhook answered {}",
+            status_line.trim_end()
+        ))),
+    }
+}
+
+#[cfg(not(feature = "webhook"))]
+fn post(_url: &str, _body: &str) -> Result<(), std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "built without the webhook feature, rebuild with --features webhook or use a command like curl",
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod send_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn pass_the_summary_as_argument_and_the_json_on_stdin() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let out = format!("./data/notify-{unique}.txt");
+        let notifier = parse_notifier(&format!(
+            r#"sh -c 'printf "%s\n" "$1" > {out}; cat >> {out}' sh {{}}"#
+        ))
+        .unwrap();
+
+        let sent = notifier.send("Removed 2 files", r#"{"files":2}"#);
+        let received = std::fs::read_to_string(&out).unwrap();
+        std::fs::remove_file(&out).unwrap();
+
+        assert!(sent.is_ok(), "{sent:?}");
+        assert_eq!(received, "Removed 2 files\n{\"files\":2}");
+    }
+
+    #[test]
+    fn report_a_failing_command() {
+        let notifier = parse_notifier("false").unwrap();
+
+        let sent = notifier.send("Removed 2 files", "{}");
+
+        assert_eq!(sent.unwrap_err().to_string(), "false exited with code 1");
+    }
+}
+
+#[cfg(test)]
+mod parse_notifier_should {
+    use super::*;
+
+    #[test]
+    fn tell_urls_from_commands() {
+        assert!(matches!(
+            parse_notifier("http://localhost:8080/hook"),
+            Ok(Notifier::Webhook(url)) if url == "http://localhost:8080/hook"
+        ));
+        assert!(matches!(
+            parse_notifier("notify-send rm-dir"),
+            Ok(Notifier::Command(_))
+        ));
+        assert!(parse_notifier("  ").is_err());
+    }
+}
@@ -0,0 +1,68 @@
+//! `--dry-run-compare`: how the paths a dry run would delete changed since a listing
+//! saved earlier with `--dry-run-to`, e.g. after tuning a filter.
+
+use std::collections::BTreeSet;
+
+/// The paths two listings of a dry run differ in.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct PlanDiff {
+    /// Paths only the new listing holds, so the change brought them in.
+    pub(crate) added: Vec<String>,
+    /// Paths only the old listing holds, so the change spared them.
+    pub(crate) removed: Vec<String>,
+    pub(crate) unchanged: usize,
+}
+
+/// Splits a `--dry-run-to` listing into its paths, one per line, or NUL separated when
+/// it holds a NUL byte, which no path can, as written with `--output-null`.
+pub(crate) fn parse_listing(listing: &[u8]) -> BTreeSet<String> {
+    let separator = if listing.contains(&b'\0') {
+        b'\0'
+    } else {
+        b'\n'
+    };
+    listing
+        .split(|byte| *byte == separator)
+        .filter(|path| !path.is_empty())
+        .map(|path| String::from_utf8_lossy(path).into_owned())
+        .collect()
+}
+
+/// Diffs the `old` listing against the `new` one, each side in path order.
+pub(crate) fn diff(old: &BTreeSet<String>, new: &BTreeSet<String>) -> PlanDiff {
+    PlanDiff {
+        added: new.difference(old).cloned().collect(),
+        removed: old.difference(new).cloned().collect(),
+        unchanged: old.intersection(new).count(),
+    }
+}
+
+#[cfg(test)]
+mod diff_should {
+    use super::*;
+
+    #[test]
+    fn tell_added_removed_and_unchanged_paths_apart() {
+        let old = parse_listing(b"/t/a.log\n/t/b.log\n/t/keep.txt\n");
+        let new = parse_listing(b"/t/a.log\n/t/b.log\n/t/c.tmp\n");
+
+        assert_eq!(
+            diff(&old, &new),
+            PlanDiff {
+                added: vec!["/t/c.tmp".to_string()],
+                removed: vec!["/t/keep.txt".to_string()],
+                unchanged: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn read_nul_separated_listings() {
+        let listing = parse_listing(b"/t/with\nnewline\0/t/b\0");
+
+        assert_eq!(
+            listing.into_iter().collect::<Vec<_>>(),
+            ["/t/b", "/t/with\nnewline"]
+        );
+    }
+}
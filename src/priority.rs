@@ -0,0 +1,128 @@
+//! `--low-priority`: lets a background cleanup yield CPU and disk time to everything
+//! else on the machine.
+//!
+//! On Linux the I/O scheduling class is set to idle through the `ioprio_set` syscall
+//! (`IOPRIO_CLASS_IDLE` for `IOPRIO_WHO_PROCESS` 0, the calling process), which only
+//! schedulers like BFQ honour. On every Unix the niceness is raised to 19 with
+//! `setpriority(PRIO_PROCESS, 0, 19)`. On Windows the process enters background mode
+//! with `SetPriorityClass(PROCESS_MODE_BACKGROUND_BEGIN)`, which lowers both the CPU
+//! and I/O priority of all its threads, the `--jobs` workers included.
+
+/// Lowers the priority of the current process as far as the platform allows. Returns
+/// a description of whatever could not be changed.
+#[cfg(unix)]
+pub(crate) fn lower_priority() -> Result<(), String> {
+    const PRIO_PROCESS: i32 = 0;
+    const LOWEST_NICENESS: i32 = 19;
+
+    extern "C" {
+        fn setpriority(which: i32, who: u32, prio: i32) -> i32;
+    }
+
+    let mut failures = Vec::new();
+
+    if let Err(e) = set_idle_io_class() {
+        failures.push(e);
+    }
+
+    // SAFETY: plain syscall wrapper taking integers only.
+    if unsafe { setpriority(PRIO_PROCESS, 0, LOWEST_NICENESS) } != 0 {
+        failures.push(format!(
+            "setpriority failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join(", "))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_idle_io_class() -> Result<(), String> {
+    use std::ffi::c_long;
+
+    const IOPRIO_WHO_PROCESS: c_long = 1;
+    const IOPRIO_CLASS_IDLE: c_long = 3;
+    const IOPRIO_CLASS_SHIFT: c_long = 13;
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_IOPRIO_SET: c_long = 251;
+    #[cfg(target_arch = "x86")]
+    const SYS_IOPRIO_SET: c_long = 289;
+    #[cfg(any(target_arch = "aarch64", target_arch = "riscv64"))]
+    const SYS_IOPRIO_SET: c_long = 30;
+    #[cfg(target_arch = "arm")]
+    const SYS_IOPRIO_SET: c_long = 314;
+    #[cfg(not(any(
+        target_arch = "x86_64",
+        target_arch = "x86",
+        target_arch = "aarch64",
+        target_arch = "riscv64",
+        target_arch = "arm"
+    )))]
+    return Err("ioprio_set is not wired up for this architecture".to_string());
+
+    #[cfg(any(
+        target_arch = "x86_64",
+        target_arch = "x86",
+        target_arch = "aarch64",
+        target_arch = "riscv64",
+        target_arch = "arm"
+    ))]
+    {
+        extern "C" {
+            fn syscall(number: c_long, ...) -> c_long;
+        }
+
+        // SAFETY: ioprio_set only takes integers and touches no memory of ours.
+        let result = unsafe {
+            syscall(
+                SYS_IOPRIO_SET,
+                IOPRIO_WHO_PROCESS,
+                0 as c_long,
+                IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            )
+        };
+        if result != 0 {
+            return Err(format!(
+                "ioprio_set failed: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn set_idle_io_class() -> Result<(), String> {
+    Err("idle I/O scheduling is only supported on Linux".to_string())
+}
+
+#[cfg(windows)]
+pub(crate) fn lower_priority() -> Result<(), String> {
+    const PROCESS_MODE_BACKGROUND_BEGIN: u32 = 0x0010_0000;
+
+    extern "system" {
+        fn GetCurrentProcess() -> isize;
+        fn SetPriorityClass(process: isize, priority_class: u32) -> i32;
+    }
+
+    // SAFETY: the pseudo handle of the current process is always valid.
+    if unsafe { SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) } == 0 {
+        return Err(format!(
+            "SetPriorityClass failed: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn lower_priority() -> Result<(), String> {
+    Err("not supported on this platform".to_string())
+}
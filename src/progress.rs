@@ -0,0 +1,552 @@
+//! Progress reporting while a walk based removal runs.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::json::{JsonFields, JsonObject};
+
+const BAR_WIDTH: usize = 30;
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Gets told about every entry a removal gets through.
+pub(crate) trait Progress {
+    /// Called once the scan is done, with the number of entries about to be removed.
+    fn start(&mut self, _total: u64) {}
+
+    /// Called before each entry is removed, with its path.
+    fn current(&mut self, _path: &Path) {}
+
+    /// Called after each removed entry.
+    fn advance(&mut self) {}
+
+    /// Called once the removal stopped, whether it succeeded or not.
+    fn finish(&mut self) {}
+
+    /// Called before a line about an entry is printed, so the progress can get out of its
+    /// way.
+    fn suspend(&mut self) {}
+
+    /// Called once that line is out.
+    fn resume(&mut self) {}
+}
+
+/// Reports nothing.
+pub(crate) struct NoProgress;
+
+impl Progress for NoProgress {}
+
+/// A single line bar with percentage, rate and ETA, redrawn in place at most every 100ms.
+pub(crate) struct ProgressBar<W: Write> {
+    output: W,
+    total: u64,
+    done: u64,
+    started: Instant,
+    last_draw: Option<Instant>,
+    merge_output: bool,
+}
+
+impl<W: Write> ProgressBar<W> {
+    pub(crate) fn new(output: W) -> Self {
+        ProgressBar {
+            output,
+            total: 0,
+            done: 0,
+            started: Instant::now(),
+            last_draw: None,
+            merge_output: false,
+        }
+    }
+
+    /// Keeps the bar below the lines printed for each entry on the same terminal, by
+    /// clearing it before every such line and drawing it again after.
+    pub(crate) fn merging_output(mut self) -> Self {
+        self.merge_output = true;
+        self
+    }
+
+    fn draw(&mut self) {
+        let line = render(self.done, self.total, self.started.elapsed());
+        // Progress is best effort, a closed stderr must not stop the removal. Erasing the
+        // rest of the line cleans up after a longer previous line.
+        let _ = write!(self.output, "\r{line}\x1b[K");
+        let _ = self.output.flush();
+        self.last_draw = Some(Instant::now());
+    }
+}
+
+impl<W: Write> Progress for ProgressBar<W> {
+    fn start(&mut self, total: u64) {
+        self.total = total;
+        self.started = Instant::now();
+        self.draw();
+    }
+
+    fn advance(&mut self) {
+        self.done += 1;
+        if self
+            .last_draw
+            .is_none_or(|drawn| drawn.elapsed() >= REDRAW_INTERVAL)
+        {
+            self.draw();
+        }
+    }
+
+    fn finish(&mut self) {
+        // Clear the line so the summary replaces the bar.
+        let _ = write!(self.output, "\r\x1b[2K");
+        let _ = self.output.flush();
+    }
+
+    fn suspend(&mut self) {
+        if self.merge_output {
+            let _ = write!(self.output, "\r\x1b[2K");
+            let _ = self.output.flush();
+        }
+    }
+
+    fn resume(&mut self) {
+        if self.merge_output {
+            self.draw();
+        }
+    }
+}
+
+/// One JSON object per line for a wrapping program to parse, e.g.
+/// `{"type":"progress","removed":10342,"total":58901}` at most every 100ms, and a final
+/// `{"type":"done","removed":58901,"total":58901,"elapsed_secs":4.2}`.
+pub(crate) struct JsonProgress<W: Write> {
+    output: W,
+    total: u64,
+    done: u64,
+    started: Instant,
+    last_event: Option<Instant>,
+}
+
+impl<W: Write> JsonProgress<W> {
+    pub(crate) fn new(output: W) -> Self {
+        JsonProgress {
+            output,
+            total: 0,
+            done: 0,
+            started: Instant::now(),
+            last_event: None,
+        }
+    }
+
+    fn emit(&mut self, event: &str) {
+        // Like the bar, a reader that went away must not stop the removal.
+        let _ = writeln!(self.output, "{event}");
+        let _ = self.output.flush();
+        self.last_event = Some(Instant::now());
+    }
+
+    fn progress_event(&self) -> String {
+        progress_fields(&mut JsonObject::new(), self.done, self.total).finish()
+    }
+}
+
+/// Writes the fields of a `progress` event to `out`, which `--json-schema` also describes.
+pub(crate) fn progress_fields<F: JsonFields>(out: &mut F, removed: u64, total: u64) -> &mut F {
+    out.tag("type", "progress")
+        .number("removed", removed)
+        .number("total", total)
+}
+
+/// Writes the fields of the final `done` event to `out`.
+pub(crate) fn done_fields<F: JsonFields>(
+    out: &mut F,
+    removed: u64,
+    total: u64,
+    elapsed: Duration,
+) -> &mut F {
+    out.tag("type", "done")
+        .number("removed", removed)
+        .number("total", total)
+        .float("elapsed_secs", elapsed.as_secs_f64())
+}
+
+impl<W: Write> Progress for JsonProgress<W> {
+    fn start(&mut self, total: u64) {
+        self.total = total;
+        self.started = Instant::now();
+        self.emit(&self.progress_event());
+    }
+
+    fn advance(&mut self) {
+        self.done += 1;
+        if self
+            .last_event
+            .is_none_or(|emitted| emitted.elapsed() >= REDRAW_INTERVAL)
+        {
+            self.emit(&self.progress_event());
+        }
+    }
+
+    fn finish(&mut self) {
+        let done = done_fields(
+            &mut JsonObject::new(),
+            self.done,
+            self.total,
+            self.started.elapsed(),
+        )
+        .finish();
+        self.emit(&done);
+    }
+}
+
+/// Keeps the file at `path` holding a snapshot of the removal for a supervisor to poll,
+/// e.g. `{"removed":10342,"total":58901,"current_path":"/srv/cache/a","elapsed_secs":4.2}`,
+/// rewritten at most every 250ms, while passing everything on to `inner`. Every snapshot
+/// is written next to the file and renamed over it, so a reader never sees half of one,
+/// not even once the process got killed.
+pub(crate) struct ProgressFile<'a, P: Progress> {
+    path: PathBuf,
+    inner: &'a mut P,
+    total: u64,
+    done: u64,
+    current: PathBuf,
+    started: Instant,
+    last_write: Option<Instant>,
+    failed: bool,
+}
+
+impl<'a, P: Progress> ProgressFile<'a, P> {
+    pub(crate) fn new(path: PathBuf, inner: &'a mut P) -> Self {
+        ProgressFile {
+            path,
+            inner,
+            total: 0,
+            done: 0,
+            current: PathBuf::new(),
+            started: Instant::now(),
+            last_write: None,
+            failed: false,
+        }
+    }
+
+    fn write(&mut self) {
+        let snapshot = snapshot_fields(
+            &mut JsonObject::new(),
+            self.done,
+            self.total,
+            &self.current.to_string_lossy(),
+            self.started.elapsed(),
+        )
+        .finish();
+        let mut temp = self.path.as_os_str().to_owned();
+        temp.push(".tmp");
+
+        let written = std::fs::write(&temp, format!("{snapshot}\n"))
+            .and_then(|_| std::fs::rename(&temp, &self.path));
+        // Warned about once, as a supervisor going without must not stop the removal.
+        if let Err(e) = written {
+            if !self.failed {
+                println!(
+                    "Warning: failed to write the progress file {}. Error: {}",
+                    self.path.to_string_lossy(),
+                    e
+                );
+                self.failed = true;
+            }
+        }
+        self.last_write = Some(Instant::now());
+    }
+}
+
+/// Writes the fields of a `--progress-file` snapshot to `out`.
+pub(crate) fn snapshot_fields<'a, F: JsonFields>(
+    out: &'a mut F,
+    removed: u64,
+    total: u64,
+    current_path: &str,
+    elapsed: Duration,
+) -> &'a mut F {
+    out.number("removed", removed)
+        .number("total", total)
+        .string("current_path", current_path)
+        .float("elapsed_secs", elapsed.as_secs_f64())
+}
+
+impl<P: Progress> Progress for ProgressFile<'_, P> {
+    fn start(&mut self, total: u64) {
+        self.total = total;
+        self.started = Instant::now();
+        self.write();
+        self.inner.start(total);
+    }
+
+    fn current(&mut self, path: &Path) {
+        self.current = path.to_path_buf();
+        self.inner.current(path);
+    }
+
+    fn advance(&mut self) {
+        self.done += 1;
+        if self
+            .last_write
+            .is_none_or(|written| written.elapsed() >= SNAPSHOT_INTERVAL)
+        {
+            self.write();
+        }
+        self.inner.advance();
+    }
+
+    fn finish(&mut self) {
+        self.write();
+        self.inner.finish();
+    }
+
+    fn suspend(&mut self) {
+        self.inner.suspend();
+    }
+
+    fn resume(&mut self) {
+        self.inner.resume();
+    }
+}
+
+/// Renders e.g. `[###############...............]  50% 500/1000 250.0/s ETA 0:02`.
+/// Once more entries than estimated went, e.g. because something kept writing to the
+/// tree, only the count and rate are shown instead of a percentage past 100.
+pub(crate) fn render(done: u64, total: u64, elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs_f64();
+    let rate = if seconds > 0.0 {
+        done as f64 / seconds
+    } else {
+        0.0
+    };
+
+    if done > total {
+        return format!("Removed {done} items (estimate exceeded) {rate:.1}/s");
+    }
+
+    let fraction = if total == 0 {
+        1.0
+    } else {
+        (done as f64 / total as f64).min(1.0)
+    };
+    let filled = (fraction * BAR_WIDTH as f64).round() as usize;
+
+    let eta = if rate > 0.0 {
+        format_eta(total.saturating_sub(done) as f64 / rate)
+    } else {
+        "-:--".to_string()
+    };
+
+    format!(
+        "[{}{}] {:>3}% {}/{} {:.1}/s ETA {}",
+        "#".repeat(filled),
+        ".".repeat(BAR_WIDTH - filled),
+        (fraction * 100.0).floor(),
+        done,
+        total,
+        rate,
+        eta
+    )
+}
+
+fn format_eta(seconds: f64) -> String {
+    let seconds = seconds.ceil() as u64;
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+#[cfg(test)]
+mod render_should {
+    use super::*;
+
+    #[test]
+    fn show_bar_percentage_rate_and_eta() {
+        let line = render(500, 1000, Duration::from_secs(2));
+        assert_eq!(
+            line,
+            format!(
+                "[{}{}]  50% 500/1000 250.0/s ETA 0:02",
+                "#".repeat(15),
+                ".".repeat(15)
+            )
+        );
+    }
+
+    #[test]
+    fn show_unknown_eta_before_anything_was_removed() {
+        let line = render(0, 10, Duration::ZERO);
+        assert!(line.ends_with("0/10 0.0/s ETA -:--"), "{line}");
+    }
+
+    #[test]
+    fn switch_to_a_plain_count_once_the_estimate_is_exceeded() {
+        assert_eq!(
+            render(12, 10, Duration::from_secs(4)),
+            "Removed 12 items (estimate exceeded) 3.0/s"
+        );
+        assert!(render(10, 10, Duration::from_secs(4)).contains("100% 10/10"));
+    }
+
+    #[test]
+    fn format_long_etas_with_hours() {
+        assert_eq!(format_eta(3725.0), "1:02:05");
+        assert_eq!(format_eta(59.2), "1:00");
+    }
+}
+
+#[cfg(test)]
+mod progress_bar_should {
+    use super::*;
+
+    #[test]
+    fn clear_the_line_when_finished() {
+        let mut output = Vec::new();
+        let mut bar = ProgressBar::new(&mut output);
+
+        bar.start(2);
+        bar.advance();
+        bar.advance();
+        bar.finish();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("\r["), "{output:?}");
+        assert!(output.ends_with("\r\x1b[2K"), "{output:?}");
+    }
+
+    #[test]
+    fn make_way_for_printed_lines_only_when_merging() {
+        let mut plain_output = Vec::new();
+        let mut plain = ProgressBar::new(&mut plain_output);
+        plain.suspend();
+        plain.resume();
+
+        let mut output = Vec::new();
+        let mut bar = ProgressBar::new(&mut output).merging_output();
+        bar.start(2);
+        bar.advance();
+        bar.suspend();
+        bar.resume();
+
+        assert!(plain_output.is_empty());
+        let output = String::from_utf8(output).unwrap();
+        let redraws: Vec<&str> = output.split("\r\x1b[2K").collect();
+        assert_eq!(redraws.len(), 2, "{output:?}");
+        assert!(redraws[1].starts_with("\r[###"), "{output:?}");
+    }
+
+    #[test]
+    fn never_draw_past_100_percent_on_an_underestimate() {
+        let mut output = Vec::new();
+        let mut bar = ProgressBar::new(&mut output);
+
+        bar.start(1);
+        for _ in 0..3 {
+            bar.last_draw = None;
+            bar.advance();
+        }
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains("200%"), "{output:?}");
+        assert!(
+            output.contains("Removed 3 items (estimate exceeded)"),
+            "{output:?}"
+        );
+    }
+}
+
+#[cfg(test)]
+mod json_progress_should {
+    use super::*;
+
+    fn field<'a>(line: &'a str, key: &str) -> &'a str {
+        let start = line.find(&format!("\"{key}\":")).unwrap() + key.len() + 3;
+        let rest = &line[start..];
+        &rest[..rest.find([',', '}']).unwrap()]
+    }
+
+    #[test]
+    fn emit_one_object_per_line_and_a_final_done_event() {
+        let mut output = Vec::new();
+        let mut progress = JsonProgress::new(&mut output);
+
+        progress.start(3);
+        for _ in 0..3 {
+            progress.last_event = None;
+            progress.advance();
+        }
+        progress.finish();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 5, "{output}");
+        assert!(lines
+            .iter()
+            .all(|line| line.starts_with('{') && line.ends_with('}')));
+
+        assert_eq!(lines[0], r#"{"type":"progress","removed":0,"total":3}"#);
+        assert_eq!(lines[3], r#"{"type":"progress","removed":3,"total":3}"#);
+        assert_eq!(field(lines[4], "type"), r#""done""#);
+        assert_eq!(field(lines[4], "removed"), "3");
+        assert!(field(lines[4], "elapsed_secs").parse::<f64>().is_ok());
+    }
+
+    #[test]
+    fn throttle_progress_events() {
+        let mut output = Vec::new();
+        let mut progress = JsonProgress::new(&mut output);
+
+        progress.start(1000);
+        for _ in 0..1000 {
+            progress.advance();
+        }
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.lines().count() < 10, "{output}");
+    }
+}
+
+#[cfg(test)]
+mod progress_file_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn hold_a_snapshot_of_the_removal_while_it_runs() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let path = PathBuf::from(format!("./data/progress-file-{unique}.json"));
+        let mut inner = JsonProgress::new(Vec::new());
+        let mut progress = ProgressFile::new(path.clone(), &mut inner);
+
+        progress.start(3);
+        let started = std::fs::read_to_string(&path).unwrap();
+        progress.current(Path::new("/srv/cache/first.bin"));
+        progress.last_write = None;
+        progress.advance();
+        progress.current(Path::new("/srv/cache/second.bin"));
+        let mid_way = std::fs::read_to_string(&path).unwrap();
+        progress.finish();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(started.starts_with(r#"{"removed":0,"total":3,"current_path":"","#));
+        assert!(
+            mid_way.starts_with(
+                r#"{"removed":1,"total":3,"current_path":"/srv/cache/first.bin","elapsed_secs":"#
+            ),
+            "{mid_way}"
+        );
+        assert!(mid_way.ends_with("}\n"), "{mid_way}");
+        assert!(!Path::new(&format!("{}.tmp", path.to_string_lossy())).exists());
+        assert_eq!(inner.done, 1);
+    }
+}
@@ -0,0 +1,36 @@
+//! `--shell-escape-paths`: printing paths so the output can be pasted or piped back
+//! into a POSIX shell.
+
+/// Wraps `text` in single quotes, the only character needing care inside them being the
+/// single quote itself, which becomes `'\''`. Spaces, `$`, backslashes and even newlines
+/// are taken literally.
+pub(crate) fn shell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod shell_quote_should {
+    use super::*;
+
+    #[test]
+    fn quote_spaces_quotes_and_newlines() {
+        assert_eq!(shell_quote("plain"), "'plain'");
+        assert_eq!(shell_quote("with space"), "'with space'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote("two\nlines"), "'two\nlines'");
+        assert_eq!(shell_quote("$HOME `id` \\"), "'$HOME `id` \\'");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn round_trip_through_a_shell() {
+        let path = "a dir/it's \"quoted\"\n$(touch nope)*";
+        let output = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(format!("printf %s {}", shell_quote(path)))
+            .output()
+            .unwrap();
+
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), path);
+    }
+}
@@ -0,0 +1,125 @@
+//! `--limit-rate-bytes`: caps the bytes per second of the work that goes through file
+//! contents, overwriting with `--secure`, writing an `--archive` and comparing with
+//! `--compare-to` or `--dedupe`, so it does not saturate shared storage.
+//!
+//! Every chunk read or written is counted, and once more went than the cap allows for the
+//! time since counting started, the caller sleeps until it fits. Time spent on other work,
+//! like unlinking, is not banked for a later burst: after a second without any counted
+//! bytes counting starts over. The limit is process wide, like `--jobs`, and composes
+//! with `--throttle-on-load`, as both only ever add pauses.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::units;
+
+/// The longest gap between two chunks that still counts as one stretch of work.
+const MAX_IDLE: Duration = Duration::from_secs(1);
+
+/// Paces the bytes `consume` is told about to at most `bytes_per_sec`.
+pub(crate) struct RateLimiter {
+    bytes_per_sec: u64,
+    started: Option<Instant>,
+    bytes: u64,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(bytes_per_sec: u64) -> Self {
+        RateLimiter {
+            bytes_per_sec,
+            started: None,
+            bytes: 0,
+        }
+    }
+
+    /// Counts `bytes` just processed and sleeps for as long as they are ahead of the cap.
+    pub(crate) fn consume(&mut self, bytes: u64) {
+        let now = Instant::now();
+        let started = match self.started {
+            Some(started) if now.duration_since(started) <= self.due() + MAX_IDLE => started,
+            _ => {
+                self.bytes = 0;
+                now
+            }
+        };
+        self.started = Some(started);
+        self.bytes += bytes;
+
+        let ahead = self.due().saturating_sub(now.duration_since(started));
+        if !ahead.is_zero() {
+            std::thread::sleep(ahead);
+        }
+    }
+
+    /// How long the bytes counted so far should take at the cap.
+    fn due(&self) -> Duration {
+        Duration::from_secs_f64(self.bytes as f64 / self.bytes_per_sec as f64)
+    }
+}
+
+static LIMIT: Mutex<Option<RateLimiter>> = Mutex::new(None);
+
+/// Makes every later `consume` keep to `bytes_per_sec`.
+pub(crate) fn set_limit(bytes_per_sec: u64) {
+    *LIMIT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(RateLimiter::new(bytes_per_sec));
+}
+
+/// Counts `bytes` of file contents just read or written against the limit, if one is set.
+pub(crate) fn consume(bytes: u64) {
+    if let Some(limiter) = LIMIT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .as_mut()
+    {
+        limiter.consume(bytes);
+    }
+}
+
+/// Parses a `--limit-rate-bytes` value, a size per second like `50M`, which cannot be 0.
+pub(crate) fn parse_rate(value: &str) -> Result<u64, String> {
+    match units::parse_size(value)? {
+        0 => Err("the rate has to be above 0 bytes per second".to_string()),
+        rate => Ok(rate),
+    }
+}
+
+#[cfg(test)]
+mod rate_limiter_should {
+    use super::*;
+
+    #[test]
+    fn keep_the_byte_rate_near_the_cap() {
+        let mut limiter = RateLimiter::new(1_000_000);
+        let started = Instant::now();
+
+        for _ in 0..20 {
+            limiter.consume(10_000);
+        }
+        let rate = 200_000.0 / started.elapsed().as_secs_f64();
+
+        assert!(rate <= 1_000_000.0 * 1.05, "{rate} bytes per second");
+        assert!(rate >= 1_000_000.0 * 0.5, "{rate} bytes per second");
+    }
+
+    #[test]
+    fn not_bank_idle_time_for_a_burst() {
+        let mut limiter = RateLimiter::new(1_000_000);
+        limiter.consume(1_000);
+        std::thread::sleep(MAX_IDLE + Duration::from_millis(100));
+
+        let started = Instant::now();
+        limiter.consume(100_000);
+
+        assert!(started.elapsed() >= Duration::from_millis(95));
+    }
+
+    #[test]
+    fn refuse_a_zero_rate() {
+        assert_eq!(parse_rate("50M"), Ok(50 * 1024 * 1024));
+        assert!(parse_rate("0").is_err());
+    }
+}
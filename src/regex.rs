@@ -0,0 +1,461 @@
+//! Minimal backtracking regular expressions for `--regex`.
+//!
+//! Supports literals, `.`, `^`, `$`, groups `(...)` and `(?:...)`, alternation `|`,
+//! the quantifiers `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}`, bracket classes like
+//! `[a-z_]` or `[^0-9]` and the classes `\d`, `\w` and `\s` with their negations.
+//! There are no captures or backreferences. A match may start anywhere unless the
+//! pattern is anchored.
+
+#[derive(Clone, Debug)]
+pub(crate) struct Regex {
+    alternatives: Vec<Vec<Node>>,
+    ignore_case: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+#[derive(Clone, Debug)]
+struct Class {
+    negated: bool,
+    items: Vec<ClassItem>,
+}
+
+#[derive(Clone, Debug)]
+enum ClassItem {
+    Range(char, char),
+    /// `\d`, `\D` and friends. The flag is set for the negated form.
+    Digit(bool),
+    Word(bool),
+    Space(bool),
+}
+
+impl ClassItem {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        match *self {
+            ClassItem::Range(low, high) => {
+                let in_range = |c: char| low <= c && c <= high;
+                in_range(c)
+                    || ignore_case
+                        && (c.to_lowercase().any(in_range) || c.to_uppercase().any(in_range))
+            }
+            ClassItem::Digit(negated) => c.is_ascii_digit() != negated,
+            ClassItem::Word(negated) => (c.is_alphanumeric() || c == '_') != negated,
+            ClassItem::Space(negated) => c.is_whitespace() != negated,
+        }
+    }
+}
+
+impl Class {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        self.items.iter().any(|item| item.matches(c, ignore_case)) != self.negated
+    }
+}
+
+impl Regex {
+    /// Compiles `pattern`. With `ignore_case` literals and the ranges of bracket classes
+    /// match either case, while escapes such as `\D` keep their meaning.
+    pub(crate) fn new(pattern: &str, ignore_case: bool) -> Result<Self, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let alternatives = parser.parse_alternatives()?;
+        if parser.pos < parser.chars.len() {
+            return Err(format!("unmatched ')' at position {}", parser.pos));
+        }
+
+        Ok(Regex {
+            alternatives,
+            ignore_case,
+        })
+    }
+
+    /// Parses a regex given on the command line, so an invalid one is a usage error.
+    /// `ignore_case` can be set afterwards, as it does not change what is valid.
+    pub(crate) fn parse(pattern: &str) -> Result<Self, String> {
+        Regex::new(pattern, false)
+    }
+
+    /// The same pattern, matched case-insensitively when `ignore_case` is set.
+    pub(crate) fn ignoring_case(self, ignore_case: bool) -> Self {
+        Regex {
+            ignore_case,
+            ..self
+        }
+    }
+
+    /// True when the pattern matches somewhere in `text`.
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+
+        let matcher = Matcher {
+            text: &text,
+            ignore_case: self.ignore_case,
+        };
+        (0..=text.len())
+            .any(|start| matcher.match_alternatives(&self.alternatives, start, &mut |_| true))
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn parse_alternatives(&mut self) -> Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![self.parse_sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.parse_sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn parse_sequence(&mut self) -> Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_quantifier(atom)?);
+        }
+        Ok(nodes)
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, String> {
+        let position = self.pos;
+        match self.next() {
+            Some('(') => {
+                if self.chars[self.pos..].starts_with(&['?', ':']) {
+                    self.pos += 2;
+                }
+                let alternatives = self.parse_alternatives()?;
+                if self.next() != Some(')') {
+                    return Err(format!("unclosed '(' at position {position}"));
+                }
+                Ok(Node::Group(alternatives))
+            }
+            Some('[') => self.parse_class(position),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => match self.parse_escape()? {
+                Escaped::Char(c) => Ok(Node::Char(c)),
+                Escaped::Class(item) => Ok(Node::Class(Class {
+                    negated: false,
+                    items: vec![item],
+                })),
+            },
+            Some(c @ ('*' | '+' | '?')) => Err(format!(
+                "nothing to repeat before '{c}' at position {position}"
+            )),
+            Some(c) => Ok(Node::Char(c)),
+            None => Err("unexpected end of pattern".to_string()),
+        }
+    }
+
+    fn parse_escape(&mut self) -> Result<Escaped, String> {
+        let escaped = match self.next() {
+            Some('d') => Escaped::Class(ClassItem::Digit(false)),
+            Some('D') => Escaped::Class(ClassItem::Digit(true)),
+            Some('w') => Escaped::Class(ClassItem::Word(false)),
+            Some('W') => Escaped::Class(ClassItem::Word(true)),
+            Some('s') => Escaped::Class(ClassItem::Space(false)),
+            Some('S') => Escaped::Class(ClassItem::Space(true)),
+            Some('n') => Escaped::Char('\n'),
+            Some('t') => Escaped::Char('\t'),
+            Some(c) => Escaped::Char(c),
+            None => return Err("trailing backslash".to_string()),
+        };
+        Ok(escaped)
+    }
+
+    fn parse_class(&mut self, position: usize) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let low = match self.next() {
+                None => return Err(format!("unclosed '[' at position {position}")),
+                // A `]` right after the opening bracket is part of the class.
+                Some(']') if !first => break,
+                Some('\\') => match self.parse_escape()? {
+                    Escaped::Char(c) => c,
+                    Escaped::Class(item) => {
+                        items.push(item);
+                        first = false;
+                        continue;
+                    }
+                },
+                Some(c) => c,
+            };
+            first = false;
+
+            let is_range = self.peek() == Some('-')
+                && !matches!(self.chars.get(self.pos + 1), None | Some(']'));
+            if !is_range {
+                items.push(ClassItem::Range(low, low));
+                continue;
+            }
+
+            self.pos += 1;
+            let high = match self.next() {
+                Some('\\') => match self.parse_escape()? {
+                    Escaped::Char(c) => c,
+                    Escaped::Class(_) => {
+                        return Err(format!("invalid range in '[' at position {position}"))
+                    }
+                },
+                Some(c) => c,
+                None => return Err(format!("unclosed '[' at position {position}")),
+            };
+            if high < low {
+                return Err(format!("invalid range {low}-{high}"));
+            }
+            items.push(ClassItem::Range(low, high));
+        }
+
+        Ok(Node::Class(Class { negated, items }))
+    }
+
+    fn parse_quantifier(&mut self, atom: Node) -> Result<Node, String> {
+        let mut node = atom;
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    (0, None)
+                }
+                Some('+') => {
+                    self.pos += 1;
+                    (1, None)
+                }
+                Some('?') => {
+                    self.pos += 1;
+                    (0, Some(1))
+                }
+                Some('{') => match self.parse_counts() {
+                    Some(counts) => counts,
+                    // Not a valid count, so the `{` is a literal and gets parsed as such.
+                    None => return Ok(node),
+                },
+                _ => return Ok(node),
+            };
+            // Lazy quantifiers match the same texts, only the shortest match differs.
+            if self.peek() == Some('?') {
+                self.pos += 1;
+            }
+
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+            };
+        }
+    }
+
+    /// Parses `{n}`, `{n,}` or `{n,m}` at the current position, consuming it on success.
+    fn parse_counts(&mut self) -> Option<(usize, Option<usize>)> {
+        let rest: String = self.chars[self.pos + 1..].iter().collect();
+        let end = rest.find('}')?;
+        let body = &rest[..end];
+
+        let (min, max) = match body.split_once(',') {
+            None => {
+                let n = body.parse().ok()?;
+                (n, Some(n))
+            }
+            Some((min, "")) => (min.parse().ok()?, None),
+            Some((min, max)) => (min.parse().ok()?, Some(max.parse().ok()?)),
+        };
+        if max.is_some_and(|max| max < min) {
+            return None;
+        }
+
+        self.pos += body.chars().count() + 2;
+        Some((min, max))
+    }
+}
+
+enum Escaped {
+    Char(char),
+    Class(ClassItem),
+}
+
+struct Matcher<'a> {
+    text: &'a [char],
+    ignore_case: bool,
+}
+
+impl Matcher<'_> {
+    fn match_alternatives(
+        &self,
+        alternatives: &[Vec<Node>],
+        pos: usize,
+        then: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        alternatives
+            .iter()
+            .any(|sequence| self.match_sequence(sequence, pos, then))
+    }
+
+    fn match_sequence(
+        &self,
+        nodes: &[Node],
+        pos: usize,
+        then: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        match nodes.split_first() {
+            None => then(pos),
+            Some((node, rest)) => {
+                self.match_node(node, pos, &mut |next| self.match_sequence(rest, next, then))
+            }
+        }
+    }
+
+    /// Matches `node` at `pos` and calls `then` with every position it can end at,
+    /// longest first, until one of them leads to a full match.
+    fn match_node(&self, node: &Node, pos: usize, then: &mut dyn FnMut(usize) -> bool) -> bool {
+        let current = self.text.get(pos).copied();
+        match node {
+            Node::Char(c) => {
+                current.is_some_and(|current| self.same_char(current, *c)) && then(pos + 1)
+            }
+            Node::Any => current.is_some() && then(pos + 1),
+            Node::Class(class) => {
+                current.is_some_and(|c| class.matches(c, self.ignore_case)) && then(pos + 1)
+            }
+            Node::Start => pos == 0 && then(pos),
+            Node::End => pos == self.text.len() && then(pos),
+            Node::Group(alternatives) => self.match_alternatives(alternatives, pos, then),
+            Node::Repeat { node, min, max } => self.match_repeat(node, *min, *max, 0, pos, then),
+        }
+    }
+
+    fn same_char(&self, text: char, pattern: char) -> bool {
+        text == pattern || self.ignore_case && text.to_lowercase().eq(pattern.to_lowercase())
+    }
+
+    fn match_repeat(
+        &self,
+        node: &Node,
+        min: usize,
+        max: Option<usize>,
+        count: usize,
+        pos: usize,
+        then: &mut dyn FnMut(usize) -> bool,
+    ) -> bool {
+        if max.is_none_or(|max| count < max) {
+            // Stop repeating empty matches once the minimum is reached, or `(a*)*` would
+            // never end.
+            let matched = self.match_node(node, pos, &mut |next| {
+                (next != pos || count < min)
+                    && self.match_repeat(node, min, max, count + 1, next, then)
+            });
+            if matched {
+                return true;
+            }
+        }
+
+        count >= min && then(pos)
+    }
+}
+
+#[cfg(test)]
+mod regex_should {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern, false).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn match_anywhere_unless_anchored() {
+        assert!(matches("log", "nested/app.log"));
+        assert!(matches(r"\.log$", "nested/app.log"));
+        assert!(!matches(r"\.log$", "nested/app.log.gz"));
+        assert!(matches("^nested/", "nested/app.log"));
+        assert!(!matches("^app", "nested/app.log"));
+    }
+
+    #[test]
+    fn support_quantifiers_classes_and_groups() {
+        assert!(matches(r"^core\.\d+$", "core.12345"));
+        assert!(!matches(r"^core\.\d+$", "core."));
+        assert!(matches("^(tmp|cache)/", "cache/file"));
+        assert!(matches("^a{2,3}$", "aaa"));
+        assert!(!matches("^a{2,3}$", "aaaa"));
+        assert!(matches("^[^0-9_]+$", "abc"));
+        assert!(!matches("^[^0-9_]+$", "ab_c"));
+        assert!(matches("^(a*)*b$", "aaab"));
+        assert!(!matches("^(a*)*b$", "aaac"));
+        assert!(matches("x{y", "x{y"));
+    }
+
+    #[test]
+    fn reject_invalid_patterns() {
+        assert!(Regex::new("(abc", false).is_err());
+        assert!(Regex::new("abc)", false).is_err());
+        assert!(Regex::new("[abc", false).is_err());
+        assert!(Regex::new("*abc", false).is_err());
+        assert!(Regex::new("[z-a]", false).is_err());
+    }
+
+    #[test]
+    fn ignore_case_when_asked() {
+        assert!(!matches(r"\.TXT$", "file.txt"));
+        assert!(Regex::new(r"\.TXT$", true).unwrap().is_match("file.txt"));
+        assert!(Regex::new("^[A-C]+$", true).unwrap().is_match("abc"));
+        assert!(Regex::new("^[a-c]+$", true).unwrap().is_match("ABC"));
+    }
+
+    #[test]
+    fn ignore_case_once_parsed() {
+        let regex = Regex::parse(r"\.TXT$").unwrap();
+
+        assert!(!regex.is_match("file.txt"));
+        assert!(regex.ignoring_case(true).is_match("file.txt"));
+        assert!(Regex::parse("(").is_err());
+    }
+
+    #[test]
+    fn keep_the_meaning_of_escapes_when_ignoring_case() {
+        for ignore_case in [false, true] {
+            let not_digit = Regex::new(r"^\D$", ignore_case).unwrap();
+            assert!(not_digit.is_match("x"), "ignore_case {ignore_case}");
+            assert!(!not_digit.is_match("1"), "ignore_case {ignore_case}");
+            let not_word = Regex::new(r"^\W$", ignore_case).unwrap();
+            assert!(not_word.is_match("-"), "ignore_case {ignore_case}");
+            assert!(!not_word.is_match("A"), "ignore_case {ignore_case}");
+        }
+    }
+}
@@ -0,0 +1,2912 @@
+//! Entry by entry deletion of a scanned tree. Used instead of `std::fs::remove_dir_all`
+//! when only the files matching a filter should go, when they get moved aside, or
+//! when every removed path has to be listed.
+//!
+//! Walking keeps the whole scan in memory, roughly a path plus its metadata per
+//! entry, so it costs noticeably more than `remove_dir_all` on huge trees.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BinaryHeap, HashMap, HashSet},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    diagnose,
+    duplicates::{self, DedupeKeep},
+    error::RemoveError,
+    exec::ExecTemplate,
+    filter::{Filter, KeepReason},
+    json::{JsonFields, JsonObject},
+    messages::Lang,
+    progress::Progress,
+    quote,
+    regex::Regex,
+    scan::{self, Entry, ScanOptions},
+    scan_cache::{self, CacheUse},
+    throttle::Throttle,
+    units, wipe,
+};
+
+/// How to run a walk based removal.
+#[derive(Default)]
+pub(crate) struct RemoveOptions {
+    pub(crate) filter: Filter,
+    /// Move matched files below this directory instead of deleting them.
+    pub(crate) quarantine: Option<PathBuf>,
+    /// Leave folders in place even once the filter emptied them.
+    pub(crate) keep_emptied_dirs: bool,
+    /// Track a whole-tree delete in this checkpoint file.
+    pub(crate) checkpoint: Option<PathBuf>,
+    /// Load the checkpoint file before deleting instead of starting fresh.
+    pub(crate) resume: bool,
+    /// How much to print while removing: 1 lists removed folders, 2 every removed path, 3
+    /// adds each file's size and mtime and the files kept along with why.
+    pub(crate) verbosity: u8,
+    /// Order files are listed and removed in. None keeps the scan order.
+    pub(crate) sort: Option<SortOrder>,
+    /// Descend into symlinked directories and delete their contents, then the link.
+    pub(crate) follow_symlinks: bool,
+    /// Descend into Windows junctions and delete the contents of their targets, then the
+    /// junction. Without it a junction is removed as a link, like a symlink.
+    pub(crate) follow_junctions: bool,
+    /// Abort the scan, before anything goes, at a symlink `follow_symlinks` would follow
+    /// out of the root.
+    pub(crate) fail_on_symlink_escape: bool,
+    /// Count the size of content reached through followed symlinks.
+    pub(crate) count_symlink_targets: bool,
+    /// Count the blocks a file takes up on disk rather than its apparent size, which is
+    /// far less for sparse files.
+    pub(crate) sparse_aware: bool,
+    /// Walk even a whole-tree delete so progress can be reported per entry.
+    pub(crate) report_progress: bool,
+    /// Report that progress as JSON lines rather than with a bar.
+    pub(crate) progress_json: bool,
+    /// Keep a snapshot of the progress in this file while removing, see `ProgressFile`.
+    pub(crate) progress_file: Option<PathBuf>,
+    /// List at most this many paths in a dry run or verbose delete, then how many more
+    /// there were. Listings into a file or ended by NUL bytes are never cut short.
+    pub(crate) preview_limit: Option<usize>,
+    /// List at most one removed entry per interval, the one removed when it is up, so
+    /// printing does not slow down a huge verbose delete.
+    pub(crate) verbose_interval: Option<Duration>,
+    /// Have the bar make way for the lines `verbose` prints on the same terminal.
+    pub(crate) merge_output: bool,
+    /// Skip the summary printed once the removal is done.
+    pub(crate) quiet: bool,
+    /// Keep nested mount points, and the folders holding them, instead of descending.
+    pub(crate) skip_mount_points: bool,
+    /// Command run for every file before it is removed.
+    pub(crate) exec: Option<ExecTemplate>,
+    /// Leave removing the files to `exec` instead of deleting them afterwards.
+    pub(crate) exec_replace: bool,
+    /// Report entries that could not be removed and carry on with the rest.
+    pub(crate) skip_errors: bool,
+    /// Collect the errors skipped under `skip_errors` into `RemoveStats::errors_by_kind`
+    /// instead of printing each as it happens.
+    pub(crate) group_errors: bool,
+    /// Append every path skipped under `skip_errors` for a permission error to this file,
+    /// one per line.
+    pub(crate) denied_to: Option<PathBuf>,
+    /// Language of the abort message and the summary lines.
+    pub(crate) lang: Lang,
+    /// Errors whose `path: message` line matches one of these are expected, so they are
+    /// neither reported nor counted.
+    pub(crate) ignore_errors: Vec<Regex>,
+    /// Overwrite regular files this many times before deleting them.
+    pub(crate) secure_passes: Option<u32>,
+    /// Have a dry run list every file with the reason it is deleted or kept.
+    pub(crate) explain: bool,
+    /// Paths relative to the root that are kept, along with everything inside them.
+    pub(crate) preserve: Vec<PathBuf>,
+    /// Also write the stats as JSON to this file.
+    pub(crate) summary_json_to: Option<PathBuf>,
+    /// Walk even a whole-tree delete so its stats can be added to a batch summary.
+    pub(crate) collect_stats: bool,
+    /// Stop removing, with an error, once the wall clock passes this instant.
+    pub(crate) deadline: Option<SystemTime>,
+    /// Quote listed paths so the output can be fed back into a shell.
+    pub(crate) shell_escape_paths: bool,
+    /// List paths relative to this folder, those outside it stay as they are.
+    pub(crate) relative_to: Option<PathBuf>,
+    /// Keep the folders this many levels deep or less, emptied, see `keeps_structure`.
+    pub(crate) keep_structure_depth: Option<usize>,
+    /// List bare paths, one per line, into the file written by `--dry-run-to`. Notes go to
+    /// stdout instead.
+    pub(crate) bare_listing: bool,
+    /// List bare paths, each ended by a NUL byte, and send notes to stderr, see `list`.
+    pub(crate) output_null: bool,
+    /// Add up the removed files per extension, see `RemoveStats::by_extension`.
+    pub(crate) group_by_extension: bool,
+    /// Keep track of this many of the largest removed files, see `RemoveStats::largest`.
+    pub(crate) report_largest: Option<usize>,
+    /// Slow down while the system load is above this, see `throttle::Throttle`.
+    pub(crate) throttle_load: Option<f64>,
+    /// Flush every folder something was removed from to disk once done, see `sync_dirs`.
+    pub(crate) fsync: bool,
+    /// Keep files some process holds open, and the folders holding them.
+    pub(crate) exclude_open_files: bool,
+    /// Keep files whose mtime changed since the scan, and the folders holding them.
+    pub(crate) skip_modified_since_scan: bool,
+    /// Only delete the extra copies of byte-identical files, keeping one of each set.
+    pub(crate) dedupe: Option<DedupeKeep>,
+    /// Reuse the scan cached in this file while it is fresh, see `scan_cache`.
+    pub(crate) scan_cache: Option<PathBuf>,
+}
+
+impl RemoveOptions {
+    /// True when the tree has to be walked rather than removed in one go.
+    pub(crate) fn needs_walk(&self) -> bool {
+        self.is_partial()
+            || self.verbosity > 0
+            || self.sort.is_some()
+            || self.follow_symlinks
+            || self.follow_junctions
+            || self.report_progress
+            || self.skip_mount_points
+            || self.exec.is_some()
+            || self.skip_errors
+            || !self.ignore_errors.is_empty()
+            || self.secure_passes.is_some()
+            || !self.preserve.is_empty()
+            || self.summary_json_to.is_some()
+            || self.collect_stats
+            || self.deadline.is_some()
+            || self.group_by_extension
+            || self.report_largest.is_some()
+            || self.throttle_load.is_some()
+            || self.fsync
+            || self.keep_structure_depth.is_some()
+            || self.sparse_aware
+            || self.exclude_open_files
+            || self.skip_modified_since_scan
+            || self.scan_cache.is_some()
+            || self.progress_file.is_some()
+    }
+
+    /// True when the folder at `relative` is part of the kept layout, counting the folders
+    /// directly in the root as depth 1.
+    fn keeps_structure(&self, relative: &Path) -> bool {
+        self.keep_structure_depth
+            .is_some_and(|depth| relative.components().count() <= depth)
+    }
+
+    /// True when the plan has to note every kept file with its reason.
+    fn lists_kept(&self) -> bool {
+        self.explain || self.verbosity >= 3
+    }
+
+    /// True when `relative` is a preserved path or inside one.
+    fn is_preserved(&self, relative: &Path) -> bool {
+        self.preserve
+            .iter()
+            .any(|preserved| relative.starts_with(preserved))
+    }
+
+    /// `path` relative to `relative_to` when it is inside.
+    fn shown<'a>(&self, path: &'a Path) -> &'a Path {
+        match self
+            .relative_to
+            .as_deref()
+            .map(|base| path.strip_prefix(base))
+        {
+            Some(Ok(relative)) if relative.as_os_str().is_empty() => Path::new("."),
+            Some(Ok(relative)) => relative,
+            _ => path,
+        }
+    }
+
+    /// `path` as listed by a verbose or dry run.
+    fn display(&self, path: &Path) -> String {
+        let path = self.shown(path).to_string_lossy();
+        if self.shell_escape_paths {
+            quote::shell_quote(&path)
+        } else {
+            path.to_string()
+        }
+    }
+
+    /// Lists `path` as `{verb} {path}{detail}` on its own line, or with `output_null` as
+    /// the bare path ended by a NUL byte, its bytes untouched, for `xargs -0`. With
+    /// `bare_listing` the bare path gets a line of its own.
+    fn list(
+        &self,
+        output: &mut impl Write,
+        verb: &str,
+        path: &Path,
+        detail: &str,
+    ) -> Result<(), std::io::Error> {
+        let terminator: &[u8] = match (self.output_null, self.bare_listing) {
+            (true, _) => b"\0",
+            (false, true) => b"\n",
+            (false, false) => {
+                return writeln!(output, "{} {}{}", verb, self.display(path), detail);
+            }
+        };
+
+        #[cfg(unix)]
+        output.write_all(std::os::unix::ffi::OsStrExt::as_bytes(
+            self.shown(path).as_os_str(),
+        ))?;
+        #[cfg(not(unix))]
+        output.write_all(self.shown(path).to_string_lossy().as_bytes())?;
+        output.write_all(terminator)
+    }
+
+    /// True when only part of the tree goes, so the root and some folders are kept.
+    pub(crate) fn is_partial(&self) -> bool {
+        self.filter.is_active() || self.quarantine.is_some() || self.dedupe.is_some()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub(crate) enum SortOrder {
+    /// Largest files first.
+    #[value(alias = "size-desc")]
+    Size,
+    /// Least recently modified files first.
+    #[value(alias = "age-desc")]
+    Mtime,
+    /// Alphabetically by path.
+    Name,
+    /// Highest size in bytes times age in seconds first, so big old files go before
+    /// small or fresh ones.
+    Score,
+}
+
+/// What a walk based removal did, or would do on a dry run.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct RemoveStats {
+    pub(crate) files: u64,
+    pub(crate) dirs: u64,
+    pub(crate) bytes: u64,
+    pub(crate) skipped_mount_points: u64,
+    /// Files left in place for being open, with `exclude_open_files`.
+    pub(crate) skipped_open_files: u64,
+    /// Files left in place for being modified after the scan, with
+    /// `skip_modified_since_scan`.
+    pub(crate) modified_since_scan: u64,
+    /// Entries left in place because removing them failed under `skip_errors`.
+    pub(crate) errors: u64,
+    /// Files kept for differing from, or missing in, the `compare_to` reference.
+    pub(crate) kept_different: u64,
+    /// Files and bytes removed per lowercase extension, like `.o`, with `group_by_extension`.
+    /// Files without an extension are grouped under an empty string.
+    pub(crate) by_extension: BTreeMap<String, ExtensionTotals>,
+    /// The largest removed files with `report_largest`.
+    pub(crate) largest: LargestFiles,
+    /// The skipped errors with `group_errors`, by a description of their kind such as
+    /// `Permission denied`.
+    pub(crate) errors_by_kind: BTreeMap<String, ErrorGroup>,
+}
+
+/// The `limit` largest files seen so far. A min-heap, so only `limit` paths are held no
+/// matter how many files go and the smallest of them is the one to drop next.
+#[derive(Debug, Default)]
+pub(crate) struct LargestFiles {
+    limit: usize,
+    heap: BinaryHeap<Reverse<(u64, PathBuf)>>,
+}
+
+impl LargestFiles {
+    pub(crate) fn new(limit: usize) -> Self {
+        LargestFiles {
+            limit,
+            heap: BinaryHeap::with_capacity(limit + 1),
+        }
+    }
+
+    fn offer(&mut self, bytes: u64, path: &Path) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.heap.len() == self.limit
+            && self
+                .heap
+                .peek()
+                .is_some_and(|Reverse((smallest, _))| bytes <= *smallest)
+        {
+            return;
+        }
+
+        self.heap.push(Reverse((bytes, path.to_path_buf())));
+        if self.heap.len() > self.limit {
+            self.heap.pop();
+        }
+    }
+
+    /// The files largest first, ties by path.
+    pub(crate) fn sorted(&self) -> Vec<(u64, &Path)> {
+        let mut files: Vec<(u64, &Path)> = self
+            .heap
+            .iter()
+            .map(|Reverse((bytes, path))| (*bytes, path.as_path()))
+            .collect();
+        files.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+        files
+    }
+}
+
+impl PartialEq for LargestFiles {
+    fn eq(&self, other: &Self) -> bool {
+        self.limit == other.limit && self.sorted() == other.sorted()
+    }
+}
+
+/// How many errors of one kind were skipped, and the first few paths they hit.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ErrorGroup {
+    pub(crate) count: u64,
+    pub(crate) paths: Vec<PathBuf>,
+}
+
+impl ErrorGroup {
+    /// How many paths are kept per group, the rest are only counted.
+    pub(crate) const LISTED_PATHS: usize = 10;
+
+    fn offer(&mut self, path: &Path) {
+        if self.paths.len() < Self::LISTED_PATHS {
+            self.paths.push(path.to_path_buf());
+        }
+    }
+}
+
+/// What went for one extension.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct ExtensionTotals {
+    pub(crate) files: u64,
+    pub(crate) bytes: u64,
+}
+
+impl RemoveStats {
+    /// Adds the numbers of another run, e.g. for a batch summary.
+    pub(crate) fn accumulate(&mut self, other: &RemoveStats) {
+        self.files += other.files;
+        self.dirs += other.dirs;
+        self.bytes += other.bytes;
+        self.skipped_mount_points += other.skipped_mount_points;
+        self.skipped_open_files += other.skipped_open_files;
+        self.modified_since_scan += other.modified_since_scan;
+        self.errors += other.errors;
+        self.kept_different += other.kept_different;
+        for (extension, totals) in &other.by_extension {
+            let sum = self.by_extension.entry(extension.clone()).or_default();
+            sum.files += totals.files;
+            sum.bytes += totals.bytes;
+        }
+        self.largest.limit = self.largest.limit.max(other.largest.limit);
+        for (bytes, path) in other.largest.sorted() {
+            self.largest.offer(bytes, path);
+        }
+        for (kind, group) in &other.errors_by_kind {
+            let sum = self.errors_by_kind.entry(kind.clone()).or_default();
+            sum.count += group.count;
+            for path in &group.paths {
+                sum.offer(path);
+            }
+        }
+    }
+
+    /// Counts a removed file towards its extension.
+    fn count_extension(&mut self, path: &Path, bytes: u64) {
+        let extension = path
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy().to_lowercase()))
+            .unwrap_or_default();
+        let totals = self.by_extension.entry(extension).or_default();
+        totals.files += 1;
+        totals.bytes += bytes;
+    }
+
+    /// The extensions by total size, largest first.
+    pub(crate) fn extensions_by_size(&self) -> Vec<(&str, ExtensionTotals)> {
+        let mut extensions: Vec<(&str, ExtensionTotals)> = self
+            .by_extension
+            .iter()
+            .map(|(extension, totals)| (extension.as_str(), *totals))
+            .collect();
+        extensions.sort_by_key(|(_, totals)| std::cmp::Reverse(totals.bytes));
+        extensions
+    }
+
+    /// The stats as a JSON object, for `--summary-json-to`.
+    pub(crate) fn to_json(&self, root: &Path, dry_run: bool, elapsed: Duration) -> String {
+        self.write_json(&mut JsonObject::new(), root, dry_run, elapsed)
+            .finish()
+    }
+
+    /// Writes the fields of `to_json` to `out`, which `--json-schema` also describes.
+    pub(crate) fn write_json<'a, F: JsonFields>(
+        &self,
+        out: &'a mut F,
+        root: &Path,
+        dry_run: bool,
+        elapsed: Duration,
+    ) -> &'a mut F {
+        out.string("target", &root.to_string_lossy())
+            .bool("dry_run", dry_run)
+            .number("files", self.files)
+            .number("dirs", self.dirs)
+            .number("bytes", self.bytes)
+            .number("skipped_mount_points", self.skipped_mount_points)
+            .number("skipped_open_files", self.skipped_open_files)
+            .number("modified_since_scan", self.modified_since_scan)
+            .number("errors", self.errors)
+            .number("kept_different", self.kept_different)
+            .float("elapsed_secs", elapsed.as_secs_f64())
+    }
+}
+
+/// The entries a walk based removal is going to touch.
+pub(crate) struct Plan {
+    /// Files, symlinks and other non-folders to remove, in removal order.
+    pub(crate) files: Vec<Entry>,
+    /// Folders to remove once the files are gone, deepest first.
+    pub(crate) dirs: Vec<Entry>,
+    /// Nested mount points left alone, along with every folder holding one.
+    pub(crate) mount_points: Vec<PathBuf>,
+    /// Whether the root itself goes once everything below it is gone.
+    pub(crate) remove_root: bool,
+    /// Files the filter keeps and why, only collected with `explain`.
+    pub(crate) kept: Vec<(PathBuf, KeepReason)>,
+    /// Files kept for differing from, or missing in, the `compare_to` reference.
+    pub(crate) kept_different: u64,
+    /// Folders kept whole for holding something recently modified.
+    pub(crate) recent_dirs: Vec<PathBuf>,
+    /// With a path length limit, the longest path scanned and its length in characters.
+    pub(crate) longest_path: Option<(usize, PathBuf)>,
+    /// Files that would go but are kept for being open, with `exclude_open_files`.
+    pub(crate) open_files: Vec<PathBuf>,
+    /// Dangling symlinks kept for having changed within the filter's grace period.
+    pub(crate) recently_broken: Vec<PathBuf>,
+    /// With `dedupe`, how many sets of byte-identical files were found.
+    pub(crate) duplicate_sets: Option<u64>,
+    /// With `scan_cache`, whether the scan came from it.
+    pub(crate) scan_cache: Option<CacheUse>,
+}
+
+/// Scans `root` and works out what to remove. Every matching file goes. In a partial
+/// delete a folder only goes when the files planned for removal empty it and
+/// `keep_emptied_dirs` is not set; folders that were empty to begin with are kept.
+/// Folders still holding a kept mount point or open file always stay.
+pub(crate) fn plan(root: &Path, options: &RemoveOptions) -> Result<Plan, std::io::Error> {
+    let scan_options = ScanOptions {
+        follow_symlinks: options.follow_symlinks,
+        follow_junctions: options.follow_junctions,
+        fail_on_escape: options.fail_on_symlink_escape,
+        skip_mount_points: options.skip_mount_points,
+    };
+    let (entries, scan_cache) = match &options.scan_cache {
+        Some(cache) => {
+            let (entries, used) = scan_cache::scan(root, &scan_options, cache)?;
+            (entries, Some(used))
+        }
+        None => (scan::scan_with(root, &scan_options)?, None),
+    };
+    let now = SystemTime::now();
+    let recent_dirs = options.filter.recent_dirs(root, &entries, now);
+    let longest_path = options.filter.path_limit.as_ref().and_then(|limit| {
+        entries
+            .iter()
+            .map(|entry| (limit.length(relative_to(root, &entry.path)), &entry.path))
+            .max_by_key(|(length, _)| *length)
+            .map(|(length, path)| (length, path.clone()))
+    });
+
+    let open = if options.exclude_open_files {
+        diagnose::open_files()
+    } else {
+        HashSet::new()
+    };
+
+    let mut remaining: HashMap<PathBuf, usize> = HashMap::new();
+    for entry in &entries {
+        if let Some(parent) = entry.path.parent() {
+            *remaining.entry(parent.to_path_buf()).or_default() += 1;
+        }
+    }
+
+    let mut files = Vec::new();
+    let mut scanned_dirs = Vec::new();
+    let mut mount_points = Vec::new();
+    let mut kept = Vec::new();
+    let mut kept_different = 0;
+    let mut open_files = Vec::new();
+    let mut recently_broken = Vec::new();
+    for entry in entries {
+        if entry.mount_point {
+            mount_points.push(entry.path);
+        } else if entry.is_dir_like() {
+            scanned_dirs.push(entry);
+        } else if options.is_preserved(relative_to(root, &entry.path)) {
+            if options.lists_kept() {
+                kept.push((entry.path, KeepReason::Preserved));
+            }
+        } else if recent_dirs.iter().any(|dir| entry.path.starts_with(dir)) {
+            if options.lists_kept() {
+                kept.push((entry.path, KeepReason::RecentFolder));
+            }
+        } else {
+            let relative = relative_to(root, &entry.path);
+            match options.filter.keep_reason(relative, &entry.metadata, now) {
+                None if is_open(&entry, &open) => {
+                    if options.lists_kept() {
+                        kept.push((entry.path.clone(), KeepReason::Open));
+                    }
+                    open_files.push(entry.path);
+                }
+                None => files.push(entry),
+                Some(reason) => {
+                    if matches!(reason, KeepReason::Different | KeepReason::NotInReference) {
+                        kept_different += 1;
+                    }
+                    if reason == KeepReason::RecentlyBroken {
+                        recently_broken.push(entry.path.clone());
+                    }
+                    if options.lists_kept() {
+                        kept.push((entry.path, reason));
+                    }
+                }
+            }
+        }
+    }
+
+    let duplicate_sets = options.dedupe.map(|keep| {
+        let duplicates = duplicates::find_duplicates(&files, keep);
+        let (copies, others): (Vec<Entry>, Vec<Entry>) = std::mem::take(&mut files)
+            .into_iter()
+            .partition(|file| duplicates.copies.contains(&file.path));
+        files = copies;
+        if options.lists_kept() {
+            kept.extend(others.into_iter().map(|file| {
+                let reason = match duplicates.kept.contains(&file.path) {
+                    true => KeepReason::KeptCopy,
+                    false => KeepReason::Unique,
+                };
+                (file.path, reason)
+            }));
+        }
+        duplicates.sets
+    });
+
+    if let Some(order) = options.sort {
+        sort_entries(&mut files, order);
+    }
+
+    let mut emptied: HashSet<PathBuf> = HashSet::new();
+    for file in &files {
+        take_from_parent(&file.path, &mut remaining, &mut emptied);
+    }
+
+    // Parents are scanned before their children, so walking backwards reaches each
+    // folder only after everything inside it.
+    let mut dirs = Vec::new();
+    for dir in scanned_dirs.into_iter().rev() {
+        let goes = is_emptied(&dir.path, &remaining)
+            && !options.is_preserved(relative_to(root, &dir.path))
+            && !options.keeps_structure(relative_to(root, &dir.path))
+            && (!options.is_partial()
+                || (!options.keep_emptied_dirs
+                    && emptied.contains(&dir.path)
+                    && !options.filter.is_excluded(relative_to(root, &dir.path))));
+
+        if goes {
+            take_from_parent(&dir.path, &mut remaining, &mut emptied);
+            dirs.push(dir);
+        }
+    }
+
+    Ok(Plan {
+        files,
+        dirs,
+        mount_points,
+        remove_root: !options.is_partial()
+            && options.keep_structure_depth.is_none()
+            && is_emptied(root, &remaining),
+        kept,
+        kept_different,
+        recent_dirs,
+        longest_path,
+        open_files,
+        recently_broken,
+        duplicate_sets,
+        scan_cache,
+    })
+}
+
+/// True when `entry` is one of the `open` files, by device and inode.
+#[cfg(unix)]
+fn is_open(entry: &Entry, open: &HashSet<(u64, u64)>) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    !open.is_empty() && open.contains(&(entry.metadata.dev(), entry.metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn is_open(_entry: &Entry, _open: &HashSet<(u64, u64)>) -> bool {
+    false
+}
+
+/// True when every scanned entry of `dir` is planned for removal.
+fn is_emptied(dir: &Path, remaining: &HashMap<PathBuf, usize>) -> bool {
+    remaining.get(dir).copied().unwrap_or(0) == 0
+}
+
+/// Removes (or quarantines) what `plan` selects. In a whole-tree delete the root goes
+/// last, otherwise it is kept.
+pub(crate) fn remove_entries(
+    root: &Path,
+    options: &RemoveOptions,
+    output: &mut impl Write,
+    progress: &mut impl Progress,
+) -> Result<RemoveStats, RemoveError> {
+    let plan = plan(root, options).map_err(|source| RemoveError::Scan {
+        path: root.to_path_buf(),
+        source,
+    })?;
+
+    let root_removal = usize::from(plan.remove_root);
+    progress.start((plan.files.len() + plan.dirs.len() + root_removal) as u64);
+    let result = remove_planned(root, &plan, options, output, progress);
+    progress.finish();
+
+    result
+}
+
+fn remove_planned(
+    root: &Path,
+    plan: &Plan,
+    options: &RemoveOptions,
+    output: &mut impl Write,
+    progress: &mut impl Progress,
+) -> Result<RemoveStats, RemoveError> {
+    let mut stats = RemoveStats {
+        skipped_mount_points: plan.mount_points.len() as u64,
+        skipped_open_files: plan.open_files.len() as u64,
+        kept_different: plan.kept_different,
+        largest: LargestFiles::new(options.report_largest.unwrap_or(0)),
+        ..Default::default()
+    };
+    let mut sizes = SizeCounter::new(options.count_symlink_targets, options.sparse_aware);
+    let mut throttle = options.throttle_load.map(Throttle::new);
+    let mut touched: HashSet<PathBuf> = HashSet::new();
+    // Folders still holding an entry whose error was ignored or skipped, they cannot go either.
+    let mut held: HashSet<PathBuf> = HashSet::new();
+    let mut last_listed: Option<Instant> = None;
+    let mut preview = Preview::new(options);
+
+    report_mount_points(plan, options, output)?;
+
+    if options.verbosity >= 3 && !options.output_null {
+        for (path, reason) in &plan.kept {
+            if preview.admit() {
+                writeln!(output, "Kept {} ({})", options.display(path), reason)?;
+            }
+        }
+    }
+
+    for entry in &plan.files {
+        check_deadline(options, &stats)?;
+        if let Some(throttle) = &mut throttle {
+            throttle.wait();
+        }
+        progress.current(&entry.path);
+        if options.skip_modified_since_scan && is_modified_since_scan(entry) {
+            write_note(
+                options,
+                output,
+                format_args!(
+                    "Warning: skipping {}, it was modified since the scan",
+                    entry.path.to_string_lossy()
+                ),
+            )?;
+            hold_ancestors(root, &entry.path, &mut held);
+            stats.modified_since_scan += 1;
+            progress.advance();
+            continue;
+        }
+        let removed = remove_file_entry(root, entry, options);
+        hold_if_failed(&removed, root, &entry.path, options, &mut held);
+        if !check_removed(removed, &entry.path, options, output, &mut stats)? {
+            progress.advance();
+            continue;
+        }
+
+        let bytes = sizes.count(entry);
+        if options.verbosity >= 2 && listing_due(options, &mut last_listed) && preview.admit() {
+            let detail = if options.verbosity >= 3 {
+                file_details(entry, bytes)
+            } else {
+                String::new()
+            };
+            progress.suspend();
+            options.list(output, "Removed", &entry.path, &detail)?;
+            output.flush()?;
+            progress.resume();
+        }
+
+        stats.files += 1;
+        stats.bytes += bytes;
+        if options.group_by_extension {
+            stats.count_extension(&entry.path, bytes);
+        }
+        stats.largest.offer(bytes, &entry.path);
+        touch_parent(&entry.path, options, &mut touched);
+        progress.advance();
+    }
+
+    for dir in &plan.dirs {
+        check_deadline(options, &stats)?;
+        if let Some(throttle) = &mut throttle {
+            throttle.wait();
+        }
+        if held.contains(&dir.path) {
+            progress.advance();
+            continue;
+        }
+        progress.current(&dir.path);
+        let removed = if dir.followed {
+            remove_symlink(&dir.path)
+        } else {
+            std::fs::remove_dir(&dir.path)
+        };
+        hold_if_failed(&removed, root, &dir.path, options, &mut held);
+        if !check_removed(removed, &dir.path, options, output, &mut stats)? {
+            progress.advance();
+            continue;
+        }
+
+        if options.verbosity >= 1 && listing_due(options, &mut last_listed) && preview.admit() {
+            progress.suspend();
+            options.list(output, "Removed", &dir.path, "")?;
+            output.flush()?;
+            progress.resume();
+        }
+
+        stats.dirs += 1;
+        touch_parent(&dir.path, options, &mut touched);
+        progress.advance();
+    }
+
+    preview.finish(output)?;
+
+    if plan.remove_root && !held.contains(root) {
+        progress.current(root);
+        let removed = std::fs::remove_dir(root);
+        if check_removed(removed, root, options, output, &mut stats)? {
+            touch_parent(root, options, &mut touched);
+        }
+        progress.advance();
+    }
+
+    sync_dirs(&touched)?;
+
+    Ok(stats)
+}
+
+/// Cuts a listing short after `preview_limit` paths, counting those left out.
+struct Preview {
+    limit: Option<usize>,
+    listed: usize,
+    hidden: u64,
+}
+
+impl Preview {
+    fn new(options: &RemoveOptions) -> Self {
+        let machine_readable = options.output_null || options.bare_listing;
+        Preview {
+            limit: options.preview_limit.filter(|_| !machine_readable),
+            listed: 0,
+            hidden: 0,
+        }
+    }
+
+    /// Whether the next path still gets listed. Counts it as left out otherwise.
+    fn admit(&mut self) -> bool {
+        if self.limit.is_some_and(|limit| self.listed >= limit) {
+            self.hidden += 1;
+            return false;
+        }
+        self.listed += 1;
+        true
+    }
+
+    /// Says how many paths were left out, if any.
+    fn finish(&self, output: &mut impl Write) -> Result<(), std::io::Error> {
+        if self.hidden == 0 {
+            return Ok(());
+        }
+        writeln!(
+            output,
+            "... and {} more (use --preview-limit 0 for all)",
+            self.hidden
+        )
+    }
+}
+
+/// Whether the removed entry at hand gets listed: always, unless `verbose_interval` has
+/// not passed since the last one was.
+fn listing_due(options: &RemoveOptions, last_listed: &mut Option<Instant>) -> bool {
+    let Some(interval) = options.verbose_interval else {
+        return true;
+    };
+    if last_listed.is_some_and(|listed| listed.elapsed() < interval) {
+        return false;
+    }
+    *last_listed = Some(Instant::now());
+    true
+}
+
+/// The ` (size, modified mtime)` a `-vvv` run adds to each removed file.
+fn file_details(entry: &Entry, bytes: u64) -> String {
+    let modified = entry
+        .metadata
+        .modified()
+        .map_or_else(|_| "unknown".to_string(), units::format_timestamp);
+    format!(" ({}, modified {})", units::format_size(bytes), modified)
+}
+
+/// Remembers the folder `path` was removed from when it has to be synced.
+fn touch_parent(path: &Path, options: &RemoveOptions, touched: &mut HashSet<PathBuf>) {
+    if options.fsync {
+        if let Some(parent) = path.parent() {
+            touched.insert(parent.to_path_buf());
+        }
+    }
+}
+
+/// Flushes the entries of every folder in `dirs` to disk, so the removals survive a crash.
+/// Folders that were removed themselves are skipped, syncing their parent covers them.
+#[cfg(unix)]
+fn sync_dirs(dirs: &HashSet<PathBuf>) -> Result<(), std::io::Error> {
+    for dir in dirs {
+        let handle = match std::fs::File::open(dir) {
+            Ok(handle) => handle,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        handle.sync_all().map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("Failed to sync {}: {}", dir.to_string_lossy(), e),
+            )
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Folders cannot be opened for syncing here, so this is a no-op.
+#[cfg(not(unix))]
+fn sync_dirs(_dirs: &HashSet<PathBuf>) -> Result<(), std::io::Error> {
+    Ok(())
+}
+
+/// Fails once the `deadline` passed, reporting how far the removal got.
+fn check_deadline(options: &RemoveOptions, stats: &RemoveStats) -> Result<(), std::io::Error> {
+    match options.deadline {
+        Some(deadline) if SystemTime::now() >= deadline => Err(std::io::Error::new(
+            ErrorKind::TimedOut,
+            format!(
+                "Deadline passed after removing {} files ({}) and {} folders",
+                stats.files,
+                units::format_size(stats.bytes),
+                stats.dirs
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Runs the `exec` hook for a file, then deletes or quarantines it unless the hook
+/// is meant to replace that. With `secure_passes` regular files get overwritten first.
+fn remove_file_entry(
+    root: &Path,
+    entry: &Entry,
+    options: &RemoveOptions,
+) -> Result<(), std::io::Error> {
+    if let Some(passes) = options.secure_passes {
+        if entry.metadata.is_file() {
+            wipe::wipe_file(&entry.path, passes)?;
+        }
+    }
+
+    if let Some(exec) = &options.exec {
+        exec.run(&entry.path)?;
+        if options.exec_replace {
+            return Ok(());
+        }
+    }
+
+    match &options.quarantine {
+        Some(quarantine_dir) => move_to_quarantine(root, &entry.path, quarantine_dir),
+        // A link to a folder is unlinked in one call and never traversed.
+        None if entry.is_link() => remove_symlink(&entry.path),
+        None => std::fs::remove_file(&entry.path),
+    }
+}
+
+/// Returns whether `path` was removed. A failure matching `ignore_errors` is swallowed
+/// quietly, any other is passed on, or with `skip_errors` reported, counted and swallowed.
+fn check_removed(
+    removed: Result<(), std::io::Error>,
+    path: &Path,
+    options: &RemoveOptions,
+    output: &mut impl Write,
+    stats: &mut RemoveStats,
+) -> Result<bool, RemoveError> {
+    match removed {
+        Ok(()) => Ok(true),
+        Err(e) if is_ignored(&e, path, options) => {
+            if options.verbosity > 0 {
+                writeln!(output, "Ignored error: {}: {}", path.to_string_lossy(), e)?;
+            }
+            Ok(false)
+        }
+        Err(e) if options.skip_errors => {
+            if let (Some(denied_to), ErrorKind::PermissionDenied) = (&options.denied_to, e.kind()) {
+                record_denied(path, denied_to, output)?;
+            }
+            if options.group_errors {
+                let group = stats.errors_by_kind.entry(error_kind(&e)).or_default();
+                group.count += 1;
+                group.offer(path);
+            } else {
+                writeln!(output, "Error: {}: {}", path.to_string_lossy(), e)?;
+            }
+            stats.errors += 1;
+            Ok(false)
+        }
+        Err(source) => Err(RemoveError::Delete {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// Appends `path` to the `denied_to` list. Failing to only gets a warning, the error
+/// itself is still reported as usual.
+fn record_denied(
+    path: &Path,
+    denied_to: &Path,
+    output: &mut impl Write,
+) -> Result<(), std::io::Error> {
+    let appended = std::fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(denied_to)
+        .and_then(|mut file| writeln!(file, "{}", path.to_string_lossy()));
+    if let Err(e) = appended {
+        writeln!(
+            output,
+            "Warning: could not add {} to {}. Error: {}",
+            path.to_string_lossy(),
+            denied_to.to_string_lossy(),
+            e
+        )?;
+    }
+    Ok(())
+}
+
+/// Describes what kind of error `e` is, e.g. `Permission denied`. Errors without a
+/// more specific kind, like a failing `exec` command, go by their message instead.
+fn error_kind(e: &std::io::Error) -> String {
+    let kind = match e.kind() {
+        ErrorKind::Other => e.to_string(),
+        kind => kind.to_string(),
+    };
+    let mut chars = kind.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => kind,
+    }
+}
+
+/// Marks the folders holding `path` as staying when its removal failed with an error that
+/// is ignored or skipped, so they are not attempted and failing as not empty.
+fn hold_if_failed(
+    removed: &Result<(), std::io::Error>,
+    root: &Path,
+    path: &Path,
+    options: &RemoveOptions,
+    held: &mut HashSet<PathBuf>,
+) {
+    if removed
+        .as_ref()
+        .is_err_and(|e| options.skip_errors || is_ignored(e, path, options))
+    {
+        hold_ancestors(root, path, held);
+    }
+}
+
+/// Keeps every folder from the one holding `path` up to `root`.
+fn hold_ancestors(root: &Path, path: &Path, held: &mut HashSet<PathBuf>) {
+    let ancestors = path.ancestors().skip(1);
+    held.extend(
+        ancestors
+            .take_while(|ancestor| ancestor.starts_with(root))
+            .map(Path::to_path_buf),
+    );
+}
+
+/// True when the mtime of `entry` on disk differs from the one the scan saw. An entry
+/// that is gone or cannot be read is left for the delete to report.
+fn is_modified_since_scan(entry: &Entry) -> bool {
+    std::fs::symlink_metadata(&entry.path)
+        .is_ok_and(|now| now.modified().ok() != entry.metadata.modified().ok())
+}
+
+fn is_ignored(error: &std::io::Error, path: &Path, options: &RemoveOptions) -> bool {
+    let line = format!("{}: {}", path.to_string_lossy(), error);
+    options
+        .ignore_errors
+        .iter()
+        .any(|regex| regex.is_match(&line))
+}
+
+/// Lists what `remove_entries` would remove without touching anything. With `explain`
+/// the files that are kept get listed too, each with the reason.
+pub(crate) fn dry_run(
+    root: &Path,
+    options: &RemoveOptions,
+    output: &mut impl Write,
+) -> Result<RemoveStats, std::io::Error> {
+    let plan = plan(root, options)?;
+    let mut stats = RemoveStats {
+        skipped_mount_points: plan.mount_points.len() as u64,
+        skipped_open_files: plan.open_files.len() as u64,
+        kept_different: plan.kept_different,
+        largest: LargestFiles::new(options.report_largest.unwrap_or(0)),
+        ..Default::default()
+    };
+    let mut sizes = SizeCounter::new(options.count_symlink_targets, options.sparse_aware);
+
+    report_mount_points(&plan, options, output)?;
+
+    let verb = if options.explain {
+        "DELETE"
+    } else {
+        "Would remove"
+    };
+
+    let mut preview = Preview::new(options);
+
+    for entry in &plan.files {
+        let bytes = sizes.count(entry);
+        if preview.admit() {
+            let size = format!(" ({})", units::format_size(bytes));
+            options.list(output, verb, &entry.path, &size)?;
+        }
+        stats.files += 1;
+        stats.bytes += bytes;
+        if options.group_by_extension {
+            stats.count_extension(&entry.path, bytes);
+        }
+        stats.largest.offer(bytes, &entry.path);
+    }
+
+    for (path, reason) in &plan.kept {
+        if preview.admit() {
+            writeln!(output, "KEEP {} ({})", options.display(path), reason)?;
+        }
+    }
+
+    for dir in &plan.dirs {
+        if preview.admit() {
+            let (verb, detail) = if options.explain && options.is_partial() {
+                ("DELETE", " (emptied folder)")
+            } else {
+                (verb, "")
+            };
+            options.list(output, verb, &dir.path, detail)?;
+        }
+        stats.dirs += 1;
+    }
+
+    preview.finish(output)?;
+
+    Ok(stats)
+}
+
+/// Notes what the plan skips. They go to stderr when stdout only holds NUL separated paths,
+/// and to stdout when the paths are listed into a file.
+fn report_mount_points(
+    plan: &Plan,
+    options: &RemoveOptions,
+    output: &mut impl Write,
+) -> Result<(), std::io::Error> {
+    if options.bare_listing {
+        return write_skipped(plan, &mut std::io::stdout());
+    }
+    if options.output_null {
+        return write_skipped(plan, &mut std::io::stderr());
+    }
+
+    write_skipped(plan, output)
+}
+
+/// Writes a note found while deleting where the plan notes go, keeping it out of a bare or
+/// NUL separated listing.
+fn write_note(
+    options: &RemoveOptions,
+    output: &mut impl Write,
+    note: std::fmt::Arguments,
+) -> Result<(), std::io::Error> {
+    if options.bare_listing {
+        return writeln!(std::io::stdout(), "{note}");
+    }
+    if options.output_null {
+        return writeln!(std::io::stderr(), "{note}");
+    }
+
+    writeln!(output, "{note}")
+}
+
+fn write_skipped(plan: &Plan, output: &mut impl Write) -> Result<(), std::io::Error> {
+    match &plan.scan_cache {
+        Some(CacheUse::Loaded(cache)) => writeln!(
+            output,
+            "Reusing the scan cached in {}",
+            cache.to_string_lossy()
+        )?,
+        Some(CacheUse::NotWritten(cache, e)) => writeln!(
+            output,
+            "Warning: failed to write the scan cache {}. Error: {}",
+            cache.to_string_lossy(),
+            e
+        )?,
+        Some(CacheUse::Written(_)) | None => {}
+    }
+
+    for mount_point in &plan.mount_points {
+        writeln!(
+            output,
+            "Warning: skipping mount point {}",
+            mount_point.to_string_lossy()
+        )?;
+    }
+
+    for dir in &plan.recent_dirs {
+        writeln!(
+            output,
+            "Keeping recently used folder {}",
+            dir.to_string_lossy()
+        )?;
+    }
+
+    for file in &plan.open_files {
+        writeln!(
+            output,
+            "Keeping {} as a process holds it open",
+            file.to_string_lossy()
+        )?;
+    }
+
+    for link in &plan.recently_broken {
+        writeln!(
+            output,
+            "Keeping dangling symlink {} as it changed within --delete-broken-after",
+            link.to_string_lossy()
+        )?;
+    }
+
+    if let Some(sets) = plan.duplicate_sets {
+        let bytes = plan.files.iter().map(|file| file.metadata.len()).sum();
+        writeln!(
+            output,
+            "Duplicate sets found: {}, with {} extra copies taking up {}",
+            sets,
+            plan.files.len(),
+            units::format_size(bytes)
+        )?;
+    }
+
+    if let Some((length, path)) = &plan.longest_path {
+        writeln!(
+            output,
+            "Longest path is {} characters: {}",
+            length,
+            path.to_string_lossy()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Works out the bytes removing an entry frees. A symlink counts as the link itself,
+/// never its target. Content reached through a followed symlink counts nothing unless
+/// `count_symlink_targets` is set, and is then counted once per physical file even if
+/// several paths lead to it. With `sparse_aware`, a size is the blocks allocated on disk.
+struct SizeCounter {
+    count_symlink_targets: bool,
+    sparse_aware: bool,
+    seen: HashSet<FileId>,
+}
+
+#[cfg(unix)]
+type FileId = (u64, u64);
+
+#[cfg(not(unix))]
+type FileId = PathBuf;
+
+impl SizeCounter {
+    fn new(count_symlink_targets: bool, sparse_aware: bool) -> Self {
+        SizeCounter {
+            count_symlink_targets,
+            sparse_aware,
+            seen: HashSet::new(),
+        }
+    }
+
+    fn count(&mut self, entry: &Entry) -> u64 {
+        if !entry.via_symlink {
+            return self.size(entry);
+        }
+
+        if !self.count_symlink_targets {
+            return 0;
+        }
+
+        match file_id(entry) {
+            Some(id) if !self.seen.insert(id) => 0,
+            _ => self.size(entry),
+        }
+    }
+
+    fn size(&self, entry: &Entry) -> u64 {
+        if self.sparse_aware {
+            disk_usage(&entry.metadata)
+        } else {
+            entry.metadata.len()
+        }
+    }
+}
+
+/// The bytes allocated for an entry on disk, in the 512 byte units `st_blocks` counts.
+#[cfg(unix)]
+fn disk_usage(metadata: &std::fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    metadata.blocks() * 512
+}
+
+/// Block counts are not available, so the apparent size it is.
+#[cfg(not(unix))]
+fn disk_usage(metadata: &std::fs::Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+fn file_id(entry: &Entry) -> Option<FileId> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((entry.metadata.dev(), entry.metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(entry: &Entry) -> Option<FileId> {
+    std::fs::canonicalize(&entry.path).ok()
+}
+
+#[cfg(not(windows))]
+pub(crate) fn remove_symlink(path: &Path) -> Result<(), std::io::Error> {
+    std::fs::remove_file(path)
+}
+
+/// Windows removes links to directories like directories.
+#[cfg(windows)]
+pub(crate) fn remove_symlink(path: &Path) -> Result<(), std::io::Error> {
+    std::fs::remove_file(path).or_else(|_| std::fs::remove_dir(path))
+}
+
+fn sort_entries(entries: &mut [Entry], order: SortOrder) {
+    match order {
+        SortOrder::Size => entries.sort_by_key(|e| std::cmp::Reverse(e.metadata.len())),
+        SortOrder::Mtime => {
+            entries.sort_by_key(|e| e.metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH))
+        }
+        SortOrder::Name => entries.sort_by(|a, b| a.path.cmp(&b.path)),
+        SortOrder::Score => {
+            let now = SystemTime::now();
+            entries.sort_by_cached_key(|e| std::cmp::Reverse(eviction_score(&e.metadata, now)))
+        }
+    }
+}
+
+/// The worth of evicting a file: its size in bytes times its age in seconds since it was
+/// last modified. A file modified in the future, or without an mtime, counts as new.
+fn eviction_score(metadata: &std::fs::Metadata, now: SystemTime) -> u128 {
+    let age = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| now.duration_since(modified).ok())
+        .unwrap_or_default();
+    u128::from(metadata.len()) * u128::from(age.as_secs())
+}
+
+fn relative_to<'a>(root: &Path, path: &'a Path) -> &'a Path {
+    path.strip_prefix(root)
+        .expect("Scanned entries are always below the root")
+}
+
+/// Records that `path` is leaving its parent folder.
+fn take_from_parent(
+    path: &Path,
+    remaining: &mut HashMap<PathBuf, usize>,
+    emptied: &mut HashSet<PathBuf>,
+) {
+    if let Some(parent) = path.parent() {
+        if let Some(count) = remaining.get_mut(parent) {
+            *count = count.saturating_sub(1);
+        }
+        emptied.insert(parent.to_path_buf());
+    }
+}
+
+/// Moves `path` to the same relative location below `quarantine_dir`. Falls back to
+/// copy and delete when the quarantine lives on another device.
+fn move_to_quarantine(
+    root: &Path,
+    path: &Path,
+    quarantine_dir: &Path,
+) -> Result<(), std::io::Error> {
+    let target = quarantine_dir.join(relative_to(root, path));
+
+    if std::fs::symlink_metadata(&target).is_ok() {
+        return Err(std::io::Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} is already quarantined", target.to_string_lossy()),
+        ));
+    }
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match std::fs::rename(path, &target) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy_entry(path, &target)?;
+            std::fs::remove_file(path)
+        }
+        result => result,
+    }
+}
+
+#[cfg(unix)]
+fn copy_entry(from: &Path, to: &Path) -> Result<(), std::io::Error> {
+    if std::fs::symlink_metadata(from)?.file_type().is_symlink() {
+        return std::os::unix::fs::symlink(std::fs::read_link(from)?, to);
+    }
+
+    std::fs::copy(from, to).map(|_| ())
+}
+
+#[cfg(not(unix))]
+fn copy_entry(from: &Path, to: &Path) -> Result<(), std::io::Error> {
+    std::fs::copy(from, to).map(|_| ())
+}
+
+#[cfg(test)]
+mod remove_entries_should {
+    use std::{io::Write, sync::atomic::AtomicU8};
+
+    use super::*;
+    use crate::{filter::DanglingLinks, glob::Glob, progress::NoProgress};
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn create_file(path: &str, size: usize) {
+        std::fs::File::create(path)
+            .unwrap()
+            .write_all(&vec![b'a'; size])
+            .unwrap();
+    }
+
+    /// Creates `big.bin` and `nested/big.bin` of 100 bytes and `small.txt` of 1 byte.
+    fn create_mixed_tree() -> String {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/remove-dir-{unique}");
+
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        create_file(&format!("{dir}/big.bin"), 100);
+        create_file(&format!("{dir}/nested/big.bin"), 100);
+        create_file(&format!("{dir}/small.txt"), 1);
+
+        dir
+    }
+
+    fn size_filter() -> Filter {
+        Filter {
+            min_size: Some(50),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sync_the_folders_it_removed_from() {
+        let dir = create_mixed_tree();
+        let options = RemoveOptions {
+            fsync: true,
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!((stats.files, stats.dirs), (3, 1));
+        assert!(!Path::new(&dir).exists());
+    }
+
+    #[test]
+    fn skip_a_file_modified_between_scan_and_delete() {
+        for output_null in [false, true] {
+            let dir = create_mixed_tree();
+            let root = Path::new(&dir);
+            let options = RemoveOptions {
+                skip_modified_since_scan: true,
+                output_null,
+                verbosity: if output_null { 2 } else { 0 },
+                ..Default::default()
+            };
+            let planned = plan(root, &options).unwrap();
+            let touched = std::fs::File::options()
+                .write(true)
+                .open(format!("{dir}/nested/big.bin"))
+                .unwrap();
+            touched
+                .set_modified(SystemTime::now() - Duration::from_secs(60))
+                .unwrap();
+            let mut output = Vec::new();
+
+            let stats =
+                remove_planned(root, &planned, &options, &mut output, &mut NoProgress).unwrap();
+            let kept = Path::new(&format!("{dir}/nested/big.bin")).exists();
+            std::fs::remove_dir_all(&dir).unwrap();
+
+            assert_eq!((stats.files, stats.dirs), (2, 0));
+            assert_eq!(stats.modified_since_scan, 1);
+            assert!(kept);
+            let output = String::from_utf8(output).unwrap();
+            if output_null {
+                // The warning went to stderr, leaving only the NUL separated listing.
+                assert!(output.contains('\0'), "Missing listing {output:?}");
+                assert!(!output.contains('\n'), "Unexpected listing {output:?}");
+            } else {
+                assert_eq!(
+                    output,
+                    format!(
+                        "Warning: skipping {dir}/nested/big.bin, it was modified since the scan\n"
+                    )
+                );
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn report_dangling_symlinks_kept_for_being_too_new() {
+        let dir = create_mixed_tree();
+        std::os::unix::fs::symlink("missing.txt", format!("{dir}/nested/dangling")).unwrap();
+        let options = RemoveOptions {
+            filter: Filter {
+                dangling: Some(DanglingLinks {
+                    root: PathBuf::from(&dir),
+                    only: true,
+                    grace: Some(Duration::from_secs(60 * 60)),
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+        let link_kept = std::fs::symlink_metadata(format!("{dir}/nested/dangling")).is_ok();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(stats.files, 0);
+        assert!(link_kept);
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("Keeping dangling symlink {dir}/nested/dangling as it changed within --delete-broken-after\n")
+        );
+    }
+
+    #[test]
+    fn delete_only_empty_files_and_the_folders_emptied() {
+        let dir = create_mixed_tree();
+        std::fs::create_dir_all(format!("{dir}/downloads")).unwrap();
+        create_file(&format!("{dir}/downloads/failed.part"), 0);
+        create_file(&format!("{dir}/nested/placeholder.out"), 0);
+        create_file(&format!("{dir}/empty.txt"), 0);
+        let options = RemoveOptions {
+            filter: Filter {
+                empty_only: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+        let exists = |path: &str| Path::new(&format!("{dir}/{path}")).exists();
+        let left = [
+            "big.bin",
+            "small.txt",
+            "nested/big.bin",
+            "nested/placeholder.out",
+            "empty.txt",
+            "downloads",
+        ]
+        .map(exists);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((stats.files, stats.dirs), (3, 1));
+        assert_eq!(left, [true, true, true, false, false, false]);
+    }
+
+    /// Links `link` to the folder `target` with a directory junction.
+    #[cfg(windows)]
+    fn create_junction(link: &str, target: &str) {
+        let status = std::process::Command::new("cmd")
+            .args(["/C", "mklink", "/J"])
+            .arg(link.replace('/', "\\"))
+            .arg(target.replace('/', "\\"))
+            .stdout(std::process::Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "mklink /J failed");
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn remove_a_junction_without_touching_its_target() {
+        let dir = create_mixed_tree();
+        let target = format!("{dir}-junction-target");
+        std::fs::create_dir_all(format!("{target}/nested")).unwrap();
+        create_file(&format!("{target}/nested/precious.bin"), 10);
+        create_junction(&format!("{dir}/nested/junction"), &target);
+
+        let stats = remove_entries(
+            Path::new(&dir),
+            &RemoveOptions::default(),
+            &mut Vec::new(),
+            &mut NoProgress,
+        );
+        let target_kept = Path::new(&format!("{target}/nested/precious.bin")).exists();
+        std::fs::remove_dir_all(&target).unwrap();
+
+        assert!(stats.is_ok(), "{stats:?}");
+        assert!(!Path::new(&dir).exists());
+        assert!(target_kept);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn delete_the_contents_of_a_junction_target_when_following_junctions() {
+        let dir = create_mixed_tree();
+        let target = format!("{dir}-junction-target");
+        std::fs::create_dir_all(format!("{target}/nested")).unwrap();
+        create_file(&format!("{target}/nested/followed.bin"), 10);
+        create_junction(&format!("{dir}/nested/junction"), &target);
+        let options = RemoveOptions {
+            follow_junctions: true,
+            ..Default::default()
+        };
+
+        let stats = remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress);
+        let target_emptied = !Path::new(&format!("{target}/nested")).exists();
+        std::fs::remove_dir_all(&target).unwrap();
+
+        assert!(stats.is_ok(), "{stats:?}");
+        assert!(!Path::new(&dir).exists());
+        assert!(target_emptied);
+    }
+
+    #[test]
+    fn delete_the_extra_copies_of_identical_files_and_the_folders_emptied() {
+        let dir = create_mixed_tree();
+        std::fs::create_dir(format!("{dir}/copies")).unwrap();
+        create_file(&format!("{dir}/copies/big.bin"), 100);
+        std::fs::File::options()
+            .write(true)
+            .open(format!("{dir}/big.bin"))
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(60 * 60))
+            .unwrap();
+        let options = RemoveOptions {
+            dedupe: Some(DedupeKeep::Oldest),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+        let left = (
+            Path::new(&format!("{dir}/big.bin")).exists(),
+            Path::new(&format!("{dir}/small.txt")).exists(),
+            Path::new(&format!("{dir}/nested")).exists(),
+            Path::new(&format!("{dir}/copies")).exists(),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((stats.files, stats.dirs, stats.bytes), (2, 2, 200));
+        assert_eq!(left, (true, true, false, false));
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Duplicate sets found: 1, with 2 extra copies taking up 200 B\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn list_the_entries_of_unwritable_folders_as_denied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = create_mixed_tree();
+        let denied = format!("{dir}-denied.txt");
+        std::fs::create_dir(format!("{dir}/locked")).unwrap();
+        create_file(&format!("{dir}/locked/a.txt"), 1);
+        create_file(&format!("{dir}/locked/b.txt"), 1);
+        let locked = std::fs::Permissions::from_mode(0o555);
+        std::fs::set_permissions(format!("{dir}/locked"), locked).unwrap();
+        let options = RemoveOptions {
+            skip_errors: true,
+            denied_to: Some(PathBuf::from(&denied)),
+            ..Default::default()
+        };
+
+        let unlock = || {
+            let unlocked = std::fs::Permissions::from_mode(0o755);
+            std::fs::set_permissions(format!("{dir}/locked"), unlocked).unwrap();
+        };
+        // Root ignores the folder's permissions, so there is nothing to deny.
+        if std::fs::File::create(format!("{dir}/locked/probe")).is_ok() {
+            unlock();
+            std::fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+
+        let stats = remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress);
+        let listed = std::fs::read_to_string(&denied).unwrap();
+        unlock();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&denied).unwrap();
+
+        assert!(stats.is_ok());
+        let mut listed: Vec<&str> = listed.lines().collect();
+        listed.sort();
+        assert_eq!(
+            listed,
+            [format!("{dir}/locked/a.txt"), format!("{dir}/locked/b.txt")]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn keep_open_files_and_their_folders_when_excluding_them() {
+        let dir = create_mixed_tree();
+        let open = std::fs::File::open(format!("{dir}/nested/big.bin")).unwrap();
+        let options = RemoveOptions {
+            exclude_open_files: true,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+        drop(open);
+        let left: Vec<_> = scan::scan_dir(Path::new(&dir))
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.path)
+            .collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((stats.files, stats.dirs), (2, 0));
+        assert_eq!(stats.skipped_open_files, 1);
+        assert_eq!(
+            left,
+            [
+                PathBuf::from(format!("{dir}/nested")),
+                PathBuf::from(format!("{dir}/nested/big.bin"))
+            ]
+        );
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output,
+            format!("Keeping {dir}/nested/big.bin as a process holds it open\n")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn count_the_blocks_of_sparse_files_when_sparse_aware() {
+        use std::os::unix::fs::MetadataExt;
+
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/remove-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+        let image = std::fs::File::create(format!("{dir}/disk.img")).unwrap();
+        image.set_len(64 * 1024 * 1024).unwrap();
+        let blocks = image.metadata().unwrap().blocks() * 512;
+        drop(image);
+        let options = RemoveOptions {
+            sparse_aware: true,
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!(stats.files, 1);
+        assert_eq!(stats.bytes, blocks);
+        assert!(stats.bytes < 64 * 1024 * 1024, "{}", stats.bytes);
+    }
+
+    #[test]
+    fn remove_only_matching_files_and_emptied_dirs() {
+        let dir = create_mixed_tree();
+        let options = RemoveOptions {
+            filter: size_filter(),
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!(
+            stats,
+            RemoveStats {
+                files: 2,
+                dirs: 1,
+                bytes: 200,
+                ..Default::default()
+            }
+        );
+        assert!(Path::new(&format!("{dir}/small.txt")).exists());
+        assert!(!Path::new(&format!("{dir}/big.bin")).exists());
+        assert!(!Path::new(&format!("{dir}/nested")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_folders_emptied_by_nested_deletes() {
+        let dir = create_mixed_tree();
+        std::fs::create_dir_all(format!("{dir}/outer/inner")).unwrap();
+        create_file(&format!("{dir}/outer/inner/big.bin"), 100);
+
+        let options = RemoveOptions {
+            filter: size_filter(),
+            ..Default::default()
+        };
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!(stats.dirs, 3, "nested, outer/inner and outer should go");
+        assert!(!Path::new(&format!("{dir}/outer")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keep_emptied_folders_when_asked() {
+        let dir = create_mixed_tree();
+        let options = RemoveOptions {
+            filter: size_filter(),
+            keep_emptied_dirs: true,
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.dirs, 0);
+        assert!(Path::new(&format!("{dir}/nested")).is_dir());
+        assert!(!Path::new(&format!("{dir}/nested/big.bin")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keep_folders_that_were_already_empty() {
+        let dir = create_mixed_tree();
+        std::fs::create_dir_all(format!("{dir}/already-empty")).unwrap();
+
+        let options = RemoveOptions {
+            filter: size_filter(),
+            ..Default::default()
+        };
+        remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert!(Path::new(&format!("{dir}/already-empty")).is_dir());
+        assert!(!Path::new(&format!("{dir}/nested")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn move_matching_files_to_quarantine() {
+        let dir = create_mixed_tree();
+        let quarantine = format!("{dir}-quarantine");
+        let options = RemoveOptions {
+            filter: size_filter(),
+            quarantine: Some(PathBuf::from(&quarantine)),
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!(stats.files, 2);
+        assert_eq!(stats.bytes, 200);
+        assert!(Path::new(&format!("{quarantine}/big.bin")).exists());
+        assert!(Path::new(&format!("{quarantine}/nested/big.bin")).exists());
+        assert!(!Path::new(&format!("{quarantine}/small.txt")).exists());
+        assert!(Path::new(&format!("{dir}/small.txt")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&quarantine).unwrap();
+    }
+
+    #[test]
+    fn refuse_to_overwrite_quarantined_files() {
+        let dir = create_mixed_tree();
+        let quarantine = format!("{dir}-quarantine");
+        std::fs::create_dir_all(&quarantine).unwrap();
+        create_file(&format!("{quarantine}/big.bin"), 5);
+
+        let options = RemoveOptions {
+            filter: size_filter(),
+            quarantine: Some(PathBuf::from(&quarantine)),
+            ..Default::default()
+        };
+        let result = remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress);
+
+        assert!(
+            matches!(
+                result,
+                Err(RemoveError::Delete { ref source, .. }) if source.kind() == ErrorKind::AlreadyExists
+            ),
+            "{result:?}"
+        );
+        assert_eq!(
+            std::fs::metadata(format!("{quarantine}/big.bin"))
+                .unwrap()
+                .len(),
+            5,
+            "Quarantined file was overwritten"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&quarantine).unwrap();
+    }
+
+    #[test]
+    fn remove_whole_tree_including_root_when_not_partial() {
+        let dir = create_mixed_tree();
+        std::fs::create_dir_all(format!("{dir}/already-empty")).unwrap();
+        let options = RemoveOptions {
+            verbosity: 2,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+
+        assert!(!Path::new(&dir).exists(), "Root was not removed");
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.dirs, 2);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().count(),
+            5,
+            "Expected a line per entry: {output}"
+        );
+    }
+
+    #[test]
+    fn list_at_most_one_entry_per_interval_when_throttled() {
+        let dir = create_mixed_tree();
+        for i in 0..50 {
+            create_file(&format!("{dir}/quick-{i}.txt"), 1);
+        }
+        let options = RemoveOptions {
+            verbosity: 2,
+            verbose_interval: Some(Duration::from_secs(3600)),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+
+        assert_eq!(stats.files, 53);
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 1, "{output}");
+        assert!(output.starts_with("Removed "), "{output}");
+    }
+
+    #[test]
+    fn print_more_at_every_verbosity_level() {
+        let output_at = |verbosity: u8| {
+            let dir = create_mixed_tree();
+            let options = RemoveOptions {
+                filter: size_filter(),
+                verbosity,
+                ..Default::default()
+            };
+            let mut output = Vec::new();
+            remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let levels: Vec<String> = (0..=3).map(output_at).collect();
+
+        assert_eq!(levels[0], "");
+        assert!(levels[1].ends_with("nested\n"), "{}", levels[1]);
+        assert_eq!(levels[1].lines().count(), 1);
+        assert_eq!(levels[2].lines().count(), 3);
+        assert!(levels[2].contains("big.bin\n"), "{}", levels[2]);
+        assert_eq!(levels[3].lines().count(), 4);
+        assert!(levels[3].contains("small.txt (too small)"), "{}", levels[3]);
+        assert!(
+            levels[3].contains("big.bin (100 B, modified 20"),
+            "{}",
+            levels[3]
+        );
+        for pair in levels.windows(2) {
+            assert!(pair[1].len() > pair[0].len());
+        }
+    }
+
+    #[test]
+    fn not_touch_anything_on_dry_run() {
+        let dir = create_mixed_tree();
+        let options = RemoveOptions {
+            filter: size_filter(),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+
+        let stats = dry_run(Path::new(&dir), &options, &mut output).unwrap();
+
+        assert_eq!(
+            stats,
+            RemoveStats {
+                files: 2,
+                dirs: 1,
+                bytes: 200,
+                ..Default::default()
+            }
+        );
+        assert!(Path::new(&format!("{dir}/nested/big.bin")).exists());
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.contains("big.bin (100 B)"),
+            "Missing file in {output}"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keep_preserved_paths_and_the_folders_holding_them() {
+        let dir = create_mixed_tree();
+        std::fs::create_dir_all(format!("{dir}/nested/deeper/kept")).unwrap();
+        create_file(&format!("{dir}/nested/deeper/important.db"), 10);
+        let options = RemoveOptions {
+            preserve: vec![
+                PathBuf::from("nested/deeper/important.db"),
+                PathBuf::from("nested/deeper/kept"),
+            ],
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!(stats.files, 3);
+        assert_eq!(stats.dirs, 0);
+        assert!(Path::new(&format!("{dir}/nested/deeper/important.db")).exists());
+        assert!(Path::new(&format!("{dir}/nested/deeper/kept")).is_dir());
+        assert!(!Path::new(&format!("{dir}/nested/big.bin")).exists());
+        assert!(!Path::new(&format!("{dir}/small.txt")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn explain_every_decision_on_dry_run() {
+        let dir = create_mixed_tree();
+        let options = RemoveOptions {
+            filter: Filter {
+                exclude: vec![Glob::new("nested")],
+                ..size_filter()
+            },
+            explain: true,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+
+        let stats = dry_run(Path::new(&dir), &options, &mut output).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(stats.files, 1);
+        let output = String::from_utf8(output).unwrap();
+        let mut lines: Vec<&str> = output.lines().collect();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                format!("DELETE {dir}/big.bin (100 B)"),
+                format!("KEEP {dir}/nested/big.bin (excluded by nested)"),
+                format!("KEEP {dir}/small.txt (too small)"),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_removed_files_by_extension() {
+        let dir = create_mixed_tree();
+        create_file(&format!("{dir}/nested/Makefile"), 10);
+        create_file(&format!("{dir}/nested/other.TXT"), 2);
+        let options = RemoveOptions {
+            group_by_extension: true,
+            ..Default::default()
+        };
+
+        let planned = dry_run(Path::new(&dir), &options, &mut Vec::new()).unwrap();
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!(planned.by_extension, stats.by_extension);
+        assert_eq!(
+            stats.extensions_by_size(),
+            vec![
+                (
+                    ".bin",
+                    ExtensionTotals {
+                        files: 2,
+                        bytes: 200
+                    }
+                ),
+                (
+                    "",
+                    ExtensionTotals {
+                        files: 1,
+                        bytes: 10
+                    }
+                ),
+                (".txt", ExtensionTotals { files: 2, bytes: 3 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn keep_only_the_largest_files() {
+        let dir = create_mixed_tree();
+        create_file(&format!("{dir}/nested/huge.bin"), 300);
+        create_file(&format!("{dir}/medium.bin"), 50);
+        let options = RemoveOptions {
+            report_largest: Some(3),
+            ..Default::default()
+        };
+
+        let planned = dry_run(Path::new(&dir), &options, &mut Vec::new()).unwrap();
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        let largest: Vec<(u64, PathBuf)> = stats
+            .largest
+            .sorted()
+            .into_iter()
+            .map(|(bytes, path)| (bytes, path.to_path_buf()))
+            .collect();
+        assert_eq!(planned.largest, stats.largest);
+        assert_eq!(
+            largest,
+            vec![
+                (300, Path::new(&dir).join("nested/huge.bin")),
+                (100, Path::new(&dir).join("big.bin")),
+                (100, Path::new(&dir).join("nested/big.bin")),
+            ]
+        );
+    }
+
+    #[test]
+    fn quote_listed_paths_when_asked() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/remove-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/a folder")).unwrap();
+        create_file(&format!("{dir}/a folder/it's"), 1);
+
+        let options = RemoveOptions {
+            shell_escape_paths: true,
+            verbosity: 2,
+            ..Default::default()
+        };
+        let mut dry_output = Vec::new();
+        dry_run(Path::new(&dir), &options, &mut dry_output).unwrap();
+        let mut output = Vec::new();
+        remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+
+        assert_eq!(
+            String::from_utf8(dry_output).unwrap(),
+            format!(
+                "Would remove '{dir}/a folder/it'\\''s' (1 B)\nWould remove '{dir}/a folder'\n"
+            )
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            format!("Removed '{dir}/a folder/it'\\''s'\nRemoved '{dir}/a folder'\n")
+        );
+    }
+
+    #[test]
+    fn cut_a_dry_run_listing_short_at_the_preview_limit() {
+        let dir = create_mixed_tree();
+        let listed = |preview_limit: Option<usize>, bare_listing: bool| {
+            let options = RemoveOptions {
+                preview_limit,
+                bare_listing,
+                ..Default::default()
+            };
+            let mut output = Vec::new();
+            let stats = dry_run(Path::new(&dir), &options, &mut output).unwrap();
+            (String::from_utf8(output).unwrap(), stats.files + stats.dirs)
+        };
+        let (limited, counted) = listed(Some(2), false);
+        let (unlimited, _) = listed(None, false);
+        let (bare, _) = listed(Some(2), true);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let lines: Vec<&str> = limited.lines().collect();
+        assert_eq!(lines.len(), 3, "{limited}");
+        assert!(lines[0].starts_with("Would remove "), "{limited}");
+        assert_eq!(
+            lines[2],
+            format!(
+                "... and {} more (use --preview-limit 0 for all)",
+                counted - 2
+            )
+        );
+        assert_eq!(unlimited.lines().count() as u64, counted);
+        assert_eq!(bare.lines().count() as u64, counted);
+    }
+
+    #[test]
+    fn list_paths_relative_to_a_base() {
+        let dir = create_mixed_tree();
+        std::fs::create_dir_all(format!("{dir}/nested/deeper")).unwrap();
+        create_file(&format!("{dir}/nested/deeper/file.rs"), 1);
+
+        let listed = |base: &str| {
+            let options = RemoveOptions {
+                filter: Filter {
+                    include: vec![Glob::new("*.rs")],
+                    ..Default::default()
+                },
+                relative_to: Some(PathBuf::from(base)),
+                ..Default::default()
+            };
+            let mut output = Vec::new();
+            dry_run(Path::new(&dir), &options, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        let from_root = listed(&dir);
+        let from_nested = listed(&format!("{dir}/nested"));
+        let from_elsewhere = listed("./elsewhere");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let listing = |folder: PathBuf| {
+            format!(
+                "Would remove {} (1 B)\nWould remove {}\n",
+                folder.join("file.rs").to_string_lossy(),
+                folder.to_string_lossy()
+            )
+        };
+        assert_eq!(from_root, listing(Path::new("nested").join("deeper")));
+        assert_eq!(from_nested, listing(PathBuf::from("deeper")));
+        assert!(from_elsewhere.contains(&dir), "{from_elsewhere}");
+    }
+
+    #[test]
+    fn separate_listed_paths_with_nul_bytes() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/remove-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        // Windows does not allow newlines in names.
+        let name = if cfg!(windows) {
+            "two lines"
+        } else {
+            "two\nlines"
+        };
+        create_file(&format!("{dir}/nested/{name}"), 1);
+
+        let options = RemoveOptions {
+            output_null: true,
+            verbosity: 2,
+            relative_to: Some(PathBuf::from(&dir)),
+            ..Default::default()
+        };
+        let mut dry_output = Vec::new();
+        dry_run(Path::new(&dir), &options, &mut dry_output).unwrap();
+        let mut output = Vec::new();
+        remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+
+        let file = Path::new("nested").join(name);
+        let expected = format!("{}\0nested\0", file.to_string_lossy()).into_bytes();
+        assert_eq!(dry_output, expected);
+        assert_eq!(output, expected);
+        assert!(!Path::new(&dir).exists());
+    }
+
+    #[test]
+    fn report_a_failed_scan_with_the_root() {
+        let options = RemoveOptions {
+            filter: size_filter(),
+            ..Default::default()
+        };
+
+        let result = remove_entries(
+            Path::new("./data/remove-dir-missing"),
+            &options,
+            &mut Vec::new(),
+            &mut NoProgress,
+        );
+
+        match result {
+            Err(error @ RemoveError::Scan { .. }) => assert!(
+                error
+                    .to_string()
+                    .starts_with("Failed to scan ./data/remove-dir-missing: "),
+                "{error}"
+            ),
+            other => panic!("Expected a scan error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn stop_once_the_deadline_passed() {
+        let dir = create_mixed_tree();
+        let options = RemoveOptions {
+            deadline: Some(SystemTime::now() - Duration::from_secs(1)),
+            ..Default::default()
+        };
+
+        let result = remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress);
+        let untouched = Path::new(&dir).join("small.txt").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let error = result.unwrap_err();
+        assert!(
+            matches!(&error, RemoveError::Io(source) if source.kind() == ErrorKind::TimedOut),
+            "{error:?}"
+        );
+        assert_eq!(
+            error.to_string(),
+            "Deadline passed after removing 0 files (0 B) and 0 folders"
+        );
+        assert!(untouched);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn let_the_exec_hook_replace_deletion() {
+        let dir = create_mixed_tree();
+        let options = RemoveOptions {
+            exec: Some(crate::exec::parse_template("rm {}").unwrap()),
+            exec_replace: true,
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert!(!Path::new(&dir).exists(), "Root was not removed");
+        assert_eq!(stats.files, 3);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keep_going_past_failed_hooks_with_skip_errors() {
+        let dir = create_mixed_tree();
+        let mut options = RemoveOptions {
+            exec: Some(crate::exec::parse_template("false {}").unwrap()),
+            ..Default::default()
+        };
+
+        let result = remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress);
+        assert!(result.is_err(), "A failed hook should stop the walk");
+
+        options.skip_errors = true;
+        let mut output = Vec::new();
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+
+        assert_eq!(stats.files, 0);
+        assert_eq!(
+            stats.errors, 3,
+            "The folders holding them are not attempted"
+        );
+        assert!(Path::new(&format!("{dir}/nested/big.bin")).exists());
+        let output = String::from_utf8(output).unwrap();
+        assert!(
+            output.contains("false exited with code 1"),
+            "Missing error in {output}"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keep_the_folders_holding_an_ignored_failure() {
+        let dir = create_mixed_tree();
+        let options = RemoveOptions {
+            exec: Some(
+                crate::exec::parse_template(r#"sh -c 'case "$0" in *small.txt) exit 3;; esac' {}"#)
+                    .unwrap(),
+            ),
+            ignore_errors: vec![Regex::new("small.txt: sh exited with code 3", false).unwrap()],
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!((stats.files, stats.dirs, stats.errors), (2, 1, 0));
+        assert!(Path::new(&format!("{dir}/small.txt")).exists());
+        assert!(!Path::new(&format!("{dir}/nested")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keep_the_folders_holding_a_skipped_failure() {
+        let dir = create_mixed_tree();
+        let options = RemoveOptions {
+            exec: Some(
+                crate::exec::parse_template(
+                    r#"sh -c 'case "$0" in *nested/big.bin) exit 3;; esac' {}"#,
+                )
+                .unwrap(),
+            ),
+            skip_errors: true,
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            (stats.files, stats.dirs, stats.errors),
+            (2, 0, 1),
+            "{output}"
+        );
+        assert!(!output.contains("not empty"), "{output}");
+        assert!(Path::new(&format!("{dir}/nested/big.bin")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod check_removed_should {
+    use super::*;
+
+    fn check(error: ErrorKind, skip_errors: bool) -> (Result<bool, RemoveError>, u64, String) {
+        let options = RemoveOptions {
+            skip_errors,
+            ignore_errors: vec![Regex::new("/proc/.*: permission denied", true).unwrap()],
+            ..Default::default()
+        };
+        let mut stats = RemoveStats::default();
+        let mut output = Vec::new();
+
+        let removed = check_removed(
+            Err(std::io::Error::from(error)),
+            Path::new("/target/proc/status"),
+            &options,
+            &mut output,
+            &mut stats,
+        );
+        (removed, stats.errors, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn swallow_matching_errors_without_counting_them() {
+        for skip_errors in [false, true] {
+            let (removed, errors, output) = check(ErrorKind::PermissionDenied, skip_errors);
+
+            assert!(matches!(removed, Ok(false)), "{removed:?}");
+            assert_eq!(errors, 0);
+            assert_eq!(output, "");
+        }
+    }
+
+    #[test]
+    fn handle_other_errors_as_usual() {
+        let (removed, _, _) = check(ErrorKind::NotFound, false);
+        match removed {
+            Err(RemoveError::Delete { path, source }) => {
+                assert_eq!(path, Path::new("/target/proc/status"));
+                assert_eq!(source.kind(), ErrorKind::NotFound);
+            }
+            other => panic!("Expected a delete error, got {other:?}"),
+        }
+
+        let (removed, errors, output) = check(ErrorKind::NotFound, true);
+        assert!(matches!(removed, Ok(false)), "{removed:?}");
+        assert_eq!(errors, 1);
+        assert!(output.starts_with("Error: /target/proc/status"), "{output}");
+    }
+
+    #[test]
+    fn list_only_permission_errors_in_the_denied_file() {
+        let denied = PathBuf::from("./data/check-removed-denied.txt");
+        let options = RemoveOptions {
+            skip_errors: true,
+            denied_to: Some(denied.clone()),
+            ..Default::default()
+        };
+        let mut stats = RemoveStats::default();
+        let mut output = Vec::new();
+
+        for (kind, name) in [
+            (ErrorKind::PermissionDenied, "locked.txt"),
+            (ErrorKind::NotFound, "gone.txt"),
+            (ErrorKind::PermissionDenied, "nested/locked"),
+        ] {
+            let path = PathBuf::from(format!("/target/{name}"));
+            let error = std::io::Error::from(kind);
+            check_removed(Err(error), &path, &options, &mut output, &mut stats).unwrap();
+        }
+        let listed = std::fs::read_to_string(&denied).unwrap();
+        std::fs::remove_file(&denied).unwrap();
+
+        assert_eq!(listed, "/target/locked.txt\n/target/nested/locked\n");
+        assert_eq!(stats.errors, 3);
+        assert_eq!(String::from_utf8(output).unwrap().lines().count(), 3);
+    }
+
+    #[test]
+    fn group_skipped_errors_by_kind_when_asked() {
+        let options = RemoveOptions {
+            skip_errors: true,
+            group_errors: true,
+            ..Default::default()
+        };
+        let mut stats = RemoveStats::default();
+        let mut output = Vec::new();
+
+        let failures = (0..12)
+            .map(|n| (std::io::Error::from(ErrorKind::PermissionDenied), n))
+            .chain([(std::io::Error::from(ErrorKind::NotADirectory), 12)])
+            .chain([(std::io::Error::other("exec exited with 1"), 13)]);
+        for (error, n) in failures {
+            let path = PathBuf::from(format!("/target/{n}"));
+            let removed = check_removed(Err(error), &path, &options, &mut output, &mut stats);
+            assert!(matches!(removed, Ok(false)), "{removed:?}");
+        }
+
+        assert!(output.is_empty());
+        assert_eq!(stats.errors, 14);
+        let kinds: Vec<(&str, u64, usize)> = stats
+            .errors_by_kind
+            .iter()
+            .map(|(kind, group)| (kind.as_str(), group.count, group.paths.len()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("Exec exited with 1", 1, 1),
+                ("Not a directory", 1, 1),
+                ("Permission denied", 12, ErrorGroup::LISTED_PATHS),
+            ]
+        );
+        assert_eq!(
+            stats.errors_by_kind["Not a directory"].paths,
+            vec![PathBuf::from("/target/12")]
+        );
+    }
+}
+
+#[cfg(all(test, unix))]
+mod symlink_accounting_should {
+    use std::{io::Write, sync::atomic::AtomicU8};
+
+    use super::*;
+    use crate::progress::NoProgress;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Creates a target dir with `link-a` and `link-b` both pointing at an outside dir
+    /// holding a 100 byte file. Returns the target, the outside dir and link-b's own size.
+    fn create_linked_tree() -> (String, String, u64) {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/symlink-size-dir-{unique}");
+        let outside = format!("{dir}-outside");
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::File::create(format!("{outside}/file.bin"))
+            .unwrap()
+            .write_all(&[b'a'; 100])
+            .unwrap();
+
+        let outside_abs = std::fs::canonicalize(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside_abs, format!("{dir}/link-a")).unwrap();
+        std::os::unix::fs::symlink(&outside_abs, format!("{dir}/link-b")).unwrap();
+        let link_size = std::fs::symlink_metadata(format!("{dir}/link-b"))
+            .unwrap()
+            .len();
+
+        (dir, outside, link_size)
+    }
+
+    #[test]
+    fn unlink_symlinked_dirs_without_touching_their_contents() {
+        let (dir, outside, _) = create_linked_tree();
+        for i in 0..5 {
+            std::fs::File::create(format!("{outside}/kept-{i}.txt")).unwrap();
+        }
+        let options = RemoveOptions {
+            verbosity: 2,
+            ..Default::default()
+        };
+
+        let mut output = Vec::new();
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut output, &mut NoProgress).unwrap();
+
+        let left: Vec<_> = std::fs::read_dir(&outside).unwrap().collect();
+        std::fs::remove_dir_all(&outside).unwrap();
+
+        assert!(!Path::new(&dir).exists(), "Target was not removed");
+        assert_eq!(stats.files, 2, "Only the two links should be removed");
+        assert_eq!(left.len(), 6, "The link target lost files");
+        let output = String::from_utf8(output).unwrap();
+        assert!(!output.contains("kept-"), "Walked into a link: {output}");
+    }
+
+    #[test]
+    fn count_only_the_links_by_default() {
+        let (dir, outside, link_size) = create_linked_tree();
+
+        let stats = remove_entries(
+            Path::new(&dir),
+            &RemoveOptions::default(),
+            &mut Vec::new(),
+            &mut NoProgress,
+        )
+        .unwrap();
+
+        assert_eq!(stats.bytes, link_size * 2);
+        assert!(Path::new(&format!("{outside}/file.bin")).exists());
+
+        std::fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn abort_before_deleting_anything_at_a_link_escaping_the_root() {
+        let (dir, outside, _) = create_linked_tree();
+        std::fs::write(format!("{dir}/own.txt"), "x").unwrap();
+        let options = RemoveOptions {
+            follow_symlinks: true,
+            fail_on_symlink_escape: true,
+            ..Default::default()
+        };
+
+        let result = remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress);
+        let own_kept = Path::new(&format!("{dir}/own.txt")).exists();
+        let outside_kept = Path::new(&format!("{outside}/file.bin")).exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+
+        let Err(RemoveError::Scan { source, .. }) = result else {
+            panic!("Expected a failed scan, got {result:?}");
+        };
+        assert_eq!(source.kind(), std::io::ErrorKind::PermissionDenied);
+        assert!(
+            source.to_string().starts_with("refusing to follow "),
+            "{source}"
+        );
+        assert!(own_kept);
+        assert!(outside_kept);
+    }
+
+    #[test]
+    fn not_count_followed_content_without_the_flag() {
+        let (dir, outside, link_size) = create_linked_tree();
+        let options = RemoveOptions {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+
+        let stats = dry_run(Path::new(&dir), &options, &mut Vec::new()).unwrap();
+
+        assert_eq!(
+            stats.bytes, link_size,
+            "Only link-b counts, link-a got followed"
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+    }
+
+    #[test]
+    fn count_followed_content_once_with_the_flag() {
+        let (dir, outside, link_size) = create_linked_tree();
+        let options = RemoveOptions {
+            follow_symlinks: true,
+            count_symlink_targets: true,
+            ..Default::default()
+        };
+
+        let stats =
+            remove_entries(Path::new(&dir), &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        assert_eq!(stats.bytes, 100 + link_size);
+        assert!(!Path::new(&dir).exists(), "Target was not removed");
+        assert!(
+            !Path::new(&format!("{outside}/file.bin")).exists(),
+            "Followed content was not removed"
+        );
+        assert!(
+            Path::new(&outside).is_dir(),
+            "Link target itself should stay"
+        );
+
+        std::fs::remove_dir_all(&outside).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod plan_should {
+    use std::{
+        io::Write,
+        sync::atomic::AtomicU8,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+    use crate::{filter::PathLimit, progress::NoProgress};
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Creates `a.txt` (30 bytes, newest), `b.txt` (10 bytes, oldest) and
+    /// `c.txt` (20 bytes).
+    fn create_sortable_tree() -> String {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/plan-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let hour = Duration::from_secs(60 * 60);
+        for (name, size, hours_old) in [("a.txt", 30, 1), ("b.txt", 10, 3), ("c.txt", 20, 2)] {
+            let mut file = std::fs::File::create(format!("{dir}/{name}")).unwrap();
+            file.write_all(&vec![b'a'; size]).unwrap();
+            file.set_modified(SystemTime::now() - hour * hours_old)
+                .unwrap();
+        }
+
+        dir
+    }
+
+    fn planned_names(dir: &str, sort: SortOrder) -> Vec<String> {
+        let options = RemoveOptions {
+            sort: Some(sort),
+            ..Default::default()
+        };
+
+        plan(Path::new(dir), &options)
+            .unwrap()
+            .files
+            .iter()
+            .map(|e| e.path.file_name().unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn sort_files_by_size_name_and_mtime() {
+        let dir = create_sortable_tree();
+
+        let by_size = planned_names(&dir, SortOrder::Size);
+        let by_name = planned_names(&dir, SortOrder::Name);
+        let by_mtime = planned_names(&dir, SortOrder::Mtime);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(by_size, vec!["a.txt", "c.txt", "b.txt"]);
+        assert_eq!(by_name, vec!["a.txt", "b.txt", "c.txt"]);
+        assert_eq!(by_mtime, vec!["b.txt", "c.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn sort_files_by_size_times_age() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/plan-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+        let hour = Duration::from_secs(60 * 60);
+        for (name, size, hours_old) in
+            [("mid.txt", 40, 5), ("old.txt", 10, 30), ("big.txt", 100, 4)]
+        {
+            let mut file = std::fs::File::create(format!("{dir}/{name}")).unwrap();
+            file.write_all(&vec![b'a'; size]).unwrap();
+            file.set_modified(SystemTime::now() - hour * hours_old)
+                .unwrap();
+        }
+
+        let by_score = planned_names(&dir, SortOrder::Score);
+        let by_size = planned_names(&dir, SortOrder::Size);
+        let by_mtime = planned_names(&dir, SortOrder::Mtime);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(by_score, vec!["big.txt", "old.txt", "mid.txt"]);
+        assert_eq!(by_size, vec!["big.txt", "mid.txt", "old.txt"]);
+        assert_eq!(by_mtime, vec!["old.txt", "mid.txt", "big.txt"]);
+    }
+
+    #[test]
+    fn only_plan_over_long_paths_and_find_the_longest() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/plan-dir-{unique}");
+        let root = Path::new(&dir);
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        for file in ["a.txt", "nested/long-file-name.txt"] {
+            std::fs::File::create(root.join(file)).unwrap();
+        }
+
+        let limit = root.join("nested/x").to_string_lossy().chars().count();
+        let options = RemoveOptions {
+            filter: Filter {
+                path_limit: Some(PathLimit {
+                    root: root.to_path_buf(),
+                    max: limit,
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let planned = plan(root, &options).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let long = root.join("nested/long-file-name.txt");
+        let files: Vec<PathBuf> = planned.files.into_iter().map(|e| e.path).collect();
+        assert_eq!(files, vec![long.clone()]);
+        assert_eq!(
+            planned.longest_path,
+            Some((long.to_string_lossy().chars().count(), long))
+        );
+    }
+
+    #[test]
+    fn keep_the_top_levels_of_folders_empty() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/plan-dir-{unique}");
+        let root = Path::new(&dir);
+        std::fs::create_dir_all(root.join("debug/build/out")).unwrap();
+        std::fs::create_dir_all(root.join("release")).unwrap();
+        for file in [
+            "top.txt",
+            "debug/app",
+            "debug/build/lib.o",
+            "debug/build/out/gen.rs",
+        ] {
+            std::fs::File::create(root.join(file)).unwrap();
+        }
+
+        let options = RemoveOptions {
+            keep_structure_depth: Some(2),
+            ..Default::default()
+        };
+        let planned = plan(root, &options).unwrap();
+        let dirs: Vec<PathBuf> = planned.dirs.iter().map(|e| e.path.clone()).collect();
+        let stats = remove_entries(root, &options, &mut Vec::new(), &mut NoProgress).unwrap();
+
+        let mut left: Vec<PathBuf> = scan::scan_dir(root)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+        left.sort();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(!planned.remove_root);
+        assert_eq!(dirs, vec![root.join("debug/build/out")]);
+        assert_eq!((stats.files, stats.dirs), (4, 1));
+        assert_eq!(
+            left,
+            vec![
+                root.join("debug"),
+                root.join("debug/build"),
+                root.join("release")
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keep_folders_holding_anything_recent_whole() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/plan-dir-{unique}");
+        let root = Path::new(&dir);
+        std::fs::create_dir_all(root.join("used/sub")).unwrap();
+        std::fs::create_dir_all(root.join("stale/sub")).unwrap();
+
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+        for (name, modified) in [
+            ("used/old.txt", week_ago),
+            ("used/sub/new.txt", SystemTime::now()),
+            ("stale/sub/old.txt", week_ago),
+            ("loose.txt", week_ago),
+        ] {
+            let file = std::fs::File::create(root.join(name)).unwrap();
+            file.set_modified(modified).unwrap();
+        }
+        // Creating the files touched the folders, only their contents may count.
+        for folder in ["used/sub", "used", "stale/sub", "stale"] {
+            let folder = std::fs::File::open(root.join(folder)).unwrap();
+            folder.set_modified(week_ago).unwrap();
+        }
+
+        let options = RemoveOptions {
+            filter: Filter {
+                keep_recent_dirs: Some(Duration::from_secs(24 * 60 * 60)),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let planned = plan(root, &options).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(planned.recent_dirs, vec![root.join("used")]);
+        let mut files: Vec<PathBuf> = planned.files.into_iter().map(|e| e.path).collect();
+        files.sort();
+        assert_eq!(
+            files,
+            vec![root.join("loose.txt"), root.join("stale/sub/old.txt")]
+        );
+        let dirs: Vec<PathBuf> = planned.dirs.into_iter().map(|e| e.path).collect();
+        assert_eq!(dirs, vec![root.join("stale/sub"), root.join("stale")]);
+    }
+
+    #[test]
+    fn plan_folders_deepest_first() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/plan-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/a/b/c")).unwrap();
+
+        let planned: Vec<PathBuf> = plan(Path::new(&dir), &RemoveOptions::default())
+            .unwrap()
+            .dirs
+            .into_iter()
+            .map(|e| e.path)
+            .collect();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let root = Path::new(&dir);
+        assert_eq!(
+            planned,
+            vec![root.join("a/b/c"), root.join("a/b"), root.join("a")]
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! `--rollback-on-error`: moves the target aside in one rename before deleting anything,
+//! so a failed delete never leaves a half deleted target at the original path.
+//!
+//! The staging folder is a hidden sibling of the target, which keeps the rename on the
+//! same filesystem. A target that is a mount point itself cannot be renamed and is refused
+//! untouched. A delete failing before anything went, like a failed scan, is rolled back by
+//! moving the staged copy back. Once entries were deleted they cannot be brought back, so
+//! what is left stays in the staging folder and the original path stays free.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::error::RemoveError;
+
+/// Where `target` is moved before being deleted: `.<name>.rm-dir-staged-<pid>` next to it.
+pub(crate) fn staging_path(target: &Path) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    target.with_file_name(format!(".{name}.rm-dir-staged-{}", std::process::id()))
+}
+
+/// Moves `target` to its staging path and runs `delete` on the staged copy, writing to
+/// `output` what became of the target when that fails.
+pub(crate) fn delete_staged<T>(
+    target: &Path,
+    output: &mut impl Write,
+    delete: impl FnOnce(&Path) -> Result<T, RemoveError>,
+) -> Result<T, RemoveError> {
+    let staged = staging_path(target);
+    if std::fs::symlink_metadata(&staged).is_ok() {
+        return Err(RemoveError::Delete {
+            path: target.to_path_buf(),
+            source: std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("the staging path {} is taken", staged.to_string_lossy()),
+            ),
+        });
+    }
+
+    std::fs::rename(target, &staged).map_err(|e| RemoveError::Delete {
+        path: target.to_path_buf(),
+        source: std::io::Error::new(
+            e.kind(),
+            format!(
+                "cannot move it to {} on the same filesystem, nothing was deleted: {e}",
+                staged.to_string_lossy()
+            ),
+        ),
+    })?;
+
+    let result = delete(&staged);
+    match &result {
+        Ok(_) => {}
+        Err(RemoveError::Scan { .. }) => match std::fs::rename(&staged, target) {
+            Ok(()) => writeln!(
+                output,
+                "Rolled back: nothing was deleted, {} is back in place",
+                target.to_string_lossy()
+            )?,
+            Err(e) => writeln!(
+                output,
+                "Error: nothing was deleted, but {} could not be moved back to {}: {}",
+                staged.to_string_lossy(),
+                target.to_string_lossy(),
+                e
+            )?,
+        },
+        Err(_) => writeln!(
+            output,
+            "{} is gone, whatever could not be deleted is left in {}",
+            target.to_string_lossy(),
+            staged.to_string_lossy()
+        )?,
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod delete_staged_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    /// Creates a target holding `a.txt` and `b.txt`.
+    fn create_target() -> PathBuf {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/rollback-dir-{unique}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+        std::fs::write(dir.join("b.txt"), "b").unwrap();
+        dir
+    }
+
+    #[test]
+    fn never_leave_a_half_deleted_target_after_a_failure_mid_delete() {
+        let dir = create_target();
+        let mut output = Vec::new();
+
+        let result = delete_staged(&dir, &mut output, |staged| {
+            std::fs::remove_file(staged.join("a.txt")).unwrap();
+            Err::<(), _>(RemoveError::Delete {
+                path: staged.join("b.txt"),
+                source: std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+            })
+        });
+        let staged = staging_path(&dir);
+        let target_gone = !dir.exists();
+        let left: Vec<_> = std::fs::read_dir(&staged)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        std::fs::remove_dir_all(&staged).unwrap();
+
+        assert!(result.is_err());
+        assert!(target_gone, "The target should not be left half deleted");
+        assert_eq!(left, ["b.txt"]);
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("is left in "), "{output}");
+    }
+
+    #[test]
+    fn move_the_target_back_when_nothing_was_deleted() {
+        let dir = create_target();
+        let mut output = Vec::new();
+
+        let result = delete_staged(&dir, &mut output, |staged| {
+            Err::<(), _>(RemoveError::Scan {
+                path: staged.to_path_buf(),
+                source: std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+            })
+        });
+        let restored = dir.join("a.txt").exists() && dir.join("b.txt").exists();
+        let staged_gone = !staging_path(&dir).exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+        assert!(restored);
+        assert!(staged_gone);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("Rolled back: "));
+    }
+
+    #[test]
+    fn delete_the_staged_copy() {
+        let dir = create_target();
+
+        let result = delete_staged(&dir, &mut Vec::new(), |staged| {
+            std::fs::remove_dir_all(staged).map_err(RemoveError::Io)
+        });
+
+        assert!(result.is_ok());
+        assert!(!dir.exists());
+        assert!(!staging_path(&dir).exists());
+    }
+}
@@ -0,0 +1,578 @@
+//! Recursive scan of a directory tree. Symlinks are recorded as links and only
+//! descended into when following them was asked for. With `--parallel-scan` the
+//! folders directly below the root are walked on several threads.
+//!
+//! Those threads are limited per device, so spinning disks are not thrashed by seeks
+//! while folders mounted from other drives still scan in parallel. Unless
+//! `--max-concurrency-per-device` sets the limit, a device that reports itself as
+//! rotational gets one thread and any other device as many as there are.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+};
+
+use crate::mount;
+
+/// A file, directory or symlink found below the scanned root.
+pub(crate) struct Entry {
+    pub(crate) path: PathBuf,
+    /// Metadata of the entry itself, so a symlink reports the link and not its target.
+    pub(crate) metadata: Metadata,
+    /// A symlink whose target directory got scanned below this path.
+    pub(crate) followed: bool,
+    /// Reached through a followed symlink rather than the tree itself.
+    pub(crate) via_symlink: bool,
+    /// A folder another filesystem is mounted on. It is never descended into and has
+    /// to be kept.
+    pub(crate) mount_point: bool,
+}
+
+impl Entry {
+    /// True for folders and for followed symlinks, which both hold scanned entries
+    /// and have to be removed after them.
+    pub(crate) fn is_dir_like(&self) -> bool {
+        (self.metadata.is_dir() && !self.is_link()) || self.followed
+    }
+
+    /// True for entries removed as a link rather than descended into, see `is_link`.
+    pub(crate) fn is_link(&self) -> bool {
+        is_link(&self.metadata)
+    }
+}
+
+/// True for a symlink and, on Windows, for any other reparse point too, such as a
+/// directory junction. Going by the attributes catches every kind of reparse point, even
+/// the ones `is_symlink` does not know about, so none is ever mistaken for a folder.
+#[cfg(windows)]
+pub(crate) fn is_link(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    metadata.file_type().is_symlink()
+        || metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT != 0
+}
+
+#[cfg(not(windows))]
+pub(crate) fn is_link(metadata: &Metadata) -> bool {
+    metadata.file_type().is_symlink()
+}
+
+#[cfg(windows)]
+const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+/// True for a reparse point standing in for a folder, like a junction, which
+/// `follow_junctions` descends into.
+#[cfg(windows)]
+fn is_junction(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+    let attributes = metadata.file_attributes();
+    attributes & FILE_ATTRIBUTE_REPARSE_POINT != 0 && attributes & FILE_ATTRIBUTE_DIRECTORY != 0
+}
+
+#[cfg(not(windows))]
+fn is_junction(_metadata: &Metadata) -> bool {
+    false
+}
+
+#[derive(Default)]
+pub(crate) struct ScanOptions {
+    /// Descend into symlinked directories. Targets inside the root, or already scanned
+    /// through another link, are not scanned twice.
+    pub(crate) follow_symlinks: bool,
+    /// Descend into Windows junctions, even ones leading outside the root. Without it a
+    /// junction is recorded as a link, like a symlink.
+    pub(crate) follow_junctions: bool,
+    /// Fail the scan rather than follow a symlink to a directory outside the root.
+    pub(crate) fail_on_escape: bool,
+    /// Flag nested mount points instead of descending into them.
+    pub(crate) skip_mount_points: bool,
+}
+
+/// Walks everything below `root` without following symlinks.
+pub(crate) fn scan_dir(root: &Path) -> Result<Vec<Entry>, std::io::Error> {
+    scan_with(root, &ScanOptions::default())
+}
+
+/// Walks everything below `root`, returning parents before their children.
+/// The root itself is not part of the result.
+pub(crate) fn scan_with(root: &Path, options: &ScanOptions) -> Result<Vec<Entry>, std::io::Error> {
+    scan_jobs(root, options, JOBS.load(Ordering::Relaxed))
+}
+
+/// Threads `--parallel-scan` walks with. 1 walks everything on the calling thread.
+static JOBS: AtomicUsize = AtomicUsize::new(1);
+
+/// Makes every later scan walk the folders directly below its root on up to `jobs`
+/// threads at once.
+pub(crate) fn set_jobs(jobs: usize) {
+    JOBS.store(jobs.max(1), Ordering::Relaxed);
+}
+
+/// Threads walking one device at once, 0 picking by whether it is rotational.
+static DEVICE_LIMIT: AtomicUsize = AtomicUsize::new(0);
+
+/// Caps the threads of every later scan walking the same device at once.
+pub(crate) fn set_device_limit(limit: usize) {
+    DEVICE_LIMIT.store(limit.max(1), Ordering::Relaxed);
+}
+
+fn device_limit(device: u64) -> usize {
+    match DEVICE_LIMIT.load(Ordering::Relaxed) {
+        0 if mount::is_rotational(device) == Some(true) => 1,
+        0 => usize::MAX,
+        limit => limit,
+    }
+}
+
+/// What a scan thread gets from the `DeviceQueue`.
+#[derive(Debug, PartialEq)]
+enum Next {
+    /// Scan the subtree with this index, on this device.
+    Subtree(usize, u64),
+    /// Every device with something left is busy, wait for a subtree to finish.
+    Wait,
+    Done,
+}
+
+/// Hands the subtrees out to the scan threads, grouped by the device they are on, with
+/// at most the device's limit of them taken at once.
+struct DeviceQueue {
+    pending: BTreeMap<u64, VecDeque<usize>>,
+    active: HashMap<u64, usize>,
+    limits: HashMap<u64, usize>,
+}
+
+impl DeviceQueue {
+    /// Groups the subtrees by `devices`, the device of each in order.
+    fn new(devices: &[u64], limit: impl Fn(u64) -> usize) -> Self {
+        let mut pending: BTreeMap<u64, VecDeque<usize>> = BTreeMap::new();
+        for (index, device) in devices.iter().enumerate() {
+            pending.entry(*device).or_default().push_back(index);
+        }
+        let limits = pending
+            .keys()
+            .map(|device| (*device, limit(*device)))
+            .collect();
+
+        DeviceQueue {
+            pending,
+            active: HashMap::new(),
+            limits,
+        }
+    }
+
+    fn take(&mut self) -> Next {
+        if self.pending.is_empty() {
+            return Next::Done;
+        }
+
+        for (device, queue) in &mut self.pending {
+            let active = self.active.entry(*device).or_default();
+            if *active < self.limits[device] {
+                if let Some(index) = queue.pop_front() {
+                    *active += 1;
+                    let device = *device;
+                    if queue.is_empty() {
+                        self.pending.remove(&device);
+                    }
+                    return Next::Subtree(index, device);
+                }
+            }
+        }
+
+        Next::Wait
+    }
+
+    fn finish(&mut self, device: u64) {
+        if let Some(active) = self.active.get_mut(&device) {
+            *active = active.saturating_sub(1);
+        }
+    }
+}
+
+/// Scans on up to `jobs` threads, each taking the next folder directly below `root`.
+/// The subtrees are put back together in the order a single thread walks them, so the
+/// result does not depend on scheduling. Following symlinks shares what was followed
+/// across the whole tree, so that always walks on one thread.
+fn scan_jobs(
+    root: &Path,
+    options: &ScanOptions,
+    jobs: usize,
+) -> Result<Vec<Entry>, std::io::Error> {
+    let canonical_root = std::fs::canonicalize(root)?;
+    let mount_points = if options.skip_mount_points {
+        mount::mount_points_below(&canonical_root)
+    } else {
+        HashSet::new()
+    };
+    let new_scan = || Scan {
+        given_root: root,
+        root: canonical_root.clone(),
+        options,
+        followed_targets: HashSet::new(),
+        mount_points: &mount_points,
+        entries: Vec::new(),
+    };
+
+    if jobs <= 1 || options.follow_symlinks || options.follow_junctions {
+        let mut scan = new_scan();
+        scan.visit(root, false)?;
+        return Ok(scan.entries);
+    }
+
+    let top_level = new_scan().read_level(root, false)?;
+    let subtrees: Vec<&Path> = top_level
+        .iter()
+        .filter(|(_, descend)| *descend)
+        .map(|(entry, _)| entry.path.as_path())
+        .collect();
+
+    let devices: Vec<u64> = top_level
+        .iter()
+        .filter(|(_, descend)| *descend)
+        .map(|(entry, _)| mount::device_id(&entry.metadata))
+        .collect();
+    let queue = Mutex::new(DeviceQueue::new(&devices, device_limit));
+    let finished = Condvar::new();
+    let mut scanned: Vec<Option<Result<Vec<Entry>, std::io::Error>>> =
+        (0..subtrees.len()).map(|_| None).collect();
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs.min(subtrees.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let mut pending = queue.lock().expect("a scan thread panicked");
+                        let (index, device) = match pending.take() {
+                            Next::Subtree(index, device) => (index, device),
+                            Next::Wait => {
+                                drop(finished.wait(pending).expect("a scan thread panicked"));
+                                continue;
+                            }
+                            Next::Done => return done,
+                        };
+                        drop(pending);
+
+                        let mut scan = new_scan();
+                        let result = scan.visit(subtrees[index], false).map(|_| scan.entries);
+                        done.push((index, result));
+
+                        queue.lock().expect("a scan thread panicked").finish(device);
+                        finished.notify_all();
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            let done = worker.join().expect("a scan thread panicked");
+            for (index, result) in done {
+                scanned[index] = Some(result);
+            }
+        }
+    });
+
+    let mut subtree_results = scanned.into_iter().flatten();
+    let mut entries = Vec::new();
+    for (entry, descend) in top_level {
+        entries.push(entry);
+        if descend {
+            if let Some(result) = subtree_results.next() {
+                entries.extend(result?);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+struct Scan<'a> {
+    given_root: &'a Path,
+    root: PathBuf,
+    options: &'a ScanOptions,
+    /// Canonical targets of the symlinks descended into so far.
+    followed_targets: HashSet<PathBuf>,
+    /// Canonical mount points below the root known from the mount table.
+    mount_points: &'a HashSet<PathBuf>,
+    entries: Vec<Entry>,
+}
+
+impl Scan<'_> {
+    fn visit(&mut self, dir: &Path, via_symlink: bool) -> Result<(), std::io::Error> {
+        for (entry, descend) in self.read_level(dir, via_symlink)? {
+            let path = entry.path.clone();
+            let via_symlink = entry.via_symlink || entry.followed;
+            self.entries.push(entry);
+
+            if descend {
+                self.visit(&path, via_symlink)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The entries directly in `dir`, each with whether it is to be descended into.
+    fn read_level(
+        &mut self,
+        dir: &Path,
+        via_symlink: bool,
+    ) -> Result<Vec<(Entry, bool)>, std::io::Error> {
+        let dir_metadata = if self.options.skip_mount_points {
+            Some(std::fs::metadata(dir)?)
+        } else {
+            None
+        };
+
+        let mut level = Vec::new();
+        for dir_entry in std::fs::read_dir(dir)? {
+            let dir_entry = dir_entry?;
+            // Like `symlink_metadata`, but looked up relative to the open folder rather
+            // than by resolving the whole path again for every entry.
+            let metadata = dir_entry.metadata()?;
+            let path = dir_entry.path();
+            // Checked on the link itself, so a symlinked folder or a junction is never
+            // descended into unless following was asked for.
+            let is_symlink = is_link(&metadata);
+            let is_dir = metadata.is_dir() && !is_symlink;
+            let followed = is_symlink && self.should_follow(&path, is_junction(&metadata))?;
+            let mount_point = is_dir
+                && dir_metadata
+                    .as_ref()
+                    .is_some_and(|parent| self.is_mount_point(&path, parent, &metadata));
+
+            let descend = !mount_point && (is_dir || followed);
+            level.push((
+                Entry {
+                    path,
+                    metadata,
+                    followed,
+                    via_symlink,
+                    mount_point,
+                },
+                descend,
+            ));
+        }
+
+        Ok(level)
+    }
+
+    fn is_mount_point(&self, path: &Path, parent: &Metadata, metadata: &Metadata) -> bool {
+        if mount::crosses_device(parent, metadata) {
+            return true;
+        }
+
+        path.strip_prefix(self.given_root)
+            .is_ok_and(|relative| self.mount_points.contains(&self.root.join(relative)))
+    }
+
+    /// Only follows links to directories outside the root that were not seen yet, which
+    /// keeps link cycles finite and every target scanned once. With `fail_on_escape` such a
+    /// link is an error instead, unless it is a `junction` `follow_junctions` asked for.
+    fn should_follow(&mut self, link: &Path, junction: bool) -> Result<bool, std::io::Error> {
+        let follows_junction = junction && self.options.follow_junctions;
+        if !self.options.follow_symlinks && !follows_junction {
+            return Ok(false);
+        }
+
+        let Ok(target) = std::fs::canonicalize(link) else {
+            return Ok(false);
+        };
+
+        if !target.is_dir()
+            || target.starts_with(&self.root)
+            || self
+                .followed_targets
+                .iter()
+                .any(|followed| target.starts_with(followed) || followed.starts_with(&target))
+        {
+            return Ok(false);
+        }
+
+        if self.options.fail_on_escape && !follows_junction {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!(
+                    "refusing to follow {} to {} outside {}, pass --allow-symlink-escape to delete through it",
+                    link.to_string_lossy(),
+                    target.to_string_lossy(),
+                    self.root.to_string_lossy()
+                ),
+            ));
+        }
+
+        Ok(self.followed_targets.insert(target))
+    }
+}
+
+#[cfg(test)]
+mod scan_dir_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn return_nested_entries() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/scan-dir-{unique}");
+
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::File::create(format!("{dir}/file1.txt")).unwrap();
+        std::fs::File::create(format!("{dir}/nested/file2.txt")).unwrap();
+
+        let entries = scan_dir(Path::new(&dir)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut found: Vec<String> = entries
+            .iter()
+            .map(|e| {
+                let relative = e.path.strip_prefix(&dir).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect();
+        found.sort();
+
+        assert_eq!(found, vec!["file1.txt", "nested", "nested/file2.txt"]);
+    }
+
+    #[test]
+    fn list_parents_before_children() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/scan-dir-{unique}");
+
+        std::fs::create_dir_all(format!("{dir}/a/b")).unwrap();
+        std::fs::File::create(format!("{dir}/a/b/file.txt")).unwrap();
+
+        let entries = scan_dir(Path::new(&dir)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let found: Vec<&Path> = entries
+            .iter()
+            .map(|e| e.path.strip_prefix(&dir).unwrap())
+            .collect();
+        assert_eq!(
+            found,
+            vec![Path::new("a"), Path::new("a/b"), Path::new("a/b/file.txt")]
+        );
+    }
+
+    #[test]
+    fn return_the_same_entries_in_the_same_order_on_several_threads() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/scan-dir-{unique}");
+        for top in 0..6 {
+            std::fs::create_dir_all(format!("{dir}/top-{top}/nested")).unwrap();
+            for file in 0..top {
+                std::fs::File::create(format!("{dir}/top-{top}/nested/file-{file}")).unwrap();
+            }
+            std::fs::File::create(format!("{dir}/loose-{top}")).unwrap();
+        }
+
+        let paths = |jobs: usize| -> Vec<PathBuf> {
+            scan_jobs(Path::new(&dir), &ScanOptions::default(), jobs)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.path)
+                .collect()
+        };
+        let sequential = paths(1);
+        let parallel: Vec<Vec<PathBuf>> = (0..5).map(|_| paths(4)).collect();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(sequential.len(), 6 * 3 + 15);
+        for run in parallel {
+            assert_eq!(run, sequential);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follow_symlinks_outside_the_root_once() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/scan-dir-{unique}");
+        let outside = format!("{dir}-outside");
+
+        std::fs::create_dir_all(format!("{dir}/inner")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::File::create(format!("{outside}/file.txt")).unwrap();
+
+        let outside_abs = std::fs::canonicalize(&outside).unwrap();
+        let inner_abs = std::fs::canonicalize(format!("{dir}/inner")).unwrap();
+        std::os::unix::fs::symlink(&outside_abs, format!("{dir}/link-a")).unwrap();
+        std::os::unix::fs::symlink(&outside_abs, format!("{dir}/link-b")).unwrap();
+        std::os::unix::fs::symlink(&inner_abs, format!("{dir}/link-inner")).unwrap();
+
+        let options = ScanOptions {
+            follow_symlinks: true,
+            ..Default::default()
+        };
+        let entries = scan_with(Path::new(&dir), &options).unwrap();
+        let not_followed = scan_dir(Path::new(&dir)).unwrap();
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_dir_all(&outside).unwrap();
+
+        let followed: Vec<&Entry> = entries.iter().filter(|e| e.followed).collect();
+        assert_eq!(
+            followed.len(),
+            1,
+            "Only one link to the outside dir is followed"
+        );
+
+        let via_symlink: Vec<&Entry> = entries.iter().filter(|e| e.via_symlink).collect();
+        assert_eq!(via_symlink.len(), 1);
+        assert!(via_symlink[0].path.ends_with("file.txt"));
+
+        assert_eq!(not_followed.len(), 4, "inner and three links");
+        assert!(not_followed.iter().all(|e| !e.followed));
+    }
+}
+
+#[cfg(test)]
+mod device_queue_should {
+    use super::*;
+
+    #[test]
+    fn limit_each_device_but_not_the_others() {
+        let mut queue = DeviceQueue::new(&[7, 7, 9, 7], |device| if device == 7 { 1 } else { 2 });
+
+        assert_eq!(queue.take(), Next::Subtree(0, 7));
+        assert_eq!(queue.take(), Next::Subtree(2, 9));
+        assert_eq!(queue.take(), Next::Wait, "Device 7 is busy, 9 is done");
+
+        queue.finish(7);
+        assert_eq!(queue.take(), Next::Subtree(1, 7));
+        queue.finish(9);
+        assert_eq!(queue.take(), Next::Wait);
+
+        queue.finish(7);
+        assert_eq!(queue.take(), Next::Subtree(3, 7));
+        assert_eq!(queue.take(), Next::Done);
+    }
+
+    #[test]
+    fn hand_out_everything_at_once_without_a_limit() {
+        let mut queue = DeviceQueue::new(&[1, 1, 1], |_| usize::MAX);
+
+        let taken: Vec<Next> = (0..4).map(|_| queue.take()).collect();
+
+        assert_eq!(
+            taken,
+            vec![
+                Next::Subtree(0, 1),
+                Next::Subtree(1, 1),
+                Next::Subtree(2, 1),
+                Next::Done
+            ]
+        );
+    }
+}
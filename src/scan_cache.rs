@@ -0,0 +1,238 @@
+//! `--scan-cache`: reuses the scan of a huge static tree across runs, e.g. dry runs
+//! tuning filters, instead of walking it again every time.
+//!
+//! The cache lists every scanned path relative to the root, with how it was reached. It
+//! is only used while it is newer than the root's mtime and was written for the same root
+//! and scan options; otherwise the tree is walked and the cache written anew. Changes
+//! deeper down leave the root's mtime alone, so they go unnoticed: the cache suits trees
+//! that do not change between runs.
+//!
+//! Sizes and mtimes are read again for every cached path, as a scan entry carries the
+//! metadata itself, so what saves time is not listing the folders. Paths gone since are
+//! dropped, so nothing is deleted or counted that no longer exists.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    checkpoint::{escape, unescape},
+    scan::{self, Entry, ScanOptions},
+};
+
+const HEADER: &str = "rm-dir scan cache v1";
+
+/// Whether a scan came from the cache, for the note printed along with the plan.
+#[derive(Debug, PartialEq)]
+pub(crate) enum CacheUse {
+    /// Loaded from this cache file.
+    Loaded(PathBuf),
+    /// Walked, then written to this cache file.
+    Written(PathBuf),
+    /// Walked, but writing this cache file failed with this error.
+    NotWritten(PathBuf, String),
+}
+
+/// The scan of `root`, loaded from `cache` when it is fresh, walked and written to it
+/// otherwise.
+pub(crate) fn scan(
+    root: &Path,
+    options: &ScanOptions,
+    cache: &Path,
+) -> Result<(Vec<Entry>, CacheUse), std::io::Error> {
+    let canonical_root = std::fs::canonicalize(root)?;
+    let key = cache_key(&canonical_root, options);
+
+    if is_fresh(root, cache) {
+        if let Some(entries) = load(root, cache, &key) {
+            return Ok((entries, CacheUse::Loaded(cache.to_path_buf())));
+        }
+    }
+
+    let entries = scan::scan_with(root, options)?;
+    let used = match save(root, cache, &key, &entries) {
+        Ok(()) => CacheUse::Written(cache.to_path_buf()),
+        Err(e) => CacheUse::NotWritten(cache.to_path_buf(), e.to_string()),
+    };
+    Ok((entries, used))
+}
+
+/// The lines a cache starts with, naming what it was written for.
+fn cache_key(canonical_root: &Path, options: &ScanOptions) -> String {
+    let flag = |set: bool, name: &str| {
+        if set {
+            format!(" {name}")
+        } else {
+            String::new()
+        }
+    };
+    format!(
+        "{HEADER}\nroot: {}\noptions:{}{}{}\n",
+        escape(&canonical_root.to_string_lossy()),
+        flag(options.follow_symlinks, "follow-symlinks"),
+        flag(options.follow_junctions, "follow-junctions"),
+        flag(options.skip_mount_points, "skip-mount-points"),
+    )
+}
+
+/// True when `cache` was written after the last change to `root` itself.
+fn is_fresh(root: &Path, cache: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified());
+    match (modified(cache), modified(root)) {
+        (Ok(cache), Ok(root)) => cache > root,
+        _ => false,
+    }
+}
+
+/// The cached entries of `root`, with their metadata read again. None when `cache` was
+/// written for another root or other options, or cannot be read.
+fn load(root: &Path, cache: &Path, key: &str) -> Option<Vec<Entry>> {
+    let contents = std::fs::read_to_string(cache).ok()?;
+    let listing = contents.strip_prefix(key)?;
+
+    let mut entries = Vec::new();
+    for line in listing.lines() {
+        let (flags, relative) = line.split_once(' ')?;
+        let path = root.join(unescape(relative));
+        let metadata = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(_) => return None,
+        };
+        entries.push(Entry {
+            path,
+            metadata,
+            followed: flags.contains('F'),
+            via_symlink: flags.contains('V'),
+            mount_point: flags.contains('M'),
+        });
+    }
+    Some(entries)
+}
+
+/// Writes to a temporary file next to `cache` and renames it over, so a crash never
+/// leaves a half written cache behind.
+fn save(root: &Path, cache: &Path, key: &str, entries: &[Entry]) -> Result<(), std::io::Error> {
+    let mut contents = key.to_string();
+    for entry in entries {
+        let Ok(relative) = entry.path.strip_prefix(root) else {
+            continue;
+        };
+        let flags: String = [
+            (entry.followed, 'F'),
+            (entry.via_symlink, 'V'),
+            (entry.mount_point, 'M'),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, flag)| *flag)
+        .collect();
+        contents.push_str(if flags.is_empty() { "-" } else { &flags });
+        contents.push(' ');
+        contents.push_str(&escape(&relative.to_string_lossy()));
+        contents.push('\n');
+    }
+
+    let mut temp = cache.as_os_str().to_owned();
+    temp.push(".tmp");
+
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, cache)
+}
+
+#[cfg(test)]
+mod scan_should {
+    use std::{
+        sync::atomic::AtomicU8,
+        time::{Duration, SystemTime},
+    };
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn create_tree() -> (String, String) {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/scan-cache-dir-{unique}");
+        let cache = format!("{dir}.cache");
+        let _ = std::fs::remove_file(&cache);
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "cached").unwrap();
+        std::fs::write(format!("{dir}/nested/gone.txt"), "gone").unwrap();
+        // Timestamps tick coarser than the clock, so the cache has to come a tick later
+        // to be newer than the root.
+        std::thread::sleep(Duration::from_millis(50));
+        (dir, cache)
+    }
+
+    fn names(entries: &[Entry], dir: &str) -> Vec<String> {
+        let mut names: Vec<String> = entries
+            .iter()
+            .map(|entry| {
+                let relative = entry.path.strip_prefix(dir).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn reuse_a_fresh_cache_without_listing_the_folders_again() {
+        let (dir, cache) = create_tree();
+        let options = ScanOptions::default();
+
+        let (walked, first) = scan(Path::new(&dir), &options, Path::new(&cache)).unwrap();
+        // Neither shows in the root's mtime, so the cache stays fresh.
+        std::fs::write(format!("{dir}/nested/unlisted.txt"), "new").unwrap();
+        std::fs::remove_file(format!("{dir}/nested/gone.txt")).unwrap();
+        let (loaded, second) = scan(Path::new(&dir), &options, Path::new(&cache)).unwrap();
+        let size = loaded
+            .iter()
+            .find(|entry| entry.path.ends_with("file.txt"))
+            .map(|entry| entry.metadata.len());
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&cache).unwrap();
+
+        assert_eq!(first, CacheUse::Written(PathBuf::from(&cache)));
+        assert_eq!(
+            names(&walked, &dir),
+            ["nested", "nested/file.txt", "nested/gone.txt"]
+        );
+        assert_eq!(second, CacheUse::Loaded(PathBuf::from(&cache)));
+        assert_eq!(names(&loaded, &dir), ["nested", "nested/file.txt"]);
+        assert_eq!(size, Some(6));
+    }
+
+    #[test]
+    fn walk_again_once_the_root_changed_after_the_cache() {
+        let (dir, cache) = create_tree();
+        let options = ScanOptions::default();
+        scan(Path::new(&dir), &options, Path::new(&cache)).unwrap();
+        std::fs::write(format!("{dir}/added.txt"), "new").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&cache)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+
+        let (entries, used) = scan(Path::new(&dir), &options, Path::new(&cache)).unwrap();
+        let other_options = ScanOptions {
+            skip_mount_points: true,
+            ..Default::default()
+        };
+        let (_, with_other_options) =
+            scan(Path::new(&dir), &other_options, Path::new(&cache)).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&cache).unwrap();
+
+        assert_eq!(used, CacheUse::Written(PathBuf::from(&cache)));
+        assert_eq!(
+            names(&entries, &dir),
+            ["added.txt", "nested", "nested/file.txt", "nested/gone.txt"]
+        );
+        assert_eq!(with_other_options, CacheUse::Written(PathBuf::from(&cache)));
+    }
+}
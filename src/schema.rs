@@ -0,0 +1,382 @@
+//! `--json-schema`: a JSON Schema (draft 2020-12) of the `--summary-json-to` summary, the
+//! `--progress-json` events and the `--progress-file` snapshot.
+//!
+//! The schema is not written by hand: the same functions that write each object write
+//! their fields to a `SchemaObject`, which notes the type of every field instead of its
+//! value. A field added to an output shows up in the schema without further changes.
+
+use std::{path::Path, time::Duration};
+
+use crate::{
+    json::{self, JsonFields, JsonObject},
+    progress,
+    remove::RemoveStats,
+};
+
+/// Describes the fields written to it, in order, as JSON Schema properties.
+#[derive(Default)]
+pub(crate) struct SchemaObject {
+    properties: Vec<(String, String)>,
+}
+
+impl SchemaObject {
+    fn property(&mut self, key: &str, schema: &mut JsonObject) -> &mut Self {
+        let description = describe(key);
+        if !description.is_empty() {
+            schema.string("description", description);
+        }
+        self.properties.push((key.to_string(), schema.finish()));
+        self
+    }
+
+    /// The schema of an object holding exactly the fields written, all of them required.
+    fn finish(&self, description: &str) -> String {
+        let mut properties = JsonObject::new();
+        for (key, schema) in &self.properties {
+            properties.raw(key, schema);
+        }
+        let required = self.properties.iter().map(|(key, _)| json::quote(key));
+
+        JsonObject::new()
+            .string("description", description)
+            .string("type", "object")
+            .raw("properties", &properties.finish())
+            .raw("required", &json::array(required))
+            .bool("additionalProperties", false)
+            .finish()
+    }
+}
+
+impl JsonFields for SchemaObject {
+    fn string(&mut self, key: &str, _value: &str) -> &mut Self {
+        self.property(key, JsonObject::new().string("type", "string"))
+    }
+
+    fn number(&mut self, key: &str, _value: u64) -> &mut Self {
+        self.property(
+            key,
+            JsonObject::new()
+                .string("type", "integer")
+                .number("minimum", 0),
+        )
+    }
+
+    /// Fractions the way `JsonObject::float` writes them, `null` when not finite.
+    fn float(&mut self, key: &str, _value: f64) -> &mut Self {
+        self.property(
+            key,
+            JsonObject::new().raw(
+                "type",
+                &json::array([json::quote("number"), json::quote("null")]),
+            ),
+        )
+    }
+
+    fn bool(&mut self, key: &str, _value: bool) -> &mut Self {
+        self.property(key, JsonObject::new().string("type", "boolean"))
+    }
+
+    fn tag(&mut self, key: &str, value: &str) -> &mut Self {
+        self.property(key, JsonObject::new().string("const", value))
+    }
+}
+
+/// What each field of the outputs holds.
+fn describe(key: &str) -> &'static str {
+    match key {
+        "target" => "The canonical path of the target",
+        "dry_run" => "Whether nothing was actually deleted",
+        "files" => "Files and symlinks removed, or that would be on a dry run",
+        "dirs" => "Folders removed, or that would be on a dry run",
+        "bytes" => "Bytes of the files removed",
+        "skipped_mount_points" => "Nested mount points left alone",
+        "skipped_open_files" => "Files kept as a process holds them open",
+        "modified_since_scan" => "Files kept for changing between the scan and their delete",
+        "errors" => "Entries that could not be removed",
+        "kept_different" => "Files kept for differing from the --compare-to reference",
+        "elapsed_secs" => "Seconds the removal took",
+        "type" => "The kind of event",
+        "removed" => "Entries removed so far",
+        "total" => "Entries found by the scan",
+        "current_path" => "The entry being removed, empty before the first one",
+        _ => "",
+    }
+}
+
+/// The schema of every JSON object rm-dir writes, one `$defs` entry per kind.
+pub(crate) fn json_schema() -> String {
+    let mut summary = SchemaObject::default();
+    RemoveStats::default().write_json(&mut summary, Path::new(""), false, Duration::ZERO);
+    let mut progress_event = SchemaObject::default();
+    progress::progress_fields(&mut progress_event, 0, 0);
+    let mut done_event = SchemaObject::default();
+    progress::done_fields(&mut done_event, 0, 0, Duration::ZERO);
+    let mut snapshot = SchemaObject::default();
+    progress::snapshot_fields(&mut snapshot, 0, 0, "", Duration::ZERO);
+
+    let defs = [
+        (
+            "summary",
+            summary.finish("The stats of one target, written by --summary-json-to"),
+        ),
+        (
+            "progress",
+            progress_event.finish("A --progress-json line while the removal runs"),
+        ),
+        (
+            "done",
+            done_event.finish("The last --progress-json line, once the removal stopped"),
+        ),
+        (
+            "snapshot",
+            snapshot.finish("The contents of the --progress-file while the removal runs"),
+        ),
+    ];
+
+    let mut definitions = JsonObject::new();
+    for (name, schema) in &defs {
+        definitions.raw(name, schema);
+    }
+    let any_of = defs.iter().map(|(name, _)| {
+        JsonObject::new()
+            .string("$ref", &format!("#/$defs/{name}"))
+            .finish()
+    });
+
+    JsonObject::new()
+        .string("$schema", "https://json-schema.org/draft/2020-12/schema")
+        .string("title", "rm-dir JSON output")
+        .raw("$defs", &definitions.finish())
+        .raw("anyOf", &json::array(any_of))
+        .finish()
+}
+
+#[cfg(test)]
+mod json_schema_should {
+    use std::{collections::BTreeMap, iter::Peekable, str::Chars};
+
+    use super::*;
+
+    /// Any JSON value, as the flat parser of `json` cannot read a schema.
+    #[derive(Debug, PartialEq)]
+    enum Value {
+        Null,
+        Bool(bool),
+        Number(f64),
+        String(String),
+        Array(Vec<Value>),
+        Object(BTreeMap<String, Value>),
+    }
+
+    fn parse(text: &str) -> Value {
+        fn value(chars: &mut Peekable<Chars>) -> Value {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.peek().copied().unwrap() {
+                '{' => {
+                    chars.next();
+                    let mut object = BTreeMap::new();
+                    while chars.next_if_eq(&'}').is_none() {
+                        chars.next_if_eq(&',');
+                        let Value::String(key) = value(chars) else {
+                            panic!("expected a key");
+                        };
+                        assert_eq!(chars.next(), Some(':'));
+                        object.insert(key, value(chars));
+                    }
+                    Value::Object(object)
+                }
+                '[' => {
+                    chars.next();
+                    let mut array = Vec::new();
+                    while chars.next_if_eq(&']').is_none() {
+                        chars.next_if_eq(&',');
+                        array.push(value(chars));
+                    }
+                    Value::Array(array)
+                }
+                '"' => {
+                    chars.next();
+                    let mut string = String::new();
+                    while let Some(c) = chars.next().filter(|c| *c != '"') {
+                        string.push(if c == '\\' { chars.next().unwrap() } else { c });
+                    }
+                    Value::String(string)
+                }
+                _ => {
+                    let mut word = String::new();
+                    while let Some(c) = chars.next_if(|c| !",}]".contains(*c)) {
+                        word.push(c);
+                    }
+                    match word.as_str() {
+                        "null" => Value::Null,
+                        "true" => Value::Bool(true),
+                        "false" => Value::Bool(false),
+                        number => Value::Number(number.parse().unwrap()),
+                    }
+                }
+            }
+        }
+
+        value(&mut text.chars().peekable())
+    }
+
+    fn get<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+        match value {
+            Value::Object(object) => object.get(key),
+            _ => None,
+        }
+    }
+
+    fn type_name(value: &Value) -> &'static str {
+        match value {
+            Value::Null => "null",
+            Value::Bool(_) => "boolean",
+            Value::Number(number) if number.fract() == 0.0 => "integer",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+
+    /// Checks `value` against `schema`, for the keywords `json_schema` uses.
+    fn validate(root: &Value, schema: &Value, value: &Value) -> Result<(), String> {
+        if let Some(Value::String(reference)) = get(schema, "$ref") {
+            let name = reference.trim_start_matches("#/$defs/");
+            return validate(root, get(get(root, "$defs").unwrap(), name).unwrap(), value);
+        }
+        if let Some(Value::Array(options)) = get(schema, "anyOf") {
+            return match options
+                .iter()
+                .any(|option| validate(root, option, value).is_ok())
+            {
+                true => Ok(()),
+                false => Err(format!("{value:?} matches none of anyOf")),
+            };
+        }
+        if let Some(expected) = get(schema, "const") {
+            if expected != value {
+                return Err(format!("{value:?} is not {expected:?}"));
+            }
+        }
+        let allowed = match get(schema, "type") {
+            Some(Value::String(name)) => vec![name.as_str()],
+            Some(Value::Array(names)) => names
+                .iter()
+                .map(|name| match name {
+                    Value::String(name) => name.as_str(),
+                    _ => panic!("type names are strings"),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let actual = type_name(value);
+        let fits = |name: &&str| *name == actual || (*name == "number" && actual == "integer");
+        if !allowed.is_empty() && !allowed.iter().any(fits) {
+            return Err(format!("{value:?} is not of type {allowed:?}"));
+        }
+        if let (Some(Value::Number(minimum)), Value::Number(number)) =
+            (get(schema, "minimum"), value)
+        {
+            if number < minimum {
+                return Err(format!("{number} is below {minimum}"));
+            }
+        }
+
+        let Value::Object(fields) = value else {
+            return Ok(());
+        };
+        if let Some(Value::Array(required)) = get(schema, "required") {
+            for key in required {
+                let Value::String(key) = key else { continue };
+                if !fields.contains_key(key) {
+                    return Err(format!("{key} is missing"));
+                }
+            }
+        }
+        let properties = get(schema, "properties");
+        for (key, field) in fields {
+            match properties.and_then(|properties| get(properties, key)) {
+                Some(property) => {
+                    validate(root, property, field).map_err(|e| format!("{key}: {e}"))?
+                }
+                None if get(schema, "additionalProperties") == Some(&Value::Bool(false)) => {
+                    return Err(format!("{key} is not allowed"))
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn validate_real_summary_and_progress_output() {
+        let schema = parse(&json_schema());
+        let stats = RemoveStats {
+            files: 3,
+            dirs: 1,
+            bytes: 1024,
+            ..Default::default()
+        };
+        let summary = stats.to_json(Path::new("/tmp/cache"), true, Duration::from_millis(20));
+        let mut events = Vec::new();
+        let mut progress = progress::JsonProgress::new(&mut events);
+        {
+            use progress::Progress;
+            progress.start(2);
+            progress.advance();
+            progress.finish();
+        }
+        let events = String::from_utf8(events).unwrap();
+        let snapshot = progress::snapshot_fields(
+            &mut JsonObject::new(),
+            1,
+            2,
+            "/tmp/cache/a",
+            Duration::from_millis(20),
+        )
+        .finish();
+
+        for output in [summary.as_str(), snapshot.as_str()]
+            .into_iter()
+            .chain(events.lines())
+        {
+            let value = parse(output);
+            assert_eq!(validate(&schema, &schema, &value), Ok(()), "{output}");
+        }
+    }
+
+    #[test]
+    fn reject_objects_that_do_not_fit() {
+        let schema = parse(&json_schema());
+
+        for output in [
+            r#"{"type":"progress","removed":1}"#,
+            r#"{"type":"progress","removed":-1,"total":2}"#,
+            r#"{"type":"progress","removed":1,"total":2,"extra":true}"#,
+            r#"{"type":"started","removed":1,"total":2}"#,
+        ] {
+            assert!(
+                validate(&schema, &schema, &parse(output)).is_err(),
+                "{output}"
+            );
+        }
+    }
+
+    #[test]
+    fn describe_every_field() {
+        let schema = parse(&json_schema());
+
+        let Some(Value::Object(defs)) = get(&schema, "$defs") else {
+            panic!("no $defs in {schema:?}");
+        };
+        for (name, def) in defs {
+            let Some(Value::Object(properties)) = get(def, "properties") else {
+                panic!("no properties in {name}");
+            };
+            for (key, property) in properties {
+                assert!(get(property, "description").is_some(), "{name}.{key}");
+            }
+        }
+    }
+}
@@ -0,0 +1,106 @@
+//! `--expect-scope-hash`: asserts a tree is still the one that was reviewed, by a hash
+//! over the sorted relative path and size of everything in it.
+//!
+//! The hash is FNV-1a over 64 bits, written out by this file rather than taken from
+//! `std`, whose hashers may change between releases, so a hash kept in a script stays
+//! valid across builds. It only has to notice changes, not withstand someone crafting a
+//! tree to match. Paths are relative to the target with `/` between their parts, so the
+//! same tree hashes the same wherever it sits. Folders count with a size of 0, so an
+//! added or removed empty folder changes the hash too.
+
+use std::path::Path;
+
+use crate::scan;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// The scope hash of the tree below `root`, as 16 lowercase hex digits.
+pub(crate) fn scope_hash(root: &Path) -> Result<String, std::io::Error> {
+    let mut scope: Vec<(String, u64)> = scan::scan_dir(root)?
+        .iter()
+        .filter_map(|entry| {
+            let relative = entry.path.strip_prefix(root).ok()?;
+            let parts: Vec<_> = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect();
+            let size = if entry.metadata.is_dir() {
+                0
+            } else {
+                entry.metadata.len()
+            };
+            Some((parts.join("/"), size))
+        })
+        .collect();
+    scope.sort();
+
+    let mut hash = FNV_OFFSET_BASIS;
+    for (path, size) in &scope {
+        for byte in format!("{path}\0{size}\n").bytes() {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+    Ok(format!("{hash:016x}"))
+}
+
+/// True when `expected`, as given on the command line, names the hash `actual`.
+pub(crate) fn matches(expected: &str, actual: &str) -> bool {
+    expected.trim().eq_ignore_ascii_case(actual)
+}
+
+#[cfg(test)]
+mod scope_hash_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    fn create_tree() -> String {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/scope-hash-dir-{unique}");
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "reviewed").unwrap();
+        std::fs::write(format!("{dir}/other.txt"), "also reviewed").unwrap();
+        dir
+    }
+
+    #[test]
+    fn be_the_same_for_the_same_tree_wherever_it_is() {
+        let first = create_tree();
+        let second = create_tree();
+
+        let hashes = (
+            scope_hash(Path::new(&first)).unwrap(),
+            scope_hash(Path::new(&second)).unwrap(),
+        );
+        std::fs::remove_dir_all(&first).unwrap();
+        std::fs::remove_dir_all(&second).unwrap();
+
+        assert_eq!(hashes.0, hashes.1);
+        assert_eq!(hashes.0.len(), 16);
+        assert!(matches(&hashes.0.to_uppercase(), &hashes.0));
+    }
+
+    #[test]
+    fn change_with_any_path_or_size() {
+        let dir = create_tree();
+        let root = Path::new(&dir);
+        let reviewed = scope_hash(root).unwrap();
+
+        std::fs::write(format!("{dir}/nested/file.txt"), "edited!!").unwrap();
+        let same_size = scope_hash(root).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "grown since").unwrap();
+        let resized = scope_hash(root).unwrap();
+        std::fs::create_dir(format!("{dir}/added")).unwrap();
+        let added = scope_hash(root).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Only paths and sizes count, not contents.
+        assert_eq!(same_size, reviewed);
+        assert_ne!(resized, reviewed);
+        assert_ne!(added, resized);
+    }
+}
@@ -0,0 +1,120 @@
+//! `--snapshot`: a record of what a tree held, without any contents, written before it
+//! gets deleted.
+
+use std::{fs::Metadata, io::Write, path::Path, time::UNIX_EPOCH};
+
+use crate::{json::JsonObject, scan};
+
+/// Scans `root` and writes every entry below it to `snapshot_path` as a JSON array, each
+/// entry with its path relative to `root`, type, size and modification time in seconds
+/// since the Unix epoch. Returns how many entries were written.
+pub(crate) fn write_snapshot(root: &Path, snapshot_path: &Path) -> Result<usize, std::io::Error> {
+    let entries = scan::scan_dir(root)?;
+
+    let tmp_path = snapshot_path.with_extension("tmp");
+    let mut file = std::io::BufWriter::new(std::fs::File::create(&tmp_path)?);
+
+    writeln!(file, "[")?;
+    for (i, entry) in entries.iter().enumerate() {
+        let relative = entry.path.strip_prefix(root).unwrap_or(&entry.path);
+        let separator = if i + 1 < entries.len() { "," } else { "" };
+        writeln!(
+            file,
+            "  {}{}",
+            entry_json(
+                &relative.to_string_lossy().replace('\\', "/"),
+                &entry.metadata
+            ),
+            separator
+        )?;
+    }
+    writeln!(file, "]")?;
+
+    file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(&tmp_path, snapshot_path)?;
+
+    Ok(entries.len())
+}
+
+fn entry_json(relative: &str, metadata: &Metadata) -> String {
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_dir() {
+        "dir"
+    } else if file_type.is_file() {
+        "file"
+    } else {
+        "other"
+    };
+
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+
+    JsonObject::new()
+        .string("path", relative)
+        .string("type", kind)
+        .number("size", metadata.len())
+        .number("mtime", mtime)
+        .finish()
+}
+
+#[cfg(test)]
+mod write_snapshot_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn record_paths_types_and_sizes() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/snapshot-dir-{unique}");
+        let snapshot = format!("{dir}-snapshot.json");
+
+        std::fs::create_dir_all(format!("{dir}/nested")).unwrap();
+        std::fs::write(format!("{dir}/nested/file.txt"), "12345").unwrap();
+
+        let written = write_snapshot(Path::new(&dir), Path::new(&snapshot)).unwrap();
+        let contents = std::fs::read_to_string(&snapshot).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&snapshot).unwrap();
+
+        assert_eq!(written, 2);
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 4, "Unexpected snapshot {contents}");
+        assert_eq!(lines[0], "[");
+        assert!(
+            lines[1].starts_with(r#"  {"path":"nested","type":"dir","#),
+            "Unexpected {}",
+            lines[1]
+        );
+        assert!(lines[1].ends_with("},"));
+        assert!(
+            lines[2].starts_with(r#"  {"path":"nested/file.txt","type":"file","size":5,"mtime":"#),
+            "Unexpected {}",
+            lines[2]
+        );
+        assert!(lines[2].ends_with('}'));
+        assert_eq!(lines[3], "]");
+    }
+
+    #[test]
+    fn fail_when_the_snapshot_cannot_be_written() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/snapshot-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let result = write_snapshot(
+            Path::new(&dir),
+            Path::new(&format!("{dir}-missing/snapshot.json")),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,196 @@
+//! `--stats-history`: appends the stats of every run to a JSON Lines file and compares
+//! them with the run before, to follow how fast e.g. a build folder grows back.
+//!
+//! Each line is one flat object with the target, when it was deleted and what went. Lines
+//! that cannot be read, e.g. cut short by a crash, are skipped rather than failing the run.
+
+use std::{
+    io::{ErrorKind, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    json::{self, JsonObject, JsonValue},
+    remove::RemoveStats,
+    units,
+};
+
+/// What one run deleted from a target.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Record {
+    pub(crate) target: String,
+    /// Seconds since the Unix epoch.
+    pub(crate) timestamp: u64,
+    pub(crate) files: u64,
+    pub(crate) dirs: u64,
+    pub(crate) bytes: u64,
+}
+
+impl Record {
+    pub(crate) fn new(target: &Path, stats: &RemoveStats, now: SystemTime) -> Self {
+        Record {
+            target: target.to_string_lossy().into_owned(),
+            timestamp: now
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |age| age.as_secs()),
+            files: stats.files,
+            dirs: stats.dirs,
+            bytes: stats.bytes,
+        }
+    }
+
+    fn to_json(&self) -> String {
+        JsonObject::new()
+            .string("target", &self.target)
+            .number("timestamp", self.timestamp)
+            .number("files", self.files)
+            .number("dirs", self.dirs)
+            .number("bytes", self.bytes)
+            .finish()
+    }
+
+    fn parse(line: &str) -> Option<Record> {
+        let fields = json::parse_object(line).ok()?;
+        let field = |name: &str| fields.iter().find(|(key, _)| key == name).map(|(_, v)| v);
+        let number = |name: &str| match field(name) {
+            Some(JsonValue::Number(value)) => Some(*value),
+            _ => None,
+        };
+
+        Some(Record {
+            target: match field("target") {
+                Some(JsonValue::String(target)) => target.clone(),
+                _ => return None,
+            },
+            timestamp: number("timestamp")?,
+            files: number("files")?,
+            dirs: number("dirs")?,
+            bytes: number("bytes")?,
+        })
+    }
+}
+
+/// The most recent record for `target` in the history at `path`. A missing or empty file
+/// holds none.
+pub(crate) fn last_record(path: &Path, target: &str) -> Result<Option<Record>, std::io::Error> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+
+    Ok(contents
+        .lines()
+        .rev()
+        .filter_map(Record::parse)
+        .find(|record| record.target == target))
+}
+
+/// Appends `record` as a line to the history at `path`, creating the file when needed.
+pub(crate) fn append(path: &Path, record: &Record) -> Result<(), std::io::Error> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", record.to_json())
+}
+
+/// How much `current` freed compared to `previous`, e.g. "Freed 20% more than last run:
+/// 4.1 GiB vs 3.4 GiB".
+pub(crate) fn compare(previous: &Record, current: &Record) -> String {
+    let (now, then) = (current.bytes, previous.bytes);
+    let sizes = format!(
+        "{} vs {}",
+        units::format_size(now),
+        units::format_size(then)
+    );
+    if now == then {
+        return format!("Freed the same as last run: {}", units::format_size(now));
+    }
+    if then == 0 {
+        return format!("Freed more than last run: {sizes}");
+    }
+
+    let percent = (now.abs_diff(then) as f64 / then as f64 * 100.0).round();
+    let direction = if now > then { "more" } else { "less" };
+    format!("Freed {percent}% {direction} than last run: {sizes}")
+}
+
+#[cfg(test)]
+mod compare_should {
+    use super::*;
+
+    fn record(bytes: u64) -> Record {
+        Record {
+            target: "/t".to_string(),
+            timestamp: 0,
+            files: 1,
+            dirs: 1,
+            bytes,
+        }
+    }
+
+    #[test]
+    fn give_the_change_in_bytes_freed_as_a_percentage() {
+        assert_eq!(
+            compare(&record(1000), &record(1200)),
+            "Freed 20% more than last run: 1.2 KiB vs 1000 B"
+        );
+        assert_eq!(
+            compare(&record(1000), &record(750)),
+            "Freed 25% less than last run: 750 B vs 1000 B"
+        );
+        assert_eq!(
+            compare(&record(5), &record(5)),
+            "Freed the same as last run: 5 B"
+        );
+        assert_eq!(
+            compare(&record(0), &record(5)),
+            "Freed more than last run: 5 B vs 0 B"
+        );
+    }
+}
+
+#[cfg(test)]
+mod last_record_should {
+    use std::{path::PathBuf, sync::atomic::AtomicU8};
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn find_the_latest_run_of_the_same_target() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let path = PathBuf::from(format!("./data/stats-history-{unique}.jsonl"));
+        let empty = last_record(&path, "/a").unwrap();
+
+        let first = record_for("/a", 1);
+        append(&path, &first).unwrap();
+        append(&path, &record_for("/b", 2)).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"target\":\"/a\",\"times")
+            .unwrap();
+        let latest = last_record(&path, "/a").unwrap();
+        let other = last_record(&path, "/c").unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(empty, None);
+        assert_eq!(latest, Some(first));
+        assert_eq!(other, None);
+    }
+
+    fn record_for(target: &str, bytes: u64) -> Record {
+        Record {
+            target: target.to_string(),
+            timestamp: 1,
+            files: 2,
+            dirs: 1,
+            bytes,
+        }
+    }
+}
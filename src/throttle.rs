@@ -0,0 +1,111 @@
+//! `--throttle-on-load`: slows a removal down while the machine is busy, so a cleanup on
+//! a shared box stays out of the way of interactive work.
+//!
+//! The one minute load average is sampled at most once a second through `getloadavg`.
+//! While it is above the target the pause between two entries doubles, up to
+//! `MAX_PAUSE`, and once it drops below the pause halves again until it is gone. Best
+//! effort and Unix only: where the load cannot be read nothing is throttled.
+
+use std::time::{Duration, Instant};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+const MIN_PAUSE: Duration = Duration::from_millis(1);
+const MAX_PAUSE: Duration = Duration::from_millis(200);
+
+/// Pauses between entries depending on how the system load compares to `max_load`.
+pub(crate) struct Throttle {
+    max_load: f64,
+    pause: Duration,
+    last_sample: Option<Instant>,
+}
+
+impl Throttle {
+    pub(crate) fn new(max_load: f64) -> Self {
+        Throttle {
+            max_load,
+            pause: Duration::ZERO,
+            last_sample: None,
+        }
+    }
+
+    /// Called before every entry, sleeps for the current pause.
+    pub(crate) fn wait(&mut self) {
+        if self
+            .last_sample
+            .is_none_or(|sampled| sampled.elapsed() >= SAMPLE_INTERVAL)
+        {
+            if let Some(load) = load_average() {
+                self.pause = adjust(self.pause, load, self.max_load);
+            }
+            self.last_sample = Some(Instant::now());
+        }
+
+        if !self.pause.is_zero() {
+            std::thread::sleep(self.pause);
+        }
+    }
+}
+
+/// The pause to use after `load` was sampled: longer while above `max_load`, shorter
+/// and eventually none below it.
+pub(crate) fn adjust(pause: Duration, load: f64, max_load: f64) -> Duration {
+    if load > max_load {
+        (pause * 2).clamp(MIN_PAUSE, MAX_PAUSE)
+    } else if pause > MIN_PAUSE {
+        pause / 2
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// The default target load: one runnable process per CPU.
+pub(crate) fn default_max_load() -> f64 {
+    std::thread::available_parallelism().map_or(1, |cpus| cpus.get()) as f64
+}
+
+/// The one minute load average, or `None` where the platform does not report it.
+#[cfg(unix)]
+pub(crate) fn load_average() -> Option<f64> {
+    extern "C" {
+        fn getloadavg(loadavg: *mut f64, nelem: i32) -> i32;
+    }
+
+    let mut load = 0.0;
+    // SAFETY: getloadavg writes at most one sample into the valid pointer it is given.
+    if unsafe { getloadavg(&mut load, 1) } == 1 {
+        Some(load)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn load_average() -> Option<f64> {
+    None
+}
+
+#[cfg(test)]
+mod adjust_should {
+    use super::*;
+
+    #[test]
+    fn back_off_while_the_load_is_high() {
+        let first = adjust(Duration::ZERO, 8.0, 4.0);
+        assert_eq!(first, MIN_PAUSE);
+        assert_eq!(adjust(first, 8.0, 4.0), MIN_PAUSE * 2);
+        assert_eq!(adjust(MAX_PAUSE, 8.0, 4.0), MAX_PAUSE);
+    }
+
+    #[test]
+    fn speed_up_again_once_the_load_drops() {
+        assert_eq!(adjust(MIN_PAUSE * 4, 1.0, 4.0), MIN_PAUSE * 2);
+        assert_eq!(adjust(MIN_PAUSE, 1.0, 4.0), Duration::ZERO);
+        assert_eq!(adjust(Duration::ZERO, 1.0, 4.0), Duration::ZERO);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn read_the_load_on_linux() {
+        assert!(load_average().is_some_and(|load| load >= 0.0));
+    }
+}
@@ -0,0 +1,163 @@
+//! `--prompt-timeout`: gives up waiting for an answer after a while and goes with the
+//! `--prompt-timeout-default` instead, `n` unless asked otherwise.
+//!
+//! Answers are read line by line on a thread of their own, as a blocking read cannot be
+//! interrupted. The thread outlives a prompt that timed out, so it is started once per
+//! stream and every prompt takes its lines from the same channel, so no answer typed late
+//! gets lost.
+
+use std::{
+    io::{BufRead, Read, Write},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// The lines read from a stream by its reader thread.
+pub(crate) type Lines = Arc<Mutex<Receiver<Result<String, std::io::Error>>>>;
+
+/// Starts a thread reading `input` line by line until its end or an error.
+pub(crate) fn spawn_lines(mut input: impl BufRead + Send + 'static) -> Lines {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || loop {
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                if sender.send(Ok(line)).is_err() {
+                    break;
+                }
+            }
+            Err(e) => {
+                let _ = sender.send(Err(e));
+                break;
+            }
+        }
+    });
+    Arc::new(Mutex::new(receiver))
+}
+
+/// Answers read from `Lines`, where a line that takes longer than `timeout` to arrive is
+/// replaced by `default`, noting so on `notice`.
+pub(crate) struct TimedInput<W: Write> {
+    lines: Lines,
+    timeout: Duration,
+    default: String,
+    notice: W,
+    pending: Vec<u8>,
+    position: usize,
+}
+
+impl<W: Write> TimedInput<W> {
+    pub(crate) fn new(lines: Lines, timeout: Duration, default: &str, notice: W) -> Self {
+        TimedInput {
+            lines,
+            timeout,
+            default: default.to_string(),
+            notice,
+            pending: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<W: Write> Read for TimedInput<W> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<W: Write> BufRead for TimedInput<W> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        if self.position >= self.pending.len() {
+            let received = self
+                .lines
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .recv_timeout(self.timeout);
+            let line = match received {
+                Ok(line) => line?,
+                Err(RecvTimeoutError::Timeout) => {
+                    writeln!(
+                        self.notice,
+                        "\nNo answer within {:?}, going with the --prompt-timeout-default '{}'",
+                        self.timeout, self.default
+                    )?;
+                    format!("{}\n", self.default)
+                }
+                // The stream ended, so does the input.
+                Err(RecvTimeoutError::Disconnected) => String::new(),
+            };
+            self.pending = line.into_bytes();
+            self.position = 0;
+        }
+
+        Ok(&self.pending[self.position..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position = (self.position + amount).min(self.pending.len());
+    }
+}
+
+#[cfg(test)]
+mod timed_input_should {
+    use super::*;
+
+    fn read_answer(input: &mut impl BufRead) -> String {
+        let mut answer = String::new();
+        input.read_line(&mut answer).unwrap();
+        answer
+    }
+
+    #[test]
+    fn go_with_no_when_nothing_is_typed_in_time() {
+        let (reader, _writer) = std::io::pipe().unwrap();
+        let lines = spawn_lines(std::io::BufReader::new(reader));
+        let mut notice = Vec::new();
+        let mut input = TimedInput::new(lines, Duration::from_millis(50), "n", &mut notice);
+
+        let answer = read_answer(&mut input);
+
+        assert_eq!(answer, "n\n");
+        assert_eq!(
+            String::from_utf8(notice).unwrap(),
+            "\nNo answer within 50ms, going with the --prompt-timeout-default 'n'\n"
+        );
+    }
+
+    #[test]
+    fn go_with_yes_when_that_is_the_default() {
+        let (reader, _writer) = std::io::pipe().unwrap();
+        let lines = spawn_lines(std::io::BufReader::new(reader));
+        let mut notice = Vec::new();
+        let mut input = TimedInput::new(lines, Duration::from_millis(50), "y", &mut notice);
+
+        let answer = read_answer(&mut input);
+
+        assert_eq!(answer, "y\n");
+        assert!(String::from_utf8(notice).unwrap().ends_with("'y'\n"));
+    }
+
+    #[test]
+    fn keep_answers_typed_in_time_and_late_ones_for_the_next_prompt() {
+        let (reader, mut writer) = std::io::pipe().unwrap();
+        let lines = spawn_lines(std::io::BufReader::new(reader));
+        let mut first = TimedInput::new(lines.clone(), Duration::from_millis(50), "n", Vec::new());
+        let timed_out = read_answer(&mut first);
+
+        writer.write_all(b"y\n").unwrap();
+        let mut second = TimedInput::new(lines, Duration::from_secs(10), "n", Vec::new());
+        let answered = read_answer(&mut second);
+
+        assert_eq!(timed_out, "n\n");
+        assert_eq!(answered, "y\n");
+        assert!(second.notice.is_empty());
+    }
+}
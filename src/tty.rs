@@ -0,0 +1,87 @@
+//! `--prompt-stream tty`: asks on the controlling terminal itself rather than stdin and
+//! stdout, so confirmations still work while those are piped.
+//!
+//! On Unix that is `/dev/tty`, which only opens for a process that has a controlling
+//! terminal. On Windows it is the `CONIN$` and `CONOUT$` console devices.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::BufReader,
+    path::Path,
+};
+
+#[cfg(unix)]
+const TERMINAL_INPUT: &str = "/dev/tty";
+#[cfg(unix)]
+const TERMINAL_OUTPUT: &str = "/dev/tty";
+
+#[cfg(windows)]
+const TERMINAL_INPUT: &str = "CONIN$";
+#[cfg(windows)]
+const TERMINAL_OUTPUT: &str = "CONOUT$";
+
+/// Opens the terminal for reading answers and for writing questions. Errors when there
+/// is none, e.g. in a cron job or a container without one.
+#[cfg(any(unix, windows))]
+pub(crate) fn open_terminal() -> Result<(BufReader<File>, File), std::io::Error> {
+    open_at(Path::new(TERMINAL_INPUT), Path::new(TERMINAL_OUTPUT))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn open_terminal() -> Result<(BufReader<File>, File), std::io::Error> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "no terminal device on this platform",
+    ))
+}
+
+fn open_at(input: &Path, output: &Path) -> Result<(BufReader<File>, File), std::io::Error> {
+    let reader = File::open(input)?;
+    let writer = OpenOptions::new().write(true).open(output)?;
+    Ok((BufReader::new(reader), writer))
+}
+
+#[cfg(test)]
+mod open_at_should {
+    use std::{
+        io::{BufRead, Write},
+        sync::atomic::AtomicU8,
+    };
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn read_answers_and_write_questions_on_the_devices() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/tty-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = Path::new(&dir).join("input");
+        let output = Path::new(&dir).join("output");
+        std::fs::write(&input, "y\n").unwrap();
+        std::fs::write(&output, "").unwrap();
+
+        let (mut reader, mut writer) = open_at(&input, &output).unwrap();
+        let mut answer = String::new();
+        reader.read_line(&mut answer).unwrap();
+        write!(writer, "Delete? ").unwrap();
+        let asked = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(answer, "y\n");
+        assert_eq!(asked, "Delete? ");
+    }
+
+    #[test]
+    fn fail_without_a_terminal() {
+        let missing = Path::new("./data/tty-missing/tty");
+
+        let result = open_at(missing, missing);
+
+        assert_eq!(
+            result.err().map(|e| e.kind()),
+            Some(std::io::ErrorKind::NotFound)
+        );
+    }
+}
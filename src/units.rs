@@ -0,0 +1,544 @@
+//! Parsing and formatting of sizes, durations and modes given on the command line.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SIZE_UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+/// Parses sizes like `500`, `10K`, `1.5MiB` or `2G`. Units are binary (1K = 1024 bytes).
+pub(crate) fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("'{value}' is not a valid size"))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        other => return Err(format!("'{other}' is not a known size unit")),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parses durations like `90s`, `15m`, `12h`, `30d` or `2w`. A bare number is seconds.
+pub(crate) fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{value}' is not a valid duration"))?;
+
+    let seconds: u64 = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        other => return Err(format!("'{other}' is not a known duration unit")),
+    };
+
+    Ok(Duration::from_secs(number * seconds))
+}
+
+/// Formats a duration in the largest unit `parse_duration` takes that divides it, e.g. `15m`.
+pub(crate) fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let units = [
+        ("w", 7 * 24 * 60 * 60),
+        ("d", 24 * 60 * 60),
+        ("h", 60 * 60),
+        ("m", 60),
+    ];
+    match units
+        .iter()
+        .find(|(_, length)| seconds > 0 && seconds.is_multiple_of(*length))
+    {
+        Some((unit, length)) => format!("{}{unit}", seconds / length),
+        None => format!("{seconds}s"),
+    }
+}
+
+/// Parses octal permission modes like `755`, `0750` or `0o2775`, up to `7777`.
+pub(crate) fn parse_mode(value: &str) -> Result<u32, String> {
+    let value = value.trim();
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 && !digits.starts_with('+') => Ok(mode),
+        _ => Err(format!("'{value}' is not an octal mode like 755")),
+    }
+}
+
+/// Parses RFC 3339 timestamps like `2026-10-15T06:00:00+02:00` or
+/// `2026-10-15T04:00:00.5Z`. Leap seconds are not supported.
+pub(crate) fn parse_timestamp(value: &str) -> Result<SystemTime, String> {
+    let invalid = || format!("'{value}' is not an RFC 3339 timestamp like 2026-10-15T06:00:00Z");
+
+    let bytes = value.trim().as_bytes();
+    let number = |range: std::ops::Range<usize>| -> Result<i64, String> {
+        let digits = bytes.get(range).ok_or_else(invalid)?;
+        if !digits.iter().all(u8::is_ascii_digit) {
+            return Err(invalid());
+        }
+        Ok(digits.iter().fold(0, |n, d| n * 10 + i64::from(d - b'0')))
+    };
+    let expect = |at: usize, allowed: &[u8]| match bytes.get(at) {
+        Some(b) if allowed.contains(b) => Ok(()),
+        _ => Err(invalid()),
+    };
+
+    let year = number(0..4)?;
+    expect(4, b"-")?;
+    let month = number(5..7)?;
+    expect(7, b"-")?;
+    let day = number(8..10)?;
+    expect(10, b"Tt ")?;
+    let hour = number(11..13)?;
+    expect(13, b":")?;
+    let minute = number(14..16)?;
+    expect(16, b":")?;
+    let second = number(17..19)?;
+
+    let mut pos = 19;
+    let mut nanos = 0u32;
+    if bytes.get(pos) == Some(&b'.') {
+        pos += 1;
+        let start = pos;
+        while bytes.get(pos).is_some_and(u8::is_ascii_digit) {
+            if pos - start < 9 {
+                nanos += u32::from(bytes[pos] - b'0') * 10u32.pow(8 - (pos - start) as u32);
+            }
+            pos += 1;
+        }
+        if pos == start {
+            return Err(invalid());
+        }
+    }
+
+    let offset_seconds = match bytes.get(pos) {
+        Some(b'Z' | b'z') if pos + 1 == bytes.len() => 0,
+        Some(sign @ (b'+' | b'-')) if pos + 6 == bytes.len() => {
+            expect(pos + 3, b":")?;
+            let hours = number(pos + 1..pos + 3)?;
+            let minutes = number(pos + 4..pos + 6)?;
+            if hours > 23 || minutes > 59 {
+                return Err(invalid());
+            }
+            let offset = hours * 3600 + minutes * 60;
+            if *sign == b'-' {
+                -offset
+            } else {
+                offset
+            }
+        }
+        _ => return Err(invalid()),
+    };
+
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if day < 1 || day > days_in_month || hour > 23 || minute > 59 || second > 59 {
+        return Err(invalid());
+    }
+
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second
+        - offset_seconds;
+    let seconds = u64::try_from(seconds).map_err(|_| format!("'{value}' is before 1970"))?;
+
+    Ok(UNIX_EPOCH + Duration::new(seconds, nanos))
+}
+
+/// The start of the local day `now` falls in, going by the system's time zone.
+/// On a day with a daylight saving change this is off by the change.
+pub(crate) fn local_midnight(now: SystemTime) -> SystemTime {
+    let (hour, minute, second) = local_time_of_day(now);
+    let since_epoch = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let since_midnight = Duration::new(
+        hour * 3600 + minute * 60 + second,
+        since_epoch.subsec_nanos(),
+    );
+
+    now.checked_sub(since_midnight).unwrap_or(UNIX_EPOCH)
+}
+
+/// Hour, minute and second of the local wall clock at `now`.
+#[cfg(unix)]
+fn local_time_of_day(now: SystemTime) -> (u64, u64, u64) {
+    use std::ffi::{c_char, c_int, c_long};
+
+    #[repr(C)]
+    struct Tm {
+        tm_sec: c_int,
+        tm_min: c_int,
+        tm_hour: c_int,
+        tm_mday: c_int,
+        tm_mon: c_int,
+        tm_year: c_int,
+        tm_wday: c_int,
+        tm_yday: c_int,
+        tm_isdst: c_int,
+        tm_gmtoff: c_long,
+        tm_zone: *const c_char,
+    }
+
+    extern "C" {
+        fn localtime_r(time: *const c_long, result: *mut Tm) -> *mut Tm;
+    }
+
+    let seconds = now
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs()) as c_long;
+    let mut tm = Tm {
+        tm_sec: 0,
+        tm_min: 0,
+        tm_hour: 0,
+        tm_mday: 0,
+        tm_mon: 0,
+        tm_year: 0,
+        tm_wday: 0,
+        tm_yday: 0,
+        tm_isdst: 0,
+        tm_gmtoff: 0,
+        tm_zone: std::ptr::null(),
+    };
+
+    // SAFETY: both pointers are valid for the call, localtime_r keeps neither.
+    if unsafe { localtime_r(&seconds, &mut tm) }.is_null() {
+        let since_midnight = seconds as u64 % 86400;
+        return (
+            since_midnight / 3600,
+            since_midnight / 60 % 60,
+            since_midnight % 60,
+        );
+    }
+
+    // A leap second shows up as 60, which is still within the day.
+    (
+        tm.tm_hour as u64,
+        tm.tm_min as u64,
+        tm.tm_sec.min(59) as u64,
+    )
+}
+
+/// Hour, minute and second of the local wall clock at `now`.
+#[cfg(windows)]
+fn local_time_of_day(now: SystemTime) -> (u64, u64, u64) {
+    #[repr(C)]
+    #[derive(Default)]
+    struct SystemTimeParts {
+        year: u16,
+        month: u16,
+        day_of_week: u16,
+        day: u16,
+        hour: u16,
+        minute: u16,
+        second: u16,
+        milliseconds: u16,
+    }
+
+    extern "system" {
+        fn GetLocalTime(time: *mut SystemTimeParts);
+    }
+
+    // GetLocalTime only reads the clock right now, which is what `now` is in practice.
+    let _ = now;
+    let mut parts = SystemTimeParts::default();
+    // SAFETY: the pointer is valid for the call.
+    unsafe { GetLocalTime(&mut parts) };
+
+    (parts.hour.into(), parts.minute.into(), parts.second.into())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn local_time_of_day(now: SystemTime) -> (u64, u64, u64) {
+    let since_midnight = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) % 86400;
+    (
+        since_midnight / 3600,
+        since_midnight / 60 % 60,
+        since_midnight % 60,
+    )
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date, after Howard Hinnant's
+/// `days_from_civil`.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+/// Formats an instant as an RFC 3339 UTC timestamp to the second, e.g.
+/// `2026-10-14T08:30:00Z`. Instants before 1970 show as the epoch.
+pub(crate) fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) as i64;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let second_of_day = seconds.rem_euclid(86400);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        second_of_day / 3600,
+        second_of_day / 60 % 60,
+        second_of_day % 60
+    )
+}
+
+/// The proleptic Gregorian date `days` after 1970-01-01, after Howard Hinnant's
+/// `civil_from_days`.
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// Formats a byte count with the largest binary unit that keeps it above 1, e.g. `1.5 KiB`.
+pub(crate) fn format_size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < SIZE_UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", SIZE_UNITS[unit])
+    }
+}
+
+/// Shortens large counts for display, e.g. `950`, `12.3K` or `1.2M`.
+pub(crate) fn format_count(count: u64) -> String {
+    match count {
+        0..=999 => count.to_string(),
+        1_000..=999_999 => format!("{:.1}K", count as f64 / 1_000.0),
+        _ => format!("{:.1}M", count as f64 / 1_000_000.0),
+    }
+}
+
+#[cfg(test)]
+mod parse_size_should {
+    use super::*;
+
+    #[test]
+    fn parse_plain_bytes() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("500B"), Ok(500));
+    }
+
+    #[test]
+    fn parse_binary_units() {
+        assert_eq!(parse_size("10K"), Ok(10 * 1024));
+        assert_eq!(parse_size("1.5MiB"), Ok(1024 * 1024 * 3 / 2));
+        assert_eq!(parse_size("2g"), Ok(2 * 1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn reject_unknown_units() {
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("big").is_err());
+    }
+}
+
+#[cfg(test)]
+mod parse_duration_should {
+    use super::*;
+
+    #[test]
+    fn parse_units() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration("30d"), Ok(Duration::from_secs(30 * 86400)));
+    }
+
+    #[test]
+    fn reject_unknown_units() {
+        assert!(parse_duration("3y").is_err());
+        assert!(parse_duration("soon").is_err());
+    }
+}
+
+#[cfg(test)]
+mod parse_mode_should {
+    use super::*;
+
+    #[test]
+    fn read_octal_modes() {
+        assert_eq!(parse_mode("755"), Ok(0o755));
+        assert_eq!(parse_mode("0750"), Ok(0o750));
+        assert_eq!(parse_mode("0o2775"), Ok(0o2775));
+    }
+
+    #[test]
+    fn reject_anything_else() {
+        assert!(parse_mode("").is_err());
+        assert!(parse_mode("789").is_err());
+        assert!(parse_mode("17777").is_err());
+        assert!(parse_mode("rwxr-xr-x").is_err());
+        assert!(parse_mode("+755").is_err());
+    }
+}
+
+#[cfg(test)]
+mod parse_timestamp_should {
+    use super::*;
+
+    fn seconds(value: &str) -> u64 {
+        parse_timestamp(value)
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    #[test]
+    fn parse_utc_and_offsets() {
+        assert_eq!(seconds("1970-01-01T00:00:00Z"), 0);
+        assert_eq!(seconds("2026-10-15T04:00:00Z"), 1_792_036_800);
+        assert_eq!(seconds("2026-10-15T06:00:00+02:00"), 1_792_036_800);
+        assert_eq!(seconds("2026-10-14 23:30:00-04:30"), 1_792_036_800);
+        assert_eq!(seconds("2000-02-29t12:00:00z"), 951_825_600);
+    }
+
+    #[test]
+    fn keep_fractional_seconds() {
+        let timestamp = parse_timestamp("1970-01-01T00:00:01.25Z").unwrap();
+        assert_eq!(
+            timestamp.duration_since(UNIX_EPOCH).unwrap(),
+            Duration::from_millis(1250)
+        );
+    }
+
+    #[test]
+    fn reject_malformed_values() {
+        for value in [
+            "",
+            "tomorrow",
+            "2026-10-15",
+            "2026-10-15T06:00:00",
+            "2026-10-15T06:00Z",
+            "2026-13-01T00:00:00Z",
+            "2026-02-29T00:00:00Z",
+            "2026-10-15T24:00:00Z",
+            "2026-10-15T06:00:00+0200",
+            "2026-10-15T06:00:00.Z",
+            "2026-10-15T06:00:00Zjunk",
+            "1969-12-31T23:59:59Z",
+        ] {
+            assert!(parse_timestamp(value).is_err(), "Accepted '{value}'");
+        }
+    }
+}
+
+#[cfg(test)]
+mod local_midnight_should {
+    use super::*;
+
+    #[test]
+    fn fall_within_the_last_day() {
+        let now = SystemTime::now();
+        let midnight = local_midnight(now);
+        let since_midnight = now.duration_since(midnight).unwrap();
+
+        assert!(
+            since_midnight < Duration::from_secs(86400),
+            "{since_midnight:?}"
+        );
+        assert_eq!(local_midnight(midnight), midnight);
+    }
+}
+
+#[cfg(test)]
+mod format_timestamp_should {
+    use super::*;
+
+    #[test]
+    fn format_utc_to_the_second() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+        let leap_day = UNIX_EPOCH + Duration::from_secs(951_827_696);
+        assert_eq!(format_timestamp(leap_day), "2000-02-29T12:34:56Z");
+    }
+
+    #[test]
+    fn round_trip_through_parse_timestamp() {
+        let time = parse_timestamp("2026-12-31T23:59:59Z").unwrap();
+        assert_eq!(format_timestamp(time), "2026-12-31T23:59:59Z");
+    }
+}
+
+#[cfg(test)]
+mod format_duration_should {
+    use super::*;
+
+    #[test]
+    fn use_the_largest_whole_unit() {
+        assert_eq!(format_duration(Duration::from_secs(90)), "90s");
+        assert_eq!(format_duration(Duration::from_secs(15 * 60)), "15m");
+        assert_eq!(format_duration(Duration::from_secs(36 * 60 * 60)), "36h");
+        assert_eq!(
+            format_duration(Duration::from_secs(14 * 24 * 60 * 60)),
+            "2w"
+        );
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+    }
+}
+
+#[cfg(test)]
+mod format_size_should {
+    use super::*;
+
+    #[test]
+    fn format_with_largest_unit() {
+        assert_eq!(format_size(12), "12 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
+
+#[cfg(test)]
+mod format_count_should {
+    use super::*;
+
+    #[test]
+    fn shorten_thousands_and_millions() {
+        assert_eq!(format_count(950), "950");
+        assert_eq!(format_count(12_345), "12.3K");
+        assert_eq!(format_count(1_234_567), "1.2M");
+    }
+}
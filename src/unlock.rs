@@ -0,0 +1,110 @@
+//! `--ignore-permissions`: makes the locked down folders of a tree the user owns
+//! readable, writable and traversable again, so everything inside them can be deleted.
+//!
+//! Only folders owned by the effective user are changed, as nobody else may change their
+//! mode anyway. Nothing is restored afterwards, the folders are about to go. Outside Unix
+//! there are no such modes and nothing is changed.
+
+use std::path::{Path, PathBuf};
+
+/// What unlocking a tree did.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Unlocked {
+    /// Folders whose mode got changed.
+    pub(crate) changed: u64,
+    /// Folders that are still locked or could not be read, with the reason.
+    pub(crate) locked: Vec<(PathBuf, String)>,
+}
+
+/// Adds owner read, write and execute permission to `root` and every folder below it that
+/// is owned by the effective user and lacks any of them. Symlinks are not followed.
+#[cfg(unix)]
+pub(crate) fn unlock_tree(root: &Path) -> Unlocked {
+    extern "C" {
+        fn geteuid() -> u32;
+    }
+
+    // SAFETY: geteuid takes no arguments and cannot fail.
+    let uid = unsafe { geteuid() };
+    let mut unlocked = Unlocked::default();
+    unlock_dir(root, uid, &mut unlocked);
+    unlocked
+}
+
+#[cfg(not(unix))]
+pub(crate) fn unlock_tree(_root: &Path) -> Unlocked {
+    Unlocked::default()
+}
+
+#[cfg(unix)]
+fn unlock_dir(dir: &Path, uid: u32, unlocked: &mut Unlocked) {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    const OWNER_RWX: u32 = 0o700;
+
+    let metadata = match std::fs::symlink_metadata(dir) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            unlocked.locked.push((dir.to_path_buf(), e.to_string()));
+            return;
+        }
+    };
+
+    let mode = metadata.permissions().mode();
+    if mode & OWNER_RWX != OWNER_RWX && metadata.uid() == uid {
+        let permissions = std::fs::Permissions::from_mode(mode | OWNER_RWX);
+        match std::fs::set_permissions(dir, permissions) {
+            Ok(()) => unlocked.changed += 1,
+            Err(e) => {
+                unlocked.locked.push((dir.to_path_buf(), e.to_string()));
+                return;
+            }
+        }
+    }
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            unlocked.locked.push((dir.to_path_buf(), e.to_string()));
+            return;
+        }
+    };
+    for entry in entries.flatten() {
+        if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            unlock_dir(&entry.path(), uid, unlocked);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod unlock_tree_should {
+    use std::{os::unix::fs::PermissionsExt, sync::atomic::AtomicU8};
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn open_up_a_locked_folder_so_it_can_be_deleted() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = PathBuf::from(format!("./data/unlock-dir-{unique}"));
+        let locked = dir.join("locked");
+        std::fs::create_dir_all(locked.join("deeper")).unwrap();
+        std::fs::write(locked.join("deeper/file.txt"), "x").unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        let unlocked = unlock_tree(&dir);
+        let mode = std::fs::metadata(&locked).unwrap().permissions().mode() & 0o777;
+        let removed = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            unlocked,
+            Unlocked {
+                changed: 1,
+                locked: Vec::new()
+            }
+        );
+        assert_eq!(mode, 0o700);
+        assert!(removed.is_ok(), "{removed:?}");
+    }
+}
@@ -0,0 +1,189 @@
+//! `--watch` mode: keeps a directory empty by deleting whatever shows up in it.
+//!
+//! The directory is polled rather than watched through inotify or
+//! ReadDirectoryChangesW, which keeps the tool free of platform specific
+//! dependencies at the cost of up to one poll interval of latency.
+
+use std::{
+    collections::HashMap,
+    path::Path,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use crate::{filter::Filter, interrupt, scan, scan::Entry};
+
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Tells an entry apart from one recreated at the same path. Inode numbers are reused
+/// right away on some filesystems, so the creation time goes along where it is known.
+#[cfg(unix)]
+type Identity = (u64, u64, Option<SystemTime>);
+
+#[cfg(not(unix))]
+type Identity = Option<SystemTime>;
+
+/// Deletes entries created below `dir` until Ctrl-C is pressed. Entries present when
+/// the watch started, and anything matching an exclude pattern, are left alone. One
+/// that is deleted or replaced later counts as new from then on. Returns how many
+/// entries were removed.
+pub(crate) fn watch(dir: &Path, filter: &Filter) -> Result<u64, std::io::Error> {
+    interrupt::install_handler();
+
+    let mut existing = identities(&scan::scan_dir(dir)?);
+    let mut removed = 0;
+
+    while !interrupt::is_interrupted() {
+        removed += remove_new_entries(dir, filter, &mut existing)?;
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    Ok(removed)
+}
+
+/// One poll: removes new files first, then new folders they left empty. Entries from
+/// the start that are gone or replaced are dropped from `existing` first.
+fn remove_new_entries(
+    dir: &Path,
+    filter: &Filter,
+    existing: &mut HashMap<PathBuf, Identity>,
+) -> Result<u64, std::io::Error> {
+    let entries = scan::scan_dir(dir)?;
+    let current = identities(&entries);
+    existing.retain(|path, identity| current.get(path) == Some(identity));
+    let mut removed = 0;
+
+    for entry in entries.iter().rev() {
+        if existing.contains_key(&entry.path) {
+            continue;
+        }
+
+        let relative = entry
+            .path
+            .strip_prefix(dir)
+            .expect("Scanned entries are always below the root");
+        if filter.is_excluded(relative) {
+            continue;
+        }
+
+        let result = if entry.metadata.is_dir() {
+            std::fs::remove_dir(&entry.path)
+        } else {
+            std::fs::remove_file(&entry.path)
+        };
+
+        match result {
+            Ok(_) => {
+                println!("Removed {}", entry.path.to_string_lossy());
+                removed += 1;
+            }
+            // Still holds something we kept, or the writer beat us to it.
+            Err(_) if entry.metadata.is_dir() => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(removed)
+}
+
+fn identities(entries: &[Entry]) -> HashMap<PathBuf, Identity> {
+    entries
+        .iter()
+        .map(|entry| (entry.path.clone(), identity(entry)))
+        .collect()
+}
+
+#[cfg(unix)]
+fn identity(entry: &Entry) -> Identity {
+    use std::os::unix::fs::MetadataExt;
+
+    (
+        entry.metadata.dev(),
+        entry.metadata.ino(),
+        entry.metadata.created().ok(),
+    )
+}
+
+#[cfg(not(unix))]
+fn identity(entry: &Entry) -> Identity {
+    entry.metadata.created().ok()
+}
+
+#[cfg(test)]
+mod remove_new_entries_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+    use crate::glob::Glob;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn remove_only_entries_created_after_start() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/watch-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::File::create(format!("{dir}/old.txt")).unwrap();
+
+        let mut existing = identities(&scan::scan_dir(Path::new(&dir)).unwrap());
+
+        std::fs::create_dir_all(format!("{dir}/new-dir")).unwrap();
+        std::fs::File::create(format!("{dir}/new-dir/new.txt")).unwrap();
+        std::fs::File::create(format!("{dir}/new.txt")).unwrap();
+
+        let removed =
+            remove_new_entries(Path::new(&dir), &Filter::default(), &mut existing).unwrap();
+
+        assert_eq!(removed, 3);
+        assert!(Path::new(&format!("{dir}/old.txt")).exists());
+        assert!(!Path::new(&format!("{dir}/new.txt")).exists());
+        assert!(!Path::new(&format!("{dir}/new-dir")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn remove_existing_entries_once_recreated() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/watch-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(format!("{dir}/old.txt"), "old").unwrap();
+        let mut existing = identities(&scan::scan_dir(Path::new(&dir)).unwrap());
+
+        std::fs::remove_file(format!("{dir}/old.txt")).unwrap();
+        let after_delete =
+            remove_new_entries(Path::new(&dir), &Filter::default(), &mut existing).unwrap();
+        std::fs::write(format!("{dir}/old.txt"), "recreated").unwrap();
+        let after_recreate =
+            remove_new_entries(Path::new(&dir), &Filter::default(), &mut existing).unwrap();
+
+        assert_eq!(after_delete, 0);
+        assert_eq!(after_recreate, 1);
+        assert!(!Path::new(&format!("{dir}/old.txt")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn keep_excluded_entries() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/watch-dir-{unique}");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        std::fs::File::create(format!("{dir}/keep.lock")).unwrap();
+        std::fs::File::create(format!("{dir}/temp.txt")).unwrap();
+
+        let filter = Filter {
+            exclude: vec![Glob::new("*.lock")],
+            ..Default::default()
+        };
+        let removed = remove_new_entries(Path::new(&dir), &filter, &mut HashMap::new()).unwrap();
+
+        assert_eq!(removed, 1);
+        assert!(Path::new(&format!("{dir}/keep.lock")).exists());
+        assert!(!Path::new(&format!("{dir}/temp.txt")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
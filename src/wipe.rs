@@ -0,0 +1,96 @@
+//! Best-effort overwriting of file contents before they get deleted.
+//!
+//! This only rewrites the blocks the filesystem hands back for the file. Copy-on-write
+//! and journaling filesystems, SSD wear levelling, snapshots and backups can all keep
+//! the old contents around, so it is no substitute for full disk encryption.
+
+use std::{
+    fs::OpenOptions,
+    io::{Seek, SeekFrom, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::rate_limit;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Overwrites the contents of the regular file at `path` with pseudo-random bytes
+/// `passes` times, syncing after every pass. The length of the file is unchanged.
+pub(crate) fn wipe_file(path: &Path, passes: u32) -> Result<(), std::io::Error> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let len = file.metadata()?.len();
+    let mut noise = Noise::new(path);
+    let mut chunk = vec![0u8; CHUNK_SIZE];
+
+    for _ in 0..passes {
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut left = len;
+        while left > 0 {
+            let size = left.min(CHUNK_SIZE as u64) as usize;
+            noise.fill(&mut chunk[..size]);
+            file.write_all(&chunk[..size])?;
+            rate_limit::consume(size as u64);
+            left -= size as u64;
+        }
+
+        file.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// A xorshift generator. Fine for noise, not meant to be unpredictable.
+struct Noise(u64);
+
+impl Noise {
+    fn new(path: &Path) -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        let salt = path.as_os_str().len() as u64;
+
+        Noise((nanos ^ salt.rotate_left(32)) | 1)
+    }
+
+    fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            chunk.copy_from_slice(&self.0.to_le_bytes()[..chunk.len()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod wipe_file_should {
+    use std::sync::atomic::AtomicU8;
+
+    use super::*;
+
+    static UNIQUE_IDENTIFIER: AtomicU8 = AtomicU8::new(0);
+
+    #[test]
+    fn overwrite_contents_and_keep_the_length() {
+        let unique = UNIQUE_IDENTIFIER.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let dir = format!("./data/wipe-dir-{unique}");
+        let path = format!("{dir}/secret.txt");
+        let original = b"top secret ".repeat(10_000);
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, &original).unwrap();
+
+        wipe_file(Path::new(&path), 2).unwrap();
+        let wiped = std::fs::read(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(wiped.len(), original.len());
+        assert_ne!(wiped, original);
+        assert!(
+            !wiped.windows(10).any(|window| window == b"top secret"),
+            "Some of the original contents survived"
+        );
+    }
+}